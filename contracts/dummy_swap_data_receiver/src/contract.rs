@@ -32,7 +32,9 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
             asset_in,
             asset_out,
             account,
-        } => receive_swap_data(deps, asset_in, asset_out, account),
+            pair,
+            ..
+        } => receive_swap_data(deps, asset_in, asset_out, account, pair),
     }
 }
 
@@ -41,10 +43,11 @@ pub fn receive_swap_data<S: Storage, A: Api, Q: Querier>(
     asset_in: Asset,
     asset_out: Asset,
     account: HumanAddr,
+    pair: HumanAddr,
 ) -> HandleResult {
     debug_print(format!(
-        "Swap data received! asset in: {} {}, asset out: {} {}, account: {}",
-        asset_in.amount, asset_in.info, asset_out.amount, asset_out.info, account
+        "Swap data received from {}! asset in: {} {}, asset out: {} {}, account: {}",
+        pair, asset_in.amount, asset_in.info, asset_out.amount, asset_out.info, account
     ));
 
     config(&mut deps.storage).update(|mut state| {
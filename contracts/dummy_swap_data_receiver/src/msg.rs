@@ -1,4 +1,4 @@
-use cosmwasm_std::HumanAddr;
+use cosmwasm_std::{HumanAddr, Uint128};
 use schemars::JsonSchema;
 use secretswap::Asset;
 use serde::{Deserialize, Serialize};
@@ -13,6 +13,10 @@ pub enum SwapDataEndpointMsg {
         asset_in: Asset,
         asset_out: Asset,
         account: HumanAddr,
+        pair: HumanAddr,
+        commission: Asset,
+        spread_amount: Uint128,
+        reserves: [Asset; 2],
     },
 }
 
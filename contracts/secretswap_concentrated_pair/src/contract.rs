@@ -0,0 +1,984 @@
+use std::str::FromStr;
+
+use cosmwasm_std::{
+    from_binary, log, to_binary, Api, Binary, CanonicalAddr, CosmosMsg, Decimal, Env, Extern,
+    HandleResponse, HandleResult, HumanAddr, InitResponse, Querier, StdError, StdResult, Storage,
+    Uint128, WasmMsg,
+};
+use primitive_types::{U256, U512};
+use secret_toolkit::snip20;
+use std::convert::TryFrom;
+
+use secretswap::{
+    deliver_swap_data, query_swap_data, Asset, AssetInfo, AssetInfoRaw, ConcentratedPairInitMsg,
+    CurveType, Deadline, Factory, PairInfo, PairInfoRaw, SwapData, SwapResponse,
+};
+
+use crate::msg::{
+    Cw20HookMsg, HandleMsg, PoolResponse, PositionResponse, PositionsResponse, QueryMsg,
+    SimulationResponse, TickResponse, TicksResponse,
+};
+use crate::position::{
+    assert_tick_range, assert_viewing_key, compute_fees_owed, create_position, load_position,
+    modify_position, save_position, set_viewing_key,
+};
+use crate::querier::query_pair_settings;
+use crate::sqrt_price_math::{
+    get_amounts_for_liquidity, get_liquidity_for_amounts, mul_div, to_uint128, RESOLUTION,
+};
+use crate::state::{
+    read_owner_positions, read_pair_info, read_pool, read_position, read_tick_info, read_ticks,
+    store_pair_info, store_pool, PoolState, Position,
+};
+use crate::swap::{apply_swap, compute_spot_return, compute_swap};
+use crate::tick_math::{get_sqrt_ratio_at_tick, get_tick_at_sqrt_ratio};
+
+/// Widest tick spacing, as in Uniswap v3
+pub const MAX_TICK_SPACING: u32 = 16_384;
+/// Decimals of `Decimal`
+const DECIMAL_FRACTIONAL: u128 = 1_000_000_000_000_000_000;
+
+// settings for pagination
+const MAX_LIMIT: u32 = 100;
+const DEFAULT_LIMIT: u32 = 30;
+
+pub fn init<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    msg: ConcentratedPairInitMsg,
+) -> StdResult<InitResponse> {
+    if msg.tick_spacing == 0 || msg.tick_spacing > MAX_TICK_SPACING {
+        return Err(StdError::generic_err(format!(
+            "Tick spacing must be between 1 and {}",
+            MAX_TICK_SPACING
+        )));
+    }
+    let sqrt_price = sqrt_price_from_decimal(msg.initial_price)?;
+
+    // create viewing key
+    let assets_viewing_key = String::from("SecretSwap");
+
+    let mut messages = vec![];
+    let mut asset_infos_raw = vec![];
+    for asset_info in msg.asset_infos.iter() {
+        match asset_info {
+            AssetInfo::Token {
+                contract_addr,
+                token_code_hash,
+                ..
+            } => {
+                messages.push(snip20::set_viewing_key_msg(
+                    assets_viewing_key.clone(),
+                    None,
+                    256,
+                    token_code_hash.clone(),
+                    contract_addr.clone(),
+                )?);
+                messages.push(snip20::register_receive_msg(
+                    env.contract_code_hash.clone(),
+                    None,
+                    256,
+                    token_code_hash.clone(),
+                    contract_addr.clone(),
+                )?);
+                asset_infos_raw.push(AssetInfoRaw::Token {
+                    contract_addr: deps.api.canonical_address(&contract_addr)?,
+                    token_code_hash: token_code_hash.clone(),
+                    viewing_key: assets_viewing_key.clone(),
+                });
+            }
+            AssetInfo::NativeToken { .. } => asset_infos_raw.push(asset_info.to_raw(&deps)?),
+        }
+    }
+
+    let hook = match msg.init_hook {
+        Some(hook) => hook,
+        None => {
+            return Err(StdError::generic_err(
+                "Must provide the factory as init hook",
+            ))
+        }
+    };
+    messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: hook.contract_addr.clone(),
+        callback_code_hash: hook.code_hash.clone(),
+        msg: hook.msg,
+        send: vec![],
+    }));
+
+    // positions aren't tokens, so there is no liquidity token
+    store_pair_info(
+        &mut deps.storage,
+        &PairInfoRaw {
+            contract_addr: deps.api.canonical_address(&env.contract.address)?,
+            liquidity_token: CanonicalAddr::default(),
+            token_code_hash: String::new(),
+            asset_infos: [asset_infos_raw[0].clone(), asset_infos_raw[1].clone()],
+            asset0_volume: Uint128::zero(),
+            asset1_volume: Uint128::zero(),
+            factory: Factory {
+                address: hook.contract_addr,
+                code_hash: hook.code_hash,
+            },
+            curve: CurveType::default(),
+        },
+    )?;
+
+    let mut pool = PoolState {
+        sqrt_price: [0; 4],
+        tick: get_tick_at_sqrt_ratio(sqrt_price)?,
+        tick_spacing: msg.tick_spacing,
+        liquidity: Uint128::zero(),
+        fee_growth_global: [Uint128::zero(); 2],
+    };
+    pool.set_sqrt_price(sqrt_price);
+    store_pool(&mut deps.storage, &pool)?;
+
+    Ok(InitResponse {
+        messages,
+        log: vec![log("status", "success")], // See https://github.com/CosmWasm/wasmd/pull/386
+    })
+}
+
+/// The decimal times 10^18. Multiplying by 10^18 overflows `Uint128` above a price of about
+/// 340, so the digits are parsed instead.
+fn decimal_atomics(decimal: Decimal) -> StdResult<u128> {
+    let decimal = decimal.to_string();
+    let mut parts = decimal.splitn(2, '.');
+    let whole = parts.next().unwrap_or_default();
+    let fractional = format!("{:0<18}", parts.next().unwrap_or_default());
+    let parse_err = |_| StdError::generic_err(format!("Invalid decimal {}", decimal));
+
+    Ok(
+        u128::from_str(whole).map_err(parse_err)? * DECIMAL_FRACTIONAL
+            + u128::from_str(&fractional).map_err(parse_err)?,
+    )
+}
+
+/// sqrt of a price as a Q64.96 number
+fn sqrt_price_from_decimal(price: Decimal) -> StdResult<U256> {
+    let price_fractional = decimal_atomics(price)?;
+    if price_fractional == 0 {
+        return Err(StdError::generic_err("Initial price must be positive"));
+    }
+
+    let price_x192 =
+        (U512::from(price_fractional) << (2 * RESOLUTION)) / U512::from(DECIMAL_FRACTIONAL);
+    let sqrt_price = U256::try_from(price_x192.integer_sqrt())
+        .map_err(|_| StdError::generic_err("Initial price is out of the tick range"))?;
+    // the price must lie within the tick range
+    get_tick_at_sqrt_ratio(sqrt_price)
+        .map_err(|_| StdError::generic_err("Initial price is out of the tick range"))?;
+
+    Ok(sqrt_price)
+}
+
+pub fn handle<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    msg: HandleMsg,
+) -> HandleResult {
+    match msg {
+        HandleMsg::Receive { amount, msg, from } => receive_cw20(deps, env, from, amount, msg),
+        HandleMsg::Swap {
+            offer_asset,
+            expected_return,
+            belief_price,
+            max_spread,
+            to,
+            deadline,
+        } => {
+            if !offer_asset.is_native_token() {
+                return Err(StdError::unauthorized());
+            }
+            assert_deadline(&env, &deadline)?;
+
+            try_swap(
+                deps,
+                env.clone(),
+                env.message.sender,
+                offer_asset,
+                expected_return,
+                belief_price,
+                max_spread,
+                to,
+            )
+        }
+        HandleMsg::OpenPosition {
+            assets,
+            tick_lower,
+            tick_upper,
+            min_liquidity,
+            deadline,
+        } => {
+            assert_deadline(&env, &deadline)?;
+
+            try_open_position(deps, env, assets, tick_lower, tick_upper, min_liquidity)
+        }
+        HandleMsg::IncreaseLiquidity {
+            position_id,
+            assets,
+            min_liquidity,
+            deadline,
+        } => {
+            assert_deadline(&env, &deadline)?;
+
+            try_increase_liquidity(deps, env, position_id, assets, min_liquidity)
+        }
+        HandleMsg::DecreaseLiquidity {
+            position_id,
+            liquidity,
+            min_amounts,
+            deadline,
+        } => {
+            assert_deadline(&env, &deadline)?;
+
+            try_decrease_liquidity(deps, env, position_id, liquidity, min_amounts)
+        }
+        HandleMsg::CollectFees { position_id } => try_collect_fees(deps, env, position_id),
+        HandleMsg::SetViewingKey { key, .. } => try_set_viewing_key(deps, env, key),
+    }
+}
+
+pub fn receive_cw20<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    from: HumanAddr,
+    amount: Uint128,
+    msg: Option<Binary>,
+) -> HandleResult {
+    let contract_addr = env.message.sender.clone();
+    if let Some(bin_msg) = msg {
+        match from_binary(&bin_msg)? {
+            Cw20HookMsg::Swap {
+                expected_return,
+                belief_price,
+                max_spread,
+                to,
+                deadline,
+            } => {
+                // only asset contract can execute this message
+                assert_pool_token(deps, &env.message.sender)?;
+                assert_deadline(&env, &deadline)?;
+
+                try_swap(
+                    deps,
+                    env,
+                    from,
+                    Asset {
+                        info: AssetInfo::Token {
+                            contract_addr,
+                            token_code_hash: Default::default(),
+                            viewing_key: Default::default(),
+                        },
+                        amount,
+                    },
+                    expected_return,
+                    belief_price,
+                    max_spread,
+                    to,
+                )
+            }
+        }
+    } else {
+        Err(StdError::generic_err("data should be given"))
+    }
+}
+
+fn assert_deadline(env: &Env, deadline: &Option<Deadline>) -> StdResult<()> {
+    match deadline {
+        Some(deadline) => deadline.assert_not_expired(&env.block),
+        None => Ok(()),
+    }
+}
+
+fn assert_pool_token<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    token: &HumanAddr,
+) -> StdResult<()> {
+    let pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
+    let token = deps.api.canonical_address(token)?;
+    let authorized = pair_info
+        .asset_infos
+        .iter()
+        .any(|asset_info| match asset_info {
+            AssetInfoRaw::Token { contract_addr, .. } => contract_addr == &token,
+            _ => false,
+        });
+
+    if !authorized {
+        return Err(StdError::unauthorized());
+    }
+
+    Ok(())
+}
+
+fn read_asset_infos<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    pair_info: &PairInfoRaw,
+) -> StdResult<[AssetInfo; 2]> {
+    Ok([
+        pair_info.asset_infos[0].to_normal(&deps)?,
+        pair_info.asset_infos[1].to_normal(&deps)?,
+    ])
+}
+
+/// Sends the amounts, aligned with `asset_infos`, that aren't zero
+fn pay_out<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: &Env,
+    asset_infos: &[AssetInfo; 2],
+    amounts: [Uint128; 2],
+    recipient: &HumanAddr,
+) -> StdResult<Vec<CosmosMsg>> {
+    let mut messages = vec![];
+    for (info, amount) in asset_infos.iter().zip(amounts.iter()) {
+        if !amount.is_zero() {
+            messages.push(
+                Asset {
+                    info: info.clone(),
+                    amount: *amount,
+                }
+                .into_msg(
+                    &deps,
+                    env.contract.address.clone(),
+                    recipient.clone(),
+                )?,
+            );
+        }
+    }
+    Ok(messages)
+}
+
+pub fn try_set_viewing_key<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    key: String,
+) -> HandleResult {
+    let account = deps.api.canonical_address(&env.message.sender)?;
+    set_viewing_key(&mut deps.storage, &account, &key)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("action", "set_viewing_key")],
+        data: None,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn try_swap<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    sender: HumanAddr,
+    offer_asset: Asset,
+    expected_return: Option<Uint128>,
+    belief_price: Option<Decimal>,
+    max_spread: Option<Decimal>,
+    to: Option<HumanAddr>,
+) -> HandleResult {
+    offer_asset.assert_sent_native_token_balance(&env)?;
+
+    let mut pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
+    let asset_infos = read_asset_infos(&deps, &pair_info)?;
+    let offer_index = asset_infos
+        .iter()
+        .position(|info| offer_asset.info.equal(info))
+        .ok_or_else(|| StdError::generic_err("Wrong asset info is given"))?;
+    let ask_index = 1 - offer_index;
+
+    let pair_settings = query_pair_settings(&deps, &pair_info)?;
+    pair_settings.status.assert_swaps_allowed()?;
+
+    let pool = read_pool(&deps.storage)?;
+    let outcome = compute_swap(
+        &deps.storage,
+        offer_index,
+        offer_asset.amount,
+        &pair_settings.swap_fee,
+    )?;
+    if outcome.return_amount.is_zero() {
+        return Err(StdError::generic_err(
+            "Offer amount is too small to return anything",
+        ));
+    }
+    let spread_amount = compute_spread_amount(
+        &pool,
+        offer_index,
+        (offer_asset.amount - outcome.commission_amount)?,
+        outcome.return_amount,
+    );
+
+    // check max spread limit if exist
+    assert_max_spread(
+        belief_price,
+        max_spread,
+        expected_return,
+        offer_asset.amount,
+        outcome.return_amount,
+        spread_amount,
+    )?;
+
+    apply_swap(&mut deps.storage, &outcome)?;
+
+    if offer_index == 0 {
+        pair_info.asset0_volume = pair_info.asset0_volume + offer_asset.amount;
+    } else {
+        pair_info.asset1_volume = pair_info.asset1_volume + offer_asset.amount;
+    }
+    store_pair_info(&mut deps.storage, &pair_info)?;
+
+    let return_asset = Asset {
+        info: asset_infos[ask_index].clone(),
+        amount: outcome.return_amount,
+    };
+    let receiver = to.unwrap_or(sender);
+
+    let mut messages = vec![return_asset.clone().into_msg(
+        &deps,
+        env.contract.address.clone(),
+        receiver.clone(),
+    )?];
+
+    let reserves = compute_virtual_reserves(&outcome.pool)?;
+    messages.extend(deliver_swap_data(
+        &mut deps.storage,
+        &env,
+        &pair_settings.swap_data_endpoints,
+        SwapData {
+            pair: env.contract.address.clone(),
+            account: receiver,
+            asset_in: Asset {
+                info: offer_asset.info.clone(),
+                amount: offer_asset.amount,
+            },
+            asset_out: return_asset.clone(),
+            commission: Asset {
+                info: offer_asset.info.clone(),
+                amount: outcome.commission_amount,
+            },
+            spread_amount,
+            reserves: [
+                Asset {
+                    info: asset_infos[0].clone(),
+                    amount: reserves[0],
+                },
+                Asset {
+                    info: asset_infos[1].clone(),
+                    amount: reserves[1],
+                },
+            ],
+        },
+    )?);
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![
+            log("action", "swap"),
+            log("offer_asset", offer_asset.info.to_string()),
+            log("ask_asset", asset_infos[ask_index].to_string()),
+            log("offer_amount", offer_asset.amount.to_string()),
+            log("return_amount", outcome.return_amount.to_string()),
+            log("spread_amount", spread_amount.to_string()),
+            log("commission_amount", outcome.commission_amount.to_string()),
+            log("crossed_ticks", outcome.crossed_ticks.len().to_string()),
+        ],
+        data: Some(to_binary(&SwapResponse {
+            offer_asset,
+            return_asset,
+            spread_amount,
+            commission_amount: outcome.commission_amount,
+            protocol_fee_amount: Uint128::zero(),
+            refund_amount: Uint128::zero(),
+        })?),
+    })
+}
+
+pub fn try_open_position<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    assets: [Asset; 2],
+    tick_lower: i32,
+    tick_upper: i32,
+    min_liquidity: Option<Uint128>,
+) -> HandleResult {
+    let pool = read_pool(&deps.storage)?;
+    assert_tick_range(tick_lower, tick_upper, pool.tick_spacing)?;
+
+    let owner = deps.api.canonical_address(&env.message.sender)?;
+    let (position_id, position) =
+        create_position(&mut deps.storage, &owner, tick_lower, tick_upper)?;
+
+    add_liquidity(deps, env, position_id, position, assets, min_liquidity)
+}
+
+pub fn try_increase_liquidity<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    position_id: u64,
+    assets: [Asset; 2],
+    min_liquidity: Option<Uint128>,
+) -> HandleResult {
+    let owner = deps.api.canonical_address(&env.message.sender)?;
+    let position = load_position(&deps.storage, &owner, position_id)?;
+
+    add_liquidity(deps, env, position_id, position, assets, min_liquidity)
+}
+
+/// Adds the liquidity the assets provide in the range of the position, takes the amounts it
+/// needs and refunds the rest of the native assets
+fn add_liquidity<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    position_id: u64,
+    mut position: Position,
+    assets: [Asset; 2],
+    min_liquidity: Option<Uint128>,
+) -> HandleResult {
+    for asset in assets.iter() {
+        asset.assert_sent_native_token_balance(&env)?;
+    }
+
+    let pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
+    query_pair_settings(&deps, &pair_info)?
+        .status
+        .assert_deposits_allowed()?;
+    let asset_infos = read_asset_infos(&deps, &pair_info)?;
+    if !assets
+        .iter()
+        .all(|asset| asset_infos.iter().any(|info| asset.info.equal(info)))
+    {
+        return Err(StdError::generic_err("Wrong asset info is given"));
+    }
+    let mut deposits = [Uint128::zero(); 2];
+    for (i, info) in asset_infos.iter().enumerate() {
+        deposits[i] = assets
+            .iter()
+            .find(|asset| asset.info.equal(info))
+            .map(|asset| asset.amount)
+            .ok_or_else(|| StdError::generic_err("Wrong asset info is given"))?;
+    }
+
+    let pool = read_pool(&deps.storage)?;
+    let liquidity = Uint128(get_liquidity_for_amounts(
+        pool.sqrt_price(),
+        get_sqrt_ratio_at_tick(position.tick_lower)?,
+        get_sqrt_ratio_at_tick(position.tick_upper)?,
+        deposits,
+    )?);
+    if liquidity.is_zero() {
+        return Err(StdError::generic_err(
+            "Deposits are too small to provide liquidity",
+        ));
+    }
+    if let Some(min_liquidity) = min_liquidity {
+        if liquidity < min_liquidity {
+            return Err(StdError::generic_err(
+                "Operation fell short of min_liquidity",
+            ));
+        }
+    }
+
+    let used_deposits = modify_position(&mut deps.storage, &mut position, liquidity, true)?;
+    save_position(&mut deps.storage, position_id, &position)?;
+
+    let mut messages: Vec<CosmosMsg> = vec![];
+    for (i, info) in asset_infos.iter().enumerate() {
+        match info {
+            // If the pool is token contract, then we need to execute TransferFrom msg to receive funds
+            AssetInfo::Token {
+                contract_addr,
+                token_code_hash,
+                ..
+            } => {
+                if !used_deposits[i].is_zero() {
+                    messages.push(snip20::transfer_from_msg(
+                        env.message.sender.clone(),
+                        env.contract.address.clone(),
+                        used_deposits[i],
+                        None,
+                        256,
+                        token_code_hash.clone(),
+                        contract_addr.clone(),
+                    )?);
+                }
+            }
+            // Native tokens were sent in full, refund the excess
+            AssetInfo::NativeToken { .. } => {
+                let excess = (deposits[i] - used_deposits[i])?;
+                if !excess.is_zero() {
+                    messages.push(
+                        Asset {
+                            info: info.clone(),
+                            amount: excess,
+                        }
+                        .into_msg(
+                            &deps,
+                            env.contract.address.clone(),
+                            env.message.sender.clone(),
+                        )?,
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![
+            log("action", "add_liquidity"),
+            log("position_id", position_id),
+            log("liquidity", liquidity),
+            log(
+                "used_assets",
+                format!(
+                    "{}{}, {}{}",
+                    used_deposits[0], asset_infos[0], used_deposits[1], asset_infos[1]
+                ),
+            ),
+        ],
+        data: None,
+    })
+}
+
+pub fn try_decrease_liquidity<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    position_id: u64,
+    liquidity: Uint128,
+    min_amounts: Option<[Uint128; 2]>,
+) -> HandleResult {
+    let owner = deps.api.canonical_address(&env.message.sender)?;
+    let mut position = load_position(&deps.storage, &owner, position_id)?;
+    if liquidity.is_zero() || liquidity > position.liquidity {
+        return Err(StdError::generic_err(
+            "Liquidity must be positive and at most the liquidity of the position",
+        ));
+    }
+
+    let amounts = modify_position(&mut deps.storage, &mut position, liquidity, false)?;
+    if let Some(min_amounts) = min_amounts {
+        if amounts[0] < min_amounts[0] || amounts[1] < min_amounts[1] {
+            return Err(StdError::generic_err("Operation fell short of min_amounts"));
+        }
+    }
+    let fees = position.fees_owed;
+    position.fees_owed = [Uint128::zero(); 2];
+    save_position(&mut deps.storage, position_id, &position)?;
+
+    let pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
+    let asset_infos = read_asset_infos(&deps, &pair_info)?;
+    let messages = pay_out(
+        &deps,
+        &env,
+        &asset_infos,
+        [amounts[0] + fees[0], amounts[1] + fees[1]],
+        &env.message.sender,
+    )?;
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![
+            log("action", "decrease_liquidity"),
+            log("position_id", position_id),
+            log("liquidity", liquidity),
+            log(
+                "withdrawn_assets",
+                format!(
+                    "{}{}, {}{}",
+                    amounts[0], asset_infos[0], amounts[1], asset_infos[1]
+                ),
+            ),
+            log(
+                "fees",
+                format!(
+                    "{}{}, {}{}",
+                    fees[0], asset_infos[0], fees[1], asset_infos[1]
+                ),
+            ),
+        ],
+        data: None,
+    })
+}
+
+pub fn try_collect_fees<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    position_id: u64,
+) -> HandleResult {
+    let owner = deps.api.canonical_address(&env.message.sender)?;
+    let mut position = load_position(&deps.storage, &owner, position_id)?;
+
+    // settles the fees without changing the liquidity
+    modify_position(&mut deps.storage, &mut position, Uint128::zero(), true)?;
+    let fees = position.fees_owed;
+    position.fees_owed = [Uint128::zero(); 2];
+    save_position(&mut deps.storage, position_id, &position)?;
+
+    let pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
+    let asset_infos = read_asset_infos(&deps, &pair_info)?;
+    let messages = pay_out(&deps, &env, &asset_infos, fees, &env.message.sender)?;
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![
+            log("action", "collect_fees"),
+            log("position_id", position_id),
+            log(
+                "fees",
+                format!(
+                    "{}{}, {}{}",
+                    fees[0], asset_infos[0], fees[1], asset_infos[1]
+                ),
+            ),
+        ],
+        data: None,
+    })
+}
+
+pub fn query<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    msg: QueryMsg,
+) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Pair {} => to_binary(&query_pair_info(&deps)?),
+        QueryMsg::Pool {} => to_binary(&query_pool(&deps)?),
+        QueryMsg::Ticks { start_after, limit } => {
+            to_binary(&query_ticks(&deps, start_after, limit)?)
+        }
+        QueryMsg::Simulation { offer_asset } => to_binary(&query_simulation(&deps, offer_asset)?),
+        QueryMsg::Positions { address, key } => to_binary(&query_positions(&deps, address, key)?),
+        QueryMsg::SwapData {
+            endpoint,
+            key,
+            start_after,
+            limit,
+        } => {
+            let pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
+            let pair_settings = query_pair_settings(&deps, &pair_info)?;
+            to_binary(&query_swap_data(
+                &deps.storage,
+                &pair_settings.swap_data_endpoints,
+                &endpoint,
+                &key,
+                start_after,
+                limit,
+            )?)
+        }
+    }
+}
+
+/// Reserves a constant product pool with the liquidity in range would have at the price of
+/// the pool, aligned with `asset_infos`
+fn compute_virtual_reserves(pool: &PoolState) -> StdResult<[Uint128; 2]> {
+    let q96 = U256::one() << RESOLUTION;
+    let liquidity = U256::from(pool.liquidity.u128());
+    Ok([
+        to_uint128(mul_div(liquidity, q96, pool.sqrt_price(), false)?)?,
+        to_uint128(mul_div(liquidity, pool.sqrt_price(), q96, false)?)?,
+    ])
+}
+
+pub fn query_pair_info<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<PairInfo> {
+    let pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
+    pair_info.to_normal(&deps)
+}
+
+pub fn query_pool<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<PoolResponse> {
+    let pool = read_pool(&deps.storage)?;
+
+    Ok(PoolResponse {
+        price: decimal_from_sqrt_price(pool.sqrt_price())?,
+        sqrt_price_x96: pool.sqrt_price().to_string(),
+        tick: pool.tick,
+        tick_spacing: pool.tick_spacing,
+        liquidity: pool.liquidity,
+    })
+}
+
+/// Price of asset 0 in asset 1, capped at the largest `Decimal`
+fn decimal_from_sqrt_price(sqrt_price: U256) -> StdResult<Decimal> {
+    let q96 = U256::one() << RESOLUTION;
+    let price_x96 = mul_div(sqrt_price, sqrt_price, q96, false)?;
+    let price_fractional = mul_div(price_x96, U256::from(DECIMAL_FRACTIONAL), q96, false)?
+        .min(U256::from(u128::MAX))
+        .low_u128();
+
+    // `Decimal::from_ratio` would overflow for large prices, so the digits are parsed instead
+    Decimal::from_str(&format!(
+        "{}.{:018}",
+        price_fractional / DECIMAL_FRACTIONAL,
+        price_fractional % DECIMAL_FRACTIONAL
+    ))
+}
+
+pub fn query_ticks<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    start_after: Option<i32>,
+    limit: Option<u32>,
+) -> StdResult<TicksResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+
+    let mut ticks = vec![];
+    for tick in read_ticks(&deps.storage)?
+        .into_iter()
+        .filter(|tick| start_after.map_or(true, |start_after| *tick > start_after))
+        .take(limit)
+    {
+        let info = read_tick_info(&deps.storage, tick)?.unwrap_or_default();
+        ticks.push(TickResponse {
+            tick,
+            liquidity_lower: info.liquidity_lower,
+            liquidity_upper: info.liquidity_upper,
+        });
+    }
+
+    Ok(TicksResponse { ticks })
+}
+
+pub fn query_simulation<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    offer_asset: Asset,
+) -> StdResult<SimulationResponse> {
+    let pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
+    let asset_infos = read_asset_infos(&deps, &pair_info)?;
+    let offer_index = asset_infos
+        .iter()
+        .position(|info| offer_asset.info.equal(info))
+        .ok_or_else(|| StdError::generic_err("Given offer asset doesn't belong to pairs"))?;
+
+    let pair_settings = query_pair_settings(&deps, &pair_info)?;
+    let pool = read_pool(&deps.storage)?;
+    let outcome = compute_swap(
+        &deps.storage,
+        offer_index,
+        offer_asset.amount,
+        &pair_settings.swap_fee,
+    )?;
+
+    Ok(SimulationResponse {
+        return_amount: outcome.return_amount,
+        spread_amount: compute_spread_amount(
+            &pool,
+            offer_index,
+            (offer_asset.amount - outcome.commission_amount)?,
+            outcome.return_amount,
+        ),
+        commission_amount: outcome.commission_amount,
+    })
+}
+
+pub fn query_positions<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: HumanAddr,
+    key: String,
+) -> StdResult<PositionsResponse> {
+    let owner = deps.api.canonical_address(&address)?;
+    assert_viewing_key(&deps.storage, &owner, &key)?;
+
+    let pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
+    let asset_infos = read_asset_infos(&deps, &pair_info)?;
+    let pool = read_pool(&deps.storage)?;
+    let to_assets = |amounts: [Uint128; 2]| -> [Asset; 2] {
+        [
+            Asset {
+                info: asset_infos[0].clone(),
+                amount: amounts[0],
+            },
+            Asset {
+                info: asset_infos[1].clone(),
+                amount: amounts[1],
+            },
+        ]
+    };
+
+    let mut positions = vec![];
+    for position_id in read_owner_positions(&deps.storage, &owner)? {
+        let position = read_position(&deps.storage, position_id)?
+            .ok_or_else(|| StdError::generic_err("Position not found"))?;
+        let amounts = get_amounts_for_liquidity(
+            pool.sqrt_price(),
+            get_sqrt_ratio_at_tick(position.tick_lower)?,
+            get_sqrt_ratio_at_tick(position.tick_upper)?,
+            position.liquidity.u128(),
+            false,
+        )?;
+
+        positions.push(PositionResponse {
+            position_id,
+            tick_lower: position.tick_lower,
+            tick_upper: position.tick_upper,
+            liquidity: position.liquidity,
+            amounts: to_assets(amounts),
+            fees_owed: to_assets(compute_fees_owed(&deps.storage, &position)?),
+        });
+    }
+
+    Ok(PositionsResponse { positions })
+}
+
+/// How much less than the pool price `offer_amount` returned, zero if it returned more
+fn compute_spread_amount(
+    pool: &PoolState,
+    offer_index: usize,
+    offer_amount: Uint128,
+    return_amount: Uint128,
+) -> Uint128 {
+    let spot_return = compute_spot_return(pool, offer_index, offer_amount);
+    Uint128(
+        spot_return
+            .saturating_sub(U256::from(return_amount.u128()))
+            .min(U256::from(u128::MAX))
+            .low_u128(),
+    )
+}
+
+/// If `expected_return` is given, we check against `return_amount`
+/// Else if `belief_price` and `max_spread` both are given,
+/// we compute new spread else we just use the spread against the pool price
+/// to check `max_spread`
+pub fn assert_max_spread(
+    belief_price: Option<Decimal>,
+    max_spread: Option<Decimal>,
+    expected_return: Option<Uint128>,
+    offer_amount: Uint128,
+    return_amount: Uint128,
+    spread_amount: Uint128,
+) -> StdResult<()> {
+    if let Some(expected_return) = expected_return {
+        if return_amount < expected_return {
+            return Err(StdError::generic_err(
+                "Operation fell short of expected_return",
+            ));
+        }
+    } else if let (Some(max_spread), Some(belief_price)) = (max_spread, belief_price) {
+        let belief_price_fractional = decimal_atomics(belief_price)?;
+        if belief_price_fractional == 0 {
+            return Err(StdError::generic_err("Belief price must be positive"));
+        }
+        let expected_return = Uint128(
+            (U256::from(offer_amount.u128()) * U256::from(DECIMAL_FRACTIONAL)
+                / U256::from(belief_price_fractional))
+            .min(U256::from(u128::MAX))
+            .low_u128(),
+        );
+        let spread_amount = (expected_return - return_amount).unwrap_or_else(|_| Uint128::zero());
+
+        if return_amount < expected_return
+            && Decimal::from_ratio(spread_amount, expected_return) > max_spread
+        {
+            return Err(StdError::generic_err(
+                "Operation exceeds max spread limit with belief_price",
+            ));
+        }
+    } else if let Some(max_spread) = max_spread {
+        if Decimal::from_ratio(spread_amount, return_amount + spread_amount) > max_spread {
+            return Err(StdError::generic_err("Operation exceeds max spread limit"));
+        }
+    }
+
+    Ok(())
+}
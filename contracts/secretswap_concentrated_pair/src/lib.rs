@@ -0,0 +1,14 @@
+pub mod contract;
+pub mod msg;
+pub mod position;
+pub mod querier;
+pub mod sqrt_price_math;
+pub mod state;
+pub mod swap;
+pub mod tick_math;
+
+#[cfg(test)]
+mod testing;
+
+#[cfg(all(target_arch = "wasm32", not(feature = "library")))]
+cosmwasm_std::create_entry_points!(contract);
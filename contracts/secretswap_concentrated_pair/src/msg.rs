@@ -0,0 +1,157 @@
+use cosmwasm_std::{Binary, Decimal, HumanAddr, Uint128};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use secretswap::{Asset, Deadline};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HandleMsg {
+    Receive {
+        from: HumanAddr,
+        msg: Option<Binary>,
+        amount: Uint128,
+    },
+    /// Swap an offer asset to the other
+    Swap {
+        offer_asset: Asset,
+        expected_return: Option<Uint128>,
+        belief_price: Option<Decimal>,
+        max_spread: Option<Decimal>,
+        to: Option<HumanAddr>,
+        /// Fails if executed after the deadline
+        deadline: Option<Deadline>,
+    },
+    /// Opens a position of the sender between two ticks with as much liquidity as the
+    /// assets provide. Tokens are taken with `transfer_from` and only what the liquidity
+    /// needs, the rest of the native assets is refunded.
+    OpenPosition {
+        assets: [Asset; 2],
+        tick_lower: i32,
+        tick_upper: i32,
+        /// Fails if less liquidity would be provided
+        min_liquidity: Option<Uint128>,
+        /// Fails if executed after the deadline
+        deadline: Option<Deadline>,
+    },
+    /// Adds liquidity to a position of the sender, like `open_position`
+    IncreaseLiquidity {
+        position_id: u64,
+        assets: [Asset; 2],
+        min_liquidity: Option<Uint128>,
+        /// Fails if executed after the deadline
+        deadline: Option<Deadline>,
+    },
+    /// Removes liquidity from a position of the sender and pays it out with the position's
+    /// fees. A position without liquidity is closed.
+    DecreaseLiquidity {
+        position_id: u64,
+        liquidity: Uint128,
+        /// Fails if less would be paid out, aligned with `asset_infos`, fees excluded
+        min_amounts: Option<[Uint128; 2]>,
+        /// Fails if executed after the deadline
+        deadline: Option<Deadline>,
+    },
+    /// Pays out the fees a position of the sender earned
+    CollectFees { position_id: u64 },
+    /// Viewing key the sender's positions are queried with. Only its hash is stored.
+    SetViewingKey {
+        key: String,
+        padding: Option<String>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Cw20HookMsg {
+    /// Sell a given amount of asset
+    Swap {
+        expected_return: Option<Uint128>,
+        belief_price: Option<Decimal>,
+        max_spread: Option<Decimal>,
+        to: Option<HumanAddr>,
+        /// Fails if executed after the deadline
+        deadline: Option<Deadline>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    Pair {},
+    Pool {},
+    /// Liquidity starting and ending at the initialized ticks, ascending
+    Ticks {
+        start_after: Option<i32>,
+        limit: Option<u32>,
+    },
+    Simulation {
+        offer_asset: Asset,
+    },
+    /// Positions of `address`, authenticated with its viewing key
+    Positions {
+        address: HumanAddr,
+        key: String,
+    },
+    /// Swaps kept for a best-effort swap data endpoint, authenticated by the key the factory
+    /// registered it with. Returns a `SwapDataResponse`.
+    SwapData {
+        endpoint: HumanAddr,
+        key: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+}
+
+/// PoolResponse returns the price and the liquidity in range of it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PoolResponse {
+    /// Price of asset 0 in asset 1
+    pub price: Decimal,
+    /// sqrt of the price as a Q64.96 number, in decimal
+    pub sqrt_price_x96: String,
+    pub tick: i32,
+    pub tick_spacing: u32,
+    pub liquidity: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TickResponse {
+    pub tick: i32,
+    /// Liquidity of the positions whose range starts at the tick
+    pub liquidity_lower: Uint128,
+    /// Liquidity of the positions whose range ends at the tick
+    pub liquidity_upper: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TicksResponse {
+    pub ticks: Vec<TickResponse>,
+}
+
+/// SimulationResponse returns swap simulation response
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SimulationResponse {
+    pub return_amount: Uint128,
+    /// Shortfall of the return against the pool price, after the commission
+    pub spread_amount: Uint128,
+    /// Commission, in the offer asset
+    pub commission_amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PositionResponse {
+    pub position_id: u64,
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    pub liquidity: Uint128,
+    /// What the liquidity would pay out now
+    pub amounts: [Asset; 2],
+    /// Fees that would be collected now
+    pub fees_owed: [Asset; 2],
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PositionsResponse {
+    pub positions: Vec<PositionResponse>,
+}
@@ -0,0 +1,318 @@
+use cosmwasm_std::{CanonicalAddr, StdError, StdResult, Storage, Uint128};
+use primitive_types::U256;
+use secret_toolkit::crypto::sha_256;
+
+use crate::sqrt_price_math::get_amounts_for_liquidity;
+use crate::state::{
+    read_owner_positions, read_pool, read_position, read_position_count, read_tick_info,
+    read_ticks, read_viewing_key, remove_position, remove_tick_info, store_owner_positions,
+    store_pool, store_position, store_position_count, store_tick_info, store_ticks,
+    store_viewing_key, PoolState, Position, TickInfo,
+};
+use crate::tick_math::{get_sqrt_ratio_at_tick, MAX_TICK, MIN_TICK};
+
+/// Ticks positions start or end at, a swap may cross all of them
+pub const MAX_TICKS: usize = 500;
+/// Open positions of a single account
+pub const MAX_POSITIONS_PER_OWNER: usize = 50;
+/// Fee growth is a Q64.64 number
+const FEE_GROWTH_RESOLUTION: usize = 64;
+
+pub fn assert_tick_range(tick_lower: i32, tick_upper: i32, tick_spacing: u32) -> StdResult<()> {
+    if tick_lower >= tick_upper {
+        return Err(StdError::generic_err(
+            "Lower tick must be below the upper tick",
+        ));
+    }
+    if tick_lower < MIN_TICK || tick_upper > MAX_TICK {
+        return Err(StdError::generic_err(format!(
+            "Ticks must be between {} and {}",
+            MIN_TICK, MAX_TICK
+        )));
+    }
+    if tick_lower % tick_spacing as i32 != 0 || tick_upper % tick_spacing as i32 != 0 {
+        return Err(StdError::generic_err(format!(
+            "Ticks must be multiples of the tick spacing {}",
+            tick_spacing
+        )));
+    }
+
+    Ok(())
+}
+
+/// Growth of the fee growth when `fee` is shared by `liquidity`
+pub fn fee_growth_delta(fee: U256, liquidity: Uint128) -> u128 {
+    if liquidity.is_zero() {
+        return 0;
+    }
+    ((fee << FEE_GROWTH_RESOLUTION) / U256::from(liquidity.u128())).low_u128()
+}
+
+/// Fee growth between two ticks, from the global one and what grew outside of each tick
+pub fn fee_growth_inside(
+    pool: &PoolState,
+    tick_lower: i32,
+    lower: &TickInfo,
+    tick_upper: i32,
+    upper: &TickInfo,
+) -> [Uint128; 2] {
+    let mut inside = [Uint128::zero(); 2];
+    for (i, growth) in inside.iter_mut().enumerate() {
+        let global = pool.fee_growth_global[i].u128();
+        let below = if pool.tick >= tick_lower {
+            lower.fee_growth_outside[i].u128()
+        } else {
+            global.wrapping_sub(lower.fee_growth_outside[i].u128())
+        };
+        let above = if pool.tick < tick_upper {
+            upper.fee_growth_outside[i].u128()
+        } else {
+            global.wrapping_sub(upper.fee_growth_outside[i].u128())
+        };
+        *growth = Uint128(global.wrapping_sub(below).wrapping_sub(above));
+    }
+    inside
+}
+
+/// Adds the fees the liquidity of the position earned since they were last settled
+fn settle_fees(position: &mut Position, fee_growth_inside: [Uint128; 2]) {
+    for i in 0..2 {
+        let growth = fee_growth_inside[i]
+            .u128()
+            .wrapping_sub(position.fee_growth_inside_last[i].u128());
+        let earned =
+            (U256::from(position.liquidity.u128()) * U256::from(growth)) >> FEE_GROWTH_RESOLUTION;
+        position.fees_owed[i] = position.fees_owed[i] + Uint128(earned.low_u128());
+    }
+    position.fee_growth_inside_last = fee_growth_inside;
+}
+
+/// Adds liquidity starting or ending at `tick`, a tick without any is initialized first
+fn update_tick<S: Storage>(
+    storage: &mut S,
+    pool: &PoolState,
+    ticks: &mut Vec<i32>,
+    tick: i32,
+    liquidity_delta: Uint128,
+    is_upper: bool,
+    add: bool,
+) -> StdResult<TickInfo> {
+    let mut info = match read_tick_info(storage, tick)? {
+        Some(info) => info,
+        None if add => {
+            if ticks.len() >= MAX_TICKS {
+                return Err(StdError::generic_err("Too many initialized ticks"));
+            }
+            if let Err(index) = ticks.binary_search(&tick) {
+                ticks.insert(index, tick);
+            }
+            // by convention all fees so far grew below the tick
+            TickInfo {
+                fee_growth_outside: if tick <= pool.tick {
+                    pool.fee_growth_global
+                } else {
+                    [Uint128::zero(); 2]
+                },
+                ..TickInfo::default()
+            }
+        }
+        None => return Err(StdError::generic_err("Tick is not initialized")),
+    };
+
+    let liquidity = if is_upper {
+        &mut info.liquidity_upper
+    } else {
+        &mut info.liquidity_lower
+    };
+    *liquidity = if add {
+        *liquidity + liquidity_delta
+    } else {
+        (*liquidity - liquidity_delta)?
+    };
+    store_tick_info(storage, tick, &info)?;
+
+    Ok(info)
+}
+
+/// Adds `liquidity_delta` to the position, or removes it, after settling its fees.
+/// Returns the amounts the liquidity takes, rounded up, or frees, rounded down, aligned with
+/// `asset_infos`. The position itself is not stored.
+pub fn modify_position<S: Storage>(
+    storage: &mut S,
+    position: &mut Position,
+    liquidity_delta: Uint128,
+    add: bool,
+) -> StdResult<[Uint128; 2]> {
+    let mut pool = read_pool(storage)?;
+    let mut ticks = read_ticks(storage)?;
+
+    let lower = update_tick(
+        storage,
+        &pool,
+        &mut ticks,
+        position.tick_lower,
+        liquidity_delta,
+        false,
+        add,
+    )?;
+    let upper = update_tick(
+        storage,
+        &pool,
+        &mut ticks,
+        position.tick_upper,
+        liquidity_delta,
+        true,
+        add,
+    )?;
+
+    settle_fees(
+        position,
+        fee_growth_inside(
+            &pool,
+            position.tick_lower,
+            &lower,
+            position.tick_upper,
+            &upper,
+        ),
+    );
+    position.liquidity = if add {
+        position.liquidity + liquidity_delta
+    } else {
+        (position.liquidity - liquidity_delta)?
+    };
+
+    // ticks no position starts or ends at anymore
+    if !add {
+        for (tick, info) in [(position.tick_lower, &lower), (position.tick_upper, &upper)].iter() {
+            if info.liquidity_gross().is_zero() {
+                remove_tick_info(storage, *tick);
+                ticks.retain(|t| t != tick);
+            }
+        }
+    }
+    store_ticks(storage, &ticks)?;
+
+    if pool.tick >= position.tick_lower && pool.tick < position.tick_upper {
+        pool.liquidity = if add {
+            pool.liquidity + liquidity_delta
+        } else {
+            (pool.liquidity - liquidity_delta)?
+        };
+        store_pool(storage, &pool)?;
+    }
+
+    get_amounts_for_liquidity(
+        pool.sqrt_price(),
+        get_sqrt_ratio_at_tick(position.tick_lower)?,
+        get_sqrt_ratio_at_tick(position.tick_upper)?,
+        liquidity_delta.u128(),
+        add,
+    )
+}
+
+/// Fees of the position that would be collected now, aligned with `asset_infos`
+pub fn compute_fees_owed<S: Storage>(storage: &S, position: &Position) -> StdResult<[Uint128; 2]> {
+    let pool = read_pool(storage)?;
+    let lower = read_tick_info(storage, position.tick_lower)?.unwrap_or_default();
+    let upper = read_tick_info(storage, position.tick_upper)?.unwrap_or_default();
+
+    let fee_growth_inside = fee_growth_inside(
+        &pool,
+        position.tick_lower,
+        &lower,
+        position.tick_upper,
+        &upper,
+    );
+    let mut position = position.clone();
+    settle_fees(&mut position, fee_growth_inside);
+    Ok(position.fees_owed)
+}
+
+/// Empty position of `owner` between two ticks, with a new id
+pub fn create_position<S: Storage>(
+    storage: &mut S,
+    owner: &CanonicalAddr,
+    tick_lower: i32,
+    tick_upper: i32,
+) -> StdResult<(u64, Position)> {
+    let mut owner_positions = read_owner_positions(storage, owner)?;
+    if owner_positions.len() >= MAX_POSITIONS_PER_OWNER {
+        return Err(StdError::generic_err("Too many open positions"));
+    }
+
+    let position_id = read_position_count(storage)?;
+    store_position_count(storage, position_id + 1)?;
+    owner_positions.push(position_id);
+    store_owner_positions(storage, owner, &owner_positions)?;
+
+    Ok((
+        position_id,
+        Position {
+            owner: owner.clone(),
+            tick_lower,
+            tick_upper,
+            liquidity: Uint128::zero(),
+            fee_growth_inside_last: [Uint128::zero(); 2],
+            fees_owed: [Uint128::zero(); 2],
+        },
+    ))
+}
+
+/// Position of `owner`. Positions of other accounts are reported as not found, so their ids
+/// aren't revealed.
+pub fn load_position<S: Storage>(
+    storage: &S,
+    owner: &CanonicalAddr,
+    position_id: u64,
+) -> StdResult<Position> {
+    match read_position(storage, position_id)? {
+        Some(position) if &position.owner == owner => Ok(position),
+        _ => Err(StdError::generic_err("Position not found")),
+    }
+}
+
+/// Stores the position, or removes it once it has neither liquidity nor fees left
+pub fn save_position<S: Storage>(
+    storage: &mut S,
+    position_id: u64,
+    position: &Position,
+) -> StdResult<()> {
+    if !position.liquidity.is_zero() || position.fees_owed.iter().any(|fee| !fee.is_zero()) {
+        return store_position(storage, position_id, position);
+    }
+
+    remove_position(storage, position_id);
+    let mut owner_positions = read_owner_positions(storage, &position.owner)?;
+    owner_positions.retain(|id| *id != position_id);
+    store_owner_positions(storage, &position.owner, &owner_positions)
+}
+
+pub fn set_viewing_key<S: Storage>(
+    storage: &mut S,
+    account: &CanonicalAddr,
+    key: &str,
+) -> StdResult<()> {
+    store_viewing_key(storage, account, &sha_256(key.as_bytes()))
+}
+
+/// Compares the hashes in constant time, so the key can't be guessed byte by byte
+pub fn assert_viewing_key<S: Storage>(
+    storage: &S,
+    account: &CanonicalAddr,
+    key: &str,
+) -> StdResult<()> {
+    let key_hash = sha_256(key.as_bytes());
+    let stored_hash = read_viewing_key(storage, account)?.unwrap_or_else(|| vec![0u8; 32]);
+
+    let mut diff = (stored_hash.len() ^ key_hash.len()) as u8;
+    for (a, b) in stored_hash.iter().zip(key_hash.iter()) {
+        diff |= a ^ b;
+    }
+    if diff != 0 {
+        return Err(StdError::generic_err(
+            "Wrong viewing key for this address or viewing key not set",
+        ));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,33 @@
+use cosmwasm_std::{to_binary, Api, Extern, Querier, QueryRequest, StdResult, Storage, WasmQuery};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use secretswap::{AssetInfo, PairInfoRaw, PairSettings};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryFactory {
+    PairSettings { asset_infos: Option<[AssetInfo; 2]> },
+}
+
+/// Settings the factory applies to the assets of this pair, including its swap fee override
+/// if any
+pub fn query_pair_settings<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    pair_info: &PairInfoRaw,
+) -> StdResult<PairSettings> {
+    let asset_infos = [
+        pair_info.asset_infos[0].to_normal(&deps)?,
+        pair_info.asset_infos[1].to_normal(&deps)?,
+    ];
+    let pair_settings: PairSettings =
+        deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+            callback_code_hash: pair_info.factory.code_hash.clone(),
+            contract_addr: pair_info.factory.address.clone(),
+            msg: to_binary(&QueryFactory::PairSettings {
+                asset_infos: Some(asset_infos),
+            })?,
+        }))?;
+
+    Ok(pair_settings)
+}
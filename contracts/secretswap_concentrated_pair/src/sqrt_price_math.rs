@@ -0,0 +1,265 @@
+use std::convert::TryFrom;
+
+use cosmwasm_std::{StdError, StdResult, Uint128};
+use primitive_types::{U256, U512};
+
+use secretswap::Fee;
+
+/// Sqrt prices are Q64.96 fixed point numbers
+pub const RESOLUTION: usize = 96;
+
+fn q96() -> U256 {
+    U256::one() << RESOLUTION
+}
+
+/// `a * b / denominator` without overflowing the product
+pub fn mul_div(a: U256, b: U256, denominator: U256, round_up: bool) -> StdResult<U256> {
+    if denominator.is_zero() {
+        return Err(StdError::generic_err("Division by zero"));
+    }
+
+    let product = U512::from(a) * U512::from(b);
+    let denominator = U512::from(denominator);
+    let mut result = product / denominator;
+    if round_up && !(product % denominator).is_zero() {
+        result += U512::one();
+    }
+
+    U256::try_from(result).map_err(|_| StdError::generic_err("Amount overflow"))
+}
+
+fn div_rounding_up(a: U256, b: U256) -> U256 {
+    let result = a / b;
+    if (a % b).is_zero() {
+        result
+    } else {
+        result + U256::one()
+    }
+}
+
+pub fn to_uint128(amount: U256) -> StdResult<Uint128> {
+    if amount > U256::from(u128::MAX) {
+        return Err(StdError::generic_err("Amount overflow"));
+    }
+    Ok(Uint128(amount.low_u128()))
+}
+
+/// Amount of asset 0 between two sqrt prices for `liquidity`, L * (1/sqrt(a) - 1/sqrt(b))
+pub fn get_amount0_delta(
+    sqrt_price_a: U256,
+    sqrt_price_b: U256,
+    liquidity: u128,
+    round_up: bool,
+) -> StdResult<U256> {
+    let (lower, upper) = if sqrt_price_a < sqrt_price_b {
+        (sqrt_price_a, sqrt_price_b)
+    } else {
+        (sqrt_price_b, sqrt_price_a)
+    };
+    if lower.is_zero() {
+        return Err(StdError::generic_err("Price must be positive"));
+    }
+
+    let numerator = U256::from(liquidity) << RESOLUTION;
+    let amount = mul_div(numerator, upper - lower, upper, round_up)?;
+    Ok(if round_up {
+        div_rounding_up(amount, lower)
+    } else {
+        amount / lower
+    })
+}
+
+/// Amount of asset 1 between two sqrt prices for `liquidity`, L * (sqrt(b) - sqrt(a))
+pub fn get_amount1_delta(
+    sqrt_price_a: U256,
+    sqrt_price_b: U256,
+    liquidity: u128,
+    round_up: bool,
+) -> StdResult<U256> {
+    let (lower, upper) = if sqrt_price_a < sqrt_price_b {
+        (sqrt_price_a, sqrt_price_b)
+    } else {
+        (sqrt_price_b, sqrt_price_a)
+    };
+
+    mul_div(U256::from(liquidity), upper - lower, q96(), round_up)
+}
+
+/// Sqrt price after `amount_in` is added to the pool. Rounded towards the current price, so
+/// the input always covers the move. `liquidity` must be positive.
+pub fn get_next_sqrt_price_from_input(
+    sqrt_price: U256,
+    liquidity: u128,
+    amount_in: U256,
+    zero_for_one: bool,
+) -> StdResult<U256> {
+    if liquidity == 0 {
+        return Err(StdError::generic_err("No liquidity in range"));
+    }
+    if amount_in.is_zero() {
+        return Ok(sqrt_price);
+    }
+
+    if zero_for_one {
+        // L * sqrt(P) / (L + amount * sqrt(P)), rounded up
+        let numerator = U512::from(U256::from(liquidity) << RESOLUTION);
+        let denominator = numerator + U512::from(amount_in) * U512::from(sqrt_price);
+        let product = numerator * U512::from(sqrt_price);
+        let mut result = product / denominator;
+        if !(product % denominator).is_zero() {
+            result += U512::one();
+        }
+        U256::try_from(result).map_err(|_| StdError::generic_err("Price overflow"))
+    } else {
+        // sqrt(P) + amount / L, rounded down
+        let quotient = mul_div(amount_in, q96(), U256::from(liquidity), false)?;
+        sqrt_price
+            .checked_add(quotient)
+            .ok_or_else(|| StdError::generic_err("Price overflow"))
+    }
+}
+
+/// Liquidity the amounts provide between two sqrt prices at the current one, the largest
+/// that neither amount limits
+pub fn get_liquidity_for_amounts(
+    sqrt_price: U256,
+    sqrt_price_lower: U256,
+    sqrt_price_upper: U256,
+    amounts: [Uint128; 2],
+) -> StdResult<u128> {
+    let liquidity0 = |lower: U256, upper: U256| -> StdResult<U256> {
+        let intermediate = mul_div(lower, upper, q96(), false)?;
+        mul_div(
+            U256::from(amounts[0].u128()),
+            intermediate,
+            upper - lower,
+            false,
+        )
+    };
+    let liquidity1 = |lower: U256, upper: U256| -> StdResult<U256> {
+        mul_div(U256::from(amounts[1].u128()), q96(), upper - lower, false)
+    };
+
+    let liquidity = if sqrt_price <= sqrt_price_lower {
+        liquidity0(sqrt_price_lower, sqrt_price_upper)?
+    } else if sqrt_price < sqrt_price_upper {
+        liquidity0(sqrt_price, sqrt_price_upper)?.min(liquidity1(sqrt_price_lower, sqrt_price)?)
+    } else {
+        liquidity1(sqrt_price_lower, sqrt_price_upper)?
+    };
+
+    Ok(to_uint128(liquidity)
+        .map_err(|_| StdError::generic_err("Liquidity overflow"))?
+        .u128())
+}
+
+/// Amounts aligned with `asset_infos` that `liquidity` between two sqrt prices holds at the
+/// current one. Rounded up for deposits and down for withdrawals.
+pub fn get_amounts_for_liquidity(
+    sqrt_price: U256,
+    sqrt_price_lower: U256,
+    sqrt_price_upper: U256,
+    liquidity: u128,
+    round_up: bool,
+) -> StdResult<[Uint128; 2]> {
+    let (amount0, amount1) = if sqrt_price <= sqrt_price_lower {
+        (
+            get_amount0_delta(sqrt_price_lower, sqrt_price_upper, liquidity, round_up)?,
+            U256::zero(),
+        )
+    } else if sqrt_price < sqrt_price_upper {
+        (
+            get_amount0_delta(sqrt_price, sqrt_price_upper, liquidity, round_up)?,
+            get_amount1_delta(sqrt_price_lower, sqrt_price, liquidity, round_up)?,
+        )
+    } else {
+        (
+            U256::zero(),
+            get_amount1_delta(sqrt_price_lower, sqrt_price_upper, liquidity, round_up)?,
+        )
+    };
+
+    Ok([to_uint128(amount0)?, to_uint128(amount1)?])
+}
+
+/// Result of swapping within a price range of constant liquidity
+#[derive(Clone, Debug, PartialEq)]
+pub struct SwapStep {
+    pub sqrt_price_next: U256,
+    pub amount_in: U256,
+    pub amount_out: U256,
+    /// Commission, taken from the input
+    pub fee_amount: U256,
+}
+
+/// Swaps `amount_remaining` towards `sqrt_price_target`, as far as it gets. The direction
+/// follows from the prices, asset 0 is sold when the target is below the current price.
+pub fn compute_swap_step(
+    sqrt_price: U256,
+    sqrt_price_target: U256,
+    liquidity: u128,
+    amount_remaining: U256,
+    swap_fee: &Fee,
+) -> StdResult<SwapStep> {
+    let fee_nom = U256::from(swap_fee.commission_rate_nom.u128());
+    let fee_denom = U256::from(swap_fee.commission_rate_denom.u128());
+    if fee_nom >= fee_denom {
+        return Err(StdError::generic_err("Swap fee must be lower than 100%"));
+    }
+    let zero_for_one = sqrt_price >= sqrt_price_target;
+
+    let amount_remaining_less_fee =
+        mul_div(amount_remaining, fee_denom - fee_nom, fee_denom, false)?;
+    let amount_in_to_target = if zero_for_one {
+        get_amount0_delta(sqrt_price_target, sqrt_price, liquidity, true)?
+    } else {
+        get_amount1_delta(sqrt_price, sqrt_price_target, liquidity, true)?
+    };
+
+    let sqrt_price_next = if amount_remaining_less_fee >= amount_in_to_target {
+        sqrt_price_target
+    } else {
+        get_next_sqrt_price_from_input(
+            sqrt_price,
+            liquidity,
+            amount_remaining_less_fee,
+            zero_for_one,
+        )?
+    };
+    let reached_target = sqrt_price_next == sqrt_price_target;
+
+    let (amount_in, amount_out) = if zero_for_one {
+        (
+            if reached_target {
+                amount_in_to_target
+            } else {
+                get_amount0_delta(sqrt_price_next, sqrt_price, liquidity, true)?
+            },
+            get_amount1_delta(sqrt_price_next, sqrt_price, liquidity, false)?,
+        )
+    } else {
+        (
+            if reached_target {
+                amount_in_to_target
+            } else {
+                get_amount1_delta(sqrt_price, sqrt_price_next, liquidity, true)?
+            },
+            get_amount0_delta(sqrt_price, sqrt_price_next, liquidity, false)?,
+        )
+    };
+
+    // the whole rest is the commission when the price stops short of the target
+    let fee_amount = if reached_target {
+        // rounding up must not take more than what is left
+        mul_div(amount_in, fee_nom, fee_denom - fee_nom, true)?.min(amount_remaining - amount_in)
+    } else {
+        amount_remaining - amount_in
+    };
+
+    Ok(SwapStep {
+        sqrt_price_next,
+        amount_in,
+        amount_out,
+        fee_amount,
+    })
+}
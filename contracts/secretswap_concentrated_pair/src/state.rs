@@ -0,0 +1,167 @@
+use cosmwasm_std::{CanonicalAddr, StdResult, Storage, Uint128};
+use cosmwasm_storage::{Bucket, ReadonlyBucket, ReadonlySingleton, Singleton};
+use primitive_types::U256;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use secretswap::PairInfoRaw;
+
+static KEY_PAIR_INFO: &[u8] = b"pair_info";
+static KEY_POOL: &[u8] = b"pool";
+static KEY_TICKS: &[u8] = b"ticks";
+static PREFIX_TICK_INFO: &[u8] = b"tick_info";
+static KEY_POSITION_COUNT: &[u8] = b"position_count";
+static PREFIX_POSITIONS: &[u8] = b"positions";
+static PREFIX_OWNER_POSITIONS: &[u8] = b"owner_positions";
+static PREFIX_VIEWING_KEYS: &[u8] = b"viewing_keys";
+
+pub fn store_pair_info<S: Storage>(storage: &mut S, data: &PairInfoRaw) -> StdResult<()> {
+    Singleton::new(storage, KEY_PAIR_INFO).save(data)
+}
+
+pub fn read_pair_info<S: Storage>(storage: &S) -> StdResult<PairInfoRaw> {
+    ReadonlySingleton::new(storage, KEY_PAIR_INFO).load()
+}
+
+/// Price and liquidity of the pool
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PoolState {
+    /// sqrt of the price of asset 0 in asset 1 as a Q64.96 number, in little endian words
+    pub sqrt_price: [u64; 4],
+    /// Greatest tick at or below the price
+    pub tick: i32,
+    pub tick_spacing: u32,
+    /// Liquidity of the positions in range of the price
+    pub liquidity: Uint128,
+    /// Commission per unit of liquidity as Q64.64 numbers, aligned with `asset_infos`.
+    /// They wrap around, only differences are meaningful.
+    pub fee_growth_global: [Uint128; 2],
+}
+
+impl PoolState {
+    pub fn sqrt_price(&self) -> U256 {
+        U256(self.sqrt_price)
+    }
+
+    pub fn set_sqrt_price(&mut self, sqrt_price: U256) {
+        self.sqrt_price = sqrt_price.0;
+    }
+}
+
+pub fn store_pool<S: Storage>(storage: &mut S, data: &PoolState) -> StdResult<()> {
+    Singleton::new(storage, KEY_POOL).save(data)
+}
+
+pub fn read_pool<S: Storage>(storage: &S) -> StdResult<PoolState> {
+    ReadonlySingleton::new(storage, KEY_POOL).load()
+}
+
+/// Ticks some position starts or ends at, ascending
+pub fn store_ticks<S: Storage>(storage: &mut S, data: &[i32]) -> StdResult<()> {
+    Singleton::new(storage, KEY_TICKS).save(&data.to_vec())
+}
+
+pub fn read_ticks<S: Storage>(storage: &S) -> StdResult<Vec<i32>> {
+    Ok(ReadonlySingleton::new(storage, KEY_TICKS)
+        .may_load()?
+        .unwrap_or_default())
+}
+
+/// Liquidity starting and ending at a tick
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default, JsonSchema)]
+pub struct TickInfo {
+    /// Liquidity of the positions whose range starts at the tick
+    pub liquidity_lower: Uint128,
+    /// Liquidity of the positions whose range ends at the tick
+    pub liquidity_upper: Uint128,
+    /// Fee growth on the other side of the tick than the price, relative to the price
+    pub fee_growth_outside: [Uint128; 2],
+}
+
+impl TickInfo {
+    pub fn liquidity_gross(&self) -> Uint128 {
+        self.liquidity_lower + self.liquidity_upper
+    }
+}
+
+pub fn store_tick_info<S: Storage>(storage: &mut S, tick: i32, data: &TickInfo) -> StdResult<()> {
+    Bucket::new(PREFIX_TICK_INFO, storage).save(&tick.to_be_bytes(), data)
+}
+
+pub fn read_tick_info<S: Storage>(storage: &S, tick: i32) -> StdResult<Option<TickInfo>> {
+    ReadonlyBucket::new(PREFIX_TICK_INFO, storage).may_load(&tick.to_be_bytes())
+}
+
+pub fn remove_tick_info<S: Storage>(storage: &mut S, tick: i32) {
+    Bucket::<S, TickInfo>::new(PREFIX_TICK_INFO, storage).remove(&tick.to_be_bytes())
+}
+
+/// Liquidity an account provides between two ticks
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Position {
+    pub owner: CanonicalAddr,
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    pub liquidity: Uint128,
+    /// Fee growth inside the range when the fees were last settled
+    pub fee_growth_inside_last: [Uint128; 2],
+    /// Settled fees not collected yet
+    pub fees_owed: [Uint128; 2],
+}
+
+pub fn store_position_count<S: Storage>(storage: &mut S, count: u64) -> StdResult<()> {
+    Singleton::new(storage, KEY_POSITION_COUNT).save(&count)
+}
+
+pub fn read_position_count<S: Storage>(storage: &S) -> StdResult<u64> {
+    Ok(ReadonlySingleton::new(storage, KEY_POSITION_COUNT)
+        .may_load()?
+        .unwrap_or_default())
+}
+
+pub fn store_position<S: Storage>(
+    storage: &mut S,
+    position_id: u64,
+    data: &Position,
+) -> StdResult<()> {
+    Bucket::new(PREFIX_POSITIONS, storage).save(&position_id.to_be_bytes(), data)
+}
+
+pub fn read_position<S: Storage>(storage: &S, position_id: u64) -> StdResult<Option<Position>> {
+    ReadonlyBucket::new(PREFIX_POSITIONS, storage).may_load(&position_id.to_be_bytes())
+}
+
+pub fn remove_position<S: Storage>(storage: &mut S, position_id: u64) {
+    Bucket::<S, Position>::new(PREFIX_POSITIONS, storage).remove(&position_id.to_be_bytes())
+}
+
+/// Ids of the open positions of an account
+pub fn store_owner_positions<S: Storage>(
+    storage: &mut S,
+    owner: &CanonicalAddr,
+    position_ids: &[u64],
+) -> StdResult<()> {
+    Bucket::new(PREFIX_OWNER_POSITIONS, storage).save(owner.as_slice(), &position_ids.to_vec())
+}
+
+pub fn read_owner_positions<S: Storage>(storage: &S, owner: &CanonicalAddr) -> StdResult<Vec<u64>> {
+    Ok(ReadonlyBucket::new(PREFIX_OWNER_POSITIONS, storage)
+        .may_load(owner.as_slice())?
+        .unwrap_or_default())
+}
+
+/// Hash of the viewing key of an account
+pub fn store_viewing_key<S: Storage>(
+    storage: &mut S,
+    account: &CanonicalAddr,
+    key_hash: &[u8],
+) -> StdResult<()> {
+    Bucket::new(PREFIX_VIEWING_KEYS, storage).save(account.as_slice(), &key_hash.to_vec())
+}
+
+pub fn read_viewing_key<S: Storage>(
+    storage: &S,
+    account: &CanonicalAddr,
+) -> StdResult<Option<Vec<u8>>> {
+    ReadonlyBucket::new(PREFIX_VIEWING_KEYS, storage).may_load(account.as_slice())
+}
@@ -0,0 +1,135 @@
+use cosmwasm_std::{StdError, StdResult, Storage, Uint128};
+use primitive_types::U256;
+
+use secretswap::Fee;
+
+use crate::position::fee_growth_delta;
+use crate::sqrt_price_math::{compute_swap_step, mul_div, to_uint128, RESOLUTION};
+use crate::state::{
+    read_pool, read_tick_info, read_ticks, store_pool, store_tick_info, PoolState, TickInfo,
+};
+use crate::tick_math::{
+    get_sqrt_ratio_at_tick, get_tick_at_sqrt_ratio, MAX_SQRT_RATIO, MIN_SQRT_RATIO,
+};
+
+/// Swap computed against the stored pool, applied with `apply_swap`
+#[derive(Clone, Debug, PartialEq)]
+pub struct SwapOutcome {
+    pub return_amount: Uint128,
+    /// Commission, in the offer asset
+    pub commission_amount: Uint128,
+    /// Pool after the swap
+    pub pool: PoolState,
+    /// Ticks the price moved across, with their updated fee growth
+    pub crossed_ticks: Vec<(i32, TickInfo)>,
+}
+
+/// Sells `offer_amount` of the asset at `offer_index`, range by range of constant liquidity.
+/// Each range's commission is shared by the liquidity in it.
+pub fn compute_swap<S: Storage>(
+    storage: &S,
+    offer_index: usize,
+    offer_amount: Uint128,
+    swap_fee: &Fee,
+) -> StdResult<SwapOutcome> {
+    // asset 0 is sold for asset 1 when the price of asset 0 falls
+    let zero_for_one = offer_index == 0;
+    let mut pool = read_pool(storage)?;
+    let ticks = read_ticks(storage)?;
+
+    let mut crossed_ticks = vec![];
+    let mut amount_remaining = U256::from(offer_amount.u128());
+    let mut return_amount = U256::zero();
+    let mut commission_amount = U256::zero();
+    while !amount_remaining.is_zero() {
+        let next_tick = if zero_for_one {
+            ticks.iter().rev().find(|tick| **tick <= pool.tick)
+        } else {
+            ticks.iter().find(|tick| **tick > pool.tick)
+        }
+        .copied();
+        let sqrt_price_target = match next_tick {
+            Some(tick) => get_sqrt_ratio_at_tick(tick)?,
+            None if zero_for_one => MIN_SQRT_RATIO,
+            None => MAX_SQRT_RATIO,
+        };
+
+        let step = compute_swap_step(
+            pool.sqrt_price(),
+            sqrt_price_target,
+            pool.liquidity.u128(),
+            amount_remaining,
+            swap_fee,
+        )?;
+        amount_remaining = amount_remaining - step.amount_in - step.fee_amount;
+        return_amount += step.amount_out;
+        commission_amount += step.fee_amount;
+
+        let growth = fee_growth_delta(step.fee_amount, pool.liquidity);
+        pool.fee_growth_global[offer_index] = Uint128(
+            pool.fee_growth_global[offer_index]
+                .u128()
+                .wrapping_add(growth),
+        );
+        pool.set_sqrt_price(step.sqrt_price_next);
+
+        match next_tick {
+            Some(tick) if step.sqrt_price_next == sqrt_price_target => {
+                let mut info = read_tick_info(storage, tick)?
+                    .ok_or_else(|| StdError::generic_err("Tick is not initialized"))?;
+                // the fees that grew on the other side of the tick are now on this side
+                for i in 0..2 {
+                    info.fee_growth_outside[i] = Uint128(
+                        pool.fee_growth_global[i]
+                            .u128()
+                            .wrapping_sub(info.fee_growth_outside[i].u128()),
+                    );
+                }
+                // ranges starting at the tick are entered going up and left going down
+                pool.liquidity = if zero_for_one {
+                    ((pool.liquidity + info.liquidity_upper) - info.liquidity_lower)?
+                } else {
+                    ((pool.liquidity + info.liquidity_lower) - info.liquidity_upper)?
+                };
+                pool.tick = if zero_for_one { tick - 1 } else { tick };
+                crossed_ticks.push((tick, info));
+            }
+            // the price reached the end of the tick range
+            None if step.sqrt_price_next == sqrt_price_target => {
+                return Err(StdError::generic_err("Not enough liquidity for this swap"));
+            }
+            _ => pool.tick = get_tick_at_sqrt_ratio(step.sqrt_price_next)?,
+        }
+    }
+
+    Ok(SwapOutcome {
+        return_amount: to_uint128(return_amount)?,
+        commission_amount: to_uint128(commission_amount)?,
+        pool,
+        crossed_ticks,
+    })
+}
+
+pub fn apply_swap<S: Storage>(storage: &mut S, outcome: &SwapOutcome) -> StdResult<()> {
+    for (tick, info) in outcome.crossed_ticks.iter() {
+        store_tick_info(storage, *tick, info)?;
+    }
+    store_pool(storage, &outcome.pool)
+}
+
+/// Amount of the other asset `offer_amount` of the asset at `offer_index` is worth at the
+/// price of the pool, before any commission and price impact
+pub fn compute_spot_return(pool: &PoolState, offer_index: usize, offer_amount: Uint128) -> U256 {
+    let sqrt_price = pool.sqrt_price();
+    let offer_amount = U256::from(offer_amount.u128());
+    let q96 = U256::one() << RESOLUTION;
+    // price = sqrt_price^2 / 2^192, applied in two steps that fit in 512 bits
+    let spot_return = if offer_index == 0 {
+        mul_div(offer_amount, sqrt_price, q96, false)
+            .and_then(|partial| mul_div(partial, sqrt_price, q96, false))
+    } else {
+        mul_div(offer_amount, q96, sqrt_price, false)
+            .and_then(|partial| mul_div(partial, q96, sqrt_price, false))
+    };
+    spot_return.unwrap_or(U256::MAX)
+}
@@ -0,0 +1,246 @@
+use cosmwasm_std::testing::MockStorage;
+use cosmwasm_std::{Binary, CanonicalAddr, Decimal, Uint128};
+use primitive_types::U256;
+
+use secretswap::Fee;
+
+use crate::contract::assert_max_spread;
+use crate::position::{
+    assert_tick_range, assert_viewing_key, compute_fees_owed, create_position, load_position,
+    modify_position, save_position, set_viewing_key,
+};
+use crate::sqrt_price_math::{
+    compute_swap_step, get_amount0_delta, get_amount1_delta, get_liquidity_for_amounts,
+};
+use crate::state::{read_owner_positions, read_pool, read_ticks, store_pool, PoolState};
+use crate::swap::{apply_swap, compute_swap};
+use crate::tick_math::{
+    get_sqrt_ratio_at_tick, get_tick_at_sqrt_ratio, MAX_SQRT_RATIO, MAX_TICK, MIN_SQRT_RATIO,
+    MIN_TICK,
+};
+
+fn q96() -> U256 {
+    U256::one() << 96
+}
+
+fn swap_fee() -> Fee {
+    Fee {
+        commission_rate_nom: Uint128(3),
+        commission_rate_denom: Uint128(1000),
+    }
+}
+
+#[test]
+fn test_tick_math() {
+    assert_eq!(get_sqrt_ratio_at_tick(0).unwrap(), q96());
+    assert_eq!(get_sqrt_ratio_at_tick(MIN_TICK).unwrap(), MIN_SQRT_RATIO);
+    assert_eq!(get_sqrt_ratio_at_tick(MAX_TICK).unwrap(), MAX_SQRT_RATIO);
+    get_sqrt_ratio_at_tick(MIN_TICK - 1).unwrap_err();
+    get_sqrt_ratio_at_tick(MAX_TICK + 1).unwrap_err();
+
+    // the tick is the greatest one at or below the price
+    for tick in [MIN_TICK, -100_000, -1, 0, 1, 60, 100_000, MAX_TICK - 1].iter() {
+        let sqrt_price = get_sqrt_ratio_at_tick(*tick).unwrap();
+        assert_eq!(get_tick_at_sqrt_ratio(sqrt_price).unwrap(), *tick);
+        assert_eq!(get_tick_at_sqrt_ratio(sqrt_price + 1).unwrap(), *tick);
+        if *tick > MIN_TICK {
+            assert_eq!(get_tick_at_sqrt_ratio(sqrt_price - 1).unwrap(), *tick - 1);
+        }
+    }
+    get_tick_at_sqrt_ratio(MIN_SQRT_RATIO - 1).unwrap_err();
+    get_tick_at_sqrt_ratio(MAX_SQRT_RATIO).unwrap_err();
+
+    assert_tick_range(-60, 60, 60).unwrap();
+    assert_tick_range(60, 60, 60).unwrap_err();
+    assert_tick_range(-60, 50, 60).unwrap_err();
+    assert_tick_range(MIN_TICK - 1, 0, 1).unwrap_err();
+}
+
+#[test]
+fn test_swap_step() {
+    // a price of 1 to 1.21 with a liquidity of 1000
+    let sqrt_price_a = q96();
+    let sqrt_price_b = q96() * 11 / 10;
+    assert_eq!(
+        get_amount0_delta(sqrt_price_a, sqrt_price_b, 1000, false).unwrap(),
+        U256::from(90)
+    );
+    assert_eq!(
+        get_amount0_delta(sqrt_price_a, sqrt_price_b, 1000, true).unwrap(),
+        U256::from(91)
+    );
+    assert_eq!(
+        get_amount1_delta(sqrt_price_b, sqrt_price_a, 1000, true).unwrap(),
+        U256::from(100)
+    );
+    assert_eq!(
+        get_liquidity_for_amounts(
+            q96(),
+            get_sqrt_ratio_at_tick(-600).unwrap(),
+            get_sqrt_ratio_at_tick(600).unwrap(),
+            [Uint128(1000), Uint128(0)],
+        )
+        .unwrap(),
+        0
+    );
+
+    // the target is out of reach, all of the input is used
+    let step = compute_swap_step(
+        q96(),
+        sqrt_price_b,
+        1_000_000,
+        U256::from(1000),
+        &swap_fee(),
+    )
+    .unwrap();
+    assert_eq!(step.amount_in + step.fee_amount, U256::from(1000));
+    assert_eq!(step.fee_amount, U256::from(3));
+    assert_eq!(step.amount_out, U256::from(996));
+    assert!(step.sqrt_price_next > q96() && step.sqrt_price_next < sqrt_price_b);
+
+    // the target is reached, the rest of the input is left
+    let step = compute_swap_step(q96(), sqrt_price_b, 1000, U256::from(1000), &swap_fee()).unwrap();
+    assert_eq!(step.sqrt_price_next, sqrt_price_b);
+    assert_eq!(step.amount_in, U256::from(100));
+    assert_eq!(step.amount_out, U256::from(90));
+    assert!(step.amount_in + step.fee_amount < U256::from(1000));
+}
+
+#[test]
+fn test_positions_and_swaps() {
+    let mut storage = MockStorage::new();
+    let owner = CanonicalAddr(Binary(b"addr0000".to_vec()));
+    let other = CanonicalAddr(Binary(b"addr0001".to_vec()));
+    let mut pool = PoolState {
+        sqrt_price: [0; 4],
+        tick: 0,
+        tick_spacing: 10,
+        liquidity: Uint128::zero(),
+        fee_growth_global: [Uint128::zero(); 2],
+    };
+    pool.set_sqrt_price(q96());
+    store_pool(&mut storage, &pool).unwrap();
+
+    // nothing to swap against yet
+    compute_swap(&storage, 0, Uint128(1000), &swap_fee()).unwrap_err();
+
+    // a narrow position around the price and a wide one of another owner
+    let (narrow_id, mut narrow) = create_position(&mut storage, &owner, -100, 100).unwrap();
+    let narrow_amounts =
+        modify_position(&mut storage, &mut narrow, Uint128(1_000_000_000), true).unwrap();
+    save_position(&mut storage, narrow_id, &narrow).unwrap();
+    assert_eq!(narrow_amounts[0], narrow_amounts[1]);
+    assert!(!narrow_amounts[0].is_zero());
+
+    let (wide_id, mut wide) = create_position(&mut storage, &other, -1000, 1000).unwrap();
+    modify_position(&mut storage, &mut wide, Uint128(1_000_000_000), true).unwrap();
+    save_position(&mut storage, wide_id, &wide).unwrap();
+
+    assert_eq!(read_ticks(&storage).unwrap(), vec![-1000, -100, 100, 1000]);
+    assert_eq!(
+        read_pool(&storage).unwrap().liquidity,
+        Uint128(2_000_000_000)
+    );
+    // positions of other owners can't be loaded
+    load_position(&storage, &owner, wide_id).unwrap_err();
+
+    // a swap within the narrow range, both positions share the commission
+    let outcome = compute_swap(&storage, 0, Uint128(1_000_000), &swap_fee()).unwrap();
+    assert_eq!(outcome.commission_amount, Uint128(3000));
+    assert!(outcome.crossed_ticks.is_empty());
+    assert!(outcome.return_amount < Uint128(997_000));
+    apply_swap(&mut storage, &outcome).unwrap();
+    assert!(read_pool(&storage).unwrap().tick < 0);
+
+    let fees = compute_fees_owed(&storage, &narrow).unwrap();
+    assert!(fees[0] >= Uint128(1499) && fees[0] <= Uint128(1500));
+    assert_eq!(fees[1], Uint128::zero());
+
+    // a swap leaving the narrow range only pays the wide position from there on
+    let outcome = compute_swap(&storage, 0, Uint128(20_000_000), &swap_fee()).unwrap();
+    assert_eq!(outcome.crossed_ticks.len(), 1);
+    assert_eq!(outcome.crossed_ticks[0].0, -100);
+    apply_swap(&mut storage, &outcome).unwrap();
+    let pool = read_pool(&storage).unwrap();
+    assert!(pool.tick < -100);
+    assert_eq!(pool.liquidity, Uint128(1_000_000_000));
+
+    let narrow_fees = compute_fees_owed(&storage, &narrow).unwrap();
+    let wide_fees = compute_fees_owed(&storage, &wide).unwrap();
+    assert!(wide_fees[0] > narrow_fees[0] + Uint128(20_000));
+
+    // the narrow position is out of range, only asset 0 is left in it
+    let amounts =
+        modify_position(&mut storage, &mut narrow, Uint128(1_000_000_000), false).unwrap();
+    assert_eq!(amounts[1], Uint128::zero());
+    assert!(amounts[0] > narrow_amounts[0]);
+    assert_eq!(narrow.fees_owed, narrow_fees);
+    assert_eq!(read_ticks(&storage).unwrap(), vec![-1000, 1000]);
+
+    // the position is kept until its fees are collected
+    save_position(&mut storage, narrow_id, &narrow).unwrap();
+    assert_eq!(
+        read_owner_positions(&storage, &owner).unwrap(),
+        vec![narrow_id]
+    );
+    narrow.fees_owed = [Uint128::zero(); 2];
+    save_position(&mut storage, narrow_id, &narrow).unwrap();
+    assert!(read_owner_positions(&storage, &owner).unwrap().is_empty());
+    load_position(&storage, &owner, narrow_id).unwrap_err();
+
+    // swapping back crosses into the wide range only
+    let outcome = compute_swap(&storage, 1, Uint128(30_000_000), &swap_fee()).unwrap();
+    assert!(outcome.crossed_ticks.is_empty());
+    apply_swap(&mut storage, &outcome).unwrap();
+    let wide_fees_after = compute_fees_owed(&storage, &wide).unwrap();
+    assert!(wide_fees_after[1] >= Uint128(89_999));
+
+    // positions are only readable with the viewing key
+    assert_viewing_key(&storage, &other, "key").unwrap_err();
+    set_viewing_key(&mut storage, &other, "key").unwrap();
+    assert_viewing_key(&storage, &other, "key").unwrap();
+    assert_viewing_key(&storage, &other, "other key").unwrap_err();
+}
+
+#[test]
+fn test_max_spread() {
+    assert_max_spread(
+        Some(Decimal::from_ratio(1200u128, 1u128)),
+        Some(Decimal::percent(1)),
+        None,
+        Uint128(1200000000),
+        Uint128(989999),
+        Uint128(0),
+    )
+    .unwrap_err();
+
+    assert_max_spread(
+        Some(Decimal::from_ratio(1200u128, 1u128)),
+        Some(Decimal::percent(1)),
+        None,
+        Uint128(1200000000),
+        Uint128(990000),
+        Uint128(0),
+    )
+    .unwrap();
+
+    assert_max_spread(
+        None,
+        Some(Decimal::percent(1)),
+        None,
+        Uint128(0),
+        Uint128(989999),
+        Uint128(10001),
+    )
+    .unwrap_err();
+
+    assert_max_spread(
+        None,
+        None,
+        Some(Uint128(990000)),
+        Uint128(0),
+        Uint128(989999),
+        Uint128(0),
+    )
+    .unwrap_err();
+}
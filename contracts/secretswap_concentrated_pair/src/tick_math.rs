@@ -0,0 +1,86 @@
+use cosmwasm_std::{StdError, StdResult};
+use primitive_types::U256;
+
+/// Lowest tick, its price 1.0001^MIN_TICK is about 2^-128
+pub const MIN_TICK: i32 = -887_272;
+/// Highest tick, its price 1.0001^MAX_TICK is about 2^128
+pub const MAX_TICK: i32 = 887_272;
+
+/// sqrt price of `MIN_TICK` as a Q64.96 number
+pub const MIN_SQRT_RATIO: U256 = U256([4_295_128_739, 0, 0, 0]);
+/// sqrt price of `MAX_TICK` as a Q64.96 number
+pub const MAX_SQRT_RATIO: U256 =
+    U256([0x5d95_1d52_6398_8d26, 0xefd1_fc6a_5064_8849, 0xfffd_8963, 0]);
+
+/// 2^128 / sqrt(1.0001)^(2^i) as Q128.128 numbers, for the bits of the absolute tick after the
+/// lowest one, as in Uniswap v3's `TickMath`
+const RATIOS: [u128; 19] = [
+    0xfff9_7272_373d_4132_59a4_6990_580e_213a,
+    0xfff2_e50f_5f65_6932_ef12_357c_f3c7_fdcc,
+    0xffe5_caca_7e10_e4e6_1c36_24ea_a094_1cd0,
+    0xffcb_9843_d60f_6159_c9db_5883_5c92_6644,
+    0xff97_3b41_fa98_c081_472e_6896_dfb2_54c0,
+    0xff2e_a164_66c9_6a38_43ec_78b3_26b5_2861,
+    0xfe5d_ee04_6a99_a2a8_11c4_61f1_969c_3053,
+    0xfcbe_86c7_900a_88ae_dcff_c83b_479a_a3a4,
+    0xf987_a725_3ac4_1317_6f2b_074c_f781_5e54,
+    0xf339_2b08_22b7_0005_940c_7a39_8e4b_70f3,
+    0xe715_9475_a2c2_9b74_43b2_9c7f_a6e8_89d9,
+    0xd097_f3bd_fd20_22b8_845a_d8f7_92aa_5825,
+    0xa9f7_4646_2d87_0fdf_8a65_dc1f_90e0_61e5,
+    0x70d8_69a1_56d2_a1b8_90bb_3df6_2baf_32f7,
+    0x31be_135f_97d0_8fd9_8123_1505_542f_cfa6,
+    0x09aa_508b_5b7a_84e1_c677_de54_f3e9_9bc9,
+    0x005d_6af8_dedb_8119_6699_c329_225e_e604,
+    0x0000_2216_e584_f5fa_1ea9_2604_1bed_fe98,
+    0x0000_0000_048a_1703_91f7_dc42_444e_8fa2,
+];
+
+/// sqrt(1.0001^tick) as a Q64.96 number, rounded up
+pub fn get_sqrt_ratio_at_tick(tick: i32) -> StdResult<U256> {
+    if !(MIN_TICK..=MAX_TICK).contains(&tick) {
+        return Err(StdError::generic_err(format!(
+            "Tick must be between {} and {}",
+            MIN_TICK, MAX_TICK
+        )));
+    }
+
+    let abs_tick = tick.unsigned_abs();
+    let mut ratio = if abs_tick & 1 != 0 {
+        U256::from(0xfffc_b933_bd6f_ad37_aa2d_162d_1a59_4001u128)
+    } else {
+        U256::one() << 128
+    };
+    for (i, factor) in RATIOS.iter().enumerate() {
+        if abs_tick & (2 << i) != 0 {
+            ratio = (ratio * U256::from(*factor)) >> 128;
+        }
+    }
+    if tick > 0 {
+        ratio = U256::MAX / ratio;
+    }
+
+    // Q128.128 to Q64.96
+    let remainder = ratio & U256::from(u32::MAX);
+    Ok((ratio >> 32) + if remainder.is_zero() { 0 } else { 1 })
+}
+
+/// Greatest tick whose sqrt price is at most `sqrt_price`
+pub fn get_tick_at_sqrt_ratio(sqrt_price: U256) -> StdResult<i32> {
+    if sqrt_price < MIN_SQRT_RATIO || sqrt_price >= MAX_SQRT_RATIO {
+        return Err(StdError::generic_err("Price is out of the tick range"));
+    }
+
+    let mut low = MIN_TICK;
+    let mut high = MAX_TICK;
+    while low < high {
+        let mid = low + (high - low + 1) / 2;
+        if get_sqrt_ratio_at_tick(mid)? <= sqrt_price {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+
+    Ok(low)
+}
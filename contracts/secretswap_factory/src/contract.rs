@@ -1,17 +1,31 @@
 use cosmwasm_std::{
-    log, to_binary, Api, Binary, CanonicalAddr, CosmosMsg, Env, Extern, HandleResponse,
+    log, to_binary, Api, Binary, CanonicalAddr, CosmosMsg, Decimal, Env, Extern, HandleResponse,
     HandleResult, HumanAddr, InitResponse, Querier, StdError, StdResult, Storage, Uint128, WasmMsg,
 };
 use secret_toolkit::crypto::{sha_256, Prng};
 
 use secretswap::{
-    AssetInfo, Factory, Fee, InitHook, PairInfo, PairInfoRaw, PairInitMsg, PairSettings,
+    validate_multi_pool, AssetInfo, AssetInfoRaw, ConcentratedPairInitMsg, CurveType, DynamicFee,
+    Factory, Fee, InitHook, MigrateFrom, MultiPoolInfo, MultiPoolInfoRaw, MultiPoolInitMsg,
+    PairInfo, PairInfoRaw, PairInitMsg, PairSettings, PairStatusLevel, ProtocolFee,
     SwapDataEndpoint,
 };
 
-use crate::msg::{ConfigResponse, HandleMsg, InitMsg, PairsResponse, QueryMsg};
-use crate::querier::query_liquidity_token;
-use crate::state::{read_config, read_pair, read_pairs, store_config, store_pair, Config};
+use crate::msg::{
+    ConfigResponse, HandleMsg, InitMsg, MultiPoolsResponse, PairHandleMsg, PairsResponse, QueryMsg,
+};
+use crate::querier::{query_liquidity_token, query_multi_pool_liquidity_token};
+use crate::state::{
+    read_concentrated_pair, read_config, read_multi_pool, read_multi_pools, read_pair,
+    read_pair_dynamic_fee, read_pair_fee, read_pair_max_price_move, read_pair_migration,
+    read_pair_reads_overrides, read_pair_status, read_pairs, remove_pair_migration,
+    store_concentrated_pair, store_config, store_multi_pool, store_pair, store_pair_dynamic_fee,
+    store_pair_fee, store_pair_max_price_move, store_pair_migration, store_pair_reads_overrides,
+    store_pair_status, Config, PairMigration,
+};
+
+/// Endpoints swaps report to, each one called by swaps costs gas
+pub const MAX_SWAP_DATA_ENDPOINTS: usize = 5;
 
 pub fn init<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
@@ -33,7 +47,16 @@ pub fn init<S: Storage, A: Api, Q: Querier>(
                 commission_rate_denom: Uint128(1000),
             },
             swap_data_endpoint: None,
+            swap_data_endpoints: vec![],
+            protocol_fee: None,
+            status: PairStatusLevel::NormalRun,
+            dynamic_fee: None,
+            max_price_move: None,
         },
+        concentrated_pair_code_id: 0,
+        concentrated_pair_code_hash: String::new(),
+        multi_pool_code_id: 0,
+        multi_pool_code_hash: String::new(),
     };
 
     store_config(&mut deps.storage, &config)?;
@@ -67,7 +90,11 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
             pair_code_hash,
             token_code_hash,
             swap_fee,
-            swap_data_endpoint,
+            protocol_fee,
+            concentrated_pair_code_id,
+            concentrated_pair_code_hash,
+            multi_pool_code_id,
+            multi_pool_code_hash,
         } => try_update_config(
             deps,
             env,
@@ -77,16 +104,70 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
             pair_code_hash,
             token_code_hash,
             swap_fee,
-            swap_data_endpoint,
+            protocol_fee,
+            concentrated_pair_code_id,
+            concentrated_pair_code_hash,
+            multi_pool_code_id,
+            multi_pool_code_hash,
         ),
         HandleMsg::CreatePair {
             asset_infos,
             init_hook,
-        } => try_create_pair(deps, env, asset_infos, init_hook),
+            curve,
+        } => try_create_pair(deps, env, asset_infos, init_hook, curve),
         HandleMsg::Register { asset_infos } => try_register(deps, env, asset_infos),
+        HandleMsg::SetPairFee {
+            asset_infos,
+            swap_fee,
+        } => try_set_pair_fee(deps, env, asset_infos, swap_fee),
+        HandleMsg::SetPairDynamicFee {
+            asset_infos,
+            dynamic_fee,
+        } => try_set_pair_dynamic_fee(deps, env, asset_infos, dynamic_fee),
+        HandleMsg::SetPairCircuitBreaker {
+            asset_infos,
+            max_price_move,
+        } => try_set_pair_circuit_breaker(deps, env, asset_infos, max_price_move),
+        HandleMsg::SetPairStatus { asset_infos, level } => {
+            try_set_pair_status(deps, env, asset_infos, level)
+        }
+        HandleMsg::MigratePair { asset_infos } => try_migrate_pair(deps, env, asset_infos),
+        HandleMsg::CreateConcentratedPair {
+            asset_infos,
+            tick_spacing,
+            initial_price,
+            init_hook,
+        } => try_create_concentrated_pair(
+            deps,
+            env,
+            asset_infos,
+            tick_spacing,
+            initial_price,
+            init_hook,
+        ),
+        HandleMsg::RegisterConcentratedPair { asset_infos } => {
+            try_register_concentrated_pair(deps, env, asset_infos)
+        }
+        HandleMsg::CreateMultiPool {
+            asset_infos,
+            curve,
+            init_hook,
+        } => try_create_multi_pool(deps, env, asset_infos, curve, init_hook),
+        HandleMsg::RegisterMultiPool { asset_infos } => {
+            try_register_multi_pool(deps, env, asset_infos)
+        }
+        HandleMsg::AddSwapDataEndpoint {
+            address,
+            code_hash,
+            best_effort_key,
+        } => try_add_swap_data_endpoint(deps, env, address, code_hash, best_effort_key),
+        HandleMsg::RemoveSwapDataEndpoint { address } => {
+            try_remove_swap_data_endpoint(deps, env, address)
+        }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 // Only owner can execute it
 pub fn try_update_config<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
@@ -97,7 +178,11 @@ pub fn try_update_config<S: Storage, A: Api, Q: Querier>(
     pair_code_hash: Option<String>,
     token_code_hash: Option<String>,
     swap_fee: Option<Fee>,
-    swap_data_endpoint: Option<SwapDataEndpoint>,
+    protocol_fee: Option<ProtocolFee>,
+    concentrated_pair_code_id: Option<u64>,
+    concentrated_pair_code_hash: Option<String>,
+    multi_pool_code_id: Option<u64>,
+    multi_pool_code_hash: Option<String>,
 ) -> HandleResult {
     let mut config: Config = read_config(&deps.storage)?;
 
@@ -123,14 +208,34 @@ pub fn try_update_config<S: Storage, A: Api, Q: Querier>(
     }
 
     if let Some(pair_code_hash) = pair_code_hash {
-        config.token_code_hash = pair_code_hash;
+        config.pair_code_hash = pair_code_hash;
     }
 
     if let Some(swap_fee) = swap_fee {
+        swap_fee.validate()?;
         config.pair_settings.swap_fee = swap_fee;
     }
 
-    config.pair_settings.swap_data_endpoint = swap_data_endpoint;
+    if let Some(protocol_fee) = protocol_fee {
+        protocol_fee.validate()?;
+        config.pair_settings.protocol_fee = Some(protocol_fee);
+    }
+
+    if let Some(concentrated_pair_code_id) = concentrated_pair_code_id {
+        config.concentrated_pair_code_id = concentrated_pair_code_id;
+    }
+
+    if let Some(concentrated_pair_code_hash) = concentrated_pair_code_hash {
+        config.concentrated_pair_code_hash = concentrated_pair_code_hash;
+    }
+
+    if let Some(multi_pool_code_id) = multi_pool_code_id {
+        config.multi_pool_code_id = multi_pool_code_id;
+    }
+
+    if let Some(multi_pool_code_hash) = multi_pool_code_hash {
+        config.multi_pool_code_hash = multi_pool_code_hash;
+    }
 
     store_config(&mut deps.storage, &config)?;
 
@@ -148,7 +253,11 @@ pub fn try_create_pair<S: Storage, A: Api, Q: Querier>(
     env: Env,
     asset_infos: [AssetInfo; 2],
     init_hook: Option<InitHook>,
+    curve: Option<CurveType>,
 ) -> HandleResult {
+    let curve = curve.unwrap_or_default();
+    curve.validate()?;
+
     let config: Config = read_config(&deps.storage)?;
     let raw_infos = [asset_infos[0].to_raw(&deps)?, asset_infos[1].to_raw(&deps)?];
     if read_pair(&deps.storage, &raw_infos).is_ok() {
@@ -168,13 +277,49 @@ pub fn try_create_pair<S: Storage, A: Api, Q: Querier>(
                 address: env.contract.address.clone(),
                 code_hash: env.contract_code_hash.clone(),
             },
+            curve: curve.clone(),
         },
     )?;
 
+    let mut messages: Vec<CosmosMsg> = vec![instantiate_pair_msg(
+        &env,
+        &config,
+        &asset_infos,
+        &curve,
+        None,
+    )?];
+
+    if let Some(hook) = init_hook {
+        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: hook.contract_addr,
+            msg: hook.msg,
+            send: vec![],
+            callback_code_hash: hook.code_hash,
+        }));
+    }
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![
+            log("action", "create_pair"),
+            log("pair", format!("{}-{}", asset_infos[0], asset_infos[1])),
+            log("curve", curve),
+        ],
+        data: None,
+    })
+}
+
+fn instantiate_pair_msg(
+    env: &Env,
+    config: &Config,
+    asset_infos: &[AssetInfo; 2],
+    curve: &CurveType,
+    migrate_from: Option<MigrateFrom>,
+) -> StdResult<CosmosMsg> {
     let mut rng = Prng::new(&config.prng_seed, &env.block.time.to_be_bytes());
     let pair_seed = rng.rand_bytes();
 
-    let mut messages: Vec<CosmosMsg> = vec![CosmosMsg::Wasm(WasmMsg::Instantiate {
+    Ok(CosmosMsg::Wasm(WasmMsg::Instantiate {
         code_id: config.pair_code_id,
         send: vec![],
         label: format!(
@@ -189,15 +334,357 @@ pub fn try_create_pair<S: Storage, A: Api, Q: Querier>(
             token_code_id: config.token_code_id,
             token_code_hash: config.token_code_hash.clone(),
             init_hook: Some(InitHook {
-                contract_addr: env.contract.address,
-                code_hash: env.contract_code_hash,
+                contract_addr: env.contract.address.clone(),
+                code_hash: env.contract_code_hash.clone(),
                 msg: to_binary(&HandleMsg::Register {
                     asset_infos: asset_infos.clone(),
                 })?,
             }),
             prng_seed: Binary::from(&pair_seed),
+            curve: Some(curve.clone()),
+            migrate_from,
         })?,
-        callback_code_hash: config.pair_code_hash,
+        callback_code_hash: config.pair_code_hash.clone(),
+    }))
+}
+
+/// create pair execute this message
+pub fn try_register<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    asset_infos: [AssetInfo; 2],
+) -> HandleResult {
+    let raw_infos = [asset_infos[0].to_raw(&deps)?, asset_infos[1].to_raw(&deps)?];
+    let pair_info: PairInfoRaw = read_pair(&deps.storage, &raw_infos)?;
+    // a migrated pair is replaced by the new one
+    let migration = read_pair_migration(&deps.storage, &raw_infos)?;
+    if pair_info.contract_addr != CanonicalAddr::default() && migration.is_none() {
+        return Err(StdError::generic_err("Pair was already registered"));
+    }
+
+    let pair_contract = env.message.sender;
+
+    let config = read_config(&deps.storage)?;
+
+    let liquidity_token = query_liquidity_token(&deps, &pair_contract, &config.pair_code_hash)?;
+    store_pair(
+        &mut deps.storage,
+        &PairInfoRaw {
+            contract_addr: deps.api.canonical_address(&pair_contract)?,
+            liquidity_token: deps.api.canonical_address(&liquidity_token)?,
+            token_code_hash: config.pair_code_hash.clone(),
+            ..pair_info
+        },
+    )?;
+
+    store_pair_reads_overrides(&mut deps.storage, &raw_infos)?;
+
+    // the old pair hands its reserves over to the new one and stops
+    let mut messages: Vec<CosmosMsg> = vec![];
+    if let Some(migration) = migration {
+        remove_pair_migration(&mut deps.storage, &raw_infos);
+        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: migration.old_pair,
+            callback_code_hash: migration.old_pair_code_hash,
+            msg: to_binary(&PairHandleMsg::MigrateReserves {
+                new_pair: pair_contract.clone(),
+                new_pair_code_hash: config.pair_code_hash,
+            })?,
+            send: vec![],
+        }));
+    }
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![
+            log("action", "register"),
+            log("pair_contract_addr", pair_contract),
+        ],
+        data: None,
+    })
+}
+
+// Only owner can execute it
+pub fn try_set_pair_fee<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    asset_infos: [AssetInfo; 2],
+    swap_fee: Option<Fee>,
+) -> HandleResult {
+    let config: Config = read_config(&deps.storage)?;
+
+    // permission check
+    if deps.api.canonical_address(&env.message.sender)? != config.owner {
+        return Err(StdError::unauthorized());
+    }
+
+    let raw_infos = [asset_infos[0].to_raw(&deps)?, asset_infos[1].to_raw(&deps)?];
+    assert_pair_reads_overrides(&deps.storage, &raw_infos)?;
+
+    if let Some(fee) = &swap_fee {
+        fee.validate()?;
+    }
+
+    let swap_fee_log = match &swap_fee {
+        Some(fee) => format!("{}/{}", fee.commission_rate_nom, fee.commission_rate_denom),
+        None => "default".to_string(),
+    };
+    store_pair_fee(&mut deps.storage, &raw_infos, swap_fee)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "set_pair_fee"),
+            log("pair", format!("{}-{}", asset_infos[0], asset_infos[1])),
+            log("swap_fee", swap_fee_log),
+        ],
+        data: None,
+    })
+}
+
+// Only owner can execute it
+pub fn try_set_pair_dynamic_fee<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    asset_infos: [AssetInfo; 2],
+    dynamic_fee: Option<DynamicFee>,
+) -> HandleResult {
+    let config: Config = read_config(&deps.storage)?;
+
+    // permission check
+    if deps.api.canonical_address(&env.message.sender)? != config.owner {
+        return Err(StdError::unauthorized());
+    }
+
+    let raw_infos = [asset_infos[0].to_raw(&deps)?, asset_infos[1].to_raw(&deps)?];
+    assert_pair_reads_overrides(&deps.storage, &raw_infos)?;
+
+    let dynamic_fee_log = match &dynamic_fee {
+        Some(dynamic_fee) => {
+            dynamic_fee.validate()?;
+            format!(
+                "{}/{} to {}/{} at {}",
+                dynamic_fee.min_fee.commission_rate_nom,
+                dynamic_fee.min_fee.commission_rate_denom,
+                dynamic_fee.max_fee.commission_rate_nom,
+                dynamic_fee.max_fee.commission_rate_denom,
+                dynamic_fee.max_volatility
+            )
+        }
+        None => "none".to_string(),
+    };
+    store_pair_dynamic_fee(&mut deps.storage, &raw_infos, dynamic_fee)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "set_pair_dynamic_fee"),
+            log("pair", format!("{}-{}", asset_infos[0], asset_infos[1])),
+            log("dynamic_fee", dynamic_fee_log),
+        ],
+        data: None,
+    })
+}
+
+// Only owner can execute it
+pub fn try_set_pair_circuit_breaker<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    asset_infos: [AssetInfo; 2],
+    max_price_move: Option<Decimal>,
+) -> HandleResult {
+    let config: Config = read_config(&deps.storage)?;
+
+    // permission check
+    if deps.api.canonical_address(&env.message.sender)? != config.owner {
+        return Err(StdError::unauthorized());
+    }
+
+    let raw_infos = [asset_infos[0].to_raw(&deps)?, asset_infos[1].to_raw(&deps)?];
+    assert_pair_reads_overrides(&deps.storage, &raw_infos)?;
+
+    let max_price_move_log = match max_price_move {
+        Some(max_price_move) => {
+            if max_price_move == Decimal::zero() || max_price_move > Decimal::one() {
+                return Err(StdError::generic_err(
+                    "Maximum price move must be greater than 0 and at most 1",
+                ));
+            }
+            max_price_move.to_string()
+        }
+        None => "none".to_string(),
+    };
+    store_pair_max_price_move(&mut deps.storage, &raw_infos, max_price_move)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "set_pair_circuit_breaker"),
+            log("pair", format!("{}-{}", asset_infos[0], asset_infos[1])),
+            log("max_price_move", max_price_move_log),
+        ],
+        data: None,
+    })
+}
+
+// Only owner can execute it
+pub fn try_set_pair_status<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    asset_infos: Option<[AssetInfo; 2]>,
+    level: PairStatusLevel,
+) -> HandleResult {
+    let mut config: Config = read_config(&deps.storage)?;
+
+    // permission check
+    if deps.api.canonical_address(&env.message.sender)? != config.owner {
+        return Err(StdError::unauthorized());
+    }
+
+    let pair_log = match asset_infos {
+        Some(asset_infos) => {
+            let raw_infos = [asset_infos[0].to_raw(&deps)?, asset_infos[1].to_raw(&deps)?];
+            assert_pair_reads_overrides(&deps.storage, &raw_infos)?;
+            store_pair_status(&mut deps.storage, &raw_infos, level)?;
+            format!("{}-{}", asset_infos[0], asset_infos[1])
+        }
+        None => {
+            config.pair_settings.status = level;
+            store_config(&mut deps.storage, &config)?;
+            "all".to_string()
+        }
+    };
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "set_pair_status"),
+            log("pair", pair_log),
+            log("level", format!("{:?}", level)),
+        ],
+        data: None,
+    })
+}
+
+// Only owner can execute it
+pub fn try_migrate_pair<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    asset_infos: [AssetInfo; 2],
+) -> HandleResult {
+    let config: Config = read_config(&deps.storage)?;
+
+    // permission check
+    if deps.api.canonical_address(&env.message.sender)? != config.owner {
+        return Err(StdError::unauthorized());
+    }
+
+    let raw_infos = [asset_infos[0].to_raw(&deps)?, asset_infos[1].to_raw(&deps)?];
+    let pair_info: PairInfoRaw = read_pair(&deps.storage, &raw_infos)?;
+    if pair_info.contract_addr == CanonicalAddr::default() {
+        return Err(StdError::generic_err("Pair is not registered yet"));
+    }
+    // the registry keeps the pair code hash in `token_code_hash`
+    if pair_info.token_code_hash == config.pair_code_hash {
+        return Err(StdError::generic_err(
+            "Pair already runs the current pair code",
+        ));
+    }
+
+    let old_pair = deps.api.human_address(&pair_info.contract_addr)?;
+    store_pair_migration(
+        &mut deps.storage,
+        &raw_infos,
+        &PairMigration {
+            old_pair: old_pair.clone(),
+            old_pair_code_hash: pair_info.token_code_hash.clone(),
+        },
+    )?;
+
+    // the new pair registers itself, which moves the reserves of the old pair to it and
+    // stops the old pair, then liquidity providers exchange their LP tokens with its
+    // `migrate_liquidity` hook
+    Ok(HandleResponse {
+        messages: vec![instantiate_pair_msg(
+            &env,
+            &config,
+            &asset_infos,
+            &pair_info.curve,
+            Some(MigrateFrom {
+                address: old_pair.clone(),
+                code_hash: pair_info.token_code_hash,
+            }),
+        )?],
+        log: vec![
+            log("action", "migrate_pair"),
+            log("pair", format!("{}-{}", asset_infos[0], asset_infos[1])),
+            log("old_pair", old_pair.as_str()),
+            log("pair_code_id", config.pair_code_id),
+        ],
+        data: None,
+    })
+}
+
+// Anyone can execute it to create a concentrated liquidity pair
+pub fn try_create_concentrated_pair<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    asset_infos: [AssetInfo; 2],
+    tick_spacing: u32,
+    initial_price: Decimal,
+    init_hook: Option<InitHook>,
+) -> HandleResult {
+    let config: Config = read_config(&deps.storage)?;
+    if config.concentrated_pair_code_id == 0 {
+        return Err(StdError::generic_err("Concentrated pair code is not set"));
+    }
+
+    let raw_infos = [asset_infos[0].to_raw(&deps)?, asset_infos[1].to_raw(&deps)?];
+    if read_concentrated_pair(&deps.storage, &raw_infos).is_ok() {
+        return Err(StdError::generic_err("Pair already exists"));
+    }
+
+    // positions aren't tokens, so the pair has no liquidity token
+    store_concentrated_pair(
+        &mut deps.storage,
+        &PairInfoRaw {
+            liquidity_token: CanonicalAddr::default(),
+            contract_addr: CanonicalAddr::default(),
+            asset_infos: raw_infos,
+            token_code_hash: config.concentrated_pair_code_hash.clone(),
+            asset0_volume: Uint128(0),
+            asset1_volume: Uint128(0),
+            factory: Factory {
+                address: env.contract.address.clone(),
+                code_hash: env.contract_code_hash.clone(),
+            },
+            curve: CurveType::default(),
+        },
+    )?;
+
+    let mut messages: Vec<CosmosMsg> = vec![CosmosMsg::Wasm(WasmMsg::Instantiate {
+        code_id: config.concentrated_pair_code_id,
+        send: vec![],
+        label: format!(
+            "{}-{}-concentrated-pair-{}-{}",
+            asset_infos[0],
+            asset_infos[1],
+            env.contract.address.clone(),
+            config.concentrated_pair_code_id
+        ),
+        msg: to_binary(&ConcentratedPairInitMsg {
+            asset_infos: asset_infos.clone(),
+            tick_spacing,
+            initial_price,
+            init_hook: Some(InitHook {
+                contract_addr: env.contract.address.clone(),
+                code_hash: env.contract_code_hash.clone(),
+                msg: to_binary(&HandleMsg::RegisterConcentratedPair {
+                    asset_infos: asset_infos.clone(),
+                })?,
+            }),
+        })?,
+        callback_code_hash: config.concentrated_pair_code_hash.clone(),
     })];
 
     if let Some(hook) = init_hook {
@@ -212,49 +699,300 @@ pub fn try_create_pair<S: Storage, A: Api, Q: Querier>(
     Ok(HandleResponse {
         messages,
         log: vec![
-            log("action", "create_pair"),
+            log("action", "create_concentrated_pair"),
             log("pair", format!("{}-{}", asset_infos[0], asset_infos[1])),
+            log("tick_spacing", tick_spacing),
+            log("initial_price", initial_price),
         ],
         data: None,
     })
 }
 
-/// create pair execute this message
-pub fn try_register<S: Storage, A: Api, Q: Querier>(
+/// create concentrated pair execute this message
+pub fn try_register_concentrated_pair<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
     asset_infos: [AssetInfo; 2],
 ) -> HandleResult {
     let raw_infos = [asset_infos[0].to_raw(&deps)?, asset_infos[1].to_raw(&deps)?];
-    let pair_info: PairInfoRaw = read_pair(&deps.storage, &raw_infos)?;
+    let pair_info: PairInfoRaw = read_concentrated_pair(&deps.storage, &raw_infos)?;
     if pair_info.contract_addr != CanonicalAddr::default() {
         return Err(StdError::generic_err("Pair was already registered"));
     }
 
     let pair_contract = env.message.sender;
-
-    let config = read_config(&deps.storage)?;
-
-    let liquidity_token = query_liquidity_token(&deps, &pair_contract, &config.pair_code_hash)?;
-    store_pair(
+    store_concentrated_pair(
         &mut deps.storage,
         &PairInfoRaw {
             contract_addr: deps.api.canonical_address(&pair_contract)?,
-            liquidity_token: deps.api.canonical_address(&liquidity_token)?,
             ..pair_info
         },
     )?;
+    store_pair_reads_overrides(&mut deps.storage, &raw_infos)?;
 
     Ok(HandleResponse {
         messages: vec![],
         log: vec![
-            log("action", "register"),
+            log("action", "register_concentrated_pair"),
             log("pair_contract_addr", pair_contract),
         ],
         data: None,
     })
 }
 
+// Anyone can execute it to create a multi-asset pool
+pub fn try_create_multi_pool<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    asset_infos: Vec<AssetInfo>,
+    curve: Option<CurveType>,
+    init_hook: Option<InitHook>,
+) -> HandleResult {
+    let curve = curve.unwrap_or_default();
+    validate_multi_pool(&asset_infos, &curve)?;
+
+    let config: Config = read_config(&deps.storage)?;
+    if config.multi_pool_code_id == 0 {
+        return Err(StdError::generic_err("Multi-asset pool code is not set"));
+    }
+
+    let raw_infos = asset_infos
+        .iter()
+        .map(|asset_info| asset_info.to_raw(&deps))
+        .collect::<StdResult<Vec<AssetInfoRaw>>>()?;
+    if read_multi_pool(&deps.storage, &raw_infos).is_ok() {
+        return Err(StdError::generic_err("Pool already exists"));
+    }
+
+    store_multi_pool(
+        &mut deps.storage,
+        &MultiPoolInfoRaw {
+            asset_infos: raw_infos,
+            contract_addr: CanonicalAddr::default(),
+            liquidity_token: CanonicalAddr::default(),
+            token_code_hash: config.token_code_hash.clone(),
+            factory: Factory {
+                address: env.contract.address.clone(),
+                code_hash: env.contract_code_hash.clone(),
+            },
+            curve: curve.clone(),
+        },
+    )?;
+
+    let pool_name = asset_infos
+        .iter()
+        .map(|asset_info| asset_info.to_string())
+        .collect::<Vec<String>>()
+        .join("-");
+    let mut rng = Prng::new(&config.prng_seed, &env.block.time.to_be_bytes());
+    let pool_seed = rng.rand_bytes();
+
+    let mut messages: Vec<CosmosMsg> = vec![CosmosMsg::Wasm(WasmMsg::Instantiate {
+        code_id: config.multi_pool_code_id,
+        send: vec![],
+        label: format!(
+            "{}-multi-pool-{}-{}",
+            pool_name,
+            env.contract.address.clone(),
+            config.multi_pool_code_id
+        ),
+        msg: to_binary(&MultiPoolInitMsg {
+            asset_infos: asset_infos.clone(),
+            token_code_id: config.token_code_id,
+            token_code_hash: config.token_code_hash.clone(),
+            init_hook: Some(InitHook {
+                contract_addr: env.contract.address.clone(),
+                code_hash: env.contract_code_hash.clone(),
+                msg: to_binary(&HandleMsg::RegisterMultiPool { asset_infos })?,
+            }),
+            prng_seed: Binary::from(&pool_seed),
+            curve: Some(curve.clone()),
+        })?,
+        callback_code_hash: config.multi_pool_code_hash.clone(),
+    })];
+
+    if let Some(hook) = init_hook {
+        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: hook.contract_addr,
+            msg: hook.msg,
+            send: vec![],
+            callback_code_hash: hook.code_hash,
+        }));
+    }
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![
+            log("action", "create_multi_pool"),
+            log("pool", pool_name),
+            log("curve", curve),
+        ],
+        data: None,
+    })
+}
+
+/// create multi pool execute this message
+pub fn try_register_multi_pool<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    asset_infos: Vec<AssetInfo>,
+) -> HandleResult {
+    let raw_infos = asset_infos
+        .iter()
+        .map(|asset_info| asset_info.to_raw(&deps))
+        .collect::<StdResult<Vec<AssetInfoRaw>>>()?;
+    let pool_info: MultiPoolInfoRaw = read_multi_pool(&deps.storage, &raw_infos)?;
+    if pool_info.contract_addr != CanonicalAddr::default() {
+        return Err(StdError::generic_err("Pool was already registered"));
+    }
+
+    let pool_contract = env.message.sender;
+
+    let config = read_config(&deps.storage)?;
+
+    let liquidity_token =
+        query_multi_pool_liquidity_token(&deps, &pool_contract, &config.multi_pool_code_hash)?;
+    store_multi_pool(
+        &mut deps.storage,
+        &MultiPoolInfoRaw {
+            contract_addr: deps.api.canonical_address(&pool_contract)?,
+            liquidity_token: deps.api.canonical_address(&liquidity_token)?,
+            ..pool_info
+        },
+    )?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "register_multi_pool"),
+            log("pool_contract_addr", pool_contract),
+        ],
+        data: None,
+    })
+}
+
+// Only owner can execute it
+pub fn try_add_swap_data_endpoint<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: HumanAddr,
+    code_hash: String,
+    best_effort_key: Option<String>,
+) -> HandleResult {
+    let mut config: Config = read_config(&deps.storage)?;
+
+    // permission check
+    if deps.api.canonical_address(&env.message.sender)? != config.owner {
+        return Err(StdError::unauthorized());
+    }
+
+    let mut endpoints = swap_data_endpoints(&config.pair_settings);
+    if endpoints.iter().any(|endpoint| endpoint.address == address) {
+        return Err(StdError::generic_err(
+            "Swap data endpoint is already registered",
+        ));
+    }
+    if endpoints.len() >= MAX_SWAP_DATA_ENDPOINTS {
+        return Err(StdError::generic_err(format!(
+            "At most {} swap data endpoints can be registered",
+            MAX_SWAP_DATA_ENDPOINTS
+        )));
+    }
+
+    let best_effort = best_effort_key.is_some();
+    endpoints.push(SwapDataEndpoint {
+        address: address.clone(),
+        code_hash,
+        best_effort_key_hash: best_effort_key.map(|key| Binary::from(&sha_256(key.as_bytes()))),
+    });
+    set_swap_data_endpoints(&mut config.pair_settings, endpoints);
+    store_config(&mut deps.storage, &config)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "add_swap_data_endpoint"),
+            log("endpoint", address),
+            log("best_effort", best_effort),
+        ],
+        data: None,
+    })
+}
+
+// Only owner can execute it
+pub fn try_remove_swap_data_endpoint<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: HumanAddr,
+) -> HandleResult {
+    let mut config: Config = read_config(&deps.storage)?;
+
+    // permission check
+    if deps.api.canonical_address(&env.message.sender)? != config.owner {
+        return Err(StdError::unauthorized());
+    }
+
+    let mut endpoints = swap_data_endpoints(&config.pair_settings);
+    if !endpoints.iter().any(|endpoint| endpoint.address == address) {
+        return Err(StdError::generic_err(
+            "Swap data endpoint is not registered",
+        ));
+    }
+    endpoints.retain(|endpoint| endpoint.address != address);
+    set_swap_data_endpoints(&mut config.pair_settings, endpoints);
+    store_config(&mut deps.storage, &config)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "remove_swap_data_endpoint"),
+            log("endpoint", address),
+        ],
+        data: None,
+    })
+}
+
+/// Endpoints of the settings, including the single endpoint of settings stored before
+/// there could be several
+fn swap_data_endpoints(pair_settings: &PairSettings) -> Vec<SwapDataEndpoint> {
+    match (
+        &pair_settings.swap_data_endpoint,
+        pair_settings.swap_data_endpoints.is_empty(),
+    ) {
+        (Some(endpoint), true) => vec![endpoint.clone()],
+        _ => pair_settings.swap_data_endpoints.clone(),
+    }
+}
+
+/// Pairs running older code only call `swap_data_endpoint`, it is kept at the first endpoint
+/// swaps call
+fn set_swap_data_endpoints(pair_settings: &mut PairSettings, endpoints: Vec<SwapDataEndpoint>) {
+    pair_settings.swap_data_endpoint = endpoints
+        .iter()
+        .find(|endpoint| !endpoint.is_best_effort())
+        .cloned();
+    pair_settings.swap_data_endpoints = endpoints;
+}
+
+/// Fee and status overrides apply to the regular and the concentrated pair of the assets
+/// Overrides are looked up by asset infos, which pairs registered before they existed
+/// don't send, so setting one for such a pair would have no effect
+fn assert_pair_reads_overrides<S: Storage>(
+    storage: &S,
+    asset_infos: &[AssetInfoRaw; 2],
+) -> StdResult<()> {
+    if read_pair(storage, asset_infos).is_err() {
+        read_concentrated_pair(storage, asset_infos)
+            .map_err(|_| StdError::generic_err("no pair data stored"))?;
+    }
+    if !read_pair_reads_overrides(storage, asset_infos)? {
+        return Err(StdError::generic_err(
+            "Pair was registered before per-pair settings and only reads the global settings",
+        ));
+    }
+    Ok(())
+}
+
 pub fn query<S: Storage, A: Api, Q: Querier>(
     deps: &Extern<S, A, Q>,
     msg: QueryMsg,
@@ -265,7 +1003,16 @@ pub fn query<S: Storage, A: Api, Q: Querier>(
         QueryMsg::Pairs { start_after, limit } => {
             to_binary(&query_pairs(deps, start_after, limit)?)
         }
-        QueryMsg::PairSettings {} => to_binary(&query_pair_settings(deps)?),
+        QueryMsg::PairSettings { asset_infos } => {
+            to_binary(&query_pair_settings(deps, asset_infos)?)
+        }
+        QueryMsg::ConcentratedPair { asset_infos } => {
+            to_binary(&query_concentrated_pair(deps, asset_infos)?)
+        }
+        QueryMsg::MultiPool { asset_infos } => to_binary(&query_multi_pool(deps, asset_infos)?),
+        QueryMsg::MultiPools { start_after, limit } => {
+            to_binary(&query_multi_pools(deps, start_after, limit)?)
+        }
     }
 }
 
@@ -279,7 +1026,14 @@ pub fn query_config<S: Storage, A: Api, Q: Querier>(
         token_code_hash: state.token_code_hash,
         pair_code_id: state.pair_code_id,
         pair_code_hash: state.pair_code_hash,
-        pair_settings: state.pair_settings,
+        concentrated_pair_code_id: state.concentrated_pair_code_id,
+        concentrated_pair_code_hash: state.concentrated_pair_code_hash,
+        multi_pool_code_id: state.multi_pool_code_id,
+        multi_pool_code_hash: state.multi_pool_code_hash,
+        pair_settings: PairSettings {
+            swap_data_endpoints: swap_data_endpoints(&state.pair_settings),
+            ..state.pair_settings
+        },
     };
 
     Ok(resp)
@@ -294,6 +1048,47 @@ pub fn query_pair<S: Storage, A: Api, Q: Querier>(
     pair_info.to_normal(&deps)
 }
 
+pub fn query_concentrated_pair<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    asset_infos: [AssetInfo; 2],
+) -> StdResult<PairInfo> {
+    let raw_infos = [asset_infos[0].to_raw(&deps)?, asset_infos[1].to_raw(&deps)?];
+    let pair_info: PairInfoRaw = read_concentrated_pair(&deps.storage, &raw_infos)?;
+    pair_info.to_normal(&deps)
+}
+
+pub fn query_multi_pool<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    asset_infos: Vec<AssetInfo>,
+) -> StdResult<MultiPoolInfo> {
+    let raw_infos = asset_infos
+        .iter()
+        .map(|asset_info| asset_info.to_raw(&deps))
+        .collect::<StdResult<Vec<AssetInfoRaw>>>()?;
+    let pool_info: MultiPoolInfoRaw = read_multi_pool(&deps.storage, &raw_infos)?;
+    pool_info.to_normal(&deps)
+}
+
+pub fn query_multi_pools<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    start_after: Option<Vec<AssetInfo>>,
+    limit: Option<u32>,
+) -> StdResult<MultiPoolsResponse> {
+    let start_after = match start_after {
+        Some(start_after) => Some(
+            start_after
+                .iter()
+                .map(|asset_info| asset_info.to_raw(&deps))
+                .collect::<StdResult<Vec<AssetInfoRaw>>>()?,
+        ),
+        None => None,
+    };
+
+    Ok(MultiPoolsResponse {
+        pools: read_multi_pools(&deps, start_after, limit)?,
+    })
+}
+
 pub fn query_pairs<S: Storage, A: Api, Q: Querier>(
     deps: &Extern<S, A, Q>,
     start_after: Option<[AssetInfo; 2]>,
@@ -313,8 +1108,23 @@ pub fn query_pairs<S: Storage, A: Api, Q: Querier>(
 
 pub fn query_pair_settings<S: Storage, A: Api, Q: Querier>(
     deps: &Extern<S, A, Q>,
+    asset_infos: Option<[AssetInfo; 2]>,
 ) -> StdResult<PairSettings> {
     let config = read_config(&deps.storage)?;
+    let mut pair_settings = config.pair_settings;
+    pair_settings.swap_data_endpoints = swap_data_endpoints(&pair_settings);
+
+    if let Some(asset_infos) = asset_infos {
+        let raw_infos = [asset_infos[0].to_raw(&deps)?, asset_infos[1].to_raw(&deps)?];
+        if let Some(swap_fee) = read_pair_fee(&deps.storage, &raw_infos)? {
+            pair_settings.swap_fee = swap_fee;
+        }
+        pair_settings.dynamic_fee = read_pair_dynamic_fee(&deps.storage, &raw_infos)?;
+        pair_settings.max_price_move = read_pair_max_price_move(&deps.storage, &raw_infos)?;
+        pair_settings.status = pair_settings
+            .status
+            .max(read_pair_status(&deps.storage, &raw_infos)?);
+    }
 
-    Ok(config.pair_settings)
+    Ok(pair_settings)
 }
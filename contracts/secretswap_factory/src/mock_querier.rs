@@ -3,11 +3,11 @@ use std::collections::HashMap;
 use cosmwasm_std::testing::{MockApi, MockQuerier, MockStorage, MOCK_CONTRACT_ADDR};
 use cosmwasm_std::{
     from_slice, to_binary, Api, Coin, Empty, Extern, HumanAddr, Querier, QuerierResult,
-    QueryRequest, SystemError, WasmQuery,
+    QueryRequest, SystemError, Uint128, WasmQuery,
 };
 use cosmwasm_storage::to_length_prefixed;
 
-use secretswap::{AssetInfoRaw, PairInfo, PairInfoRaw};
+use secretswap::{AssetInfoRaw, MultiPoolInfo, PairInfo, PairInfoRaw};
 
 /// mock_dependencies is a drop-in replacement for cosmwasm_std::testing::mock_dependencies
 /// this uses our CustomQuerier.
@@ -32,6 +32,7 @@ pub fn mock_dependencies(
 pub struct WasmMockQuerier {
     base: MockQuerier<Empty>,
     terraswap_pair_querier: TerraswapPairQuerier,
+    multi_pools: HashMap<HumanAddr, MultiPoolInfo>,
     canonical_length: usize,
 }
 
@@ -75,7 +76,9 @@ impl Querier for WasmMockQuerier {
 impl WasmMockQuerier {
     pub fn handle_query(&self, request: &QueryRequest<Empty>) -> QuerierResult {
         match &request {
-            QueryRequest::Wasm(WasmQuery::Raw { contract_addr, key }) => {
+            QueryRequest::Wasm(WasmQuery::Raw {
+                contract_addr, key, ..
+            }) => {
                 let key: &[u8] = key.as_slice();
                 let prefix_pair_info = to_length_prefixed(b"pair_info").to_vec();
 
@@ -106,6 +109,11 @@ impl WasmMockQuerier {
                                     denom: "uusd".to_string(),
                                 },
                             ],
+                            token_code_hash: pair_info.token_code_hash.clone(),
+                            asset0_volume: Uint128::zero(),
+                            asset1_volume: Uint128::zero(),
+                            factory: pair_info.factory.clone(),
+                            curve: pair_info.curve.clone(),
                         })
                         .unwrap(),
                     ))
@@ -113,6 +121,22 @@ impl WasmMockQuerier {
                     panic!("DO NOT ENTER HERE")
                 }
             }
+            QueryRequest::Wasm(WasmQuery::Smart { contract_addr, .. }) => {
+                match (
+                    self.multi_pools.get(contract_addr),
+                    self.terraswap_pair_querier.pairs.get(contract_addr),
+                ) {
+                    (Some(pool_info), _) => Ok(to_binary(pool_info)),
+                    (None, Some(pair_info)) => Ok(to_binary(pair_info)),
+                    (None, None) => Err(SystemError::InvalidRequest {
+                        error: format!(
+                            "PairInfo or MultiPoolInfo is not found for {}",
+                            contract_addr
+                        ),
+                        request: Default::default(),
+                    }),
+                }
+            }
             _ => self.base.handle_query(request),
         }
     }
@@ -123,6 +147,7 @@ impl WasmMockQuerier {
         WasmMockQuerier {
             base,
             terraswap_pair_querier: TerraswapPairQuerier::default(),
+            multi_pools: HashMap::new(),
             canonical_length,
         }
     }
@@ -132,6 +157,14 @@ impl WasmMockQuerier {
         self.terraswap_pair_querier = TerraswapPairQuerier::new(pairs);
     }
 
+    // configure the multi-asset pools answering the `multi_pool` query
+    pub fn with_multi_pools(&mut self, pools: &[(&HumanAddr, &MultiPoolInfo)]) {
+        for (addr, pool_info) in pools.iter() {
+            self.multi_pools
+                .insert(HumanAddr::from(*addr), (*pool_info).clone());
+        }
+    }
+
     // pub fn with_balance(&mut self, balances: &[(&HumanAddr, &[Coin])]) {
     //     for (addr, balance) in balances {
     //         self.base.update_balance(addr, balance.to_vec());
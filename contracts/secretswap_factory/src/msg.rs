@@ -1,8 +1,11 @@
-use cosmwasm_std::{Binary, HumanAddr};
+use cosmwasm_std::{Binary, Decimal, HumanAddr};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use secretswap::{AssetInfo, Fee, InitHook, PairInfo, PairSettings, SwapDataEndpoint};
+use secretswap::{
+    AssetInfo, CurveType, DynamicFee, Fee, InitHook, MultiPoolInfo, PairInfo, PairSettings,
+    PairStatusLevel, ProtocolFee,
+};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InitMsg {
@@ -26,7 +29,13 @@ pub enum HandleMsg {
         pair_code_hash: Option<String>,
         token_code_hash: Option<String>,
         swap_fee: Option<Fee>,
-        swap_data_endpoint: Option<SwapDataEndpoint>,
+        /// Protocol share of the swap commission, a zero share turns it off while
+        /// pairs can still send what they accrued to the collector
+        protocol_fee: Option<ProtocolFee>,
+        concentrated_pair_code_id: Option<u64>,
+        concentrated_pair_code_hash: Option<String>,
+        multi_pool_code_id: Option<u64>,
+        multi_pool_code_hash: Option<String>,
     },
     /// CreatePair instantiates pair contract
     CreatePair {
@@ -34,16 +43,96 @@ pub enum HandleMsg {
         asset_infos: [AssetInfo; 2],
         /// Init hook for after works
         init_hook: Option<InitHook>,
+        /// Pool curve, defaults to constant product
+        curve: Option<CurveType>,
     },
     /// Register is invoked from created pair contract after initialzation
-    Register { asset_infos: [AssetInfo; 2] },
+    Register {
+        asset_infos: [AssetInfo; 2],
+    },
+    /// SetPairFee overrides the swap fee of a single pair, `None` falls back to the global fee
+    SetPairFee {
+        asset_infos: [AssetInfo; 2],
+        swap_fee: Option<Fee>,
+    },
+    /// SetPairDynamicFee makes the swap fee of a single pair follow its price volatility
+    /// between two bounds, `None` goes back to the static fee
+    SetPairDynamicFee {
+        asset_infos: [AssetInfo; 2],
+        dynamic_fee: Option<DynamicFee>,
+    },
+    /// SetPairCircuitBreaker limits how far swaps can move the price of a single pair within a
+    /// block, relative to the price at the start of the block. `None` removes the limit.
+    SetPairCircuitBreaker {
+        asset_infos: [AssetInfo; 2],
+        max_price_move: Option<Decimal>,
+    },
+    /// SetPairStatus stops a single pair, or every pair when `asset_infos` is `None`.
+    /// A pair runs with the stricter of its own and the global status.
+    SetPairStatus {
+        asset_infos: Option<[AssetInfo; 2]>,
+        level: PairStatusLevel,
+    },
+    /// MigratePair instantiates the current pair code for an existing pair and points the
+    /// registry at it. LP tokens of the old pair are moved over with the new pair's
+    /// `migrate_liquidity` hook, which withdraws them from the old pair and deposits the assets.
+    MigratePair {
+        asset_infos: [AssetInfo; 2],
+    },
+    /// CreateConcentratedPair instantiates a concentrated liquidity pair contract. There is at
+    /// most one for each pair of assets, besides the regular pair.
+    CreateConcentratedPair {
+        /// Asset infos
+        asset_infos: [AssetInfo; 2],
+        /// Distance between the ticks positions can start or end at
+        tick_spacing: u32,
+        /// Starting price of asset 0 in asset 1
+        initial_price: Decimal,
+        /// Init hook for after works
+        init_hook: Option<InitHook>,
+    },
+    /// RegisterConcentratedPair is invoked from created concentrated pair contract after
+    /// initialzation
+    RegisterConcentratedPair {
+        asset_infos: [AssetInfo; 2],
+    },
+    /// CreateMultiPool instantiates a pool of 3 to 8 assets with a single LP token. There is
+    /// at most one for each set of assets.
+    CreateMultiPool {
+        /// Asset infos
+        asset_infos: Vec<AssetInfo>,
+        /// Pool curve, defaults to constant product. Weighted curves aren't supported.
+        curve: Option<CurveType>,
+        /// Init hook for after works
+        init_hook: Option<InitHook>,
+    },
+    /// RegisterMultiPool is invoked from created multi-asset pool contract after
+    /// initialzation
+    RegisterMultiPool {
+        asset_infos: Vec<AssetInfo>,
+    },
+    /// AddSwapDataEndpoint makes every pair report its swaps to the endpoint. With a
+    /// `best_effort_key` the endpoint reads them from the pairs with the key instead of being
+    /// called by every swap, so it can't make swaps fail.
+    AddSwapDataEndpoint {
+        address: HumanAddr,
+        code_hash: String,
+        best_effort_key: Option<String>,
+    },
+    RemoveSwapDataEndpoint {
+        address: HumanAddr,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
     Config {},
-    PairSettings {},
+    /// Settings that apply to the pair of `asset_infos`, or the global settings.
+    /// Queries don't carry their caller, pairs that send no asset infos only get the global ones
+    PairSettings {
+        asset_infos: Option<[AssetInfo; 2]>,
+    },
     Pair {
         asset_infos: [AssetInfo; 2],
     },
@@ -51,6 +140,28 @@ pub enum QueryMsg {
         start_after: Option<[AssetInfo; 2]>,
         limit: Option<u32>,
     },
+    ConcentratedPair {
+        asset_infos: [AssetInfo; 2],
+    },
+    /// Multi-asset pool of the assets, in any order
+    MultiPool {
+        asset_infos: Vec<AssetInfo>,
+    },
+    /// Registered multi-asset pools in the order they were created
+    MultiPools {
+        start_after: Option<Vec<AssetInfo>>,
+        limit: Option<u32>,
+    },
+}
+
+/// Pair messages the factory sends, copied from secretswap_pair
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PairHandleMsg {
+    MigrateReserves {
+        new_pair: HumanAddr,
+        new_pair_code_hash: String,
+    },
 }
 
 // We define a custom struct for each query response
@@ -61,6 +172,10 @@ pub struct ConfigResponse {
     pub pair_code_hash: String,
     pub token_code_id: u64,
     pub token_code_hash: String,
+    pub concentrated_pair_code_id: u64,
+    pub concentrated_pair_code_hash: String,
+    pub multi_pool_code_id: u64,
+    pub multi_pool_code_hash: String,
     pub pair_settings: PairSettings,
 }
 
@@ -69,3 +184,8 @@ pub struct ConfigResponse {
 pub struct PairsResponse {
     pub pairs: Vec<PairInfo>,
 }
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MultiPoolsResponse {
+    pub pools: Vec<MultiPoolInfo>,
+}
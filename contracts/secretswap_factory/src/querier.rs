@@ -4,13 +4,14 @@ use cosmwasm_std::{
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use secretswap::PairInfo;
+use secretswap::{MultiPoolInfo, PairInfo};
 
 // copied from secretswap_pair.. todo: move it to secretswap common package
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsgPair {
     Pair {},
+    MultiPool {},
 }
 
 pub fn query_liquidity_token<S: Storage, A: Api, Q: Querier>(
@@ -27,3 +28,17 @@ pub fn query_liquidity_token<S: Storage, A: Api, Q: Querier>(
 
     Ok(pair_info.liquidity_token)
 }
+
+pub fn query_multi_pool_liquidity_token<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    contract_addr: &HumanAddr,
+    code_hash: &String,
+) -> StdResult<HumanAddr> {
+    let pool_info: MultiPoolInfo = deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+        callback_code_hash: code_hash.clone(),
+        contract_addr: contract_addr.clone(),
+        msg: to_binary(&QueryMsgPair::MultiPool {})?,
+    }))?;
+
+    Ok(pool_info.liquidity_token)
+}
@@ -1,11 +1,25 @@
-use cosmwasm_std::{Api, CanonicalAddr, Extern, Querier, StdError, StdResult, Storage};
+use cosmwasm_std::{
+    Api, CanonicalAddr, Decimal, Extern, HumanAddr, Querier, StdError, StdResult, Storage,
+};
 use cosmwasm_storage::{Bucket, ReadonlyBucket, ReadonlySingleton, Singleton};
 use schemars::JsonSchema;
-use secretswap::{AssetInfoRaw, PairInfo, PairInfoRaw, PairSettings};
+use secretswap::{
+    AssetInfoRaw, DynamicFee, Fee, MultiPoolInfo, MultiPoolInfoRaw, PairInfo, PairInfoRaw,
+    PairSettings, PairStatusLevel,
+};
 use serde::{Deserialize, Serialize};
 static KEY_CONFIG: &[u8] = b"config";
 static PAIR_TRACKER: &[u8] = b"pair_tracker";
 static PREFIX_PAIR_INFO: &[u8] = b"pair_info";
+static PREFIX_PAIR_FEE: &[u8] = b"pair_fee";
+static PREFIX_PAIR_DYNAMIC_FEE: &[u8] = b"pair_dynamic_fee";
+static PREFIX_PAIR_MAX_PRICE_MOVE: &[u8] = b"pair_max_price_move";
+static PREFIX_PAIR_STATUS: &[u8] = b"pair_status";
+static PREFIX_PAIR_MIGRATION: &[u8] = b"pair_migration";
+static PREFIX_PAIR_READS_OVERRIDES: &[u8] = b"pair_reads_overrides";
+static PREFIX_CONCENTRATED_PAIR_INFO: &[u8] = b"concentrated_pair_info";
+static MULTI_POOL_TRACKER: &[u8] = b"multi_pool_tracker";
+static PREFIX_MULTI_POOL_INFO: &[u8] = b"multi_pool_info";
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Config {
     pub owner: CanonicalAddr,
@@ -15,6 +29,16 @@ pub struct Config {
     pub pair_code_hash: String,
     pub prng_seed: Vec<u8>,
     pub pair_settings: PairSettings,
+    /// Concentrated pair code, zero until the owner sets it
+    #[serde(default)]
+    pub concentrated_pair_code_id: u64,
+    #[serde(default)]
+    pub concentrated_pair_code_hash: String,
+    /// Multi-asset pool code, zero until the owner sets it
+    #[serde(default)]
+    pub multi_pool_code_id: u64,
+    #[serde(default)]
+    pub multi_pool_code_hash: String,
 }
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default, JsonSchema)]
 pub struct PairTracker(pub Vec<Vec<u8>>);
@@ -30,10 +54,14 @@ pub fn store_config<S: Storage>(storage: &mut S, data: &Config) -> StdResult<()>
 pub fn read_config<S: Storage>(storage: &S) -> StdResult<Config> {
     ReadonlySingleton::new(storage, KEY_CONFIG).load()
 }
-pub fn store_pair<S: Storage>(storage: &mut S, data: &PairInfoRaw) -> StdResult<()> {
-    let mut asset_infos = data.asset_infos.clone().to_vec();
+// pairs are keyed by their sorted asset infos, so both orders find the same pair
+pub fn pair_key(asset_infos: &[AssetInfoRaw; 2]) -> Vec<u8> {
+    let mut asset_infos = asset_infos.clone().to_vec();
     asset_infos.sort_by(|a, b| a.as_bytes().cmp(&b.as_bytes()));
-    let key = &[asset_infos[0].as_bytes(), asset_infos[1].as_bytes()].concat();
+    [asset_infos[0].as_bytes(), asset_infos[1].as_bytes()].concat()
+}
+pub fn store_pair<S: Storage>(storage: &mut S, data: &PairInfoRaw) -> StdResult<()> {
+    let key = &pair_key(&data.asset_infos);
     let mut pair_bucket: Bucket<S, PairInfoRaw> = Bucket::new(PREFIX_PAIR_INFO, storage);
     pair_bucket.save(key, &data)?;
 
@@ -60,11 +88,9 @@ pub fn read_pair<S: Storage>(
     storage: &S,
     asset_infos: &[AssetInfoRaw; 2],
 ) -> StdResult<PairInfoRaw> {
-    let mut asset_infos = asset_infos.clone().to_vec();
-    asset_infos.sort_by(|a, b| a.as_bytes().cmp(&b.as_bytes()));
     let pair_bucket: ReadonlyBucket<S, PairInfoRaw> =
         ReadonlyBucket::new(PREFIX_PAIR_INFO, storage);
-    match pair_bucket.load(&[asset_infos[0].as_bytes(), asset_infos[1].as_bytes()].concat()) {
+    match pair_bucket.load(&pair_key(asset_infos)) {
         Ok(v) => Ok(v),
         Err(_e) => Err(StdError::generic_err("no pair data stored")),
     }
@@ -107,13 +133,225 @@ pub fn read_pairs<S: Storage, A: Api, Q: Querier>(
 // this will set the first key after the provided key, by appending a 1 byte
 fn calc_range_start(start_after: Option<[AssetInfoRaw; 2]>) -> Option<Vec<u8>> {
     start_after.map(|asset_infos| {
-        let mut asset_infos = asset_infos.to_vec();
-        asset_infos.sort_by(|a, b| a.as_bytes().cmp(&b.as_bytes()));
-        let mut v = [asset_infos[0].as_bytes(), asset_infos[1].as_bytes()]
-            .concat()
-            .as_slice()
-            .to_vec();
+        let mut v = pair_key(&asset_infos);
         v.push(1);
         v
     })
 }
+/// Swap fee override of a single pair, `None` removes it
+pub fn store_pair_fee<S: Storage>(
+    storage: &mut S,
+    asset_infos: &[AssetInfoRaw; 2],
+    fee: Option<Fee>,
+) -> StdResult<()> {
+    let mut fee_bucket: Bucket<S, Fee> = Bucket::new(PREFIX_PAIR_FEE, storage);
+    match fee {
+        Some(fee) => fee_bucket.save(&pair_key(asset_infos), &fee),
+        None => {
+            fee_bucket.remove(&pair_key(asset_infos));
+            Ok(())
+        }
+    }
+}
+pub fn read_pair_fee<S: Storage>(
+    storage: &S,
+    asset_infos: &[AssetInfoRaw; 2],
+) -> StdResult<Option<Fee>> {
+    let fee_bucket: ReadonlyBucket<S, Fee> = ReadonlyBucket::new(PREFIX_PAIR_FEE, storage);
+    fee_bucket.may_load(&pair_key(asset_infos))
+}
+/// Dynamic swap fee of a single pair, `None` removes it
+pub fn store_pair_dynamic_fee<S: Storage>(
+    storage: &mut S,
+    asset_infos: &[AssetInfoRaw; 2],
+    dynamic_fee: Option<DynamicFee>,
+) -> StdResult<()> {
+    let mut dynamic_fee_bucket: Bucket<S, DynamicFee> =
+        Bucket::new(PREFIX_PAIR_DYNAMIC_FEE, storage);
+    match dynamic_fee {
+        Some(dynamic_fee) => dynamic_fee_bucket.save(&pair_key(asset_infos), &dynamic_fee),
+        None => {
+            dynamic_fee_bucket.remove(&pair_key(asset_infos));
+            Ok(())
+        }
+    }
+}
+pub fn read_pair_dynamic_fee<S: Storage>(
+    storage: &S,
+    asset_infos: &[AssetInfoRaw; 2],
+) -> StdResult<Option<DynamicFee>> {
+    let dynamic_fee_bucket: ReadonlyBucket<S, DynamicFee> =
+        ReadonlyBucket::new(PREFIX_PAIR_DYNAMIC_FEE, storage);
+    dynamic_fee_bucket.may_load(&pair_key(asset_infos))
+}
+/// Circuit breaker of a single pair, `None` removes it
+pub fn store_pair_max_price_move<S: Storage>(
+    storage: &mut S,
+    asset_infos: &[AssetInfoRaw; 2],
+    max_price_move: Option<Decimal>,
+) -> StdResult<()> {
+    let mut max_price_move_bucket: Bucket<S, Decimal> =
+        Bucket::new(PREFIX_PAIR_MAX_PRICE_MOVE, storage);
+    match max_price_move {
+        Some(max_price_move) => max_price_move_bucket.save(&pair_key(asset_infos), &max_price_move),
+        None => {
+            max_price_move_bucket.remove(&pair_key(asset_infos));
+            Ok(())
+        }
+    }
+}
+pub fn read_pair_max_price_move<S: Storage>(
+    storage: &S,
+    asset_infos: &[AssetInfoRaw; 2],
+) -> StdResult<Option<Decimal>> {
+    let max_price_move_bucket: ReadonlyBucket<S, Decimal> =
+        ReadonlyBucket::new(PREFIX_PAIR_MAX_PRICE_MOVE, storage);
+    max_price_move_bucket.may_load(&pair_key(asset_infos))
+}
+/// Status of a single pair, `NormalRun` removes it
+pub fn store_pair_status<S: Storage>(
+    storage: &mut S,
+    asset_infos: &[AssetInfoRaw; 2],
+    status: PairStatusLevel,
+) -> StdResult<()> {
+    let mut status_bucket: Bucket<S, PairStatusLevel> = Bucket::new(PREFIX_PAIR_STATUS, storage);
+    match status {
+        PairStatusLevel::NormalRun => {
+            status_bucket.remove(&pair_key(asset_infos));
+            Ok(())
+        }
+        _ => status_bucket.save(&pair_key(asset_infos), &status),
+    }
+}
+pub fn read_pair_status<S: Storage>(
+    storage: &S,
+    asset_infos: &[AssetInfoRaw; 2],
+) -> StdResult<PairStatusLevel> {
+    let status_bucket: ReadonlyBucket<S, PairStatusLevel> =
+        ReadonlyBucket::new(PREFIX_PAIR_STATUS, storage);
+    Ok(status_bucket
+        .may_load(&pair_key(asset_infos))?
+        .unwrap_or_default())
+}
+/// Marks a pair that queries its settings with its asset infos and so picks up the
+/// per-pair overrides. Pairs registered before overrides existed only read the global settings.
+pub fn store_pair_reads_overrides<S: Storage>(
+    storage: &mut S,
+    asset_infos: &[AssetInfoRaw; 2],
+) -> StdResult<()> {
+    Bucket::new(PREFIX_PAIR_READS_OVERRIDES, storage).save(&pair_key(asset_infos), &true)
+}
+pub fn read_pair_reads_overrides<S: Storage>(
+    storage: &S,
+    asset_infos: &[AssetInfoRaw; 2],
+) -> StdResult<bool> {
+    Ok(ReadonlyBucket::new(PREFIX_PAIR_READS_OVERRIDES, storage)
+        .may_load(&pair_key(asset_infos))?
+        .unwrap_or(false))
+}
+/// Pair being replaced by a new instance, until the new pair registers
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PairMigration {
+    pub old_pair: HumanAddr,
+    pub old_pair_code_hash: String,
+}
+pub fn store_pair_migration<S: Storage>(
+    storage: &mut S,
+    asset_infos: &[AssetInfoRaw; 2],
+    data: &PairMigration,
+) -> StdResult<()> {
+    Bucket::new(PREFIX_PAIR_MIGRATION, storage).save(&pair_key(asset_infos), data)
+}
+pub fn read_pair_migration<S: Storage>(
+    storage: &S,
+    asset_infos: &[AssetInfoRaw; 2],
+) -> StdResult<Option<PairMigration>> {
+    ReadonlyBucket::new(PREFIX_PAIR_MIGRATION, storage).may_load(&pair_key(asset_infos))
+}
+pub fn remove_pair_migration<S: Storage>(storage: &mut S, asset_infos: &[AssetInfoRaw; 2]) {
+    Bucket::<S, PairMigration>::new(PREFIX_PAIR_MIGRATION, storage).remove(&pair_key(asset_infos))
+}
+/// Concentrated liquidity pair of the assets, kept apart from the regular pairs
+pub fn store_concentrated_pair<S: Storage>(storage: &mut S, data: &PairInfoRaw) -> StdResult<()> {
+    Bucket::new(PREFIX_CONCENTRATED_PAIR_INFO, storage).save(&pair_key(&data.asset_infos), data)
+}
+pub fn read_concentrated_pair<S: Storage>(
+    storage: &S,
+    asset_infos: &[AssetInfoRaw; 2],
+) -> StdResult<PairInfoRaw> {
+    let pair_bucket: ReadonlyBucket<S, PairInfoRaw> =
+        ReadonlyBucket::new(PREFIX_CONCENTRATED_PAIR_INFO, storage);
+    match pair_bucket.load(&pair_key(asset_infos)) {
+        Ok(v) => Ok(v),
+        Err(_e) => Err(StdError::generic_err("no concentrated pair data stored")),
+    }
+}
+/// Multi-asset pools are keyed by their sorted asset infos, each one prefixed with its length
+/// so that different sets of assets can't make the same key
+pub fn multi_pool_key(asset_infos: &[AssetInfoRaw]) -> Vec<u8> {
+    let mut asset_infos = asset_infos.to_vec();
+    asset_infos.sort_by(|a, b| a.as_bytes().cmp(&b.as_bytes()));
+    asset_infos
+        .iter()
+        .flat_map(|asset_info| {
+            let bytes = asset_info.as_bytes();
+            [&(bytes.len() as u16).to_be_bytes()[..], bytes].concat()
+        })
+        .collect()
+}
+pub fn store_multi_pool<S: Storage>(storage: &mut S, data: &MultiPoolInfoRaw) -> StdResult<()> {
+    let key = multi_pool_key(&data.asset_infos);
+    Bucket::new(PREFIX_MULTI_POOL_INFO, storage).save(&key, data)?;
+
+    let mut tracker: PairTracker = ReadonlySingleton::new(storage, MULTI_POOL_TRACKER)
+        .may_load()?
+        .unwrap_or_default();
+    if !tracker.0.iter().any(|i| *i == key) {
+        tracker.0.push(key);
+        Singleton::new(storage, MULTI_POOL_TRACKER).save(&tracker)?;
+    }
+    Ok(())
+}
+pub fn read_multi_pool<S: Storage>(
+    storage: &S,
+    asset_infos: &[AssetInfoRaw],
+) -> StdResult<MultiPoolInfoRaw> {
+    let pool_bucket: ReadonlyBucket<S, MultiPoolInfoRaw> =
+        ReadonlyBucket::new(PREFIX_MULTI_POOL_INFO, storage);
+    match pool_bucket.load(&multi_pool_key(asset_infos)) {
+        Ok(v) => Ok(v),
+        Err(_e) => Err(StdError::generic_err("no multi-asset pool data stored")),
+    }
+}
+/// Registered multi-asset pools in the order they were created
+pub fn read_multi_pools<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    start_after: Option<Vec<AssetInfoRaw>>,
+    limit: Option<u32>,
+) -> StdResult<Vec<MultiPoolInfo>> {
+    let tracker: PairTracker = ReadonlySingleton::new(&deps.storage, MULTI_POOL_TRACKER)
+        .may_load()?
+        .unwrap_or_default();
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let mut iter = tracker.0.iter();
+    if let Some(start_after) = start_after {
+        let start = multi_pool_key(&start_after);
+        iter.position(|key| key == &start);
+    }
+
+    let pool_bucket: ReadonlyBucket<S, MultiPoolInfoRaw> =
+        ReadonlyBucket::new(PREFIX_MULTI_POOL_INFO, &deps.storage);
+    let mut pools = vec![];
+    for key in iter {
+        let pool_info = pool_bucket.load(key)?;
+        // pools are listed once they registered
+        if pool_info.contract_addr == CanonicalAddr::default() {
+            continue;
+        }
+        pools.push(pool_info.to_normal(&deps)?);
+        if pools.len() == limit {
+            break;
+        }
+    }
+    Ok(pools)
+}
@@ -1,14 +1,24 @@
 use cosmwasm_std::testing::{mock_env, MOCK_CONTRACT_ADDR};
 use cosmwasm_std::{
-    from_binary, log, to_binary, CanonicalAddr, CosmosMsg, HumanAddr, StdError, WasmMsg,
+    from_binary, log, to_binary, Api, Binary, CanonicalAddr, CosmosMsg, Decimal, Extern, HumanAddr,
+    Querier, StdError, Storage, Uint128, WasmMsg,
 };
+use secret_toolkit::crypto::sha_256;
 
-use secretswap::{AssetInfo, InitHook, PairInfo, PairInitMsg};
+use secretswap::{
+    AssetInfo, ConcentratedPairInitMsg, CurveType, DynamicFee, Factory, Fee, InitHook, MigrateFrom,
+    MultiPoolInfo, MultiPoolInitMsg, PairInfo, PairInfoRaw, PairInitMsg, PairSettings,
+    PairStatusLevel, ProtocolFee,
+};
 
 use crate::contract::{handle, init, query};
 use crate::mock_querier::mock_dependencies;
-use crate::msg::{ConfigResponse, HandleMsg, InitMsg, PairsResponse, QueryMsg};
-use crate::state::read_pair;
+use crate::msg::{
+    ConfigResponse, HandleMsg, InitMsg, MultiPoolsResponse, PairHandleMsg, PairsResponse, QueryMsg,
+};
+use crate::state::{
+    read_pair, read_pair_migration, store_pair, store_pair_reads_overrides, PairMigration,
+};
 
 #[test]
 fn proper_initialization() {
@@ -125,6 +135,7 @@ fn create_pair() {
     let msg = HandleMsg::CreatePair {
         asset_infos: asset_infos.clone(),
         init_hook: None,
+        curve: None,
     };
 
     let env = mock_env("addr0000", &[]);
@@ -191,6 +202,7 @@ fn register() {
     let msg = HandleMsg::CreatePair {
         asset_infos: asset_infos.clone(),
         init_hook: None,
+        curve: None,
     };
 
     let env = mock_env("addr0000", &[]);
@@ -262,6 +274,7 @@ fn register() {
     let msg = HandleMsg::CreatePair {
         asset_infos: asset_infos_2.clone(),
         init_hook: None,
+        curve: None,
     };
 
     let env = mock_env("addr0000", &[]);
@@ -346,3 +359,1281 @@ fn register() {
         }]
     );
 }
+
+#[test]
+fn pair_fee_override() {
+    let mut deps = mock_dependencies(20, &[]);
+
+    let msg = InitMsg {
+        pair_code_id: 321u64,
+        token_code_id: 123u64,
+        init_hook: None,
+        token_code_hash: "token_hash".to_string(),
+        pair_code_hash: "pair_hash".to_string(),
+        prng_seed: Binary::from("seed".as_bytes()),
+    };
+    let env = mock_env("owner0000", &[]);
+    let _res = init(&mut deps, env, msg).unwrap();
+
+    let asset_infos = [
+        AssetInfo::NativeToken {
+            denom: "uscrt".to_string(),
+        },
+        AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        },
+    ];
+    let raw_infos = [
+        asset_infos[0].to_raw(&deps).unwrap(),
+        asset_infos[1].to_raw(&deps).unwrap(),
+    ];
+    store_pair(
+        &mut deps.storage,
+        &PairInfoRaw {
+            asset_infos: raw_infos.clone(),
+            contract_addr: deps
+                .api
+                .canonical_address(&HumanAddr::from("pair0000"))
+                .unwrap(),
+            liquidity_token: CanonicalAddr::default(),
+            token_code_hash: "token_hash".to_string(),
+            asset0_volume: Uint128::zero(),
+            asset1_volume: Uint128::zero(),
+            factory: Factory {
+                address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                code_hash: "factory_hash".to_string(),
+            },
+            curve: CurveType::ConstantProduct {},
+        },
+    )
+    .unwrap();
+    store_pair_reads_overrides(&mut deps.storage, &raw_infos).unwrap();
+
+    let stable_fee = Fee {
+        commission_rate_nom: Uint128(4),
+        commission_rate_denom: Uint128(10000),
+    };
+    let set_msg = |swap_fee: Option<Fee>| HandleMsg::SetPairFee {
+        asset_infos: asset_infos.clone(),
+        swap_fee,
+    };
+
+    // only the owner can set an override
+    let env = mock_env("addr0000", &[]);
+    match handle(&mut deps, env, set_msg(Some(stable_fee.clone()))) {
+        Err(StdError::Unauthorized { .. }) => {}
+        _ => panic!("Must return unauthorized error"),
+    }
+
+    // a fee of 100% or more is rejected
+    let env = mock_env("owner0000", &[]);
+    let invalid_fee = Fee {
+        commission_rate_nom: Uint128(1),
+        commission_rate_denom: Uint128(1),
+    };
+    match handle(&mut deps, env, set_msg(Some(invalid_fee))) {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "Swap fee must be lower than 100%")
+        }
+        _ => panic!("Must return generic error"),
+    }
+
+    let env = mock_env("owner0000", &[]);
+    let _res = handle(&mut deps, env, set_msg(Some(stable_fee.clone()))).unwrap();
+
+    // the override applies in both asset orders, other queries keep the global fee
+    let global_fee = query_settings(&deps, None).swap_fee;
+    assert_ne!(global_fee, stable_fee);
+    assert_eq!(
+        query_settings(&deps, Some(asset_infos.clone())).swap_fee,
+        stable_fee
+    );
+    assert_eq!(
+        query_settings(
+            &deps,
+            Some([asset_infos[1].clone(), asset_infos[0].clone()])
+        )
+        .swap_fee,
+        stable_fee
+    );
+
+    // removing the override falls back to the global fee
+    let env = mock_env("owner0000", &[]);
+    let _res = handle(&mut deps, env, set_msg(None)).unwrap();
+    assert_eq!(
+        query_settings(&deps, Some(asset_infos.clone())).swap_fee,
+        global_fee
+    );
+}
+
+#[test]
+fn legacy_pair_overrides() {
+    let mut deps = mock_dependencies(20, &[]);
+
+    let msg = InitMsg {
+        pair_code_id: 321u64,
+        token_code_id: 123u64,
+        init_hook: None,
+        token_code_hash: "token_hash".to_string(),
+        pair_code_hash: "pair_hash".to_string(),
+        prng_seed: Binary::from("seed".as_bytes()),
+    };
+    let env = mock_env("owner0000", &[]);
+    let _res = init(&mut deps, env, msg).unwrap();
+
+    // a pair registered before per-pair settings, it queries them without asset infos
+    let legacy_infos = [
+        AssetInfo::NativeToken {
+            denom: "uscrt".to_string(),
+        },
+        AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        },
+    ];
+    let legacy_raw_infos = [
+        legacy_infos[0].to_raw(&deps).unwrap(),
+        legacy_infos[1].to_raw(&deps).unwrap(),
+    ];
+    store_pair(
+        &mut deps.storage,
+        &PairInfoRaw {
+            asset_infos: legacy_raw_infos,
+            contract_addr: deps
+                .api
+                .canonical_address(&HumanAddr::from("pair0000"))
+                .unwrap(),
+            liquidity_token: CanonicalAddr::default(),
+            token_code_hash: "pair_hash".to_string(),
+            asset0_volume: Uint128::zero(),
+            asset1_volume: Uint128::zero(),
+            factory: Factory {
+                address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                code_hash: "factory_hash".to_string(),
+            },
+            curve: CurveType::ConstantProduct {},
+        },
+    )
+    .unwrap();
+
+    let stable_fee = Fee {
+        commission_rate_nom: Uint128(4),
+        commission_rate_denom: Uint128(10000),
+    };
+
+    // the override would never reach it
+    let env = mock_env("owner0000", &[]);
+    let res = handle(
+        &mut deps,
+        env,
+        HandleMsg::SetPairFee {
+            asset_infos: legacy_infos.clone(),
+            swap_fee: Some(stable_fee.clone()),
+        },
+    );
+    match res {
+        Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+            msg,
+            "Pair was registered before per-pair settings and only reads the global settings"
+        ),
+        _ => panic!("Must return generic error"),
+    }
+
+    // the global fee reaches it, but only a valid one
+    let update = |swap_fee: Fee| HandleMsg::UpdateConfig {
+        owner: None,
+        token_code_id: None,
+        pair_code_id: None,
+        pair_code_hash: None,
+        token_code_hash: None,
+        swap_fee: Some(swap_fee),
+        protocol_fee: None,
+        concentrated_pair_code_id: None,
+        concentrated_pair_code_hash: None,
+        multi_pool_code_id: None,
+        multi_pool_code_hash: None,
+    };
+    let invalid_fee = Fee {
+        commission_rate_nom: Uint128(1),
+        commission_rate_denom: Uint128(1),
+    };
+    let env = mock_env("owner0000", &[]);
+    match handle(&mut deps, env, update(invalid_fee)) {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "Swap fee must be lower than 100%")
+        }
+        _ => panic!("Must return generic error"),
+    }
+    let env = mock_env("owner0000", &[]);
+    let _res = handle(&mut deps, env, update(stable_fee.clone())).unwrap();
+    assert_eq!(query_settings(&deps, None).swap_fee, stable_fee);
+
+    // pairs created and registered through the factory take overrides
+    let asset_infos = [
+        AssetInfo::NativeToken {
+            denom: "uscrt".to_string(),
+        },
+        AssetInfo::NativeToken {
+            denom: "ueur".to_string(),
+        },
+    ];
+    let env = mock_env("addr0000", &[]);
+    let _res = handle(
+        &mut deps,
+        env,
+        HandleMsg::CreatePair {
+            asset_infos: asset_infos.clone(),
+            init_hook: None,
+            curve: None,
+        },
+    )
+    .unwrap();
+    deps.querier.with_terraswap_pairs(&[(
+        &HumanAddr::from("pair0001"),
+        &PairInfo {
+            asset_infos: asset_infos.clone(),
+            contract_addr: HumanAddr::from("pair0001"),
+            liquidity_token: HumanAddr::from("liquidity0001"),
+            token_code_hash: "token_hash".to_string(),
+            asset0_volume: Uint128::zero(),
+            asset1_volume: Uint128::zero(),
+            factory: Factory {
+                address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                code_hash: "".to_string(),
+            },
+            curve: CurveType::ConstantProduct {},
+        },
+    )]);
+    let env = mock_env("pair0001", &[]);
+    let _res = handle(
+        &mut deps,
+        env,
+        HandleMsg::Register {
+            asset_infos: asset_infos.clone(),
+        },
+    )
+    .unwrap();
+
+    let pair_fee = Fee {
+        commission_rate_nom: Uint128(1),
+        commission_rate_denom: Uint128(100),
+    };
+    let env = mock_env("owner0000", &[]);
+    let _res = handle(
+        &mut deps,
+        env,
+        HandleMsg::SetPairFee {
+            asset_infos: asset_infos.clone(),
+            swap_fee: Some(pair_fee.clone()),
+        },
+    )
+    .unwrap();
+    assert_eq!(query_settings(&deps, Some(asset_infos)).swap_fee, pair_fee);
+}
+
+#[test]
+fn pair_dynamic_fee() {
+    let mut deps = mock_dependencies(20, &[]);
+
+    let msg = InitMsg {
+        pair_code_id: 321u64,
+        token_code_id: 123u64,
+        init_hook: None,
+        token_code_hash: "token_hash".to_string(),
+        pair_code_hash: "pair_hash".to_string(),
+        prng_seed: Binary::from("seed".as_bytes()),
+    };
+    let env = mock_env("owner0000", &[]);
+    let _res = init(&mut deps, env, msg).unwrap();
+
+    let asset_infos = [
+        AssetInfo::NativeToken {
+            denom: "uscrt".to_string(),
+        },
+        AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        },
+    ];
+    let raw_infos = [
+        asset_infos[0].to_raw(&deps).unwrap(),
+        asset_infos[1].to_raw(&deps).unwrap(),
+    ];
+    store_pair(
+        &mut deps.storage,
+        &PairInfoRaw {
+            asset_infos: raw_infos.clone(),
+            contract_addr: deps
+                .api
+                .canonical_address(&HumanAddr::from("pair0000"))
+                .unwrap(),
+            liquidity_token: CanonicalAddr::default(),
+            token_code_hash: "token_hash".to_string(),
+            asset0_volume: Uint128::zero(),
+            asset1_volume: Uint128::zero(),
+            factory: Factory {
+                address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                code_hash: "factory_hash".to_string(),
+            },
+            curve: CurveType::ConstantProduct {},
+        },
+    )
+    .unwrap();
+    store_pair_reads_overrides(&mut deps.storage, &raw_infos).unwrap();
+
+    let dynamic_fee = DynamicFee {
+        min_fee: Fee {
+            commission_rate_nom: Uint128(1),
+            commission_rate_denom: Uint128(1000),
+        },
+        max_fee: Fee {
+            commission_rate_nom: Uint128(10),
+            commission_rate_denom: Uint128(1000),
+        },
+        max_volatility: Decimal::percent(2),
+    };
+    let set_msg = |dynamic_fee: Option<DynamicFee>| HandleMsg::SetPairDynamicFee {
+        asset_infos: asset_infos.clone(),
+        dynamic_fee,
+    };
+
+    // only the owner can set a dynamic fee
+    let env = mock_env("addr0000", &[]);
+    match handle(&mut deps, env, set_msg(Some(dynamic_fee.clone()))) {
+        Err(StdError::Unauthorized { .. }) => {}
+        _ => panic!("Must return unauthorized error"),
+    }
+
+    // the bounds must be ordered
+    let env = mock_env("owner0000", &[]);
+    let invalid_fee = DynamicFee {
+        min_fee: dynamic_fee.max_fee.clone(),
+        max_fee: dynamic_fee.min_fee.clone(),
+        ..dynamic_fee.clone()
+    };
+    match handle(&mut deps, env, set_msg(Some(invalid_fee))) {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "Minimum fee must not exceed the maximum fee")
+        }
+        _ => panic!("Must return generic error"),
+    }
+
+    let env = mock_env("owner0000", &[]);
+    let res = handle(&mut deps, env, set_msg(Some(dynamic_fee.clone()))).unwrap();
+    assert_eq!(
+        res.log,
+        vec![
+            log("action", "set_pair_dynamic_fee"),
+            log("pair", "uscrt-uusd"),
+            log("dynamic_fee", "1/1000 to 10/1000 at 0.02"),
+        ]
+    );
+
+    // pairs get the dynamic fee in both asset orders, the static fee is unchanged
+    let global_settings = query_settings(&deps, None);
+    assert_eq!(global_settings.dynamic_fee, None);
+    let pair_settings = query_settings(
+        &deps,
+        Some([asset_infos[1].clone(), asset_infos[0].clone()]),
+    );
+    assert_eq!(pair_settings.dynamic_fee, Some(dynamic_fee));
+    assert_eq!(pair_settings.swap_fee, global_settings.swap_fee);
+
+    let env = mock_env("owner0000", &[]);
+    let _res = handle(&mut deps, env, set_msg(None)).unwrap();
+    assert_eq!(
+        query_settings(&deps, Some(asset_infos.clone())).dynamic_fee,
+        None
+    );
+}
+
+#[test]
+fn pair_circuit_breaker() {
+    let mut deps = mock_dependencies(20, &[]);
+
+    let msg = InitMsg {
+        pair_code_id: 321u64,
+        token_code_id: 123u64,
+        init_hook: None,
+        token_code_hash: "token_hash".to_string(),
+        pair_code_hash: "pair_hash".to_string(),
+        prng_seed: Binary::from("seed".as_bytes()),
+    };
+    let env = mock_env("owner0000", &[]);
+    let _res = init(&mut deps, env, msg).unwrap();
+
+    let asset_infos = [
+        AssetInfo::NativeToken {
+            denom: "uscrt".to_string(),
+        },
+        AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        },
+    ];
+    let raw_infos = [
+        asset_infos[0].to_raw(&deps).unwrap(),
+        asset_infos[1].to_raw(&deps).unwrap(),
+    ];
+    store_pair(
+        &mut deps.storage,
+        &PairInfoRaw {
+            asset_infos: raw_infos.clone(),
+            contract_addr: deps
+                .api
+                .canonical_address(&HumanAddr::from("pair0000"))
+                .unwrap(),
+            liquidity_token: CanonicalAddr::default(),
+            token_code_hash: "token_hash".to_string(),
+            asset0_volume: Uint128::zero(),
+            asset1_volume: Uint128::zero(),
+            factory: Factory {
+                address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                code_hash: "factory_hash".to_string(),
+            },
+            curve: CurveType::ConstantProduct {},
+        },
+    )
+    .unwrap();
+    store_pair_reads_overrides(&mut deps.storage, &raw_infos).unwrap();
+
+    let set_msg = |max_price_move: Option<Decimal>| HandleMsg::SetPairCircuitBreaker {
+        asset_infos: asset_infos.clone(),
+        max_price_move,
+    };
+
+    // only the owner can set a circuit breaker
+    let env = mock_env("addr0000", &[]);
+    match handle(&mut deps, env, set_msg(Some(Decimal::percent(5)))) {
+        Err(StdError::Unauthorized { .. }) => {}
+        _ => panic!("Must return unauthorized error"),
+    }
+
+    for invalid in [Decimal::zero(), Decimal::percent(101)].iter() {
+        let env = mock_env("owner0000", &[]);
+        match handle(&mut deps, env, set_msg(Some(*invalid))) {
+            Err(StdError::GenericErr { msg, .. }) => {
+                assert_eq!(
+                    msg,
+                    "Maximum price move must be greater than 0 and at most 1"
+                )
+            }
+            _ => panic!("Must return generic error"),
+        }
+    }
+
+    let env = mock_env("owner0000", &[]);
+    let res = handle(&mut deps, env, set_msg(Some(Decimal::percent(5)))).unwrap();
+    assert_eq!(
+        res.log,
+        vec![
+            log("action", "set_pair_circuit_breaker"),
+            log("pair", "uscrt-uusd"),
+            log("max_price_move", "0.05"),
+        ]
+    );
+
+    // pairs get the limit in both asset orders
+    assert_eq!(query_settings(&deps, None).max_price_move, None);
+    assert_eq!(
+        query_settings(
+            &deps,
+            Some([asset_infos[1].clone(), asset_infos[0].clone()])
+        )
+        .max_price_move,
+        Some(Decimal::percent(5))
+    );
+
+    let env = mock_env("owner0000", &[]);
+    let _res = handle(&mut deps, env, set_msg(None)).unwrap();
+    assert_eq!(
+        query_settings(&deps, Some(asset_infos.clone())).max_price_move,
+        None
+    );
+}
+
+#[test]
+fn pair_status() {
+    let mut deps = mock_dependencies(20, &[]);
+
+    let msg = InitMsg {
+        pair_code_id: 321u64,
+        token_code_id: 123u64,
+        init_hook: None,
+        token_code_hash: "token_hash".to_string(),
+        pair_code_hash: "pair_hash".to_string(),
+        prng_seed: Binary::from("seed".as_bytes()),
+    };
+    let env = mock_env("owner0000", &[]);
+    let _res = init(&mut deps, env, msg).unwrap();
+
+    let asset_infos = [
+        AssetInfo::NativeToken {
+            denom: "uscrt".to_string(),
+        },
+        AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        },
+    ];
+    let raw_infos = [
+        asset_infos[0].to_raw(&deps).unwrap(),
+        asset_infos[1].to_raw(&deps).unwrap(),
+    ];
+    store_pair(
+        &mut deps.storage,
+        &PairInfoRaw {
+            asset_infos: raw_infos.clone(),
+            contract_addr: deps
+                .api
+                .canonical_address(&HumanAddr::from("pair0000"))
+                .unwrap(),
+            liquidity_token: CanonicalAddr::default(),
+            token_code_hash: "token_hash".to_string(),
+            asset0_volume: Uint128::zero(),
+            asset1_volume: Uint128::zero(),
+            factory: Factory {
+                address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                code_hash: "factory_hash".to_string(),
+            },
+            curve: CurveType::ConstantProduct {},
+        },
+    )
+    .unwrap();
+    store_pair_reads_overrides(&mut deps.storage, &raw_infos).unwrap();
+
+    let set_msg = |asset_infos: Option<[AssetInfo; 2]>, level: PairStatusLevel| {
+        HandleMsg::SetPairStatus { asset_infos, level }
+    };
+
+    // only the owner can stop pairs
+    let env = mock_env("addr0000", &[]);
+    match handle(&mut deps, env, set_msg(None, PairStatusLevel::StopSwaps)) {
+        Err(StdError::Unauthorized { .. }) => {}
+        _ => panic!("Must return unauthorized error"),
+    }
+
+    let env = mock_env("owner0000", &[]);
+    let _res = handle(
+        &mut deps,
+        env,
+        set_msg(Some(asset_infos.clone()), PairStatusLevel::StopSwaps),
+    )
+    .unwrap();
+
+    assert_eq!(
+        query_settings(&deps, Some(asset_infos.clone())).status,
+        PairStatusLevel::StopSwaps
+    );
+    assert_eq!(
+        query_settings(&deps, None).status,
+        PairStatusLevel::NormalRun
+    );
+
+    // the stricter of the pair and the global status applies
+    let env = mock_env("owner0000", &[]);
+    let _res = handle(
+        &mut deps,
+        env,
+        set_msg(None, PairStatusLevel::StopAllButWithdrawals),
+    )
+    .unwrap();
+    assert_eq!(
+        query_settings(&deps, Some(asset_infos.clone())).status,
+        PairStatusLevel::StopAllButWithdrawals
+    );
+
+    let env = mock_env("owner0000", &[]);
+    let _res = handle(&mut deps, env, set_msg(None, PairStatusLevel::NormalRun)).unwrap();
+    assert_eq!(
+        query_settings(&deps, Some(asset_infos.clone())).status,
+        PairStatusLevel::StopSwaps
+    );
+
+    let env = mock_env("owner0000", &[]);
+    let _res = handle(
+        &mut deps,
+        env,
+        set_msg(Some(asset_infos.clone()), PairStatusLevel::NormalRun),
+    )
+    .unwrap();
+    assert_eq!(
+        query_settings(&deps, Some(asset_infos.clone())).status,
+        PairStatusLevel::NormalRun
+    );
+}
+
+#[test]
+fn migrate_pair() {
+    let mut deps = mock_dependencies(20, &[]);
+
+    let msg = InitMsg {
+        pair_code_id: 321u64,
+        token_code_id: 123u64,
+        init_hook: None,
+        token_code_hash: "token_hash".to_string(),
+        pair_code_hash: "pair_hash".to_string(),
+        prng_seed: Binary::from("seed".as_bytes()),
+    };
+    let env = mock_env("owner0000", &[]);
+    let _res = init(&mut deps, env, msg).unwrap();
+
+    let asset_infos = [
+        AssetInfo::NativeToken {
+            denom: "uscrt".to_string(),
+        },
+        AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        },
+    ];
+    let raw_infos = [
+        asset_infos[0].to_raw(&deps).unwrap(),
+        asset_infos[1].to_raw(&deps).unwrap(),
+    ];
+    store_pair(
+        &mut deps.storage,
+        &PairInfoRaw {
+            asset_infos: raw_infos.clone(),
+            contract_addr: deps
+                .api
+                .canonical_address(&HumanAddr::from("pair0000"))
+                .unwrap(),
+            liquidity_token: CanonicalAddr::default(),
+            token_code_hash: "pair_hash".to_string(),
+            asset0_volume: Uint128::zero(),
+            asset1_volume: Uint128::zero(),
+            factory: Factory {
+                address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                code_hash: "factory_hash".to_string(),
+            },
+            curve: CurveType::StableSwap { amp: 100 },
+        },
+    )
+    .unwrap();
+
+    let migrate_msg = HandleMsg::MigratePair {
+        asset_infos: asset_infos.clone(),
+    };
+
+    // nothing to migrate to yet
+    let env = mock_env("owner0000", &[]);
+    match handle(&mut deps, env, migrate_msg.clone()) {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "Pair already runs the current pair code")
+        }
+        _ => panic!("Must return generic error"),
+    }
+
+    // a new pair code
+    let env = mock_env("owner0000", &[]);
+    let _res = handle(
+        &mut deps,
+        env,
+        HandleMsg::UpdateConfig {
+            owner: None,
+            token_code_id: None,
+            pair_code_id: Some(322u64),
+            pair_code_hash: Some("pair_hash_v2".to_string()),
+            token_code_hash: None,
+            swap_fee: None,
+            protocol_fee: None,
+            concentrated_pair_code_id: None,
+            concentrated_pair_code_hash: None,
+            multi_pool_code_id: None,
+            multi_pool_code_hash: None,
+        },
+    )
+    .unwrap();
+    let config_res: ConfigResponse =
+        from_binary(&query(&deps, QueryMsg::Config {}).unwrap()).unwrap();
+    assert_eq!(config_res.pair_code_hash, "pair_hash_v2");
+    assert_eq!(config_res.token_code_hash, "token_hash");
+
+    // only the owner can migrate pairs
+    let env = mock_env("addr0000", &[]);
+    match handle(&mut deps, env, migrate_msg.clone()) {
+        Err(StdError::Unauthorized { .. }) => {}
+        _ => panic!("Must return unauthorized error"),
+    }
+
+    // the new pair is instantiated with the same curve and the old pair to migrate from
+    let env = mock_env("owner0000", &[]);
+    let res = handle(&mut deps, env, migrate_msg).unwrap();
+    assert_eq!(res.messages.len(), 1);
+    match &res.messages[0] {
+        CosmosMsg::Wasm(WasmMsg::Instantiate {
+            code_id,
+            msg,
+            callback_code_hash,
+            ..
+        }) => {
+            assert_eq!(*code_id, 322u64);
+            assert_eq!(callback_code_hash, "pair_hash_v2");
+            let init_msg: PairInitMsg = from_binary(msg).unwrap();
+            assert_eq!(
+                init_msg.migrate_from,
+                Some(MigrateFrom {
+                    address: HumanAddr::from("pair0000"),
+                    code_hash: "pair_hash".to_string(),
+                })
+            );
+            assert_eq!(init_msg.curve, Some(CurveType::StableSwap { amp: 100 }));
+        }
+        _ => panic!("Must instantiate the new pair"),
+    }
+    assert_eq!(
+        read_pair_migration(&deps.storage, &raw_infos).unwrap(),
+        Some(PairMigration {
+            old_pair: HumanAddr::from("pair0000"),
+            old_pair_code_hash: "pair_hash".to_string(),
+        })
+    );
+
+    // the new pair registers in its place and the old pair hands its reserves over
+    deps.querier.with_terraswap_pairs(&[(
+        &HumanAddr::from("pair0001"),
+        &PairInfo {
+            asset_infos: asset_infos.clone(),
+            contract_addr: HumanAddr::from("pair0001"),
+            liquidity_token: HumanAddr::from("liquidity0001"),
+            token_code_hash: "token_hash".to_string(),
+            asset0_volume: Uint128::zero(),
+            asset1_volume: Uint128::zero(),
+            factory: Factory {
+                address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                code_hash: "".to_string(),
+            },
+            curve: CurveType::StableSwap { amp: 100 },
+        },
+    )]);
+    let env = mock_env("pair0001", &[]);
+    let res = handle(
+        &mut deps,
+        env,
+        HandleMsg::Register {
+            asset_infos: asset_infos.clone(),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: HumanAddr::from("pair0000"),
+            callback_code_hash: "pair_hash".to_string(),
+            msg: to_binary(&PairHandleMsg::MigrateReserves {
+                new_pair: HumanAddr::from("pair0001"),
+                new_pair_code_hash: "pair_hash_v2".to_string(),
+            })
+            .unwrap(),
+            send: vec![],
+        })]
+    );
+    assert_eq!(
+        read_pair_migration(&deps.storage, &raw_infos).unwrap(),
+        None
+    );
+    let pair_info = read_pair(&deps.storage, &raw_infos).unwrap();
+    assert_eq!(
+        pair_info.contract_addr,
+        deps.api
+            .canonical_address(&HumanAddr::from("pair0001"))
+            .unwrap()
+    );
+    assert_eq!(pair_info.token_code_hash, "pair_hash_v2");
+}
+
+#[test]
+fn create_concentrated_pair() {
+    let mut deps = mock_dependencies(20, &[]);
+
+    let msg = InitMsg {
+        pair_code_id: 321u64,
+        token_code_id: 123u64,
+        init_hook: None,
+        token_code_hash: "token_hash".to_string(),
+        pair_code_hash: "pair_hash".to_string(),
+        prng_seed: Binary::from("seed".as_bytes()),
+    };
+    let env = mock_env("owner0000", &[]);
+    let _res = init(&mut deps, env, msg).unwrap();
+
+    let asset_infos = [
+        AssetInfo::NativeToken {
+            denom: "uscrt".to_string(),
+        },
+        AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        },
+    ];
+    let create_msg = HandleMsg::CreateConcentratedPair {
+        asset_infos: asset_infos.clone(),
+        tick_spacing: 60,
+        initial_price: Decimal::percent(250),
+        init_hook: None,
+    };
+
+    // the owner has to set the code first
+    let env = mock_env("addr0000", &[]);
+    match handle(&mut deps, env, create_msg.clone()) {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "Concentrated pair code is not set")
+        }
+        _ => panic!("Must return generic error"),
+    }
+
+    let env = mock_env("owner0000", &[]);
+    let _res = handle(
+        &mut deps,
+        env,
+        HandleMsg::UpdateConfig {
+            owner: None,
+            token_code_id: None,
+            pair_code_id: None,
+            pair_code_hash: None,
+            token_code_hash: None,
+            swap_fee: None,
+            protocol_fee: None,
+            concentrated_pair_code_id: Some(400u64),
+            concentrated_pair_code_hash: Some("cl_pair_hash".to_string()),
+            multi_pool_code_id: None,
+            multi_pool_code_hash: None,
+        },
+    )
+    .unwrap();
+    let config_res: ConfigResponse =
+        from_binary(&query(&deps, QueryMsg::Config {}).unwrap()).unwrap();
+    assert_eq!(config_res.concentrated_pair_code_id, 400u64);
+    assert_eq!(config_res.pair_code_id, 321u64);
+
+    let env = mock_env("addr0000", &[]);
+    let res = handle(&mut deps, env, create_msg.clone()).unwrap();
+    assert_eq!(res.messages.len(), 1);
+    match &res.messages[0] {
+        CosmosMsg::Wasm(WasmMsg::Instantiate {
+            code_id,
+            msg,
+            callback_code_hash,
+            ..
+        }) => {
+            assert_eq!(*code_id, 400u64);
+            assert_eq!(callback_code_hash, "cl_pair_hash");
+            let init_msg: ConcentratedPairInitMsg = from_binary(msg).unwrap();
+            assert_eq!(init_msg.tick_spacing, 60);
+            assert_eq!(init_msg.initial_price, Decimal::percent(250));
+            assert_eq!(
+                init_msg.init_hook.unwrap().msg,
+                to_binary(&HandleMsg::RegisterConcentratedPair {
+                    asset_infos: asset_infos.clone(),
+                })
+                .unwrap()
+            );
+        }
+        _ => panic!("Must instantiate the concentrated pair"),
+    }
+
+    // one concentrated pair for each pair of assets, alongside the regular pair
+    let env = mock_env("addr0000", &[]);
+    match handle(&mut deps, env, create_msg) {
+        Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "Pair already exists"),
+        _ => panic!("Must return generic error"),
+    }
+    query(
+        &deps,
+        QueryMsg::Pair {
+            asset_infos: asset_infos.clone(),
+        },
+    )
+    .unwrap_err();
+
+    // the pair registers itself once
+    let register_msg = HandleMsg::RegisterConcentratedPair {
+        asset_infos: [asset_infos[1].clone(), asset_infos[0].clone()],
+    };
+    let env = mock_env("clpair0000", &[]);
+    let _res = handle(&mut deps, env, register_msg.clone()).unwrap();
+    let env = mock_env("clpair0001", &[]);
+    match handle(&mut deps, env, register_msg) {
+        Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "Pair was already registered"),
+        _ => panic!("Must return generic error"),
+    }
+
+    let pair_res: PairInfo = from_binary(
+        &query(
+            &deps,
+            QueryMsg::ConcentratedPair {
+                asset_infos: asset_infos.clone(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(pair_res.contract_addr, HumanAddr::from("clpair0000"));
+    assert_eq!(pair_res.token_code_hash, "cl_pair_hash");
+
+    // settings overrides apply to the concentrated pair too
+    let env = mock_env("owner0000", &[]);
+    let _res = handle(
+        &mut deps,
+        env,
+        HandleMsg::SetPairStatus {
+            asset_infos: Some(asset_infos.clone()),
+            level: PairStatusLevel::StopSwaps,
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        query_settings(&deps, Some(asset_infos)).status,
+        PairStatusLevel::StopSwaps
+    );
+}
+
+#[test]
+fn swap_data_endpoints() {
+    let mut deps = mock_dependencies(20, &[]);
+
+    let msg = InitMsg {
+        pair_code_id: 321u64,
+        token_code_id: 123u64,
+        init_hook: None,
+        token_code_hash: "token_hash".to_string(),
+        pair_code_hash: "pair_hash".to_string(),
+        prng_seed: Binary::from("seed".as_bytes()),
+    };
+    let env = mock_env("owner0000", &[]);
+    let _res = init(&mut deps, env, msg).unwrap();
+
+    let add_msg = HandleMsg::AddSwapDataEndpoint {
+        address: HumanAddr::from("stats0000"),
+        code_hash: "stats_hash".to_string(),
+        best_effort_key: None,
+    };
+
+    // only the owner can add endpoints
+    let env = mock_env("addr0000", &[]);
+    match handle(&mut deps, env, add_msg.clone()) {
+        Err(StdError::Unauthorized { .. }) => {}
+        _ => panic!("Must return unauthorized error"),
+    }
+
+    let env = mock_env("owner0000", &[]);
+    let _res = handle(&mut deps, env, add_msg.clone()).unwrap();
+    let env = mock_env("owner0000", &[]);
+    match handle(&mut deps, env, add_msg) {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "Swap data endpoint is already registered")
+        }
+        _ => panic!("Must return generic error"),
+    }
+
+    // a best-effort endpoint only keeps the hash of its key
+    let env = mock_env("owner0000", &[]);
+    let res = handle(
+        &mut deps,
+        env,
+        HandleMsg::AddSwapDataEndpoint {
+            address: HumanAddr::from("indexer0000"),
+            code_hash: "indexer_hash".to_string(),
+            best_effort_key: Some("key".to_string()),
+        },
+    )
+    .unwrap();
+    assert_eq!(res.log[2], log("best_effort", "true"));
+
+    let settings = query_settings(&deps, None);
+    assert_eq!(settings.swap_data_endpoints.len(), 2);
+    assert!(!settings.swap_data_endpoints[0].is_best_effort());
+    assert_eq!(
+        settings.swap_data_endpoints[1].best_effort_key_hash,
+        Some(Binary::from(&sha_256(b"key")))
+    );
+    // pairs running older code call the first endpoint that isn't best-effort
+    assert_eq!(
+        settings.swap_data_endpoint,
+        Some(settings.swap_data_endpoints[0].clone())
+    );
+
+    let env = mock_env("owner0000", &[]);
+    let _res = handle(
+        &mut deps,
+        env,
+        HandleMsg::RemoveSwapDataEndpoint {
+            address: HumanAddr::from("stats0000"),
+        },
+    )
+    .unwrap();
+    let settings = query_settings(&deps, None);
+    assert_eq!(settings.swap_data_endpoints.len(), 1);
+    assert_eq!(settings.swap_data_endpoint, None);
+
+    let env = mock_env("owner0000", &[]);
+    match handle(
+        &mut deps,
+        env,
+        HandleMsg::RemoveSwapDataEndpoint {
+            address: HumanAddr::from("stats0000"),
+        },
+    ) {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "Swap data endpoint is not registered")
+        }
+        _ => panic!("Must return generic error"),
+    }
+}
+
+#[test]
+fn create_multi_pool() {
+    let mut deps = mock_dependencies(20, &[]);
+
+    let msg = InitMsg {
+        pair_code_id: 321u64,
+        token_code_id: 123u64,
+        init_hook: None,
+        token_code_hash: "token_hash".to_string(),
+        pair_code_hash: "pair_hash".to_string(),
+        prng_seed: Binary::from("seed".as_bytes()),
+    };
+    let env = mock_env("owner0000", &[]);
+    let _res = init(&mut deps, env, msg).unwrap();
+
+    let asset_infos: Vec<AssetInfo> = ["uscrt", "uusd", "uatom"]
+        .iter()
+        .map(|denom| AssetInfo::NativeToken {
+            denom: denom.to_string(),
+        })
+        .collect();
+    let create_msg = HandleMsg::CreateMultiPool {
+        asset_infos: asset_infos.clone(),
+        curve: Some(CurveType::StableSwap { amp: 100 }),
+        init_hook: None,
+    };
+
+    // the owner has to set the code first
+    let env = mock_env("addr0000", &[]);
+    match handle(&mut deps, env, create_msg.clone()) {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "Multi-asset pool code is not set")
+        }
+        _ => panic!("Must return generic error"),
+    }
+
+    let env = mock_env("owner0000", &[]);
+    let _res = handle(
+        &mut deps,
+        env,
+        HandleMsg::UpdateConfig {
+            owner: None,
+            token_code_id: None,
+            pair_code_id: None,
+            pair_code_hash: None,
+            token_code_hash: None,
+            swap_fee: None,
+            protocol_fee: None,
+            concentrated_pair_code_id: None,
+            concentrated_pair_code_hash: None,
+            multi_pool_code_id: Some(500u64),
+            multi_pool_code_hash: Some("multi_pool_hash".to_string()),
+        },
+    )
+    .unwrap();
+    let config_res: ConfigResponse =
+        from_binary(&query(&deps, QueryMsg::Config {}).unwrap()).unwrap();
+    assert_eq!(config_res.multi_pool_code_id, 500u64);
+    assert_eq!(config_res.multi_pool_code_hash, "multi_pool_hash");
+
+    // two assets make a pair and weighted curves aren't supported
+    let env = mock_env("addr0000", &[]);
+    let _res = handle(
+        &mut deps,
+        env,
+        HandleMsg::CreateMultiPool {
+            asset_infos: asset_infos[..2].to_vec(),
+            curve: None,
+            init_hook: None,
+        },
+    )
+    .unwrap_err();
+    let env = mock_env("addr0000", &[]);
+    let _res = handle(
+        &mut deps,
+        env,
+        HandleMsg::CreateMultiPool {
+            asset_infos: asset_infos.clone(),
+            curve: Some(CurveType::Weighted { weights: [50, 50] }),
+            init_hook: None,
+        },
+    )
+    .unwrap_err();
+
+    let env = mock_env("addr0000", &[]);
+    let res = handle(&mut deps, env, create_msg).unwrap();
+    assert_eq!(res.messages.len(), 1);
+    match &res.messages[0] {
+        CosmosMsg::Wasm(WasmMsg::Instantiate {
+            code_id,
+            msg,
+            callback_code_hash,
+            ..
+        }) => {
+            assert_eq!(*code_id, 500u64);
+            assert_eq!(callback_code_hash, "multi_pool_hash");
+            let init_msg: MultiPoolInitMsg = from_binary(msg).unwrap();
+            assert_eq!(init_msg.asset_infos, asset_infos);
+            assert_eq!(init_msg.token_code_id, 123u64);
+            assert_eq!(init_msg.curve, Some(CurveType::StableSwap { amp: 100 }));
+            assert_eq!(
+                init_msg.init_hook.unwrap().msg,
+                to_binary(&HandleMsg::RegisterMultiPool {
+                    asset_infos: asset_infos.clone(),
+                })
+                .unwrap()
+            );
+        }
+        _ => panic!("Must instantiate the multi-asset pool"),
+    }
+
+    // one pool for each set of assets, whatever their order
+    let reordered = vec![
+        asset_infos[2].clone(),
+        asset_infos[0].clone(),
+        asset_infos[1].clone(),
+    ];
+    let env = mock_env("addr0000", &[]);
+    match handle(
+        &mut deps,
+        env,
+        HandleMsg::CreateMultiPool {
+            asset_infos: reordered.clone(),
+            curve: None,
+            init_hook: None,
+        },
+    ) {
+        Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "Pool already exists"),
+        _ => panic!("Must return generic error"),
+    }
+
+    // pools are listed once they are registered
+    let pools_res: MultiPoolsResponse = from_binary(
+        &query(
+            &deps,
+            QueryMsg::MultiPools {
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(pools_res.pools, vec![]);
+
+    deps.querier.with_multi_pools(&[(
+        &HumanAddr::from("pool0000"),
+        &MultiPoolInfo {
+            asset_infos: asset_infos.clone(),
+            contract_addr: HumanAddr::from("pool0000"),
+            liquidity_token: HumanAddr::from("liquidity0000"),
+            token_code_hash: "token_hash".to_string(),
+            factory: Factory {
+                address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                code_hash: "".to_string(),
+            },
+            curve: CurveType::StableSwap { amp: 100 },
+        },
+    )]);
+    let register_msg = HandleMsg::RegisterMultiPool {
+        asset_infos: reordered,
+    };
+    let env = mock_env("pool0000", &[]);
+    let _res = handle(&mut deps, env, register_msg.clone()).unwrap();
+    let env = mock_env("pool0001", &[]);
+    match handle(&mut deps, env, register_msg) {
+        Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "Pool was already registered"),
+        _ => panic!("Must return generic error"),
+    }
+
+    let pool_res: MultiPoolInfo = from_binary(
+        &query(
+            &deps,
+            QueryMsg::MultiPool {
+                asset_infos: asset_infos.clone(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(pool_res.contract_addr, HumanAddr::from("pool0000"));
+    assert_eq!(pool_res.liquidity_token, HumanAddr::from("liquidity0000"));
+    assert_eq!(pool_res.token_code_hash, "token_hash");
+    assert_eq!(pool_res.curve, CurveType::StableSwap { amp: 100 });
+
+    let pools_res: MultiPoolsResponse = from_binary(
+        &query(
+            &deps,
+            QueryMsg::MultiPools {
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(pools_res.pools, vec![pool_res]);
+}
+
+#[test]
+fn protocol_fee_switch() {
+    let mut deps = mock_dependencies(20, &[]);
+
+    let msg = InitMsg {
+        pair_code_id: 321u64,
+        token_code_id: 123u64,
+        init_hook: None,
+        token_code_hash: "token_hash".to_string(),
+        pair_code_hash: "pair_hash".to_string(),
+        prng_seed: Binary::from("seed".as_bytes()),
+    };
+    let env = mock_env("owner0000", &[]);
+    let _res = init(&mut deps, env, msg).unwrap();
+    assert_eq!(query_settings(&deps, None).protocol_fee, None);
+
+    let update = |share_nom: u128| HandleMsg::UpdateConfig {
+        owner: None,
+        token_code_id: None,
+        pair_code_id: None,
+        pair_code_hash: None,
+        token_code_hash: None,
+        swap_fee: None,
+        protocol_fee: Some(ProtocolFee {
+            share_nom: Uint128(share_nom),
+            share_denom: Uint128(6u128),
+            collector: HumanAddr::from("collector0000"),
+        }),
+        concentrated_pair_code_id: None,
+        concentrated_pair_code_hash: None,
+        multi_pool_code_id: None,
+        multi_pool_code_hash: None,
+    };
+
+    // a sixth of the commission goes to the protocol
+    handle(&mut deps, mock_env("owner0000", &[]), update(1)).unwrap();
+    let protocol_fee = query_settings(&deps, None).protocol_fee.unwrap();
+    assert_eq!(protocol_fee.share_nom, Uint128(1u128));
+    assert_eq!(protocol_fee.compute(Uint128(600u128)), Uint128(100u128));
+
+    // a zero share turns it off, the collector stays to receive what pairs accrued
+    handle(&mut deps, mock_env("owner0000", &[]), update(0)).unwrap();
+    let protocol_fee = query_settings(&deps, None).protocol_fee.unwrap();
+    assert_eq!(protocol_fee.compute(Uint128(600u128)), Uint128::zero());
+    assert_eq!(protocol_fee.collector, HumanAddr::from("collector0000"));
+
+    // shares above 1 are rejected
+    let res = handle(&mut deps, mock_env("owner0000", &[]), update(7));
+    match res {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "Protocol fee share must be a fraction between 0 and 1")
+        }
+        _ => panic!("Must return generic error"),
+    }
+}
+
+fn query_settings<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    asset_infos: Option<[AssetInfo; 2]>,
+) -> PairSettings {
+    from_binary(&query(&deps, QueryMsg::PairSettings { asset_infos }).unwrap()).unwrap()
+}
@@ -162,6 +162,7 @@ fn create_pair() {
     let msg = HandleMsg::CreatePair {
         asset_infos: asset_infos.clone(),
         init_hook: None,
+        curve: None,
     };
 
     let env = mock_env("addr0000", &[]);
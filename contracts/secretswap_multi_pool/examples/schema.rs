@@ -0,0 +1,31 @@
+use std::env::current_dir;
+use std::fs::create_dir_all;
+
+use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
+use cosmwasm_std::HandleResponse;
+
+use secretswap::{MultiPoolInfo, MultiPoolInitMsg, SwapDataResponse, SwapResponse};
+use secretswap_multi_pool::msg::{
+    Cw20HookMsg, HandleMsg, PoolResponse, ProvideResponse, QueryMsg, SimulationResponse,
+    WithdrawResponse,
+};
+
+fn main() {
+    let mut out_dir = current_dir().unwrap();
+    out_dir.push("schema");
+    create_dir_all(&out_dir).unwrap();
+    remove_schemas(&out_dir).unwrap();
+
+    export_schema(&schema_for!(MultiPoolInitMsg), &out_dir);
+    export_schema(&schema_for!(HandleMsg), &out_dir);
+    export_schema(&schema_for!(Cw20HookMsg), &out_dir);
+    export_schema(&schema_for!(HandleResponse), &out_dir);
+    export_schema(&schema_for!(SwapResponse), &out_dir);
+    export_schema(&schema_for!(ProvideResponse), &out_dir);
+    export_schema(&schema_for!(WithdrawResponse), &out_dir);
+    export_schema(&schema_for!(QueryMsg), &out_dir);
+    export_schema(&schema_for!(MultiPoolInfo), &out_dir);
+    export_schema(&schema_for!(PoolResponse), &out_dir);
+    export_schema(&schema_for!(SimulationResponse), &out_dir);
+    export_schema(&schema_for!(SwapDataResponse), &out_dir);
+}
@@ -0,0 +1,833 @@
+use std::str::FromStr;
+
+use cosmwasm_std::{
+    from_binary, log, to_binary, Api, Binary, CanonicalAddr, CosmosMsg, Decimal, Env, Extern,
+    HandleResponse, HandleResult, HumanAddr, InitResponse, Querier, StdError, StdResult, Storage,
+    Uint128, WasmMsg,
+};
+use primitive_types::U256;
+use secret_toolkit::snip20;
+
+use secretswap::{
+    ack_swap_data, deliver_swap_data, query_supply, query_swap_data, validate_multi_pool, Asset,
+    AssetInfo, AssetInfoRaw, Deadline, Factory, InitHook, MultiPoolInfo, MultiPoolInfoRaw,
+    MultiPoolInitMsg, SwapData, SwapResponse, TokenInitMsg,
+};
+
+use crate::math::{
+    compute_deposit_share, compute_initial_share, compute_swap, compute_used_deposits,
+    compute_withdrawn_amounts, MINIMUM_LIQUIDITY,
+};
+use crate::msg::{
+    Cw20HookMsg, HandleMsg, PoolResponse, ProvideResponse, QueryMsg, SimulationResponse,
+    WithdrawResponse,
+};
+use crate::querier::query_pool_settings;
+use crate::state::{read_pool_info, read_reserves, store_pool_info, store_reserves};
+
+/// Decimals of `Decimal`
+const DECIMAL_FRACTIONAL: u128 = 1_000_000_000_000_000_000;
+
+pub fn init<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    msg: MultiPoolInitMsg,
+) -> StdResult<InitResponse> {
+    let curve = msg.curve.unwrap_or_default();
+    validate_multi_pool(&msg.asset_infos, &curve)?;
+
+    // create viewing key
+    let assets_viewing_key = String::from("SecretSwap");
+
+    let mut messages = vec![];
+    let mut asset_infos_raw = vec![];
+    for asset_info in msg.asset_infos.iter() {
+        match asset_info {
+            AssetInfo::Token {
+                contract_addr,
+                token_code_hash,
+                ..
+            } => {
+                messages.push(snip20::set_viewing_key_msg(
+                    assets_viewing_key.clone(),
+                    None,
+                    256,
+                    token_code_hash.clone(),
+                    contract_addr.clone(),
+                )?);
+                messages.push(snip20::register_receive_msg(
+                    env.contract_code_hash.clone(),
+                    None,
+                    256,
+                    token_code_hash.clone(),
+                    contract_addr.clone(),
+                )?);
+                asset_infos_raw.push(AssetInfoRaw::Token {
+                    contract_addr: deps.api.canonical_address(&contract_addr)?,
+                    token_code_hash: token_code_hash.clone(),
+                    viewing_key: assets_viewing_key.clone(),
+                });
+            }
+            AssetInfo::NativeToken { .. } => asset_infos_raw.push(asset_info.to_raw(&deps)?),
+        }
+    }
+
+    let pool_name = msg
+        .asset_infos
+        .iter()
+        .map(|asset_info| asset_info.to_string())
+        .collect::<Vec<String>>()
+        .join("-");
+
+    // Create LP token
+    messages.push(CosmosMsg::Wasm(WasmMsg::Instantiate {
+        code_id: msg.token_code_id,
+        msg: to_binary(&TokenInitMsg::new(
+            format!("SecretSwap Liquidity Provider (LP) token for {}", pool_name),
+            env.contract.address.clone(),
+            "SWAP-LP".to_string(),
+            6,
+            msg.prng_seed,
+            InitHook {
+                msg: to_binary(&HandleMsg::PostInitialize {})?,
+                contract_addr: env.contract.address.clone(),
+                code_hash: env.contract_code_hash.clone(),
+            },
+        ))?,
+        send: vec![],
+        label: format!(
+            "{}-SecretSwap-LP-Token-{}",
+            pool_name,
+            &env.contract.address.clone()
+        ),
+        callback_code_hash: msg.token_code_hash.clone(),
+    }));
+
+    let hook = match msg.init_hook {
+        Some(hook) => hook,
+        None => {
+            return Err(StdError::generic_err(
+                "Must provide the factory as init hook",
+            ))
+        }
+    };
+    messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: hook.contract_addr.clone(),
+        callback_code_hash: hook.code_hash.clone(),
+        msg: hook.msg,
+        send: vec![],
+    }));
+
+    store_pool_info(
+        &mut deps.storage,
+        &MultiPoolInfoRaw {
+            contract_addr: deps.api.canonical_address(&env.contract.address)?,
+            liquidity_token: CanonicalAddr::default(),
+            token_code_hash: msg.token_code_hash,
+            asset_infos: asset_infos_raw,
+            factory: Factory {
+                address: hook.contract_addr,
+                code_hash: hook.code_hash,
+            },
+            curve,
+        },
+    )?;
+
+    Ok(InitResponse {
+        messages,
+        log: vec![log("status", "success")], // See https://github.com/CosmWasm/wasmd/pull/386
+    })
+}
+
+pub fn handle<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    msg: HandleMsg,
+) -> HandleResult {
+    match msg {
+        HandleMsg::Receive { amount, msg, from } => receive_cw20(deps, env, from, amount, msg),
+        HandleMsg::PostInitialize {} => try_post_initialize(deps, env),
+        HandleMsg::ProvideLiquidity {
+            assets,
+            min_shares,
+            deadline,
+        } => {
+            assert_deadline(&env, &deadline)?;
+
+            try_provide_liquidity(deps, env, assets, min_shares)
+        }
+        HandleMsg::Swap {
+            offer_asset,
+            ask_asset,
+            expected_return,
+            belief_price,
+            max_spread,
+            to,
+            deadline,
+        } => {
+            if !offer_asset.is_native_token() {
+                return Err(StdError::unauthorized());
+            }
+            assert_deadline(&env, &deadline)?;
+
+            try_swap(
+                deps,
+                env.clone(),
+                env.message.sender,
+                offer_asset,
+                ask_asset,
+                expected_return,
+                belief_price,
+                max_spread,
+                to,
+            )
+        }
+        HandleMsg::AckSwapData { endpoint, key, seq } => {
+            try_ack_swap_data(deps, endpoint, key, seq)
+        }
+    }
+}
+
+pub fn try_ack_swap_data<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    endpoint: HumanAddr,
+    key: String,
+    seq: u64,
+) -> HandleResult {
+    let pool_info: MultiPoolInfoRaw = read_pool_info(&deps.storage)?;
+    let pool_settings = query_pool_settings(&deps, &pool_info)?;
+    ack_swap_data(
+        &mut deps.storage,
+        &pool_settings.swap_data_endpoints,
+        &endpoint,
+        &key,
+        seq,
+    )?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("action", "ack_swap_data"), log("seq", seq.to_string())],
+        data: None,
+    })
+}
+
+pub fn receive_cw20<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    from: HumanAddr,
+    amount: Uint128,
+    msg: Option<Binary>,
+) -> HandleResult {
+    let contract_addr = env.message.sender.clone();
+    if let Some(bin_msg) = msg {
+        match from_binary(&bin_msg)? {
+            Cw20HookMsg::Swap {
+                ask_asset,
+                expected_return,
+                belief_price,
+                max_spread,
+                to,
+                deadline,
+            } => {
+                // only asset contract can execute this message
+                assert_pool_token(deps, &env.message.sender)?;
+                assert_deadline(&env, &deadline)?;
+
+                try_swap(
+                    deps,
+                    env,
+                    from,
+                    Asset {
+                        info: AssetInfo::Token {
+                            contract_addr,
+                            token_code_hash: Default::default(),
+                            viewing_key: Default::default(),
+                        },
+                        amount,
+                    },
+                    ask_asset,
+                    expected_return,
+                    belief_price,
+                    max_spread,
+                    to,
+                )
+            }
+            Cw20HookMsg::WithdrawLiquidity { deadline } => {
+                // only the liquidity token can execute this message
+                let pool_info: MultiPoolInfoRaw = read_pool_info(&deps.storage)?;
+                if deps.api.canonical_address(&env.message.sender)? != pool_info.liquidity_token {
+                    return Err(StdError::unauthorized());
+                }
+                assert_deadline(&env, &deadline)?;
+
+                try_withdraw_liquidity(deps, env, from, amount)
+            }
+        }
+    } else {
+        Err(StdError::generic_err("data should be given"))
+    }
+}
+
+fn assert_deadline(env: &Env, deadline: &Option<Deadline>) -> StdResult<()> {
+    match deadline {
+        Some(deadline) => deadline.assert_not_expired(&env.block),
+        None => Ok(()),
+    }
+}
+
+fn assert_pool_token<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    token: &HumanAddr,
+) -> StdResult<()> {
+    let pool_info: MultiPoolInfoRaw = read_pool_info(&deps.storage)?;
+    let token = deps.api.canonical_address(token)?;
+    let authorized = pool_info
+        .asset_infos
+        .iter()
+        .any(|asset_info| match asset_info {
+            AssetInfoRaw::Token { contract_addr, .. } => contract_addr == &token,
+            _ => false,
+        });
+
+    if !authorized {
+        return Err(StdError::unauthorized());
+    }
+
+    Ok(())
+}
+
+fn read_asset_infos<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    pool_info: &MultiPoolInfoRaw,
+) -> StdResult<Vec<AssetInfo>> {
+    pool_info
+        .asset_infos
+        .iter()
+        .map(|asset_info| asset_info.to_normal(&deps))
+        .collect()
+}
+
+/// Position of `asset_info` in the pool's `asset_infos`
+fn asset_index(asset_infos: &[AssetInfo], asset_info: &AssetInfo) -> StdResult<usize> {
+    asset_infos
+        .iter()
+        .position(|info| asset_info.equal(info))
+        .ok_or_else(|| StdError::generic_err("Given asset doesn't belong to the pool"))
+}
+
+fn to_assets(asset_infos: &[AssetInfo], amounts: &[Uint128]) -> Vec<Asset> {
+    asset_infos
+        .iter()
+        .zip(amounts.iter())
+        .map(|(info, amount)| Asset {
+            info: info.clone(),
+            amount: *amount,
+        })
+        .collect()
+}
+
+fn format_assets(assets: &[Asset]) -> String {
+    assets
+        .iter()
+        .map(|asset| asset.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+pub fn try_post_initialize<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> HandleResult {
+    let config: MultiPoolInfoRaw = read_pool_info(&deps.storage)?;
+
+    // permission check
+    if config.liquidity_token != CanonicalAddr::default() {
+        return Err(StdError::unauthorized());
+    }
+
+    store_pool_info(
+        &mut deps.storage,
+        &MultiPoolInfoRaw {
+            liquidity_token: deps.api.canonical_address(&env.message.sender)?,
+            ..config.clone()
+        },
+    )?;
+
+    Ok(HandleResponse {
+        messages: vec![snip20::register_receive_msg(
+            env.contract_code_hash,
+            None,
+            256,
+            config.token_code_hash,
+            env.message.sender.clone(),
+        )?],
+        log: vec![log("liquidity_token_addr", env.message.sender.as_str())],
+        data: None,
+    })
+}
+
+/// CONTRACT - should approve contract to use the amount of token
+pub fn try_provide_liquidity<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    assets: Vec<Asset>,
+    min_shares: Option<Uint128>,
+) -> HandleResult {
+    for asset in assets.iter() {
+        asset.assert_sent_native_token_balance(&env)?;
+    }
+
+    let pool_info: MultiPoolInfoRaw = read_pool_info(&deps.storage)?;
+    query_pool_settings(&deps, &pool_info)?
+        .status
+        .assert_deposits_allowed()?;
+    let asset_infos = read_asset_infos(&deps, &pool_info)?;
+    let mut deposits = vec![None; asset_infos.len()];
+    for asset in assets.iter() {
+        let index = asset_index(&asset_infos, &asset.info)?;
+        if deposits[index].replace(asset.amount).is_some() {
+            return Err(StdError::generic_err(format!(
+                "Asset {} is given more than once",
+                asset.info
+            )));
+        }
+    }
+    let deposits: Vec<Uint128> = deposits
+        .into_iter()
+        .map(|deposit| deposit.unwrap_or_default())
+        .collect();
+
+    let reserves = read_reserves(&deps.storage, asset_infos.len())?;
+    let liquidity_token = deps.api.human_address(&pool_info.liquidity_token)?;
+    let total_share = query_supply(&deps, &liquidity_token, &pool_info.token_code_hash)?;
+
+    let mut messages: Vec<CosmosMsg> = vec![];
+    let (share, used_deposits) = if total_share.is_zero() {
+        let initial_share = compute_initial_share(&pool_info.curve, &deposits)?;
+
+        // MINIMUM_LIQUIDITY is minted to the pool itself and locked forever, so a donation
+        // can't inflate the price of a share beyond what small deposits can afford
+        messages.push(snip20::mint_msg(
+            env.contract.address.clone(),
+            Uint128(MINIMUM_LIQUIDITY),
+            None,
+            256,
+            pool_info.token_code_hash.clone(),
+            liquidity_token.clone(),
+        )?);
+
+        (
+            (initial_share - Uint128(MINIMUM_LIQUIDITY))?,
+            deposits.clone(),
+        )
+    } else {
+        // Only the amounts matching the pool ratio are taken, the excess stays with the provider
+        let share = compute_deposit_share(&deposits, &reserves, total_share);
+        (share, compute_used_deposits(share, &reserves, total_share))
+    };
+    if share.is_zero() {
+        return Err(StdError::generic_err(
+            "Deposits are too small to provide liquidity",
+        ));
+    }
+    if let Some(min_shares) = min_shares {
+        if share < min_shares {
+            return Err(StdError::generic_err("Operation fell short of min_shares"));
+        }
+    }
+
+    for (i, info) in asset_infos.iter().enumerate() {
+        match info {
+            // If the pool is token contract, then we need to execute TransferFrom msg to receive funds
+            AssetInfo::Token {
+                contract_addr,
+                token_code_hash,
+                ..
+            } => {
+                if !used_deposits[i].is_zero() {
+                    messages.push(snip20::transfer_from_msg(
+                        env.message.sender.clone(),
+                        env.contract.address.clone(),
+                        used_deposits[i],
+                        None,
+                        256,
+                        token_code_hash.clone(),
+                        contract_addr.clone(),
+                    )?);
+                }
+            }
+            // Native tokens were sent in full, refund the excess
+            AssetInfo::NativeToken { .. } => {
+                let excess = (deposits[i] - used_deposits[i])?;
+                if !excess.is_zero() {
+                    messages.push(
+                        Asset {
+                            info: info.clone(),
+                            amount: excess,
+                        }
+                        .into_msg(
+                            &deps,
+                            env.contract.address.clone(),
+                            env.message.sender.clone(),
+                        )?,
+                    );
+                }
+            }
+        }
+    }
+
+    store_reserves(
+        &mut deps.storage,
+        &reserves
+            .iter()
+            .zip(used_deposits.iter())
+            .map(|(reserve, used)| *reserve + *used)
+            .collect::<Vec<Uint128>>(),
+    )?;
+
+    messages.push(snip20::mint_msg(
+        env.message.sender,
+        share,
+        None,
+        256,
+        pool_info.token_code_hash,
+        liquidity_token,
+    )?);
+
+    let used_assets = to_assets(&asset_infos, &used_deposits);
+    Ok(HandleResponse {
+        messages,
+        log: vec![
+            log("action", "provide_liquidity"),
+            log("assets", format_assets(&assets)),
+            log("used_assets", format_assets(&used_assets)),
+            log("share", &share),
+        ],
+        data: Some(to_binary(&ProvideResponse { share, used_assets })?),
+    })
+}
+
+pub fn try_withdraw_liquidity<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    sender: HumanAddr,
+    amount: Uint128,
+) -> HandleResult {
+    let pool_info: MultiPoolInfoRaw = read_pool_info(&deps.storage)?;
+    let liquidity_token = deps.api.human_address(&pool_info.liquidity_token)?;
+    let asset_infos = read_asset_infos(&deps, &pool_info)?;
+
+    let reserves = read_reserves(&deps.storage, asset_infos.len())?;
+    let total_share = query_supply(&deps, &liquidity_token, &pool_info.token_code_hash)?;
+    let amounts = compute_withdrawn_amounts(&reserves, amount, total_share)?;
+    store_reserves(
+        &mut deps.storage,
+        &reserves
+            .iter()
+            .zip(amounts.iter())
+            .map(|(reserve, amount)| *reserve - *amount)
+            .collect::<StdResult<Vec<Uint128>>>()?,
+    )?;
+
+    let refund_assets = to_assets(&asset_infos, &amounts);
+    let mut messages = vec![];
+    for asset in refund_assets.iter().filter(|asset| !asset.amount.is_zero()) {
+        messages.push(asset.clone().into_msg(
+            &deps,
+            env.contract.address.clone(),
+            sender.clone(),
+        )?);
+    }
+    // burn liquidity token
+    messages.push(snip20::burn_msg(
+        amount,
+        None,
+        256,
+        pool_info.token_code_hash,
+        liquidity_token,
+    )?);
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![
+            log("action", "withdraw_liquidity"),
+            log("withdrawn_share", &amount.to_string()),
+            log("refund_assets", format_assets(&refund_assets)),
+        ],
+        data: Some(to_binary(&WithdrawResponse {
+            withdrawn_share: amount,
+            refund_assets,
+        })?),
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn try_swap<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    sender: HumanAddr,
+    offer_asset: Asset,
+    ask_asset: AssetInfo,
+    expected_return: Option<Uint128>,
+    belief_price: Option<Decimal>,
+    max_spread: Option<Decimal>,
+    to: Option<HumanAddr>,
+) -> HandleResult {
+    offer_asset.assert_sent_native_token_balance(&env)?;
+
+    let pool_info: MultiPoolInfoRaw = read_pool_info(&deps.storage)?;
+    let asset_infos = read_asset_infos(&deps, &pool_info)?;
+    let offer_index = asset_index(&asset_infos, &offer_asset.info)?;
+    let ask_index = asset_index(&asset_infos, &ask_asset)?;
+    if offer_index == ask_index {
+        return Err(StdError::generic_err(
+            "Offer and ask asset must be different",
+        ));
+    }
+
+    let pool_settings = query_pool_settings(&deps, &pool_info)?;
+    pool_settings.status.assert_swaps_allowed()?;
+
+    let mut reserves = read_reserves(&deps.storage, asset_infos.len())?;
+    let (return_amount, spread_amount, commission_amount) = compute_swap(
+        &pool_info.curve,
+        &reserves,
+        offer_index,
+        ask_index,
+        offer_asset.amount,
+        pool_settings.swap_fee.commission_rate_nom,
+        pool_settings.swap_fee.commission_rate_denom,
+    )?;
+    if return_amount.is_zero() {
+        return Err(StdError::generic_err(
+            "Offer amount is too small to return anything",
+        ));
+    }
+
+    // check max spread limit if exist
+    assert_max_spread(
+        belief_price,
+        max_spread,
+        expected_return,
+        offer_asset.amount,
+        return_amount,
+        spread_amount,
+    )?;
+
+    // the commission stays in the pool
+    reserves[offer_index] += offer_asset.amount;
+    reserves[ask_index] = (reserves[ask_index] - return_amount)?;
+    store_reserves(&mut deps.storage, &reserves)?;
+
+    let return_asset = Asset {
+        info: asset_infos[ask_index].clone(),
+        amount: return_amount,
+    };
+    let receiver = to.unwrap_or(sender);
+
+    let mut messages = vec![return_asset.clone().into_msg(
+        &deps,
+        env.contract.address.clone(),
+        receiver.clone(),
+    )?];
+
+    messages.extend(deliver_swap_data(
+        &mut deps.storage,
+        &env,
+        &pool_settings.swap_data_endpoints,
+        SwapData {
+            pair: env.contract.address.clone(),
+            account: receiver,
+            asset_in: offer_asset.clone(),
+            asset_out: return_asset.clone(),
+            commission: Asset {
+                info: asset_infos[ask_index].clone(),
+                amount: commission_amount,
+            },
+            spread_amount,
+            // reserves of the two assets that were swapped
+            reserves: [
+                Asset {
+                    info: asset_infos[offer_index].clone(),
+                    amount: reserves[offer_index],
+                },
+                Asset {
+                    info: asset_infos[ask_index].clone(),
+                    amount: reserves[ask_index],
+                },
+            ],
+        },
+    )?);
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![
+            log("action", "swap"),
+            log("offer_asset", offer_asset.info.to_string()),
+            log("ask_asset", asset_infos[ask_index].to_string()),
+            log("offer_amount", offer_asset.amount.to_string()),
+            log("return_amount", return_amount.to_string()),
+            log("spread_amount", spread_amount.to_string()),
+            log("commission_amount", commission_amount.to_string()),
+        ],
+        data: Some(to_binary(&SwapResponse {
+            offer_asset,
+            return_asset,
+            spread_amount,
+            commission_amount,
+            protocol_fee_amount: Uint128::zero(),
+            refund_amount: Uint128::zero(),
+        })?),
+    })
+}
+
+pub fn query<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    msg: QueryMsg,
+) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::MultiPool {} => to_binary(&query_multi_pool_info(&deps)?),
+        QueryMsg::Pool {} => to_binary(&query_pool(&deps)?),
+        QueryMsg::Simulation {
+            offer_asset,
+            ask_asset,
+        } => to_binary(&query_simulation(&deps, offer_asset, ask_asset)?),
+        QueryMsg::SwapData {
+            endpoint,
+            key,
+            start_after,
+            limit,
+        } => {
+            let pool_info: MultiPoolInfoRaw = read_pool_info(&deps.storage)?;
+            let pool_settings = query_pool_settings(&deps, &pool_info)?;
+            to_binary(&query_swap_data(
+                &deps.storage,
+                &pool_settings.swap_data_endpoints,
+                &endpoint,
+                &key,
+                start_after,
+                limit,
+            )?)
+        }
+    }
+}
+
+pub fn query_multi_pool_info<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<MultiPoolInfo> {
+    let pool_info: MultiPoolInfoRaw = read_pool_info(&deps.storage)?;
+    pool_info.to_normal(&deps)
+}
+
+pub fn query_pool<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<PoolResponse> {
+    let pool_info: MultiPoolInfoRaw = read_pool_info(&deps.storage)?;
+    let asset_infos = read_asset_infos(&deps, &pool_info)?;
+    let reserves = read_reserves(&deps.storage, asset_infos.len())?;
+    let total_share = query_supply(
+        &deps,
+        &deps.api.human_address(&pool_info.liquidity_token)?,
+        &pool_info.token_code_hash,
+    )?;
+
+    Ok(PoolResponse {
+        assets: to_assets(&asset_infos, &reserves),
+        total_share,
+    })
+}
+
+pub fn query_simulation<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    offer_asset: Asset,
+    ask_asset: AssetInfo,
+) -> StdResult<SimulationResponse> {
+    let pool_info: MultiPoolInfoRaw = read_pool_info(&deps.storage)?;
+    let asset_infos = read_asset_infos(&deps, &pool_info)?;
+    let offer_index = asset_index(&asset_infos, &offer_asset.info)?;
+    let ask_index = asset_index(&asset_infos, &ask_asset)?;
+    if offer_index == ask_index {
+        return Err(StdError::generic_err(
+            "Offer and ask asset must be different",
+        ));
+    }
+
+    let pool_settings = query_pool_settings(&deps, &pool_info)?;
+    let (return_amount, spread_amount, commission_amount) = compute_swap(
+        &pool_info.curve,
+        &read_reserves(&deps.storage, asset_infos.len())?,
+        offer_index,
+        ask_index,
+        offer_asset.amount,
+        pool_settings.swap_fee.commission_rate_nom,
+        pool_settings.swap_fee.commission_rate_denom,
+    )?;
+
+    Ok(SimulationResponse {
+        return_amount,
+        spread_amount,
+        commission_amount,
+    })
+}
+
+/// The decimal times 10^18. Multiplying by 10^18 overflows `Uint128` above a price of about
+/// 340, so the digits are parsed instead.
+fn decimal_atomics(decimal: Decimal) -> StdResult<u128> {
+    let decimal = decimal.to_string();
+    let mut parts = decimal.splitn(2, '.');
+    let whole = parts.next().unwrap_or_default();
+    let fractional = format!("{:0<18}", parts.next().unwrap_or_default());
+    let parse_err = |_| StdError::generic_err(format!("Invalid decimal {}", decimal));
+
+    Ok(
+        u128::from_str(whole).map_err(parse_err)? * DECIMAL_FRACTIONAL
+            + u128::from_str(&fractional).map_err(parse_err)?,
+    )
+}
+
+/// If `expected_return` is given, we check against `return_amount`
+/// Else if `belief_price` and `max_spread` both are given,
+/// we compute new spread else we just use the spread against the pool price
+/// to check `max_spread`
+pub fn assert_max_spread(
+    belief_price: Option<Decimal>,
+    max_spread: Option<Decimal>,
+    expected_return: Option<Uint128>,
+    offer_amount: Uint128,
+    return_amount: Uint128,
+    spread_amount: Uint128,
+) -> StdResult<()> {
+    if let Some(expected_return) = expected_return {
+        if return_amount < expected_return {
+            return Err(StdError::generic_err(
+                "Operation fell short of expected_return",
+            ));
+        }
+    } else if let (Some(max_spread), Some(belief_price)) = (max_spread, belief_price) {
+        let belief_price_fractional = decimal_atomics(belief_price)?;
+        if belief_price_fractional == 0 {
+            return Err(StdError::generic_err("Belief price must be positive"));
+        }
+        let expected_return = Uint128(
+            (U256::from(offer_amount.u128()) * U256::from(DECIMAL_FRACTIONAL)
+                / U256::from(belief_price_fractional))
+            .min(U256::from(u128::MAX))
+            .low_u128(),
+        );
+        let spread_amount = (expected_return - return_amount).unwrap_or_else(|_| Uint128::zero());
+
+        if return_amount < expected_return
+            && Decimal::from_ratio(spread_amount, expected_return) > max_spread
+        {
+            return Err(StdError::generic_err(
+                "Operation exceeds max spread limit with belief_price",
+            ));
+        }
+    } else if let Some(max_spread) = max_spread {
+        if Decimal::from_ratio(spread_amount, return_amount + spread_amount) > max_spread {
+            return Err(StdError::generic_err("Operation exceeds max spread limit"));
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,335 @@
+use std::cmp::Ordering;
+
+use cosmwasm_std::{StdError, StdResult, Uint128};
+use primitive_types::U256;
+
+use secretswap::CurveType;
+
+/// LP shares locked forever by the first deposit, as in Uniswap v2
+pub const MINIMUM_LIQUIDITY: u128 = 1_000;
+/// Newton's method iterations before giving up on convergence
+const MAX_ITERATIONS: usize = 255;
+
+/// StableSwap invariant for n coins, from the Curve whitepaper:
+///
+/// A * n^n * sum(x_i) + D = A * D * n^n + D^(n+1) / (n^n * prod(x_i))
+///
+/// Solved for D with Newton's method, as in Curve's `get_D`. Every balance must be positive.
+pub fn compute_d(amp: u64, balances: &[U256]) -> Option<U256> {
+    let n_coins = U256::from(balances.len());
+    let sum = balances
+        .iter()
+        .try_fold(U256::zero(), |sum, balance| sum.checked_add(*balance))?;
+    if sum.is_zero() {
+        return Some(U256::zero());
+    }
+
+    // Ann = A * n^n
+    let ann = U256::from(amp).checked_mul(n_pow_n(balances.len())?)?;
+
+    let mut d = sum;
+    for _ in 0..MAX_ITERATIONS {
+        // d_p = D^(n+1) / (n^n * prod(x_i))
+        let mut d_p = d;
+        for balance in balances.iter() {
+            d_p = d_p
+                .checked_mul(d)?
+                .checked_div(balance.checked_mul(n_coins)?)?;
+        }
+
+        let d_prev = d;
+        // D = (Ann * S + D_P * n) * D / ((Ann - 1) * D + (n + 1) * D_P)
+        let numerator = ann
+            .checked_mul(sum)?
+            .checked_add(d_p.checked_mul(n_coins)?)?
+            .checked_mul(d)?;
+        let denominator = ann
+            .checked_sub(U256::one())?
+            .checked_mul(d)?
+            .checked_add(n_coins.checked_add(U256::one())?.checked_mul(d_p)?)?;
+        d = numerator.checked_div(denominator)?;
+
+        if abs_diff(d, d_prev) <= U256::one() {
+            return Some(d);
+        }
+    }
+
+    None
+}
+
+/// Balance of the ask coin that keeps the invariant `d` once the offer coin has
+/// `new_offer_balance`. Port of Curve's `get_y`.
+pub fn compute_y(
+    amp: u64,
+    balances: &[U256],
+    offer_index: usize,
+    ask_index: usize,
+    new_offer_balance: U256,
+    d: U256,
+) -> Option<U256> {
+    let n_coins = U256::from(balances.len());
+    let ann = U256::from(amp).checked_mul(n_pow_n(balances.len())?)?;
+
+    // c = D^(n+1) / (n^n * prod(x_k) * Ann) and S = sum(x_k), over every coin but the ask coin
+    let mut c = d;
+    let mut sum = U256::zero();
+    for (k, balance) in balances.iter().enumerate() {
+        if k == ask_index {
+            continue;
+        }
+        let x = if k == offer_index {
+            new_offer_balance
+        } else {
+            *balance
+        };
+        sum = sum.checked_add(x)?;
+        c = c.checked_mul(d)?.checked_div(x.checked_mul(n_coins)?)?;
+    }
+    c = c.checked_mul(d)?.checked_div(ann.checked_mul(n_coins)?)?;
+    // b = S + D / Ann
+    let b = sum.checked_add(d.checked_div(ann)?)?;
+
+    let mut y = d;
+    for _ in 0..MAX_ITERATIONS {
+        let y_prev = y;
+        // y = (y^2 + c) / (2 * y + b - D)
+        y = y.checked_mul(y)?.checked_add(c)?.checked_div(
+            y.checked_mul(U256::from(2))?
+                .checked_add(b)?
+                .checked_sub(d)?,
+        )?;
+
+        if abs_diff(y, y_prev) <= U256::one() {
+            return Some(y);
+        }
+    }
+
+    None
+}
+
+fn n_pow_n(n_coins: usize) -> Option<U256> {
+    U256::from(n_coins).checked_pow(U256::from(n_coins))
+}
+
+fn abs_diff(a: U256, b: U256) -> U256 {
+    if a > b {
+        a - b
+    } else {
+        b - a
+    }
+}
+
+/// Product of u128 values as little endian 64 bit words, the product of eight reserves
+/// doesn't fit any fixed size integer of `primitive_types`
+fn product_words(values: &[u128]) -> Vec<u64> {
+    let mut product = vec![1u64];
+    for value in values.iter() {
+        let factor = [*value as u64, (*value >> 64) as u64];
+        let mut result = vec![0u64; product.len() + factor.len()];
+        for (i, a) in product.iter().enumerate() {
+            let mut carry = 0u128;
+            for (j, b) in factor.iter().enumerate() {
+                let cur = result[i + j] as u128 + (*a as u128) * (*b as u128) + carry;
+                result[i + j] = cur as u64;
+                carry = cur >> 64;
+            }
+            result[i + factor.len()] = carry as u64;
+        }
+        while result.len() > 1 && result[result.len() - 1] == 0 {
+            result.pop();
+        }
+        product = result;
+    }
+    product
+}
+
+fn compare_words(a: &[u64], b: &[u64]) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+/// Geometric mean of the values, rounded down. It lies between the smallest and the largest
+/// value, so it is found by bisection.
+pub fn geometric_mean(values: &[u128]) -> u128 {
+    let target = product_words(values);
+    let mut low = values.iter().copied().min().unwrap_or_default();
+    let mut high = values.iter().copied().max().unwrap_or_default();
+    while low < high {
+        let mid = low + (high - low + 1) / 2;
+        if compare_words(&product_words(&vec![mid; values.len()]), &target) == Ordering::Greater {
+            high = mid - 1;
+        } else {
+            low = mid;
+        }
+    }
+    low
+}
+
+/// Shares minted by the first deposit, including the locked `MINIMUM_LIQUIDITY`
+pub fn compute_initial_share(curve: &CurveType, deposits: &[Uint128]) -> StdResult<Uint128> {
+    if deposits.iter().any(|deposit| deposit.is_zero()) {
+        return Err(StdError::generic_err(
+            "The first deposit must provide every asset",
+        ));
+    }
+
+    let initial_share = match curve {
+        // D is the total amount of coins when the pool is balanced
+        CurveType::StableSwap { amp } => {
+            let balances: Vec<U256> = deposits.iter().map(|d| U256::from(d.u128())).collect();
+            compute_d(*amp, &balances)
+                .filter(|d| *d <= U256::from(u128::MAX))
+                .ok_or_else(|| {
+                    StdError::generic_err(format!("Cannot calculate D of deposits {:?}", deposits))
+                })?
+                .low_u128()
+        }
+        // sqrt(deposit_0 * deposit_1) for two assets, as in the pair
+        _ => geometric_mean(&deposits.iter().map(|d| d.u128()).collect::<Vec<u128>>()),
+    };
+
+    if initial_share <= MINIMUM_LIQUIDITY {
+        return Err(StdError::generic_err(format!(
+            "Initial liquidity {} must be greater than the minimum liquidity {}",
+            initial_share, MINIMUM_LIQUIDITY
+        )));
+    }
+
+    Ok(Uint128(initial_share))
+}
+
+/// Share minted for a deposit into a non-empty pool, the least share any of the deposits
+/// pays for
+pub fn compute_deposit_share(
+    deposits: &[Uint128],
+    reserves: &[Uint128],
+    total_share: Uint128,
+) -> Uint128 {
+    let total_share = U256::from(total_share.u128());
+    let share = deposits
+        .iter()
+        .zip(reserves.iter())
+        .map(|(deposit, reserve)| {
+            (U256::from(deposit.u128()) * total_share)
+                .checked_div(U256::from(reserve.u128()))
+                .unwrap_or_default()
+        })
+        .min()
+        .unwrap_or_default();
+
+    Uint128(share.low_u128())
+}
+
+/// Parts of a deposit `share` pays for, `ceil(share * reserve / total_share)` of every
+/// asset, rounded in favor of the pool. They don't exceed the deposits the share was
+/// computed from.
+pub fn compute_used_deposits(
+    share: Uint128,
+    reserves: &[Uint128],
+    total_share: Uint128,
+) -> Vec<Uint128> {
+    let share = U256::from(share.u128());
+    let total_share = U256::from(total_share.u128());
+    reserves
+        .iter()
+        .map(|reserve| {
+            let used =
+                (share * U256::from(reserve.u128()) + total_share - U256::one()) / total_share;
+            Uint128(used.low_u128())
+        })
+        .collect()
+}
+
+/// Amounts returned for burning `amount` of `total_share`, rounded down
+pub fn compute_withdrawn_amounts(
+    reserves: &[Uint128],
+    amount: Uint128,
+    total_share: Uint128,
+) -> StdResult<Vec<Uint128>> {
+    if amount > total_share {
+        return Err(StdError::generic_err(
+            "Cannot withdraw more than the total share",
+        ));
+    }
+
+    let amount = U256::from(amount.u128());
+    let total_share = U256::from(total_share.u128());
+    Ok(reserves
+        .iter()
+        .map(|reserve| Uint128((U256::from(reserve.u128()) * amount / total_share).low_u128()))
+        .collect())
+}
+
+/// Swaps `offer_amount` of the asset at `offer_index` for the asset at `ask_index`.
+/// Returns the return amount after the commission, the spread and the commission, which
+/// stays in the pool. The spread is measured against the pool price for the constant
+/// product curve and against the peg for StableSwap.
+pub fn compute_swap(
+    curve: &CurveType,
+    reserves: &[Uint128],
+    offer_index: usize,
+    ask_index: usize,
+    offer_amount: Uint128,
+    commission_rate_nom: Uint128,
+    commission_rate_denom: Uint128,
+) -> StdResult<(Uint128, Uint128, Uint128)> {
+    let offer_pool = U256::from(reserves[offer_index].u128());
+    let ask_pool = U256::from(reserves[ask_index].u128());
+    let offer = U256::from(offer_amount.u128());
+    if offer_pool.is_zero() || ask_pool.is_zero() {
+        return Err(StdError::generic_err("The pool has no liquidity"));
+    }
+
+    let (return_amount, spread_amount) = match curve {
+        CurveType::StableSwap { amp } => {
+            let balances: Vec<U256> = reserves.iter().map(|r| U256::from(r.u128())).collect();
+            let new_ask_pool = compute_d(*amp, &balances)
+                .and_then(|d| {
+                    compute_y(
+                        *amp,
+                        &balances,
+                        offer_index,
+                        ask_index,
+                        offer_pool.checked_add(offer)?,
+                        d,
+                    )
+                })
+                .ok_or_else(|| {
+                    StdError::generic_err(format!(
+                        "Cannot calculate new ask pool for reserves {:?} + offer_amount {}",
+                        reserves, offer_amount
+                    ))
+                })?;
+
+            // return_amount = ask_pool - new_ask_pool - 1, the extra unit covers rounding in y
+            let return_amount = ask_pool
+                .saturating_sub(new_ask_pool)
+                .saturating_sub(U256::one());
+            (return_amount, offer.saturating_sub(return_amount))
+        }
+        _ => {
+            // return_amount = ask_pool - ceil(cp / (offer_pool + offer_amount)), the ask pool
+            // left keeps the product from shrinking
+            let cp = offer_pool * ask_pool;
+            let new_offer_pool = offer_pool + offer;
+            let new_ask_pool = (cp + new_offer_pool - U256::one()) / new_offer_pool;
+            let return_amount = ask_pool - new_ask_pool;
+            // spread = offer_amount * ask_pool / offer_pool - return_amount
+            let spread_amount = (offer * ask_pool / offer_pool).saturating_sub(return_amount);
+            (return_amount, spread_amount)
+        }
+    };
+
+    // commission will be absorbed to pool
+    let commission_amount = (return_amount * U256::from(commission_rate_nom.u128()))
+        .checked_div(U256::from(commission_rate_denom.u128()))
+        .ok_or_else(|| StdError::generic_err("Swap fee denominator is zero"))?;
+    let return_amount = return_amount - commission_amount;
+
+    Ok((
+        Uint128(return_amount.low_u128()),
+        Uint128(spread_amount.min(U256::from(u128::MAX)).low_u128()),
+        Uint128(commission_amount.low_u128()),
+    ))
+}
@@ -0,0 +1,116 @@
+use cosmwasm_std::{Binary, Decimal, HumanAddr, Uint128};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use secretswap::{Asset, AssetInfo, Deadline};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HandleMsg {
+    Receive {
+        from: HumanAddr,
+        msg: Option<Binary>,
+        amount: Uint128,
+    },
+    /// Post initize step to allow user to set controlled contract address after creating it
+    PostInitialize {},
+    /// ProvideLiquidity a user provides pool liquidity. The first deposit sets the ratio of
+    /// the pool and needs every asset, later deposits only take the amounts matching it.
+    ProvideLiquidity {
+        assets: Vec<Asset>,
+        /// Fails if less LP shares would be minted
+        min_shares: Option<Uint128>,
+        /// Fails if executed after the deadline
+        deadline: Option<Deadline>,
+    },
+    /// Swap an offer asset to `ask_asset`
+    Swap {
+        offer_asset: Asset,
+        ask_asset: AssetInfo,
+        expected_return: Option<Uint128>,
+        belief_price: Option<Decimal>,
+        max_spread: Option<Decimal>,
+        to: Option<HumanAddr>,
+        /// Fails if executed after the deadline
+        deadline: Option<Deadline>,
+    },
+    /// Acknowledges the swaps a best-effort swap data endpoint read, up to `seq`, authenticated
+    /// by the key the factory registered it with
+    AckSwapData {
+        endpoint: HumanAddr,
+        key: String,
+        seq: u64,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Cw20HookMsg {
+    /// Sell a given amount of asset for `ask_asset`
+    Swap {
+        ask_asset: AssetInfo,
+        expected_return: Option<Uint128>,
+        belief_price: Option<Decimal>,
+        max_spread: Option<Decimal>,
+        to: Option<HumanAddr>,
+        /// Fails if executed after the deadline
+        deadline: Option<Deadline>,
+    },
+    /// Burn the sent LP tokens for a proportional part of every asset
+    WithdrawLiquidity {
+        /// Fails if executed after the deadline
+        deadline: Option<Deadline>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    MultiPool {},
+    Pool {},
+    Simulation {
+        offer_asset: Asset,
+        ask_asset: AssetInfo,
+    },
+    /// Swaps kept for a best-effort swap data endpoint, authenticated by the key the factory
+    /// registered it with. Returns a `SwapDataResponse`.
+    SwapData {
+        endpoint: HumanAddr,
+        key: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+}
+
+// We define a custom struct for each query response
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PoolResponse {
+    pub assets: Vec<Asset>,
+    pub total_share: Uint128,
+}
+
+/// SimulationResponse returns swap simulation response
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SimulationResponse {
+    pub return_amount: Uint128,
+    pub spread_amount: Uint128,
+    pub commission_amount: Uint128,
+}
+
+/// ProvideResponse is returned in the data of a liquidity provision
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ProvideResponse {
+    /// Liquidity tokens minted to the provider
+    pub share: Uint128,
+    /// Assets taken from the provider, aligned with `asset_infos`
+    pub used_assets: Vec<Asset>,
+}
+
+/// WithdrawResponse is returned in the data of a liquidity withdrawal
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct WithdrawResponse {
+    /// Liquidity tokens burned
+    pub withdrawn_share: Uint128,
+    /// Assets paid out, aligned with `asset_infos`
+    pub refund_assets: Vec<Asset>,
+}
@@ -0,0 +1,24 @@
+use cosmwasm_std::{to_binary, Api, Extern, Querier, QueryRequest, StdResult, Storage, WasmQuery};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use secretswap::{AssetInfo, MultiPoolInfoRaw, PairSettings};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryFactory {
+    PairSettings { asset_infos: Option<[AssetInfo; 2]> },
+}
+
+/// Global settings of the factory. Fee and status overrides are set for pairs of assets, so
+/// they don't apply to multi-asset pools.
+pub fn query_pool_settings<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    pool_info: &MultiPoolInfoRaw,
+) -> StdResult<PairSettings> {
+    deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+        callback_code_hash: pool_info.factory.code_hash.clone(),
+        contract_addr: pool_info.factory.address.clone(),
+        msg: to_binary(&QueryFactory::PairSettings { asset_infos: None })?,
+    }))
+}
@@ -0,0 +1,27 @@
+use cosmwasm_std::{StdResult, Storage, Uint128};
+use cosmwasm_storage::{ReadonlySingleton, Singleton};
+
+use secretswap::MultiPoolInfoRaw;
+
+static KEY_POOL_INFO: &[u8] = b"pool_info";
+static KEY_RESERVES: &[u8] = b"reserves";
+
+pub fn store_pool_info<S: Storage>(storage: &mut S, data: &MultiPoolInfoRaw) -> StdResult<()> {
+    Singleton::new(storage, KEY_POOL_INFO).save(data)
+}
+
+pub fn read_pool_info<S: Storage>(storage: &S) -> StdResult<MultiPoolInfoRaw> {
+    ReadonlySingleton::new(storage, KEY_POOL_INFO).load()
+}
+
+/// Balances the pool accounts for, aligned with `asset_infos`. Tokens sent to the pool
+/// without a swap or a deposit aren't part of them.
+pub fn store_reserves<S: Storage>(storage: &mut S, reserves: &[Uint128]) -> StdResult<()> {
+    Singleton::new(storage, KEY_RESERVES).save(&reserves.to_vec())
+}
+
+pub fn read_reserves<S: Storage>(storage: &S, n_assets: usize) -> StdResult<Vec<Uint128>> {
+    Ok(ReadonlySingleton::new(storage, KEY_RESERVES)
+        .may_load()?
+        .unwrap_or_else(|| vec![Uint128::zero(); n_assets]))
+}
@@ -0,0 +1,207 @@
+use cosmwasm_std::{HumanAddr, Uint128};
+use primitive_types::U256;
+
+use secretswap::{validate_multi_pool, AssetInfo, CurveType, MAX_MULTI_POOL_ASSETS};
+
+use crate::math::{
+    compute_d, compute_deposit_share, compute_initial_share, compute_swap, compute_used_deposits,
+    compute_withdrawn_amounts, compute_y, geometric_mean, MINIMUM_LIQUIDITY,
+};
+
+fn native(denom: &str) -> AssetInfo {
+    AssetInfo::NativeToken {
+        denom: denom.to_string(),
+    }
+}
+
+fn amounts(values: &[u128]) -> Vec<Uint128> {
+    values.iter().map(|value| Uint128(*value)).collect()
+}
+
+#[test]
+fn test_validate_multi_pool() {
+    let constant_product = CurveType::ConstantProduct {};
+    let three = vec![
+        native("uscrt"),
+        native("uatom"),
+        AssetInfo::Token {
+            contract_addr: HumanAddr::from("token0000"),
+            token_code_hash: "".to_string(),
+            viewing_key: "".to_string(),
+        },
+    ];
+    validate_multi_pool(&three, &constant_product).unwrap();
+    validate_multi_pool(&three, &CurveType::StableSwap { amp: 100 }).unwrap();
+
+    // too few or too many assets
+    validate_multi_pool(&three[..2], &constant_product).unwrap_err();
+    let denoms: Vec<String> = (0..=MAX_MULTI_POOL_ASSETS)
+        .map(|i| format!("udenom{}", i))
+        .collect();
+    let too_many: Vec<AssetInfo> = denoms.iter().map(|denom| native(denom)).collect();
+    validate_multi_pool(&too_many, &constant_product).unwrap_err();
+    validate_multi_pool(&too_many[..MAX_MULTI_POOL_ASSETS], &constant_product).unwrap();
+
+    // duplicates, weighted or invalid curves
+    let duplicate = vec![native("uscrt"), native("uatom"), native("uscrt")];
+    validate_multi_pool(&duplicate, &constant_product).unwrap_err();
+    validate_multi_pool(&three, &CurveType::Weighted { weights: [50, 50] }).unwrap_err();
+    validate_multi_pool(&three, &CurveType::StableSwap { amp: 0 }).unwrap_err();
+}
+
+#[test]
+fn test_geometric_mean() {
+    assert_eq!(geometric_mean(&[4, 9]), 6);
+    assert_eq!(geometric_mean(&[2, 4, 8]), 4);
+    assert_eq!(geometric_mean(&[10, 10, 11]), 10);
+    assert_eq!(
+        geometric_mean(&[1, 1_000_000, 1_000_000_000_000]),
+        1_000_000
+    );
+    // the product of eight of the largest amounts has 1024 bits
+    assert_eq!(geometric_mean(&[u128::MAX; 8]), u128::MAX);
+    assert_eq!(
+        geometric_mean(&[u128::MAX, u128::MAX, 1]),
+        // floor of the cube root of (2^128 - 1)^2
+        48_740_834_812_604_276_470_692_694
+    );
+}
+
+#[test]
+fn test_stable_swap_math() {
+    let balances = vec![U256::from(1_000_000u64); 3];
+    // D is the sum of the balances when the pool is balanced
+    assert_eq!(compute_d(100, &balances).unwrap(), U256::from(3_000_000u64));
+    assert_eq!(compute_d(100, &[U256::zero(); 3]).unwrap(), U256::zero());
+
+    // without an offer the ask balance doesn't change
+    let d = compute_d(100, &balances).unwrap();
+    let y = compute_y(100, &balances, 0, 2, balances[0], d).unwrap();
+    assert!(y >= U256::from(999_999u64) && y <= U256::from(1_000_001u64));
+
+    // a small swap of pegged assets returns about as much as is offered, before the fee
+    let reserves = amounts(&[1_000_000, 1_000_000, 1_000_000]);
+    let (return_amount, spread_amount, commission_amount) = compute_swap(
+        &CurveType::StableSwap { amp: 100 },
+        &reserves,
+        0,
+        2,
+        Uint128(1_000),
+        Uint128(0),
+        Uint128(1000),
+    )
+    .unwrap();
+    assert!(return_amount >= Uint128(997) && return_amount < Uint128(1_000));
+    assert_eq!(spread_amount, (Uint128(1_000) - return_amount).unwrap());
+    assert_eq!(commission_amount, Uint128::zero());
+
+    // a lower amplification moves the price sooner
+    let (low_amp_return, _, _) = compute_swap(
+        &CurveType::StableSwap { amp: 1 },
+        &reserves,
+        0,
+        2,
+        Uint128(100_000),
+        Uint128(0),
+        Uint128(1000),
+    )
+    .unwrap();
+    let (high_amp_return, _, _) = compute_swap(
+        &CurveType::StableSwap { amp: 100 },
+        &reserves,
+        0,
+        2,
+        Uint128(100_000),
+        Uint128(0),
+        Uint128(1000),
+    )
+    .unwrap();
+    assert!(low_amp_return < high_amp_return);
+}
+
+#[test]
+fn test_constant_product_swap() {
+    let reserves = amounts(&[1_000_000, 2_000_000, 3_000_000]);
+    let (return_amount, spread_amount, commission_amount) = compute_swap(
+        &CurveType::ConstantProduct {},
+        &reserves,
+        0,
+        2,
+        Uint128(1_000),
+        Uint128(3),
+        Uint128(1000),
+    )
+    .unwrap();
+    // 3_000_000 - ceil(3_000_000_000_000 / 1_001_000) = 2_997
+    assert_eq!(commission_amount, Uint128(8));
+    assert_eq!(return_amount, Uint128(2_989));
+    assert_eq!(spread_amount, Uint128(3));
+
+    // the assets not swapped don't matter to the constant product curve
+    let (other_return, _, _) = compute_swap(
+        &CurveType::ConstantProduct {},
+        &amounts(&[1_000_000, 1, 3_000_000]),
+        0,
+        2,
+        Uint128(1_000),
+        Uint128(3),
+        Uint128(1000),
+    )
+    .unwrap();
+    assert_eq!(other_return, return_amount);
+
+    compute_swap(
+        &CurveType::ConstantProduct {},
+        &amounts(&[1_000_000, 2_000_000, 0]),
+        0,
+        2,
+        Uint128(1_000),
+        Uint128(3),
+        Uint128(1000),
+    )
+    .unwrap_err();
+}
+
+#[test]
+fn test_shares() {
+    let constant_product = CurveType::ConstantProduct {};
+    let deposits = amounts(&[1_000, 1_000_000, 1_000_000_000]);
+    assert_eq!(
+        compute_initial_share(&constant_product, &deposits).unwrap(),
+        Uint128(1_000_000)
+    );
+    assert_eq!(
+        compute_initial_share(&CurveType::StableSwap { amp: 100 }, &amounts(&[5_000; 3])).unwrap(),
+        Uint128(15_000)
+    );
+    // the first deposit needs every asset and more than the locked liquidity
+    compute_initial_share(&constant_product, &amounts(&[1_000, 0, 1_000])).unwrap_err();
+    compute_initial_share(&constant_product, &amounts(&[MINIMUM_LIQUIDITY; 3])).unwrap_err();
+
+    // the least share of the deposits is minted and the rest isn't used
+    let reserves = amounts(&[10_000, 20_000, 30_000]);
+    let total_share = Uint128(1_000);
+    let share = compute_deposit_share(&amounts(&[1_000, 1_000, 3_000]), &reserves, total_share);
+    assert_eq!(share, Uint128(50));
+    assert_eq!(
+        compute_used_deposits(share, &reserves, total_share),
+        amounts(&[500, 1_000, 1_500])
+    );
+    // used deposits are rounded up
+    assert_eq!(
+        compute_used_deposits(Uint128(1), &amounts(&[10_001, 20_000, 30_000]), total_share),
+        amounts(&[11, 20, 30])
+    );
+    assert_eq!(
+        compute_deposit_share(&amounts(&[1_000, 0, 3_000]), &reserves, total_share),
+        Uint128::zero()
+    );
+
+    // withdrawals are rounded down
+    assert_eq!(
+        compute_withdrawn_amounts(&amounts(&[10_001, 20_000, 30_000]), Uint128(1), total_share)
+            .unwrap(),
+        amounts(&[10, 20, 30])
+    );
+    compute_withdrawn_amounts(&reserves, Uint128(1_001), total_share).unwrap_err();
+}
@@ -4,9 +4,15 @@ use std::fs::create_dir_all;
 use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
 use cosmwasm_std::HandleResponse;
 
-use secretswap::{PairInfo, PairInitMsg};
+use secretswap::{
+    PairInfo, PairInitMsg, ProvideResponse, SwapDataResponse, SwapResponse, WithdrawResponse,
+    ZapInResponse, ZapOutResponse,
+};
 use secretswap_pair::msg::{
-    Cw20HookMsg, HandleMsg, PoolResponse, QueryMsg, ReverseSimulationResponse, SimulationResponse,
+    CircuitBreakerResponse, CumulativePricesResponse, Cw20HookMsg, FlashSwapReceiverMsg, HandleMsg,
+    LedgerResponse, LimitOrdersResponse, MigrationResponse, PoolResponse, ProtocolFeesResponse,
+    QueryMsg, ReverseSimulationResponse, SimulationResponse, StatsResponse, StatusResponse,
+    SwapFeeResponse, TwapResponse,
 };
 
 fn main() {
@@ -18,10 +24,27 @@ fn main() {
     export_schema(&schema_for!(PairInitMsg), &out_dir);
     export_schema(&schema_for!(HandleMsg), &out_dir);
     export_schema(&schema_for!(Cw20HookMsg), &out_dir);
+    export_schema(&schema_for!(FlashSwapReceiverMsg), &out_dir);
     export_schema(&schema_for!(HandleResponse), &out_dir);
+    export_schema(&schema_for!(SwapResponse), &out_dir);
+    export_schema(&schema_for!(ProvideResponse), &out_dir);
+    export_schema(&schema_for!(WithdrawResponse), &out_dir);
+    export_schema(&schema_for!(ZapInResponse), &out_dir);
+    export_schema(&schema_for!(ZapOutResponse), &out_dir);
     export_schema(&schema_for!(QueryMsg), &out_dir);
     export_schema(&schema_for!(PairInfo), &out_dir);
     export_schema(&schema_for!(PoolResponse), &out_dir);
     export_schema(&schema_for!(ReverseSimulationResponse), &out_dir);
     export_schema(&schema_for!(SimulationResponse), &out_dir);
+    export_schema(&schema_for!(CumulativePricesResponse), &out_dir);
+    export_schema(&schema_for!(TwapResponse), &out_dir);
+    export_schema(&schema_for!(ProtocolFeesResponse), &out_dir);
+    export_schema(&schema_for!(StatusResponse), &out_dir);
+    export_schema(&schema_for!(SwapFeeResponse), &out_dir);
+    export_schema(&schema_for!(CircuitBreakerResponse), &out_dir);
+    export_schema(&schema_for!(MigrationResponse), &out_dir);
+    export_schema(&schema_for!(StatsResponse), &out_dir);
+    export_schema(&schema_for!(LedgerResponse), &out_dir);
+    export_schema(&schema_for!(LimitOrdersResponse), &out_dir);
+    export_schema(&schema_for!(SwapDataResponse), &out_dir);
 }
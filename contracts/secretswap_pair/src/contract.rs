@@ -13,27 +13,65 @@ use primitive_types::U256;
 use secret_toolkit::snip20;
 
 use secretswap::{
-    query_supply, Asset, AssetInfo, AssetInfoRaw, Factory, InitHook, PairInfo, PairInfoRaw,
-    PairInitMsg, TokenInitMsg,
+    ack_swap_data, deliver_swap_data, query_supply, query_swap_data, Asset, AssetInfo,
+    AssetInfoRaw, CurveType, Deadline, Factory, InitHook, PairInfo, PairInfoRaw, PairInitMsg,
+    PairSettings, ProvideResponse, SwapData, SwapResponse, TokenInitMsg, WithdrawResponse,
+    ZapInResponse, ZapOutResponse,
 };
 
 use crate::{
+    flash_swap::assert_flash_swap_invariant,
+    ledger::{
+        accrue_fees, assert_viewing_key, compute_position, record_deposit, record_withdrawal,
+        set_viewing_key,
+    },
+    limit_order::{cancel_limit_order, fill_limit_orders, place_limit_order, Fill},
     math::{decimal_multiplication, decimal_subtraction, reverse_decimal},
     msg::{
-        Cw20HookMsg, HandleMsg, PoolResponse, QueryMsg, ReverseSimulationResponse,
-        SimulationResponse,
+        CircuitBreakerResponse, CumulativePricesResponse, Cw20HookMsg, FlashSwapReceiverMsg,
+        HandleMsg, LedgerResponse, LimitOrderResponse, LimitOrdersResponse, MigrationResponse,
+        PoolResponse, ProtocolFeesResponse, QueryMsg, QueryWithPermit, ReverseSimulationResponse,
+        SimulationResponse, StatsBucketResponse, StatsInterval, StatsResponse, StatusResponse,
+        SwapFeeResponse, TwapResponse,
+    },
+    oracle::{
+        assert_price_band, compute_price_band, compute_twap, init_price_cumulative,
+        read_average_change, read_block_prices, update_price_cumulative,
     },
+    permit::{validate_permit, Permission},
+    stableswap::{compute_d, compute_stable_offer_amount, compute_stable_swap},
+    stats::{read_stats, record_trade},
     u256_math::*,
+    weighted::{
+        compute_weighted_initial_share, compute_weighted_offer_amount, compute_weighted_swap,
+    },
+    zap::compute_zap_in_swap_amount,
+};
+
+use crate::querier::{query_other_pair_info, query_pair_settings};
+use crate::state::{
+    read_flash_swap, read_limit_order, read_migrate_from, read_migrated_to, read_order_escrow,
+    read_owner_orders, read_pair_info, read_price_cumulative, read_protocol_fees, read_reserves,
+    remove_flash_swap, store_flash_swap, store_migrate_from, store_migrated_to, store_pair_info,
+    store_permit_revoked, store_protocol_fees, store_reserves, FlashSwap, MigrateFrom,
 };
 
-use crate::querier::query_pair_settings;
-use crate::state::{read_pair_info, store_pair_info};
+/// LP shares locked forever by the first deposit, as in Uniswap v2
+pub const MINIMUM_LIQUIDITY: u128 = 1_000;
+
+/// Bound on the rounds raising an approximated offer amount in `compute_offer_amount`
+const MAX_OFFER_CORRECTIONS: usize = 16;
 
 pub fn init<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
     msg: PairInitMsg,
 ) -> StdResult<InitResponse> {
+    let curve = msg.curve.unwrap_or_default();
+    curve.validate()?;
+
+    init_price_cumulative(&mut deps.storage, env.block.time)?;
+
     // create viewing key
     let assets_viewing_key = String::from("SecretSwap");
 
@@ -114,7 +152,7 @@ pub fn init<S: Storage, A: Api, Q: Querier>(
             InitHook {
                 msg: to_binary(&HandleMsg::PostInitialize {})?,
                 contract_addr: env.contract.address.clone(),
-                code_hash: env.contract_code_hash,
+                code_hash: env.contract_code_hash.clone(),
             },
         ))?,
         send: vec![],
@@ -146,11 +184,42 @@ pub fn init<S: Storage, A: Api, Q: Querier>(
                 address: hook.contract_addr,
                 code_hash: hook.code_hash,
             },
+            curve,
         };
 
         // create viewing keys
 
         store_pair_info(&mut deps.storage, &pair_info)?;
+
+        if let Some(migrate_from) = msg.migrate_from {
+            let old_pair_info =
+                query_other_pair_info(&deps, &migrate_from.address, &migrate_from.code_hash)?;
+            if !old_pair_info.asset_infos[0].equal(&msg.asset_infos[0])
+                || !old_pair_info.asset_infos[1].equal(&msg.asset_infos[1])
+            {
+                return Err(StdError::generic_err(
+                    "The pair to migrate from must have the same asset infos",
+                ));
+            }
+
+            // its LP tokens are sent here with the `migrate_liquidity` hook
+            messages.push(snip20::register_receive_msg(
+                env.contract_code_hash.clone(),
+                None,
+                256,
+                old_pair_info.token_code_hash.clone(),
+                old_pair_info.liquidity_token.clone(),
+            )?);
+            store_migrate_from(
+                &mut deps.storage,
+                &MigrateFrom {
+                    pair: migrate_from.address,
+                    pair_code_hash: migrate_from.code_hash,
+                    liquidity_token: old_pair_info.liquidity_token,
+                    token_code_hash: old_pair_info.token_code_hash,
+                },
+            )?;
+        }
     } else {
         return Err(StdError::generic_err(
             "Must provide the factory as init hook",
@@ -168,23 +237,54 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
     env: Env,
     msg: HandleMsg,
 ) -> HandleResult {
+    // the pools are out of balance until a flash swap is finalized
+    if read_flash_swap(&deps.storage)?.is_some() && !matches!(msg, HandleMsg::FinalizeFlashSwap {})
+    {
+        return Err(StdError::generic_err("Pair is locked by a flash swap"));
+    }
+
+    // a migrated pair has no reserves left, only what doesn't touch them still runs
+    if let Some(new_pair) = read_migrated_to(&deps.storage)? {
+        match msg {
+            HandleMsg::CollectProtocolFees {}
+            | HandleMsg::CancelLimitOrder { .. }
+            | HandleMsg::SetViewingKey { .. }
+            | HandleMsg::RevokePermit { .. }
+            | HandleMsg::AckSwapData { .. } => {}
+            _ => {
+                return Err(StdError::generic_err(format!(
+                    "Pair was migrated to {}",
+                    new_pair
+                )))
+            }
+        }
+    }
+
     match msg {
         HandleMsg::Receive { amount, msg, from } => receive_cw20(deps, env, from, amount, msg),
         HandleMsg::PostInitialize {} => try_post_initialize(deps, env),
         HandleMsg::ProvideLiquidity {
             assets,
             slippage_tolerance,
-        } => try_provide_liquidity(deps, env, assets, slippage_tolerance),
+            deadline,
+        } => {
+            assert_deadline(&env, &deadline)?;
+
+            try_provide_liquidity(deps, env, assets, slippage_tolerance)
+        }
         HandleMsg::Swap {
             offer_asset,
             expected_return,
             belief_price,
             max_spread,
             to,
+            ask_amount,
+            deadline,
         } => {
             if !offer_asset.is_native_token() {
                 return Err(StdError::unauthorized());
             }
+            assert_deadline(&env, &deadline)?;
 
             try_swap(
                 deps,
@@ -195,11 +295,117 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
                 belief_price,
                 max_spread,
                 to,
+                ask_amount,
+            )
+        }
+        HandleMsg::ZapIn {
+            offer_asset,
+            min_shares,
+            deadline,
+        } => {
+            if !offer_asset.is_native_token() {
+                return Err(StdError::unauthorized());
+            }
+            assert_deadline(&env, &deadline)?;
+
+            try_zap_in(
+                deps,
+                env.clone(),
+                env.message.sender,
+                offer_asset,
+                min_shares,
             )
         }
+        HandleMsg::CollectProtocolFees {} => try_collect_protocol_fees(deps, env),
+        HandleMsg::Sync {} => try_sync(deps, env),
+        HandleMsg::Skim { to } => try_skim(deps, env, to),
+        HandleMsg::FlashSwap {
+            asset,
+            receiver,
+            receiver_code_hash,
+            msg,
+        } => try_flash_swap(deps, env, asset, receiver, receiver_code_hash, msg),
+        HandleMsg::FinalizeFlashSwap {} => try_finalize_flash_swap(deps, env),
+        HandleMsg::MigrateReserves {
+            new_pair,
+            new_pair_code_hash,
+        } => try_migrate_reserves(deps, env, new_pair, new_pair_code_hash),
+        HandleMsg::FinalizeMigration {
+            reserves,
+            total_share,
+        } => try_finalize_migration(deps, env, reserves, total_share),
+        HandleMsg::SetViewingKey { key, .. } => try_set_viewing_key(deps, env, key),
+        HandleMsg::RevokePermit { permit_name, .. } => try_revoke_permit(deps, env, permit_name),
+        HandleMsg::PlaceLimitOrder { offer_asset, price } => {
+            if !offer_asset.is_native_token() {
+                return Err(StdError::unauthorized());
+            }
+
+            try_place_limit_order(deps, env.clone(), env.message.sender, offer_asset, price)
+        }
+        HandleMsg::CancelLimitOrder { order_id } => try_cancel_limit_order(deps, env, order_id),
+        HandleMsg::AckSwapData { endpoint, key, seq } => {
+            try_ack_swap_data(deps, endpoint, key, seq)
+        }
     }
 }
 
+pub fn try_set_viewing_key<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    key: String,
+) -> HandleResult {
+    let account = deps.api.canonical_address(&env.message.sender)?;
+    set_viewing_key(&mut deps.storage, &account, &key)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("action", "set_viewing_key")],
+        data: None,
+    })
+}
+
+pub fn try_revoke_permit<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    permit_name: String,
+) -> HandleResult {
+    let account = deps.api.canonical_address(&env.message.sender)?;
+    store_permit_revoked(&mut deps.storage, &account, &permit_name)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "revoke_permit"),
+            log("permit_name", permit_name),
+        ],
+        data: None,
+    })
+}
+
+pub fn try_ack_swap_data<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    endpoint: HumanAddr,
+    key: String,
+    seq: u64,
+) -> HandleResult {
+    let pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
+    let pair_settings = query_pair_settings(&deps, &pair_info)?;
+    ack_swap_data(
+        &mut deps.storage,
+        &pair_settings.swap_data_endpoints,
+        &endpoint,
+        &key,
+        seq,
+    )?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("action", "ack_swap_data"), log("seq", seq.to_string())],
+        data: None,
+    })
+}
+
 pub fn receive_cw20<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
@@ -216,22 +422,12 @@ pub fn receive_cw20<S: Storage, A: Api, Q: Querier>(
                 belief_price,
                 max_spread,
                 to,
+                ask_amount,
+                deadline,
             } => {
                 // only asset contract can execute this message
-                let mut authorized: bool = false;
-                let config: PairInfoRaw = read_pair_info(&deps.storage)?;
-                let pools: [Asset; 2] = config.query_pools(deps, &env.contract.address)?;
-                for pool in pools.iter() {
-                    if let AssetInfo::Token { contract_addr, .. } = &pool.info {
-                        if contract_addr == &env.message.sender {
-                            authorized = true;
-                        }
-                    }
-                }
-
-                if !authorized {
-                    return Err(StdError::unauthorized());
-                }
+                assert_pool_token(deps, &env.message.sender)?;
+                assert_deadline(&env, &deadline)?;
 
                 try_swap(
                     deps,
@@ -249,22 +445,116 @@ pub fn receive_cw20<S: Storage, A: Api, Q: Querier>(
                     belief_price,
                     max_spread,
                     to,
+                    ask_amount,
+                )
+            }
+            Cw20HookMsg::ZapIn {
+                min_shares,
+                deadline,
+            } => {
+                // only asset contract can execute this message
+                assert_pool_token(deps, &env.message.sender)?;
+                assert_deadline(&env, &deadline)?;
+
+                try_zap_in(
+                    deps,
+                    env,
+                    from,
+                    Asset {
+                        info: AssetInfo::Token {
+                            contract_addr,
+                            token_code_hash: Default::default(),
+                            viewing_key: Default::default(),
+                        },
+                        amount,
+                    },
+                    min_shares,
                 )
             }
-            Cw20HookMsg::WithdrawLiquidity {} => {
+            Cw20HookMsg::WithdrawLiquidity { deadline } => {
                 let config: PairInfoRaw = read_pair_info(&deps.storage)?;
                 if deps.api.canonical_address(&env.message.sender)? != config.liquidity_token {
                     return Err(StdError::unauthorized());
                 }
+                assert_deadline(&env, &deadline)?;
 
                 try_withdraw_liquidity(deps, env, from, amount)
             }
+            Cw20HookMsg::ZapOut {
+                ask_asset,
+                min_return,
+                deadline,
+            } => {
+                let config: PairInfoRaw = read_pair_info(&deps.storage)?;
+                if deps.api.canonical_address(&env.message.sender)? != config.liquidity_token {
+                    return Err(StdError::unauthorized());
+                }
+                assert_deadline(&env, &deadline)?;
+
+                try_zap_out(deps, env, from, amount, ask_asset, min_return)
+            }
+            Cw20HookMsg::MigrateLiquidity {} => {
+                // only the LP token of the pair this one replaces can execute this message
+                match read_migrate_from(&deps.storage)? {
+                    Some(migrate_from) if migrate_from.liquidity_token == env.message.sender => {
+                        try_migrate_liquidity(deps, migrate_from, from, amount)
+                    }
+                    _ => Err(StdError::unauthorized()),
+                }
+            }
+            Cw20HookMsg::PlaceLimitOrder { price } => {
+                // only asset contract can execute this message
+                assert_pool_token(deps, &env.message.sender)?;
+
+                try_place_limit_order(
+                    deps,
+                    env,
+                    from,
+                    Asset {
+                        info: AssetInfo::Token {
+                            contract_addr,
+                            token_code_hash: Default::default(),
+                            viewing_key: Default::default(),
+                        },
+                        amount,
+                    },
+                    price,
+                )
+            }
         }
     } else {
         Err(StdError::generic_err("data should be given"))
     }
 }
 
+fn assert_deadline(env: &Env, deadline: &Option<Deadline>) -> StdResult<()> {
+    match deadline {
+        Some(deadline) => deadline.assert_not_expired(&env.block),
+        None => Ok(()),
+    }
+}
+
+fn assert_pool_token<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    token: &HumanAddr,
+) -> StdResult<()> {
+    let pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
+    let token = deps.api.canonical_address(token)?;
+    let authorized = pair_info
+        .asset_infos
+        .iter()
+        .any(|asset_info| match asset_info {
+            AssetInfoRaw::Token { contract_addr, .. } => contract_addr == &token,
+            _ => false,
+        });
+
+    if !authorized {
+        return Err(StdError::unauthorized());
+    }
+
+    Ok(())
+}
+
 // Must token contract execute it
 pub fn try_post_initialize<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
@@ -312,7 +602,10 @@ pub fn try_provide_liquidity<S: Storage, A: Api, Q: Querier>(
     // Note: pair info + viewing keys are read from storage, therefore the input
     // viewing keys to this function are not used
     let pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
-    let mut pools: [Asset; 2] = pair_info.query_pools(deps, &env.contract.address)?;
+    query_pair_settings(&deps, &pair_info)?
+        .status
+        .assert_deposits_allowed()?;
+    let pools: [Asset; 2] = read_pools(&deps, &pair_info)?;
     let deposits: [Uint128; 2] = [
         assets
             .iter()
@@ -326,95 +619,91 @@ pub fn try_provide_liquidity<S: Storage, A: Api, Q: Querier>(
             .expect("Wrong asset info is given"),
     ];
 
-    let mut i = 0;
-    let mut messages: Vec<CosmosMsg> = vec![];
-    for pool in pools.iter_mut() {
-        // If the pool is token contract, then we need to execute TransferFrom msg to receive funds
-        if let AssetInfo::Token {
-            contract_addr,
-            token_code_hash,
-            ..
-        } = &pool.info
-        {
-            messages.push(snip20::transfer_from_msg(
-                env.message.sender.clone(),
-                env.contract.address.clone(),
-                deposits[i],
-                None,
-                256,
-                token_code_hash.clone(),
-                contract_addr.clone(),
-            )?);
-        } else {
-            // If the asset is native token, balance is already increased
-            // To calculated properly we should subtract user deposit from the pool
-            pool.amount = (pool.amount - deposits[i])?;
-        }
-
-        i += 1;
-    }
+    update_price_cumulative(
+        &mut deps.storage,
+        &pair_info.curve,
+        [pools[0].amount, pools[1].amount],
+        env.block.time,
+    )?;
 
     // assert slippage tolerance
     assert_slippage_tolerance(&slippage_tolerance, &deposits, &pools)?;
 
     let liquidity_token = deps.api.human_address(&pair_info.liquidity_token)?;
     let total_share = query_supply(&deps, &liquidity_token, &pair_info.token_code_hash)?;
-    let share = if total_share == Uint128::zero() {
-        // Initial share = collateral amount
-        let deposit_0 = U256::from(deposits[0].u128());
-        let deposit_1 = U256::from(deposits[1].u128());
-
-        let sqrt = mul(Some(deposit_0), Some(deposit_1))
-            .and_then(|prod| u256_sqrt(prod))
-            .ok_or_else(|| {
-                StdError::generic_err(format!(
-                    "Cannot calculate sqrt(deposit_0 {} * deposit_1 {})",
-                    deposit_0, deposit_1
-                ))
-            })?;
 
-        Uint128(sqrt.low_u128())
+    // Only the amounts matching the pool ratio are taken, the excess stays with the provider
+    let used_deposits = if total_share.is_zero() {
+        deposits
     } else {
-        // min(1, 2)
-        // 1. sqrt(deposit_0 * exchange_rate_0_to_1 * deposit_0) * (total_share / sqrt(pool_0 * pool_1))
-        // == deposit_0 * total_share / pool_0
-        // 2. sqrt(deposit_1 * exchange_rate_1_to_0 * deposit_1) * (total_share / sqrt(pool_1 * pool_1))
-        // == deposit_1 * total_share / pool_1
-
-        // This was:
-        // std::cmp::min(
-        //   deposits[0].multiply_ratio(total_share, pools[0].amount),
-        //   deposits[1].multiply_ratio(total_share, pools[1].amount),
-        // )
-
-        let total_share = Some(U256::from(total_share.u128()));
-
-        let deposit0 = Some(U256::from(deposits[0].u128()));
-        let pools0_amount = Some(U256::from(pools[0].amount.u128()));
+        compute_used_deposits(&deposits, &[pools[0].amount, pools[1].amount])
+    };
 
-        let share0 = div(mul(deposit0, total_share), pools0_amount).ok_or_else(|| {
-            StdError::generic_err(format!(
-                "Cannot calculate deposits[0] {} * total_share {} / pools[0].amount {}",
-                deposit0.unwrap(),
-                total_share.unwrap(),
-                pools0_amount.unwrap()
-            ))
-        })?;
+    let mut messages: Vec<CosmosMsg> = vec![];
+    for (i, pool) in pools.iter().enumerate() {
+        match &pool.info {
+            // If the pool is token contract, then we need to execute TransferFrom msg to receive funds
+            AssetInfo::Token {
+                contract_addr,
+                token_code_hash,
+                ..
+            } => {
+                if !used_deposits[i].is_zero() {
+                    messages.push(snip20::transfer_from_msg(
+                        env.message.sender.clone(),
+                        env.contract.address.clone(),
+                        used_deposits[i],
+                        None,
+                        256,
+                        token_code_hash.clone(),
+                        contract_addr.clone(),
+                    )?);
+                }
+            }
+            // Native tokens were sent in full, refund the excess
+            AssetInfo::NativeToken { .. } => {
+                let excess = (deposits[i] - used_deposits[i])?;
+                if !excess.is_zero() {
+                    messages.push(
+                        Asset {
+                            info: pool.info.clone(),
+                            amount: excess,
+                        }
+                        .into_msg(
+                            &deps,
+                            env.contract.address.clone(),
+                            env.message.sender.clone(),
+                        )?,
+                    );
+                }
+            }
+        }
+    }
 
-        let deposit1 = Some(U256::from(deposits[1].u128()));
-        let pools1_amount = Some(U256::from(pools[1].amount.u128()));
+    let share = compute_minted_share(
+        &env,
+        &pair_info,
+        &liquidity_token,
+        total_share,
+        &[pools[0].amount, pools[1].amount],
+        &used_deposits,
+        &mut messages,
+    )?;
 
-        let share1 = div(mul(deposit1, total_share), pools1_amount).ok_or_else(|| {
-            StdError::generic_err(format!(
-                "Cannot calculate deposits[1] {} * total_share {} / pools[1].amount {}",
-                deposit1.unwrap(),
-                total_share.unwrap(),
-                pools1_amount.unwrap()
-            ))
-        })?;
+    store_reserves(
+        &mut deps.storage,
+        &[
+            pools[0].amount + used_deposits[0],
+            pools[1].amount + used_deposits[1],
+        ],
+    )?;
 
-        Uint128(std::cmp::min(share0, share1).low_u128())
-    };
+    record_deposit(
+        &mut deps.storage,
+        &deps.api.canonical_address(&env.message.sender)?,
+        used_deposits,
+        share,
+    )?;
 
     messages.push(snip20::mint_msg(
         env.message.sender,
@@ -422,7 +711,7 @@ pub fn try_provide_liquidity<S: Storage, A: Api, Q: Querier>(
         None,
         256,
         pair_info.token_code_hash,
-        deps.api.human_address(&pair_info.liquidity_token)?,
+        liquidity_token,
     )?);
 
     Ok(HandleResponse {
@@ -430,68 +719,326 @@ pub fn try_provide_liquidity<S: Storage, A: Api, Q: Querier>(
         log: vec![
             log("action", "provide_liquidity"),
             log("assets", format!("{}, {}", assets[0], assets[1])),
+            log(
+                "used_assets",
+                format!(
+                    "{}{}, {}{}",
+                    used_deposits[0], pools[0].info, used_deposits[1], pools[1].info
+                ),
+            ),
             log("share", &share),
         ],
-        data: None,
+        data: Some(to_binary(&ProvideResponse {
+            share,
+            used_assets: [
+                Asset {
+                    info: pools[0].info.clone(),
+                    amount: used_deposits[0],
+                },
+                Asset {
+                    info: pools[1].info.clone(),
+                    amount: used_deposits[1],
+                },
+            ],
+        })?),
     })
 }
 
-pub fn try_withdraw_liquidity<S: Storage, A: Api, Q: Querier>(
+/// Share minted for `deposits`. The first deposit into the pool also mints the locked
+/// `MINIMUM_LIQUIDITY` to the pair itself.
+fn compute_minted_share(
+    env: &Env,
+    pair_info: &PairInfoRaw,
+    liquidity_token: &HumanAddr,
+    total_share: Uint128,
+    pools: &[Uint128; 2],
+    deposits: &[Uint128; 2],
+    messages: &mut Vec<CosmosMsg>,
+) -> StdResult<Uint128> {
+    if total_share.is_zero() {
+        let initial_share = compute_initial_share(&pair_info.curve, deposits)?;
+
+        // MINIMUM_LIQUIDITY is minted to the pair itself and locked forever, so a donation
+        // can't inflate the price of a share beyond what small deposits can afford
+        messages.push(snip20::mint_msg(
+            env.contract.address.clone(),
+            Uint128(MINIMUM_LIQUIDITY),
+            None,
+            256,
+            pair_info.token_code_hash.clone(),
+            liquidity_token.clone(),
+        )?);
+
+        Ok((initial_share - Uint128(MINIMUM_LIQUIDITY))?)
+    } else {
+        compute_deposit_share(deposits, pools, total_share)
+    }
+}
+
+/// Provides liquidity with a single asset. The optimal part of it is swapped into the other
+/// asset against the pool first, so the swapped amount never leaves the pair.
+pub fn try_zap_in<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
     sender: HumanAddr,
-    amount: Uint128,
+    offer_asset: Asset,
+    min_shares: Option<Uint128>,
 ) -> HandleResult {
-    let pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
-    let liquidity_addr: HumanAddr = deps.api.human_address(&pair_info.liquidity_token)?;
-
-    let pools: [Asset; 2] = pair_info.query_pools(&deps, &env.contract.address)?;
-    let total_share: Uint128 = query_supply(&deps, &liquidity_addr, &pair_info.token_code_hash)?;
+    offer_asset.assert_sent_native_token_balance(&env)?;
 
-    let refund_assets: Vec<Asset> = pools
+    let mut pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
+    let pools: [Asset; 2] = read_pools(&deps, &pair_info)?;
+    let offer_index = pools
         .iter()
-        .map(|a| {
-            // withdrawn_asset_amount = a.amount * amount / total_share
+        .position(|pool| offer_asset.info.equal(&pool.info))
+        .ok_or_else(|| StdError::generic_err("Wrong asset info is given"))?;
+    let ask_index = 1 - offer_index;
 
-            let current_pool_amount = Some(U256::from(a.amount.u128()));
-            let withdrawn_share_amount = Some(U256::from(amount.u128()));
-            let total_share = Some(U256::from(total_share.u128()));
+    update_price_cumulative(
+        &mut deps.storage,
+        &pair_info.curve,
+        [pools[0].amount, pools[1].amount],
+        env.block.time,
+    )?;
 
-            let withdrawn_asset_amount = div(
-                mul(current_pool_amount, withdrawn_share_amount),
-                total_share,
-            )
-                .ok_or_else(|| {
-                    StdError::generic_err(format!(
-                    "Cannot calculate current_pool_amount {} * withdrawn_share_amount {} / total_share {}",
-                    a.amount,
-                    amount,
-                    total_share.unwrap()
-                    ))
-                })?;
+    let liquidity_token = deps.api.human_address(&pair_info.liquidity_token)?;
+    let total_share = query_supply(&deps, &liquidity_token, &pair_info.token_code_hash)?;
+    let pair_settings = query_pair_settings(&deps, &pair_info)?;
+    pair_settings.status.assert_swaps_allowed()?;
+    pair_settings.status.assert_deposits_allowed()?;
+
+    let offer_pool = pools[offer_index].amount;
+    let ask_pool = pools[ask_index].amount;
+    // (return_amount, spread_amount, commission_amount, protocol_fee_amount) of swapping `amount`
+    let swap = |amount: Uint128| -> StdResult<(Uint128, Uint128, Uint128, Uint128)> {
+        let (return_amount, spread_amount, commission_amount) = compute_swap(
+            &pair_info.curve,
+            offer_index,
+            offer_pool,
+            ask_pool,
+            amount,
+            pair_settings.swap_fee.commission_rate_nom,
+            pair_settings.swap_fee.commission_rate_denom,
+        )?;
+        let protocol_fee_amount = match &pair_settings.protocol_fee {
+            Some(protocol_fee) => protocol_fee.compute(commission_amount),
+            None => Uint128::zero(),
+        };
 
-            Ok(Asset {
-                info: a.info.clone(),
-                amount: Uint128(withdrawn_asset_amount.low_u128()),
-            })
-        })
-        .collect::<StdResult<Vec<Asset>>>()?;
+        Ok((
+            return_amount,
+            spread_amount,
+            commission_amount,
+            protocol_fee_amount,
+        ))
+    };
 
-    // update pool info
-    Ok(HandleResponse {
-        messages: vec![
-            // refund asset tokens
-            refund_assets[0].clone().into_msg(
-                deps,
-                env.contract.address.clone(),
-                sender.clone(),
-            )?,
-            refund_assets[1].clone().into_msg(
-                deps,
-                env.contract.address.clone(),
-                sender.clone(),
-            )?,
-            // burn liquidity token
+    let swap_amount =
+        compute_zap_in_swap_amount(offer_pool, ask_pool, offer_asset.amount, |amount| {
+            let (return_amount, _, _, protocol_fee_amount) = swap(amount)?;
+            Ok((return_amount, return_amount + protocol_fee_amount))
+        })?;
+    let (return_amount, spread_amount, commission_amount, protocol_fee_amount) = swap(swap_amount)?;
+
+    if !protocol_fee_amount.is_zero() {
+        let mut protocol_fees = read_protocol_fees(&deps.storage)?;
+        protocol_fees[ask_index] = protocol_fees[ask_index] + protocol_fee_amount;
+        store_protocol_fees(&mut deps.storage, &protocol_fees)?;
+    }
+    // the fees are earned by the shares before the deposit
+    let mut lp_fees = [Uint128::zero(); 2];
+    lp_fees[ask_index] = (commission_amount - protocol_fee_amount)?;
+    accrue_fees(&mut deps.storage, lp_fees, total_share)?;
+
+    if offer_index == 0 {
+        pair_info.asset0_volume = pair_info.asset0_volume.add(swap_amount);
+    } else {
+        pair_info.asset1_volume = pair_info.asset1_volume.add(swap_amount);
+    }
+    store_pair_info(&mut deps.storage, &pair_info)?;
+    record_trade(
+        &mut deps.storage,
+        env.block.time,
+        offer_index,
+        swap_amount,
+        commission_amount,
+    )?;
+
+    // the rest of the offer asset and the return amount are deposited into the swapped pool
+    let mut deposits = [Uint128::zero(); 2];
+    deposits[offer_index] = (offer_asset.amount - swap_amount)?;
+    deposits[ask_index] = return_amount;
+    let mut pools_after = [Uint128::zero(); 2];
+    pools_after[offer_index] = offer_pool + swap_amount;
+    pools_after[ask_index] = (ask_pool - (return_amount + protocol_fee_amount))?;
+
+    let share = compute_deposit_share(&deposits, &pools_after, total_share)?;
+    if share.is_zero() {
+        return Err(StdError::generic_err(
+            "Zap in amount is too small to mint any share",
+        ));
+    }
+    if let Some(min_shares) = min_shares {
+        if share < min_shares {
+            return Err(StdError::generic_err(format!(
+                "Share {} is lower than min_shares {}",
+                share, min_shares
+            )));
+        }
+    }
+
+    // the swap amount is rounded down, the offer asset the deposit doesn't use is refunded
+    let used_deposits = compute_used_deposits(&deposits, &pools_after);
+    let refund_amount = (deposits[offer_index] - used_deposits[offer_index])?;
+
+    // the deposit stays in the pool, only the protocol fee leaves the ask side
+    let mut reserves = [Uint128::zero(); 2];
+    reserves[offer_index] = (offer_pool + offer_asset.amount - refund_amount)?;
+    reserves[ask_index] = (ask_pool - protocol_fee_amount)?;
+    store_reserves(&mut deps.storage, &reserves)?;
+
+    // the swap raised the price of the ask asset, orders selling it may fill now
+    let (fills, order_msgs) = fill_orders(
+        deps,
+        &env,
+        &mut pair_info,
+        &pair_settings,
+        ask_index,
+        total_share + share,
+    )?;
+    assert_price_band(
+        &deps.storage,
+        &pair_info.curve,
+        pair_settings.max_price_move,
+        env.block.height,
+    )?;
+
+    let mut deposited = [Uint128::zero(); 2];
+    deposited[offer_index] = (offer_asset.amount - refund_amount)?;
+    record_deposit(
+        &mut deps.storage,
+        &deps.api.canonical_address(&sender)?,
+        deposited,
+        share,
+    )?;
+
+    let mut messages = vec![snip20::mint_msg(
+        sender.clone(),
+        share,
+        None,
+        256,
+        pair_info.token_code_hash.clone(),
+        liquidity_token,
+    )?];
+    if !refund_amount.is_zero() {
+        messages.push(
+            Asset {
+                info: pools[offer_index].info.clone(),
+                amount: refund_amount,
+            }
+            .into_msg(&deps, env.contract.address.clone(), sender.clone())?,
+        );
+    }
+    messages.extend(order_msgs);
+    messages.extend(swap_data_msgs(
+        deps,
+        &env,
+        &pair_info,
+        &pair_settings,
+        sender,
+        Asset {
+            info: pools[offer_index].info.clone(),
+            amount: swap_amount,
+        },
+        &Asset {
+            info: pools[ask_index].info.clone(),
+            amount: return_amount,
+        },
+        commission_amount,
+        spread_amount,
+    )?);
+    messages.extend(fill_swap_data_msgs(
+        deps,
+        &env,
+        &pair_info,
+        &pair_settings,
+        ask_index,
+        &fills,
+    )?);
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![
+            log("action", "zap_in"),
+            log("offer_asset", offer_asset.to_string()),
+            log("swap_amount", swap_amount.to_string()),
+            log("return_amount", return_amount.to_string()),
+            log("commission_amount", commission_amount.to_string()),
+            log("protocol_fee_amount", protocol_fee_amount.to_string()),
+            log("share", &share),
+            log("refund_amount", refund_amount.to_string()),
+            log("filled_orders", fills.len().to_string()),
+        ],
+        data: Some(to_binary(&ZapInResponse {
+            share,
+            swap_amount,
+            return_amount,
+            commission_amount,
+            protocol_fee_amount,
+            refund_amount,
+        })?),
+    })
+}
+
+pub fn try_withdraw_liquidity<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    sender: HumanAddr,
+    amount: Uint128,
+) -> HandleResult {
+    let pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
+    let liquidity_addr: HumanAddr = deps.api.human_address(&pair_info.liquidity_token)?;
+
+    let pools: [Asset; 2] = read_pools(&deps, &pair_info)?;
+    let total_share: Uint128 = query_supply(&deps, &liquidity_addr, &pair_info.token_code_hash)?;
+
+    update_price_cumulative(
+        &mut deps.storage,
+        &pair_info.curve,
+        [pools[0].amount, pools[1].amount],
+        env.block.time,
+    )?;
+
+    let refund_assets = compute_withdrawn_assets(&pools, amount, total_share)?;
+    store_reserves(
+        &mut deps.storage,
+        &[
+            (pools[0].amount - refund_assets[0].amount)?,
+            (pools[1].amount - refund_assets[1].amount)?,
+        ],
+    )?;
+    record_withdrawal(
+        &mut deps.storage,
+        &deps.api.canonical_address(&sender)?,
+        [refund_assets[0].amount, refund_assets[1].amount],
+        amount,
+    )?;
+
+    // update pool info
+    Ok(HandleResponse {
+        messages: vec![
+            // refund asset tokens
+            refund_assets[0].clone().into_msg(
+                deps,
+                env.contract.address.clone(),
+                sender.clone(),
+            )?,
+            refund_assets[1].clone().into_msg(
+                deps,
+                env.contract.address.clone(),
+                sender.clone(),
+            )?,
+            // burn liquidity token
             snip20::burn_msg(
                 amount,
                 None,
@@ -508,11 +1055,204 @@ pub fn try_withdraw_liquidity<S: Storage, A: Api, Q: Querier>(
                 format!("{}, {}", refund_assets[0].clone(), refund_assets[1].clone()),
             ),
         ],
-        data: None,
+        data: Some(to_binary(&WithdrawResponse {
+            withdrawn_share: amount,
+            refund_assets: [refund_assets[0].clone(), refund_assets[1].clone()],
+        })?),
+    })
+}
+
+/// Withdraws liquidity as a single asset. The other withdrawn asset is swapped against the
+/// pool left after the withdrawal.
+pub fn try_zap_out<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    sender: HumanAddr,
+    amount: Uint128,
+    ask_asset: AssetInfo,
+    min_return: Option<Uint128>,
+) -> HandleResult {
+    let mut pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
+    let liquidity_addr: HumanAddr = deps.api.human_address(&pair_info.liquidity_token)?;
+
+    let pools: [Asset; 2] = read_pools(&deps, &pair_info)?;
+    let total_share: Uint128 = query_supply(&deps, &liquidity_addr, &pair_info.token_code_hash)?;
+
+    let ask_index = pools
+        .iter()
+        .position(|pool| ask_asset.equal(&pool.info))
+        .ok_or_else(|| StdError::generic_err("Wrong asset info is given"))?;
+    let offer_index = 1 - ask_index;
+
+    update_price_cumulative(
+        &mut deps.storage,
+        &pair_info.curve,
+        [pools[0].amount, pools[1].amount],
+        env.block.time,
+    )?;
+
+    let refund_assets = compute_withdrawn_assets(&pools, amount, total_share)?;
+    let offer_amount = refund_assets[offer_index].amount;
+    let offer_pool = (pools[offer_index].amount - offer_amount)?;
+    let ask_pool = (pools[ask_index].amount - refund_assets[ask_index].amount)?;
+
+    let pair_settings = query_pair_settings(&deps, &pair_info)?;
+    pair_settings.status.assert_swaps_allowed()?;
+    let (swap_return_amount, spread_amount, commission_amount) = compute_swap(
+        &pair_info.curve,
+        offer_index,
+        offer_pool,
+        ask_pool,
+        offer_amount,
+        pair_settings.swap_fee.commission_rate_nom,
+        pair_settings.swap_fee.commission_rate_denom,
+    )?;
+
+    let return_amount = refund_assets[ask_index].amount + swap_return_amount;
+    if let Some(min_return) = min_return {
+        if return_amount < min_return {
+            return Err(StdError::generic_err(format!(
+                "Return amount {} is lower than min_return {}",
+                return_amount, min_return
+            )));
+        }
+    }
+
+    // the protocol share of the commission stays in the pair until it is collected
+    let protocol_fee_amount = match &pair_settings.protocol_fee {
+        Some(protocol_fee) => protocol_fee.compute(commission_amount),
+        None => Uint128::zero(),
+    };
+    if !protocol_fee_amount.is_zero() {
+        let mut protocol_fees = read_protocol_fees(&deps.storage)?;
+        protocol_fees[ask_index] = protocol_fees[ask_index] + protocol_fee_amount;
+        store_protocol_fees(&mut deps.storage, &protocol_fees)?;
+    }
+
+    let mut withdrawn = [Uint128::zero(); 2];
+    withdrawn[ask_index] = return_amount;
+    record_withdrawal(
+        &mut deps.storage,
+        &deps.api.canonical_address(&sender)?,
+        withdrawn,
+        amount,
+    )?;
+    // the fees are earned by the shares left after the withdrawal
+    let mut lp_fees = [Uint128::zero(); 2];
+    lp_fees[ask_index] = (commission_amount - protocol_fee_amount)?;
+    accrue_fees(&mut deps.storage, lp_fees, (total_share - amount)?)?;
+
+    if offer_index == 0 {
+        pair_info.asset0_volume = pair_info.asset0_volume.add(offer_amount);
+    } else {
+        pair_info.asset1_volume = pair_info.asset1_volume.add(offer_amount);
+    }
+    store_pair_info(&mut deps.storage, &pair_info)?;
+    record_trade(
+        &mut deps.storage,
+        env.block.time,
+        offer_index,
+        offer_amount,
+        commission_amount,
+    )?;
+
+    // the withdrawn offer asset is swapped right back into the pool
+    let mut reserves = [Uint128::zero(); 2];
+    reserves[offer_index] = pools[offer_index].amount;
+    reserves[ask_index] = (ask_pool - (swap_return_amount + protocol_fee_amount))?;
+    store_reserves(&mut deps.storage, &reserves)?;
+
+    // the swap raised the price of the ask asset, orders selling it may fill now
+    let (fills, order_msgs) = fill_orders(
+        deps,
+        &env,
+        &mut pair_info,
+        &pair_settings,
+        ask_index,
+        (total_share - amount)?,
+    )?;
+    assert_price_band(
+        &deps.storage,
+        &pair_info.curve,
+        pair_settings.max_price_move,
+        env.block.height,
+    )?;
+
+    let return_asset = Asset {
+        info: pools[ask_index].info.clone(),
+        amount: return_amount,
+    };
+
+    let mut messages = vec![
+        return_asset
+            .clone()
+            .into_msg(deps, env.contract.address.clone(), sender.clone())?,
+        // burn liquidity token
+        snip20::burn_msg(
+            amount,
+            None,
+            256,
+            pair_info.token_code_hash.clone(),
+            liquidity_addr,
+        )?,
+    ];
+    messages.extend(order_msgs);
+    messages.extend(swap_data_msgs(
+        deps,
+        &env,
+        &pair_info,
+        &pair_settings,
+        sender,
+        Asset {
+            info: pools[offer_index].info.clone(),
+            amount: offer_amount,
+        },
+        &Asset {
+            info: pools[ask_index].info.clone(),
+            amount: swap_return_amount,
+        },
+        commission_amount,
+        spread_amount,
+    )?);
+    messages.extend(fill_swap_data_msgs(
+        deps,
+        &env,
+        &pair_info,
+        &pair_settings,
+        ask_index,
+        &fills,
+    )?);
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![
+            log("action", "zap_out"),
+            log("withdrawn_share", &amount.to_string()),
+            log(
+                "refund_assets",
+                format!("{}, {}", refund_assets[0], refund_assets[1]),
+            ),
+            log("swap_return_amount", swap_return_amount.to_string()),
+            log("spread_amount", spread_amount.to_string()),
+            log("commission_amount", commission_amount.to_string()),
+            log("protocol_fee_amount", protocol_fee_amount.to_string()),
+            log("return_asset", return_asset.to_string()),
+            log("filled_orders", fills.len().to_string()),
+        ],
+        data: Some(to_binary(&ZapOutResponse {
+            withdrawn_share: amount,
+            return_asset,
+            swap_return_amount,
+            spread_amount,
+            commission_amount,
+            protocol_fee_amount,
+        })?),
     })
 }
 
 // CONTRACT - a user must do token approval
+/// With `ask_amount`, `offer_asset` is the most the sender spends: exactly `ask_amount` is
+/// returned and the unused part of the offer is refunded to the sender
 pub fn try_swap<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
@@ -522,68 +1262,87 @@ pub fn try_swap<S: Storage, A: Api, Q: Querier>(
     belief_price: Option<Decimal>,
     max_spread: Option<Decimal>,
     to: Option<HumanAddr>,
+    ask_amount: Option<Uint128>,
 ) -> HandleResult {
     offer_asset.assert_sent_native_token_balance(&env)?;
 
     let mut pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
 
-    let pools: [Asset; 2] = pair_info.query_pools(&deps, &env.contract.address)?;
+    let pools: [Asset; 2] = read_pools(&deps, &pair_info)?;
 
     let offer_pool: Asset;
     let ask_pool: Asset;
+    let offer_index: usize;
 
-    // If the asset balance is already increased
-    // To calculated properly we should subtract user deposit from the pool
     if offer_asset.info.equal(&pools[0].info) {
-        let pool_amount = U256::from(pools[0].amount.u128());
-        let offer_amount = U256::from(offer_asset.amount.u128());
-
-        let amount = pool_amount.checked_sub(offer_amount).ok_or_else(|| {
-            StdError::generic_err("offer_amount larger than pool_amount + offer_amount")
-        })?;
-
-        offer_pool = Asset {
-            amount: Uint128(amount.low_u128()),
-            info: pools[0].info.clone(),
-        };
+        offer_pool = pools[0].clone();
         ask_pool = pools[1].clone();
-
-        pair_info.asset0_volume = pair_info.asset0_volume.add(offer_asset.amount);
+        offer_index = 0;
     } else if offer_asset.info.equal(&pools[1].info) {
-        let pool_amount = U256::from(pools[1].amount.u128());
-        let offer_amount = U256::from(offer_asset.amount.u128());
-
-        let amount = pool_amount.checked_sub(offer_amount).ok_or_else(|| {
-            StdError::generic_err("offer_amount larger than pool_amount + offer_amount")
-        })?;
-
-        offer_pool = Asset {
-            amount: Uint128(amount.low_u128()),
-            info: pools[1].info.clone(),
-        };
+        offer_pool = pools[1].clone();
         ask_pool = pools[0].clone();
-
-        pair_info.asset1_volume = pair_info.asset1_volume.add(offer_asset.amount);
+        offer_index = 1;
     } else {
         return Err(StdError::generic_err("Wrong asset info is given"));
     }
 
-    store_pair_info(&mut deps.storage, &pair_info)?;
-
-    let pair_settings = query_pair_settings(
-        &deps,
-        &pair_info.factory.address,
-        &pair_info.factory.code_hash,
+    // pools before this swap, in asset_infos order
+    let pools_before = if offer_index == 0 {
+        [offer_pool.amount, ask_pool.amount]
+    } else {
+        [ask_pool.amount, offer_pool.amount]
+    };
+    update_price_cumulative(
+        &mut deps.storage,
+        &pair_info.curve,
+        pools_before,
+        env.block.time,
     )?;
 
-    let offer_amount = offer_asset.amount;
+    let pair_settings = query_pair_settings(&deps, &pair_info)?;
+    pair_settings.status.assert_swaps_allowed()?;
+
+    let offer_amount = match ask_amount {
+        Some(ask_amount) => {
+            let (offer_amount, _, _) = compute_offer_amount(
+                &pair_info.curve,
+                offer_index,
+                offer_pool.amount,
+                ask_pool.amount,
+                ask_amount,
+                pair_settings.swap_fee.commission_rate_nom.u128(),
+                pair_settings.swap_fee.commission_rate_denom.u128(),
+            )?;
+            if offer_amount > offer_asset.amount {
+                return Err(StdError::generic_err(format!(
+                    "Offer amount {} exceeds the maximum offer amount {}",
+                    offer_amount, offer_asset.amount
+                )));
+            }
+            offer_amount
+        }
+        None => offer_asset.amount,
+    };
+    let refund_amount = (offer_asset.amount - offer_amount)?;
+
+    if offer_index == 0 {
+        pair_info.asset0_volume = pair_info.asset0_volume.add(offer_amount);
+    } else {
+        pair_info.asset1_volume = pair_info.asset1_volume.add(offer_amount);
+    }
+    store_pair_info(&mut deps.storage, &pair_info)?;
+
     let (return_amount, spread_amount, commission_amount) = compute_swap(
+        &pair_info.curve,
+        offer_index,
         offer_pool.amount,
         ask_pool.amount,
         offer_amount,
         pair_settings.swap_fee.commission_rate_nom,
         pair_settings.swap_fee.commission_rate_denom,
     )?;
+    // the offer amount is rounded up, what it returns above the ask amount stays in the pool
+    let return_amount = ask_amount.unwrap_or(return_amount);
 
     // check max spread limit if exist
     assert_max_spread(
@@ -596,6 +1355,55 @@ pub fn try_swap<S: Storage, A: Api, Q: Querier>(
         spread_amount,
     )?;
 
+    // the protocol share of the commission stays in the pair until it is collected
+    let protocol_fee_amount = match &pair_settings.protocol_fee {
+        Some(protocol_fee) => protocol_fee.compute(commission_amount),
+        None => Uint128::zero(),
+    };
+    if !protocol_fee_amount.is_zero() {
+        let mut protocol_fees = read_protocol_fees(&deps.storage)?;
+        protocol_fees[1 - offer_index] = protocol_fees[1 - offer_index] + protocol_fee_amount;
+        store_protocol_fees(&mut deps.storage, &protocol_fees)?;
+    }
+    // the rest of the commission stays in the pool for the liquidity providers
+    let mut lp_fees = [Uint128::zero(); 2];
+    lp_fees[1 - offer_index] = (commission_amount - protocol_fee_amount)?;
+    let total_share = query_supply(
+        &deps,
+        &deps.api.human_address(&pair_info.liquidity_token)?,
+        &pair_info.token_code_hash,
+    )?;
+    accrue_fees(&mut deps.storage, lp_fees, total_share)?;
+
+    record_trade(
+        &mut deps.storage,
+        env.block.time,
+        offer_index,
+        offer_amount,
+        commission_amount,
+    )?;
+
+    let mut reserves = [Uint128::zero(); 2];
+    reserves[offer_index] = offer_pool.amount + offer_amount;
+    reserves[1 - offer_index] = (ask_pool.amount - (return_amount + protocol_fee_amount))?;
+    store_reserves(&mut deps.storage, &reserves)?;
+
+    // the swap raised the price of the ask asset, orders selling it may fill now
+    let (fills, order_msgs) = fill_orders(
+        deps,
+        &env,
+        &mut pair_info,
+        &pair_settings,
+        1 - offer_index,
+        total_share,
+    )?;
+    assert_price_band(
+        &deps.storage,
+        &pair_info.curve,
+        pair_settings.max_price_move,
+        env.block.height,
+    )?;
+
     let return_asset = Asset {
         info: ask_pool.info.clone(),
         amount: return_amount,
@@ -608,29 +1416,786 @@ pub fn try_swap<S: Storage, A: Api, Q: Querier>(
         to.clone().unwrap_or(sender.clone()),
     )?);
 
-    if let Some(data_endpoint) = pair_settings.swap_data_endpoint {
-        messages.push(data_endpoint.into_msg(
-            offer_asset.clone(),
+    if !refund_amount.is_zero() {
+        messages.push(
             Asset {
-                info: return_asset.info,
-                amount: return_amount + commission_amount,
-            },
-            to.unwrap_or(sender),
-        )?);
+                info: offer_pool.info.clone(),
+                amount: refund_amount,
+            }
+            .into_msg(&deps, env.contract.address.clone(), sender.clone())?,
+        );
     }
 
+    messages.extend(order_msgs);
+    messages.extend(swap_data_msgs(
+        deps,
+        &env,
+        &pair_info,
+        &pair_settings,
+        to.unwrap_or(sender),
+        Asset {
+            info: offer_asset.info.clone(),
+            amount: offer_amount,
+        },
+        &return_asset,
+        commission_amount,
+        spread_amount,
+    )?);
+    messages.extend(fill_swap_data_msgs(
+        deps,
+        &env,
+        &pair_info,
+        &pair_settings,
+        1 - offer_index,
+        &fills,
+    )?);
+
     // 1. send collateral token from the contract to a user
     // 2. send inactive commission to collector
     Ok(HandleResponse {
         messages,
         log: vec![
-            log("action", "swap"),
-            log("offer_asset", offer_asset.info.to_string()),
-            log("ask_asset", ask_pool.info.to_string()),
-            log("offer_amount", offer_amount.to_string()),
-            log("return_amount", return_amount.to_string()),
-            log("spread_amount", spread_amount.to_string()),
-            log("commission_amount", commission_amount.to_string()),
+            log("action", "swap"),
+            log("offer_asset", offer_asset.info.to_string()),
+            log("ask_asset", ask_pool.info.to_string()),
+            log("offer_amount", offer_amount.to_string()),
+            log("return_amount", return_amount.to_string()),
+            log("spread_amount", spread_amount.to_string()),
+            log("commission_amount", commission_amount.to_string()),
+            log("protocol_fee_amount", protocol_fee_amount.to_string()),
+            log("refund_amount", refund_amount.to_string()),
+            log("filled_orders", fills.len().to_string()),
+        ],
+        data: Some(to_binary(&SwapResponse {
+            offer_asset: Asset {
+                info: offer_pool.info,
+                amount: offer_amount,
+            },
+            return_asset,
+            spread_amount,
+            commission_amount,
+            protocol_fee_amount,
+            refund_amount,
+        })?),
+    })
+}
+
+/// Fills the limit orders selling the asset at `offer_index` the pools pay the price of,
+/// and adds them to the volume. Orders only fill while swaps are allowed. The messages pay
+/// the proceeds of the fills and the rest of the closed dust orders.
+fn fill_orders<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    pair_info: &mut PairInfoRaw,
+    pair_settings: &PairSettings,
+    offer_index: usize,
+    total_share: Uint128,
+) -> StdResult<(Vec<Fill>, Vec<CosmosMsg>)> {
+    let (fills, closed) = fill_limit_orders(
+        &mut deps.storage,
+        &pair_info.curve,
+        pair_settings,
+        offer_index,
+        total_share,
+        env.block.time,
+    )?;
+
+    let volume = fills
+        .iter()
+        .fold(Uint128::zero(), |volume, fill| volume + fill.amount);
+    if !volume.is_zero() {
+        if offer_index == 0 {
+            pair_info.asset0_volume = pair_info.asset0_volume.add(volume);
+        } else {
+            pair_info.asset1_volume = pair_info.asset1_volume.add(volume);
+        }
+        store_pair_info(&mut deps.storage, pair_info)?;
+    }
+
+    let mut messages: Vec<CosmosMsg> = vec![];
+    for fill in fills.iter() {
+        messages.push(payout_msg(
+            deps,
+            env,
+            pair_info,
+            1 - offer_index,
+            deps.api.human_address(&fill.owner)?,
+            fill.return_amount,
+        )?);
+    }
+    for order in closed.iter() {
+        messages.push(payout_msg(
+            deps,
+            env,
+            pair_info,
+            offer_index,
+            deps.api.human_address(&order.owner)?,
+            order.remaining,
+        )?);
+    }
+
+    Ok((fills, messages))
+}
+
+/// Pays out of the pair without calling the recipient back, e.g. so an order owner can't make
+/// the swap that filled or evicted its order fail
+fn payout_msg<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: &Env,
+    pair_info: &PairInfoRaw,
+    index: usize,
+    owner: HumanAddr,
+    amount: Uint128,
+) -> StdResult<CosmosMsg> {
+    match pair_info.asset_infos[index].to_normal(&deps)? {
+        AssetInfo::Token {
+            contract_addr,
+            token_code_hash,
+            ..
+        } => snip20::transfer_msg(owner, amount, None, 256, token_code_hash, contract_addr),
+        info => Asset { info, amount }.into_msg(deps, env.contract.address.clone(), owner),
+    }
+}
+
+/// Delivers the swap data of a swap against the pools to the endpoints, with the reserves
+/// after the swap and the orders it filled
+fn swap_data_msgs<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    pair_info: &PairInfoRaw,
+    pair_settings: &PairSettings,
+    account: HumanAddr,
+    asset_in: Asset,
+    return_asset: &Asset,
+    commission_amount: Uint128,
+    spread_amount: Uint128,
+) -> StdResult<Vec<CosmosMsg>> {
+    let reserves: [Asset; 2] = read_pools(&deps, pair_info)?;
+
+    deliver_swap_data(
+        &mut deps.storage,
+        env,
+        &pair_settings.swap_data_endpoints,
+        SwapData {
+            pair: env.contract.address.clone(),
+            account,
+            asset_in,
+            asset_out: Asset {
+                info: return_asset.info.clone(),
+                amount: return_asset.amount + commission_amount,
+            },
+            commission: Asset {
+                info: return_asset.info.clone(),
+                amount: commission_amount,
+            },
+            spread_amount,
+            reserves,
+        },
+    )
+}
+
+/// Delivers the swap data of the orders filled after a swap, each as a swap of its owner
+/// selling the asset at `offer_index`
+fn fill_swap_data_msgs<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    pair_info: &PairInfoRaw,
+    pair_settings: &PairSettings,
+    offer_index: usize,
+    fills: &[Fill],
+) -> StdResult<Vec<CosmosMsg>> {
+    let asset_infos = [
+        pair_info.asset_infos[0].to_normal(&deps)?,
+        pair_info.asset_infos[1].to_normal(&deps)?,
+    ];
+    let ask_index = 1 - offer_index;
+
+    let mut messages: Vec<CosmosMsg> = vec![];
+    for fill in fills.iter() {
+        let asset = |index: usize, amount: Uint128| Asset {
+            info: asset_infos[index].clone(),
+            amount,
+        };
+        messages.extend(deliver_swap_data(
+            &mut deps.storage,
+            env,
+            &pair_settings.swap_data_endpoints,
+            SwapData {
+                pair: env.contract.address.clone(),
+                account: deps.api.human_address(&fill.owner)?,
+                asset_in: asset(offer_index, fill.amount),
+                asset_out: asset(ask_index, fill.return_amount + fill.commission_amount),
+                commission: asset(ask_index, fill.commission_amount),
+                spread_amount: fill.spread_amount,
+                reserves: [asset(0, fill.reserves[0]), asset(1, fill.reserves[1])],
+            },
+        )?);
+    }
+
+    Ok(messages)
+}
+
+/// Escrows the offer asset in the pair. An order the pools already pay the price of is
+/// filled right away.
+pub fn try_place_limit_order<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    sender: HumanAddr,
+    offer_asset: Asset,
+    price: Decimal,
+) -> HandleResult {
+    offer_asset.assert_sent_native_token_balance(&env)?;
+
+    let mut pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
+    let pools: [Asset; 2] = read_pools(&deps, &pair_info)?;
+    let offer_index = pools
+        .iter()
+        .position(|pool| offer_asset.info.equal(&pool.info))
+        .ok_or_else(|| StdError::generic_err("Wrong asset info is given"))?;
+
+    let pair_settings = query_pair_settings(&deps, &pair_info)?;
+    pair_settings.status.assert_swaps_allowed()?;
+
+    let (order_id, evicted) = place_limit_order(
+        &mut deps.storage,
+        &deps.api.canonical_address(&sender)?,
+        offer_index,
+        offer_asset.amount,
+        price,
+    )?;
+
+    // the orders pushed out of a full account or book get their unsold amount back
+    let mut messages: Vec<CosmosMsg> = vec![];
+    let mut log_evicted = vec![];
+    for evicted in evicted.iter() {
+        if !evicted.remaining.is_zero() {
+            messages.push(payout_msg(
+                deps,
+                &env,
+                &pair_info,
+                evicted.offer_index as usize,
+                deps.api.human_address(&evicted.owner)?,
+                evicted.remaining,
+            )?);
+        }
+        log_evicted.push(log("evicted_remaining", evicted.remaining.to_string()));
+    }
+
+    update_price_cumulative(
+        &mut deps.storage,
+        &pair_info.curve,
+        [pools[0].amount, pools[1].amount],
+        env.block.time,
+    )?;
+    let total_share = query_supply(
+        &deps,
+        &deps.api.human_address(&pair_info.liquidity_token)?,
+        &pair_info.token_code_hash,
+    )?;
+    let (fills, order_msgs) = fill_orders(
+        deps,
+        &env,
+        &mut pair_info,
+        &pair_settings,
+        offer_index,
+        total_share,
+    )?;
+    assert_price_band(
+        &deps.storage,
+        &pair_info.curve,
+        pair_settings.max_price_move,
+        env.block.height,
+    )?;
+
+    messages.extend(order_msgs);
+    messages.extend(fill_swap_data_msgs(
+        deps,
+        &env,
+        &pair_info,
+        &pair_settings,
+        offer_index,
+        &fills,
+    )?);
+
+    let mut logs = vec![
+        log("action", "place_limit_order"),
+        log("order_id", order_id.to_string()),
+        log("filled_orders", fills.len().to_string()),
+    ];
+    logs.extend(log_evicted);
+
+    Ok(HandleResponse {
+        messages,
+        log: logs,
+        data: None,
+    })
+}
+
+pub fn try_cancel_limit_order<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    order_id: u64,
+) -> HandleResult {
+    let order = cancel_limit_order(
+        &mut deps.storage,
+        &deps.api.canonical_address(&env.message.sender)?,
+        order_id,
+    )?;
+
+    let pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
+    let mut messages: Vec<CosmosMsg> = vec![];
+    if !order.remaining.is_zero() {
+        messages.push(
+            Asset {
+                info: pair_info.asset_infos[order.offer_index as usize].to_normal(&deps)?,
+                amount: order.remaining,
+            }
+            .into_msg(
+                &deps,
+                env.contract.address.clone(),
+                env.message.sender.clone(),
+            )?,
+        );
+    }
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![
+            log("action", "cancel_limit_order"),
+            log("order_id", order_id.to_string()),
+            log("remaining", order.remaining.to_string()),
+            log("proceeds", order.proceeds.to_string()),
+        ],
+        data: None,
+    })
+}
+
+/// Anyone can execute it, the fees always go to the collector set in the factory
+pub fn try_collect_protocol_fees<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> HandleResult {
+    let pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
+    let pair_settings = query_pair_settings(&deps, &pair_info)?;
+    let collector = match pair_settings.protocol_fee {
+        Some(protocol_fee) => protocol_fee.collector,
+        None => return Err(StdError::generic_err("Protocol fee collector is not set")),
+    };
+
+    let protocol_fees = read_protocol_fees(&deps.storage)?;
+    store_protocol_fees(&mut deps.storage, &[Uint128::zero(), Uint128::zero()])?;
+
+    let mut messages: Vec<CosmosMsg> = vec![];
+    let mut collected: Vec<String> = vec![];
+    for (asset_info, amount) in pair_info.asset_infos.iter().zip(protocol_fees.iter()) {
+        if amount.is_zero() {
+            continue;
+        }
+
+        let asset = Asset {
+            info: asset_info.to_normal(&deps)?,
+            amount: *amount,
+        };
+        collected.push(asset.to_string());
+        messages.push(asset.into_msg(&deps, env.contract.address.clone(), collector.clone())?);
+    }
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![
+            log("action", "collect_protocol_fees"),
+            log("collector", collector.as_str()),
+            log("collected", collected.join(", ")),
+        ],
+        data: None,
+    })
+}
+
+/// Anyone can execute it, sets the reserves to the actual balances
+pub fn try_sync<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> HandleResult {
+    let pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
+    let pools: [Asset; 2] = read_pools(&deps, &pair_info)?;
+    let balances: [Asset; 2] = query_balances(&deps, &pair_info, &env.contract.address)?;
+
+    update_price_cumulative(
+        &mut deps.storage,
+        &pair_info.curve,
+        [pools[0].amount, pools[1].amount],
+        env.block.time,
+    )?;
+    store_reserves(&mut deps.storage, &[balances[0].amount, balances[1].amount])?;
+    // donations move the price like swaps
+    let pair_settings = query_pair_settings(&deps, &pair_info)?;
+    assert_price_band(
+        &deps.storage,
+        &pair_info.curve,
+        pair_settings.max_price_move,
+        env.block.height,
+    )?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "sync"),
+            log("reserves", format!("{}, {}", balances[0], balances[1])),
+        ],
+        data: None,
+    })
+}
+
+/// Anyone can execute it, sends the balances above the reserves to `to`
+pub fn try_skim<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    to: HumanAddr,
+) -> HandleResult {
+    let pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
+    let pools: [Asset; 2] = read_pools(&deps, &pair_info)?;
+    let balances: [Asset; 2] = query_balances(&deps, &pair_info, &env.contract.address)?;
+
+    let mut messages: Vec<CosmosMsg> = vec![];
+    let mut skimmed: Vec<String> = vec![];
+    for (pool, balance) in pools.iter().zip(balances.iter()) {
+        let excess = Uint128(balance.amount.u128().saturating_sub(pool.amount.u128()));
+        if excess.is_zero() {
+            continue;
+        }
+
+        let asset = Asset {
+            info: pool.info.clone(),
+            amount: excess,
+        };
+        skimmed.push(asset.to_string());
+        messages.push(asset.into_msg(&deps, env.contract.address.clone(), to.clone())?);
+    }
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![
+            log("action", "skim"),
+            log("to", to.as_str()),
+            log("skimmed", skimmed.join(", ")),
+        ],
+        data: None,
+    })
+}
+
+pub fn try_flash_swap<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    asset: Asset,
+    receiver: HumanAddr,
+    receiver_code_hash: String,
+    msg: Option<Binary>,
+) -> HandleResult {
+    // sent funds would be counted as part of the pool before the loan
+    if !env.message.sent_funds.is_empty() {
+        return Err(StdError::generic_err(
+            "Flash swaps can't be sent funds, pay back in the callback instead",
+        ));
+    }
+
+    let pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
+    query_pair_settings(&deps, &pair_info)?
+        .status
+        .assert_swaps_allowed()?;
+    let pools: [Asset; 2] = read_pools(&deps, &pair_info)?;
+
+    let mut amounts_out = [Uint128::zero(); 2];
+    let index = pools
+        .iter()
+        .position(|pool| asset.info.equal(&pool.info))
+        .ok_or_else(|| StdError::generic_err("Wrong asset info is given"))?;
+    if asset.amount.is_zero() || asset.amount > pools[index].amount {
+        return Err(StdError::generic_err(format!(
+            "Flash swap amount must be between 1 and the reserve {}",
+            pools[index].amount
+        )));
+    }
+    amounts_out[index] = asset.amount;
+
+    let pools_before = [pools[0].amount, pools[1].amount];
+    update_price_cumulative(
+        &mut deps.storage,
+        &pair_info.curve,
+        pools_before,
+        env.block.time,
+    )?;
+    store_flash_swap(
+        &mut deps.storage,
+        &FlashSwap {
+            pools_before,
+            amounts_out,
+        },
+    )?;
+
+    let lent_asset = Asset {
+        info: pools[index].info.clone(),
+        amount: asset.amount,
+    };
+
+    Ok(HandleResponse {
+        messages: vec![
+            lent_asset
+                .clone()
+                .into_msg(&deps, env.contract.address.clone(), receiver.clone())?,
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: receiver.clone(),
+                callback_code_hash: receiver_code_hash,
+                msg: to_binary(&FlashSwapReceiverMsg::FlashSwapCallback {
+                    sender: env.message.sender,
+                    asset: lent_asset.clone(),
+                    msg,
+                })?,
+                send: vec![],
+            }),
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: env.contract.address,
+                callback_code_hash: env.contract_code_hash,
+                msg: to_binary(&HandleMsg::FinalizeFlashSwap {})?,
+                send: vec![],
+            }),
+        ],
+        log: vec![
+            log("action", "flash_swap"),
+            log("asset", lent_asset.to_string()),
+            log("receiver", receiver.as_str()),
+        ],
+        data: None,
+    })
+}
+
+// Only the pair itself can execute it, after the receiver callback
+pub fn try_finalize_flash_swap<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> HandleResult {
+    if env.message.sender != env.contract.address {
+        return Err(StdError::unauthorized());
+    }
+    let flash_swap = read_flash_swap(&deps.storage)?
+        .ok_or_else(|| StdError::generic_err("No flash swap in progress"))?;
+
+    let pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
+    // the repayment is whatever the balances gained, like in Uniswap v2
+    let pools: [Asset; 2] = query_balances(&deps, &pair_info, &env.contract.address)?;
+    let pair_settings = query_pair_settings(&deps, &pair_info)?;
+
+    let fees = assert_flash_swap_invariant(
+        &pair_info.curve,
+        flash_swap.pools_before,
+        flash_swap.amounts_out,
+        [pools[0].amount, pools[1].amount],
+        pair_settings.swap_fee.commission_rate_nom,
+        pair_settings.swap_fee.commission_rate_denom,
+    )?;
+
+    // the protocol share of the fees stays in the pair until it is collected, like swaps
+    let mut reserves = [pools[0].amount, pools[1].amount];
+    let mut lp_fees = fees;
+    if let Some(protocol_fee) = &pair_settings.protocol_fee {
+        let mut protocol_fees = read_protocol_fees(&deps.storage)?;
+        for i in 0..2 {
+            let protocol_fee_amount = protocol_fee.compute(fees[i]);
+            protocol_fees[i] = protocol_fees[i] + protocol_fee_amount;
+            reserves[i] = (reserves[i] - protocol_fee_amount)?;
+            lp_fees[i] = (lp_fees[i] - protocol_fee_amount)?;
+        }
+        store_protocol_fees(&mut deps.storage, &protocol_fees)?;
+    }
+    store_reserves(&mut deps.storage, &reserves)?;
+    assert_price_band(
+        &deps.storage,
+        &pair_info.curve,
+        pair_settings.max_price_move,
+        env.block.height,
+    )?;
+    let total_share = query_supply(
+        &deps,
+        &deps.api.human_address(&pair_info.liquidity_token)?,
+        &pair_info.token_code_hash,
+    )?;
+    accrue_fees(&mut deps.storage, lp_fees, total_share)?;
+    remove_flash_swap(&mut deps.storage);
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "finalize_flash_swap"),
+            log(
+                "fees",
+                format!("{}{}, {}{}", fees[0], pools[0].info, fees[1], pools[1].info),
+            ),
+        ],
+        data: None,
+    })
+}
+
+/// Hands the reserves over to the pair replacing this one, with the LP supply they back, and
+/// stops this pair. The factory executes it when the new pair registers.
+pub fn try_migrate_reserves<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    new_pair: HumanAddr,
+    new_pair_code_hash: String,
+) -> HandleResult {
+    let pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
+    if env.message.sender != pair_info.factory.address {
+        return Err(StdError::unauthorized());
+    }
+
+    let reserves = read_reserves(&deps.storage)?;
+    let liquidity_token = deps.api.human_address(&pair_info.liquidity_token)?;
+    let total_share = query_supply(&deps, &liquidity_token, &pair_info.token_code_hash)?;
+
+    let mut messages: Vec<CosmosMsg> = vec![];
+    for (i, reserve) in reserves.iter().enumerate() {
+        if !reserve.is_zero() {
+            messages.push(payout_msg(
+                &deps,
+                &env,
+                &pair_info,
+                i,
+                new_pair.clone(),
+                *reserve,
+            )?);
+        }
+    }
+    messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: new_pair.clone(),
+        callback_code_hash: new_pair_code_hash,
+        msg: to_binary(&HandleMsg::FinalizeMigration {
+            reserves,
+            total_share,
+        })?,
+        send: vec![],
+    }));
+
+    store_reserves(&mut deps.storage, &[Uint128::zero(), Uint128::zero()])?;
+    store_migrated_to(&mut deps.storage, &new_pair)?;
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![
+            log("action", "migrate_reserves"),
+            log("new_pair", new_pair.as_str()),
+            log(
+                "reserves",
+                format!(
+                    "{}{}, {}{}",
+                    reserves[0],
+                    pair_info.asset_infos[0].to_normal(&deps)?,
+                    reserves[1],
+                    pair_info.asset_infos[1].to_normal(&deps)?
+                ),
+            ),
+            log("total_share", total_share),
+        ],
+        data: None,
+    })
+}
+
+/// Takes over the reserves the replaced pair sent and mints as many LP tokens as it had to
+/// this pair, they are handed out 1:1 for its LP tokens by `try_migrate_liquidity`
+pub fn try_finalize_migration<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    reserves: [Uint128; 2],
+    total_share: Uint128,
+) -> HandleResult {
+    match read_migrate_from(&deps.storage)? {
+        Some(migrate_from) if migrate_from.pair == env.message.sender => {}
+        _ => return Err(StdError::unauthorized()),
+    }
+
+    let pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
+    let liquidity_token = deps.api.human_address(&pair_info.liquidity_token)?;
+    if !query_supply(&deps, &liquidity_token, &pair_info.token_code_hash)?.is_zero() {
+        return Err(StdError::generic_err("Pair already has liquidity"));
+    }
+
+    update_price_cumulative(
+        &mut deps.storage,
+        &pair_info.curve,
+        [Uint128::zero(), Uint128::zero()],
+        env.block.time,
+    )?;
+    store_reserves(&mut deps.storage, &reserves)?;
+
+    let mut messages: Vec<CosmosMsg> = vec![];
+    if !total_share.is_zero() {
+        messages.push(snip20::mint_msg(
+            env.contract.address.clone(),
+            total_share,
+            None,
+            256,
+            pair_info.token_code_hash.clone(),
+            liquidity_token,
+        )?);
+    }
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![
+            log("action", "finalize_migration"),
+            log("migrated_from", env.message.sender.as_str()),
+            log("total_share", total_share),
+        ],
+        data: None,
+    })
+}
+
+/// Burns LP tokens of the replaced pair and sends as many LP tokens of this pair from the
+/// supply minted in `try_finalize_migration`
+pub fn try_migrate_liquidity<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    migrate_from: MigrateFrom,
+    recipient: HumanAddr,
+    amount: Uint128,
+) -> HandleResult {
+    let pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
+    let liquidity_token = deps.api.human_address(&pair_info.liquidity_token)?;
+    let total_share = query_supply(&deps, &liquidity_token, &pair_info.token_code_hash)?;
+
+    // the migrated share counts as a deposit of the assets it is worth
+    let reserves = read_reserves(&deps.storage)?;
+    let deposits = [
+        reserves[0].multiply_ratio(amount, total_share),
+        reserves[1].multiply_ratio(amount, total_share),
+    ];
+    record_deposit(
+        &mut deps.storage,
+        &deps.api.canonical_address(&recipient)?,
+        deposits,
+        amount,
+    )?;
+
+    Ok(HandleResponse {
+        messages: vec![
+            snip20::burn_msg(
+                amount,
+                None,
+                256,
+                migrate_from.token_code_hash,
+                migrate_from.liquidity_token,
+            )?,
+            snip20::transfer_msg(
+                recipient.clone(),
+                amount,
+                None,
+                256,
+                pair_info.token_code_hash,
+                liquidity_token,
+            )?,
+        ],
+        log: vec![
+            log("action", "migrate_liquidity"),
+            log("migrated_from", migrate_from.pair.as_str()),
+            log("recipient", recipient.as_str()),
+            log("share", amount),
         ],
         data: None,
     })
@@ -647,7 +2212,250 @@ pub fn query<S: Storage, A: Api, Q: Querier>(
         QueryMsg::ReverseSimulation { ask_asset } => {
             to_binary(&query_reverse_simulation(&deps, ask_asset)?)
         }
+        QueryMsg::CumulativePrices {} => to_binary(&query_cumulative_prices(&deps)?),
+        QueryMsg::Twap {
+            start_time,
+            end_time,
+        } => to_binary(&query_twap(&deps, start_time, end_time)?),
+        QueryMsg::ProtocolFees {} => to_binary(&query_protocol_fees(&deps)?),
+        QueryMsg::Status {} => to_binary(&query_status(&deps)?),
+        QueryMsg::SwapFee {} => to_binary(&query_swap_fee(&deps)?),
+        QueryMsg::CircuitBreaker {} => to_binary(&query_circuit_breaker(&deps)?),
+        QueryMsg::Migration {} => to_binary(&query_migration(&deps)?),
+        QueryMsg::Stats {
+            interval,
+            start_after,
+            limit,
+        } => to_binary(&query_stats(&deps, interval, start_after, limit)?),
+        QueryMsg::Ledger { address, key } => to_binary(&query_ledger(&deps, address, key)?),
+        QueryMsg::LimitOrders { address, key } => {
+            to_binary(&query_limit_orders(&deps, address, key)?)
+        }
+        QueryMsg::WithPermit { permit, query } => {
+            let pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
+            let contract_addr = deps.api.human_address(&pair_info.contract_addr)?;
+            let account = validate_permit(&deps, &permit, &contract_addr, Permission::Owner)?;
+            let account = deps.api.canonical_address(&account)?;
+
+            match query {
+                QueryWithPermit::Ledger {} => to_binary(&ledger_of(&deps, &account)?),
+                QueryWithPermit::LimitOrders {} => to_binary(&limit_orders_of(&deps, &account)?),
+            }
+        }
+        QueryMsg::SwapData {
+            endpoint,
+            key,
+            start_after,
+            limit,
+        } => {
+            let pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
+            let pair_settings = query_pair_settings(&deps, &pair_info)?;
+            to_binary(&query_swap_data(
+                &deps.storage,
+                &pair_settings.swap_data_endpoints,
+                &endpoint,
+                &key,
+                start_after,
+                limit,
+            )?)
+        }
+    }
+}
+
+pub fn query_limit_orders<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: HumanAddr,
+    key: String,
+) -> StdResult<LimitOrdersResponse> {
+    let owner = deps.api.canonical_address(&address)?;
+    assert_viewing_key(&deps.storage, &owner, &key)?;
+
+    limit_orders_of(deps, &owner)
+}
+
+fn limit_orders_of<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    owner: &CanonicalAddr,
+) -> StdResult<LimitOrdersResponse> {
+    let pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
+    let mut orders = vec![];
+    for order_id in read_owner_orders(&deps.storage, owner)? {
+        let order = read_limit_order(&deps.storage, order_id)?
+            .ok_or_else(|| StdError::generic_err("Limit order not found"))?;
+        let offer_index = order.offer_index as usize;
+
+        orders.push(LimitOrderResponse {
+            order_id,
+            offer_asset: Asset {
+                info: pair_info.asset_infos[offer_index].to_normal(&deps)?,
+                amount: order.offer_amount,
+            },
+            price: order.price,
+            remaining: order.remaining,
+            proceeds: Asset {
+                info: pair_info.asset_infos[1 - offer_index].to_normal(&deps)?,
+                amount: order.proceeds,
+            },
+        });
     }
+
+    Ok(LimitOrdersResponse { orders })
+}
+
+pub fn query_ledger<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: HumanAddr,
+    key: String,
+) -> StdResult<LedgerResponse> {
+    let account = deps.api.canonical_address(&address)?;
+    assert_viewing_key(&deps.storage, &account, &key)?;
+
+    ledger_of(deps, &account)
+}
+
+fn ledger_of<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    account: &CanonicalAddr,
+) -> StdResult<LedgerResponse> {
+    let pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
+    let pools: [Asset; 2] = read_pools(&deps, &pair_info)?;
+    let total_share = query_supply(
+        &deps,
+        &deps.api.human_address(&pair_info.liquidity_token)?,
+        &pair_info.token_code_hash,
+    )?;
+
+    let position = compute_position(
+        &deps.storage,
+        account,
+        &pair_info.curve,
+        [pools[0].amount, pools[1].amount],
+        total_share,
+    )?;
+    let to_assets = |amounts: [Uint128; 2]| -> [Asset; 2] {
+        [
+            Asset {
+                info: pools[0].info.clone(),
+                amount: amounts[0],
+            },
+            Asset {
+                info: pools[1].info.clone(),
+                amount: amounts[1],
+            },
+        ]
+    };
+
+    Ok(LedgerResponse {
+        deposited: to_assets(position.entry.deposited),
+        withdrawn: to_assets(position.entry.withdrawn),
+        share: position.entry.share,
+        cost_basis: to_assets(position.entry.cost_basis),
+        current_value: to_assets(position.current_value),
+        fees_earned: to_assets(position.entry.fees_earned),
+        hodl_value: position.hodl_value,
+        position_value: position.position_value,
+        impermanent_loss: position.impermanent_loss,
+    })
+}
+
+const DEFAULT_STATS_LIMIT: u32 = 24;
+const MAX_STATS_LIMIT: u32 = 168;
+
+pub fn query_stats<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    interval: StatsInterval,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<StatsResponse> {
+    let pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
+    let asset_infos = [
+        pair_info.asset_infos[0].to_normal(&deps)?,
+        pair_info.asset_infos[1].to_normal(&deps)?,
+    ];
+    let to_assets = |amounts: [Uint128; 2]| -> [Asset; 2] {
+        [
+            Asset {
+                info: asset_infos[0].clone(),
+                amount: amounts[0],
+            },
+            Asset {
+                info: asset_infos[1].clone(),
+                amount: amounts[1],
+            },
+        ]
+    };
+
+    let limit = limit.unwrap_or(DEFAULT_STATS_LIMIT).min(MAX_STATS_LIMIT) as usize;
+    let buckets = read_stats(&deps.storage, interval, start_after, limit)?
+        .into_iter()
+        .map(|bucket| StatsBucketResponse {
+            start_time: bucket.start_time,
+            volume: to_assets(bucket.volume),
+            trade_count: bucket.trade_count,
+            commission: to_assets(bucket.commission),
+        })
+        .collect();
+
+    Ok(StatsResponse { buckets })
+}
+
+pub fn query_migration<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<MigrationResponse> {
+    let migrate_from = read_migrate_from(&deps.storage)?;
+
+    Ok(MigrationResponse {
+        old_pair: migrate_from.as_ref().map(|m| m.pair.clone()),
+        old_liquidity_token: migrate_from.map(|m| m.liquidity_token),
+        new_pair: read_migrated_to(&deps.storage)?,
+    })
+}
+
+pub fn query_swap_fee<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<SwapFeeResponse> {
+    let pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
+    let pair_settings = query_pair_settings(&deps, &pair_info)?;
+
+    Ok(SwapFeeResponse {
+        swap_fee: pair_settings.swap_fee,
+        dynamic_fee: pair_settings.dynamic_fee,
+        volatility: read_average_change(&deps.storage)?,
+    })
+}
+
+pub fn query_circuit_breaker<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<CircuitBreakerResponse> {
+    let pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
+    let pair_settings = query_pair_settings(&deps, &pair_info)?;
+    let (block_price, price) = read_block_prices(&deps.storage, &pair_info.curve)?;
+    let price_band = match (block_price, pair_settings.max_price_move) {
+        (Some(block_price), Some(max_price_move)) => {
+            Some(compute_price_band(block_price, max_price_move)?)
+        }
+        _ => None,
+    };
+
+    Ok(CircuitBreakerResponse {
+        max_price_move: pair_settings.max_price_move,
+        block_time: read_price_cumulative(&deps.storage)?.block_time_last,
+        block_price,
+        price,
+        min_price: price_band.map(|(min_price, _)| min_price),
+        max_price: price_band.map(|(_, max_price)| max_price),
+    })
+}
+
+pub fn query_status<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<StatusResponse> {
+    let pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
+    let pair_settings = query_pair_settings(&deps, &pair_info)?;
+
+    Ok(StatusResponse {
+        status: pair_settings.status,
+    })
 }
 
 pub fn query_pair_info<S: Storage, A: Api, Q: Querier>(
@@ -661,8 +2469,7 @@ pub fn query_pool<S: Storage, A: Api, Q: Querier>(
     deps: &Extern<S, A, Q>,
 ) -> StdResult<PoolResponse> {
     let pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
-    let contract_addr = deps.api.human_address(&pair_info.contract_addr)?;
-    let assets: [Asset; 2] = pair_info.query_pools(&deps, &contract_addr)?;
+    let assets: [Asset; 2] = read_pools(&deps, &pair_info)?;
     let total_share: Uint128 = query_supply(
         &deps,
         &deps.api.human_address(&pair_info.liquidity_token)?,
@@ -683,30 +2490,30 @@ pub fn query_simulation<S: Storage, A: Api, Q: Querier>(
 ) -> StdResult<SimulationResponse> {
     let pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
 
-    let contract_addr = deps.api.human_address(&pair_info.contract_addr)?;
-    let pools: [Asset; 2] = pair_info.query_pools(&deps, &contract_addr)?;
+    let pools: [Asset; 2] = read_pools(&deps, &pair_info)?;
 
     let offer_pool: Asset;
     let ask_pool: Asset;
+    let offer_index: usize;
     if offer_asset.info.equal(&pools[0].info) {
         offer_pool = pools[0].clone();
         ask_pool = pools[1].clone();
+        offer_index = 0;
     } else if offer_asset.info.equal(&pools[1].info) {
         offer_pool = pools[1].clone();
         ask_pool = pools[0].clone();
+        offer_index = 1;
     } else {
         return Err(StdError::generic_err(
             "Given offer asset is not belong to pairs",
         ));
     }
 
-    let pair_settings = query_pair_settings(
-        &deps,
-        &pair_info.factory.address,
-        &pair_info.factory.code_hash,
-    )?;
+    let pair_settings = query_pair_settings(&deps, &pair_info)?;
 
     let (return_amount, spread_amount, commission_amount) = compute_swap(
+        &pair_info.curve,
+        offer_index,
         offer_pool.amount,
         ask_pool.amount,
         offer_asset.amount,
@@ -727,30 +2534,30 @@ pub fn query_reverse_simulation<S: Storage, A: Api, Q: Querier>(
 ) -> StdResult<ReverseSimulationResponse> {
     let pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
 
-    let contract_addr = deps.api.human_address(&pair_info.contract_addr)?;
-    let pools: [Asset; 2] = pair_info.query_pools(&deps, &contract_addr)?;
+    let pools: [Asset; 2] = read_pools(&deps, &pair_info)?;
 
     let offer_pool: Asset;
     let ask_pool: Asset;
+    let offer_index: usize;
     if ask_asset.info.equal(&pools[0].info) {
         ask_pool = pools[0].clone();
         offer_pool = pools[1].clone();
+        offer_index = 1;
     } else if ask_asset.info.equal(&pools[1].info) {
         ask_pool = pools[1].clone();
         offer_pool = pools[0].clone();
+        offer_index = 0;
     } else {
         return Err(StdError::generic_err(
             "Given ask asset is not blong to pairs",
         ));
     }
 
-    let pair_settings = query_pair_settings(
-        &deps,
-        &pair_info.factory.address,
-        &pair_info.factory.code_hash,
-    )?;
+    let pair_settings = query_pair_settings(&deps, &pair_info)?;
 
     let (offer_amount, spread_amount, commission_amount) = compute_offer_amount(
+        &pair_info.curve,
+        offer_index,
         offer_pool.amount,
         ask_pool.amount,
         ask_asset.amount,
@@ -765,7 +2572,217 @@ pub fn query_reverse_simulation<S: Storage, A: Api, Q: Querier>(
     })
 }
 
-fn compute_swap(
+pub fn query_cumulative_prices<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<CumulativePricesResponse> {
+    let cumulative = read_price_cumulative(&deps.storage)?;
+
+    Ok(CumulativePricesResponse {
+        price0_cumulative_last: cumulative.price0_cumulative_last,
+        price1_cumulative_last: cumulative.price1_cumulative_last,
+        block_time_last: cumulative.block_time_last,
+    })
+}
+
+pub fn query_twap<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    start_time: u64,
+    end_time: u64,
+) -> StdResult<TwapResponse> {
+    let pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
+
+    let pools: [Asset; 2] = read_pools(&deps, &pair_info)?;
+
+    let (price0_average, price1_average) = compute_twap(
+        &deps.storage,
+        &pair_info.curve,
+        [pools[0].amount, pools[1].amount],
+        start_time,
+        end_time,
+    )?;
+
+    Ok(TwapResponse {
+        price0_average,
+        price1_average,
+    })
+}
+
+pub fn query_protocol_fees<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<ProtocolFeesResponse> {
+    let pair_info: PairInfoRaw = read_pair_info(&deps.storage)?;
+    let protocol_fees = read_protocol_fees(&deps.storage)?;
+
+    Ok(ProtocolFeesResponse {
+        assets: [
+            Asset {
+                info: pair_info.asset_infos[0].to_normal(&deps)?,
+                amount: protocol_fees[0],
+            },
+            Asset {
+                info: pair_info.asset_infos[1].to_normal(&deps)?,
+                amount: protocol_fees[1],
+            },
+        ],
+    })
+}
+
+/// Pools as accounted by the pair, aligned with `asset_infos`
+fn read_pools<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    pair_info: &PairInfoRaw,
+) -> StdResult<[Asset; 2]> {
+    let reserves = read_reserves(&deps.storage)?;
+
+    Ok([
+        Asset {
+            info: pair_info.asset_infos[0].to_normal(&deps)?,
+            amount: reserves[0],
+        },
+        Asset {
+            info: pair_info.asset_infos[1].to_normal(&deps)?,
+            amount: reserves[1],
+        },
+    ])
+}
+
+/// Actual balances of the pair without the protocol fees that accrued but were not collected yet
+/// and the assets held for limit orders
+fn query_balances<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    pair_info: &PairInfoRaw,
+    contract_addr: &HumanAddr,
+) -> StdResult<[Asset; 2]> {
+    let mut pools: [Asset; 2] = pair_info.query_pools(deps, contract_addr)?;
+    let protocol_fees = read_protocol_fees(&deps.storage)?;
+    let order_escrow = read_order_escrow(&deps.storage)?;
+    for (i, pool) in pools.iter_mut().enumerate() {
+        pool.amount = Uint128(
+            pool.amount
+                .u128()
+                .saturating_sub(protocol_fees[i].u128())
+                .saturating_sub(order_escrow[i].u128()),
+        );
+    }
+
+    Ok(pools)
+}
+
+/// Dispatches to the swap math of the pair's curve.
+/// `offer_index` is the position of the offer asset in the pair's `asset_infos`.
+pub fn compute_swap(
+    curve: &CurveType,
+    offer_index: usize,
+    offer_pool: Uint128,
+    ask_pool: Uint128,
+    offer_amount: Uint128,
+    commission_rate_nom: Uint128,
+    commission_rate_denom: Uint128,
+) -> StdResult<(Uint128, Uint128, Uint128)> {
+    match curve {
+        CurveType::ConstantProduct {} => compute_constant_product_swap(
+            offer_pool,
+            ask_pool,
+            offer_amount,
+            commission_rate_nom,
+            commission_rate_denom,
+        ),
+        CurveType::StableSwap { amp } => compute_stable_swap(
+            *amp,
+            offer_pool,
+            ask_pool,
+            offer_amount,
+            commission_rate_nom,
+            commission_rate_denom,
+        ),
+        CurveType::Weighted { weights } => compute_weighted_swap(
+            weights[offer_index],
+            weights[1 - offer_index],
+            offer_pool,
+            ask_pool,
+            offer_amount,
+            commission_rate_nom,
+            commission_rate_denom,
+        ),
+    }
+}
+
+/// Dispatches to the reverse swap math of the pair's curve.
+/// `offer_index` is the position of the offer asset in the pair's `asset_infos`.
+pub fn compute_offer_amount(
+    curve: &CurveType,
+    offer_index: usize,
+    offer_pool: Uint128,
+    ask_pool: Uint128,
+    ask_amount: Uint128,
+    commission_rate_nom: u128,
+    commission_rate_denom: u128,
+) -> StdResult<(Uint128, Uint128, Uint128)> {
+    let (offer_amount, _, _) = match curve {
+        CurveType::ConstantProduct {} => {
+            // exact, no correction needed
+            return compute_constant_product_offer_amount(
+                offer_pool,
+                ask_pool,
+                ask_amount,
+                commission_rate_nom,
+                commission_rate_denom,
+            );
+        }
+        CurveType::StableSwap { amp } => compute_stable_offer_amount(
+            *amp,
+            offer_pool,
+            ask_pool,
+            ask_amount,
+            commission_rate_nom,
+            commission_rate_denom,
+        ),
+        CurveType::Weighted { weights } => compute_weighted_offer_amount(
+            weights[offer_index],
+            weights[1 - offer_index],
+            offer_pool,
+            ask_pool,
+            ask_amount,
+            commission_rate_nom,
+            commission_rate_denom,
+        ),
+    }?;
+
+    // the stable and weighted math approximates, so raise the offer amount until the forward
+    // path returns at least the ask amount, and report the forward spread and commission
+    let mut offer_amount = offer_amount;
+    let mut last_raise = Uint128::zero();
+    for _ in 0..MAX_OFFER_CORRECTIONS {
+        let (return_amount, spread_amount, commission_amount) = compute_swap(
+            curve,
+            offer_index,
+            offer_pool,
+            ask_pool,
+            offer_amount,
+            Uint128(commission_rate_nom),
+            Uint128(commission_rate_denom),
+        )?;
+        if return_amount >= ask_amount {
+            return Ok((offer_amount, spread_amount, commission_amount));
+        }
+
+        let shortfall = (ask_amount - return_amount)?;
+        let raise = if return_amount.is_zero() {
+            shortfall
+        } else {
+            offer_amount.multiply_ratio(shortfall, return_amount)
+        } + Uint128(1);
+        // the fixed point forward math moves in steps on large pools, so a raise below a
+        // step changes nothing, doubling the raise each round gets over it
+        let raise = raise.max(last_raise + last_raise);
+        offer_amount += raise;
+        last_raise = raise;
+    }
+
+    Err(StdError::generic_err("Cannot calculate the offer amount"))
+}
+
+fn compute_constant_product_swap(
     offer_pool: Uint128,
     ask_pool: Uint128,
     offer_amount: Uint128,
@@ -845,34 +2862,99 @@ fn compute_swap(
     ))
 }
 
-fn compute_offer_amount(
+/// Inverse of `compute_constant_product_swap`: the smallest offer amount whose swap returns at
+/// least `ask_amount`. Every step is rounded like the forward path, in favor of the pool.
+fn compute_constant_product_offer_amount(
     offer_pool: Uint128,
     ask_pool: Uint128,
     ask_amount: Uint128,
     commission_rate_nom: u128,
     commission_rate_denom: u128,
 ) -> StdResult<(Uint128, Uint128, Uint128)> {
-    // Note: SecretSwap never goes in here
-
     // ask => offer
-    // offer_amount = cp / (ask_pool - ask_amount / (1 - commission_rate)) - offer_pool
-    let cp = Uint128(offer_pool.u128() * ask_pool.u128());
-    let one_minus_commission = decimal_subtraction(
-        Decimal::one(),
-        Decimal::from_ratio(commission_rate_nom, commission_rate_denom),
-    )?;
-
-    let offer_amount: Uint128 = (cp.multiply_ratio(
-        1u128,
-        (ask_pool - ask_amount * reverse_decimal(one_minus_commission))?,
-    ) - offer_pool)?;
-
-    let before_commission_deduction = ask_amount * reverse_decimal(one_minus_commission);
-    let spread_amount = (offer_amount * Decimal::from_ratio(ask_pool, offer_pool)
-        - before_commission_deduction)
-        .unwrap_or_else(|_| Uint128::zero());
-    let commission_amount = before_commission_deduction
-        * Decimal::from_ratio(commission_rate_nom, commission_rate_denom);
+    let ask_amount = U256::from(ask_amount.u128());
+    let nom = U256::from(commission_rate_nom);
+    let denom = U256::from(commission_rate_denom);
+    let one_minus_commission = denom
+        .checked_sub(nom)
+        .filter(|one_minus_commission| !one_minus_commission.is_zero())
+        .ok_or_else(|| StdError::generic_err("Commission rate must be lower than 100%"))?;
+
+    // the forward path returns return_amount - floor(return_amount * commission_rate),
+    // so the smallest return_amount giving ask_amount lies between ask_amount and
+    // ceil(ask_amount / (1 - commission_rate))
+    let after_commission =
+        |return_amount: U256| -> U256 { return_amount - return_amount * nom / denom };
+    let mut lo = ask_amount;
+    let mut hi = div(
+        sub(
+            add(
+                mul(Some(ask_amount), Some(denom)),
+                Some(one_minus_commission),
+            ),
+            Some(U256::one()),
+        ),
+        Some(one_minus_commission),
+    )
+    .ok_or_else(|| {
+        StdError::generic_err(format!(
+            "Cannot calculate ask_amount {} / (1 - commission_rate)",
+            ask_amount
+        ))
+    })?;
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if after_commission(mid) >= ask_amount {
+            hi = mid;
+        } else {
+            lo = mid + U256::one();
+        }
+    }
+    let before_commission_deduction = lo;
+
+    let offer_pool_u256 = U256::from(offer_pool.u128());
+    let ask_pool_u256 = U256::from(ask_pool.u128());
+    if before_commission_deduction >= ask_pool_u256 {
+        return Err(StdError::generic_err(format!(
+            "Ask amount {} before commission exceeds the ask pool {}",
+            before_commission_deduction, ask_pool_u256
+        )));
+    }
+
+    // the forward path returns ask_pool - floor(cp / (offer_pool + offer_amount)), which is at
+    // least before_commission_deduction once
+    // offer_pool + offer_amount > cp / (ask_pool - before_commission_deduction + 1)
+    let cp = mul(Some(offer_pool_u256), Some(ask_pool_u256));
+    let offer_amount = div(
+        cp,
+        Some(ask_pool_u256 - before_commission_deduction + U256::one()),
+    )
+    .ok_or_else(|| {
+        StdError::generic_err(format!(
+            "Cannot calculate cp = offer_pool {} * ask_pool {}",
+            offer_pool_u256, ask_pool_u256
+        ))
+    })?
+    .saturating_add(U256::one())
+    .saturating_sub(offer_pool_u256);
+
+    if offer_amount > U256::from(u128::MAX) {
+        return Err(StdError::generic_err(format!(
+            "Offer amount {} exceeds the maximum amount",
+            offer_amount
+        )));
+    }
+    let offer_amount = Uint128(offer_amount.low_u128());
+
+    // spread and commission of the swap as it will be executed
+    let (_, spread_amount, commission_amount) = compute_constant_product_swap(
+        offer_pool,
+        ask_pool,
+        offer_amount,
+        Uint128(commission_rate_nom),
+        Uint128(commission_rate_denom),
+    )?;
+
     Ok((offer_amount, spread_amount, commission_amount))
 }
 
@@ -921,6 +3003,164 @@ pub fn assert_max_spread(
     Ok(())
 }
 
+/// Share minted for the first deposit into the pool, including the locked `MINIMUM_LIQUIDITY`
+pub fn compute_initial_share(curve: &CurveType, deposits: &[Uint128; 2]) -> StdResult<Uint128> {
+    // Initial share = collateral amount
+    let deposit_0 = U256::from(deposits[0].u128());
+    let deposit_1 = U256::from(deposits[1].u128());
+
+    let initial_share = match curve {
+        CurveType::ConstantProduct {} => mul(Some(deposit_0), Some(deposit_1))
+            .and_then(|prod| u256_sqrt(prod))
+            .ok_or_else(|| {
+                StdError::generic_err(format!(
+                    "Cannot calculate sqrt(deposit_0 {} * deposit_1 {})",
+                    deposit_0, deposit_1
+                ))
+            })?,
+        // D is the total amount of coins when the pool is balanced
+        CurveType::StableSwap { amp } => {
+            compute_d(*amp, deposit_0, deposit_1).ok_or_else(|| {
+                StdError::generic_err(format!(
+                    "Cannot calculate D(deposit_0 {}, deposit_1 {})",
+                    deposit_0, deposit_1
+                ))
+            })?
+        }
+        // weighted geometric mean, sqrt(deposit_0 * deposit_1) for 50/50 weights
+        CurveType::Weighted { weights } => {
+            compute_weighted_initial_share(*weights, deposit_0, deposit_1).ok_or_else(|| {
+                StdError::generic_err(format!(
+                    "Cannot calculate weighted mean of deposit_0 {} and deposit_1 {}",
+                    deposit_0, deposit_1
+                ))
+            })?
+        }
+    };
+
+    if initial_share <= U256::from(MINIMUM_LIQUIDITY) {
+        return Err(StdError::generic_err(format!(
+            "Initial liquidity {} must be greater than the minimum liquidity {}",
+            initial_share, MINIMUM_LIQUIDITY
+        )));
+    }
+
+    Ok(Uint128(initial_share.low_u128()))
+}
+
+/// Assets returned for burning `amount` of `total_share`
+fn compute_withdrawn_assets(
+    pools: &[Asset; 2],
+    amount: Uint128,
+    total_share: Uint128,
+) -> StdResult<Vec<Asset>> {
+    // Withdrawals are proportional to the pool for every curve type, which leaves the
+    // price unchanged
+    pools
+        .iter()
+        .map(|a| {
+            // withdrawn_asset_amount = a.amount * amount / total_share
+
+            let current_pool_amount = Some(U256::from(a.amount.u128()));
+            let withdrawn_share_amount = Some(U256::from(amount.u128()));
+            let total_share = Some(U256::from(total_share.u128()));
+
+            let withdrawn_asset_amount = div(
+                mul(current_pool_amount, withdrawn_share_amount),
+                total_share,
+            )
+                .ok_or_else(|| {
+                    StdError::generic_err(format!(
+                    "Cannot calculate current_pool_amount {} * withdrawn_share_amount {} / total_share {}",
+                    a.amount,
+                    amount,
+                    total_share.unwrap()
+                    ))
+                })?;
+
+            Ok(Asset {
+                info: a.info.clone(),
+                amount: Uint128(withdrawn_asset_amount.low_u128()),
+            })
+        })
+        .collect::<StdResult<Vec<Asset>>>()
+}
+
+/// Parts of a deposit into a non-empty pool that match the pool ratio. The over-supplied
+/// asset is cut down to `ceil(deposit * pool_other / pool)` of the limiting one, rounded in
+/// favor of the pool, so the share of `compute_deposit_share` doesn't change.
+pub fn compute_used_deposits(deposits: &[Uint128; 2], pools: &[Uint128; 2]) -> [Uint128; 2] {
+    if pools[0].is_zero() || pools[1].is_zero() {
+        return *deposits;
+    }
+
+    let deposit0 = U256::from(deposits[0].u128());
+    let deposit1 = U256::from(deposits[1].u128());
+    let pool0 = U256::from(pools[0].u128());
+    let pool1 = U256::from(pools[1].u128());
+
+    // the over-supplied asset has the larger deposit to pool ratio
+    let (index, needed) = if deposit0 * pool1 <= deposit1 * pool0 {
+        (1, (deposit0 * pool1 + pool0 - U256::one()) / pool0)
+    } else {
+        (0, (deposit1 * pool0 + pool1 - U256::one()) / pool1)
+    };
+
+    let mut used_deposits = *deposits;
+    if needed < U256::from(deposits[index].u128()) {
+        used_deposits[index] = Uint128(needed.low_u128());
+    }
+
+    used_deposits
+}
+
+/// Share minted for a deposit into a non-empty pool
+pub fn compute_deposit_share(
+    deposits: &[Uint128; 2],
+    pools: &[Uint128; 2],
+    total_share: Uint128,
+) -> StdResult<Uint128> {
+    // min(1, 2)
+    // 1. sqrt(deposit_0 * exchange_rate_0_to_1 * deposit_0) * (total_share / sqrt(pool_0 * pool_1))
+    // == deposit_0 * total_share / pool_0
+    // 2. sqrt(deposit_1 * exchange_rate_1_to_0 * deposit_1) * (total_share / sqrt(pool_1 * pool_1))
+    // == deposit_1 * total_share / pool_1
+
+    // This was:
+    // std::cmp::min(
+    //   deposits[0].multiply_ratio(total_share, pools[0].amount),
+    //   deposits[1].multiply_ratio(total_share, pools[1].amount),
+    // )
+
+    let total_share = Some(U256::from(total_share.u128()));
+
+    let deposit0 = Some(U256::from(deposits[0].u128()));
+    let pools0_amount = Some(U256::from(pools[0].u128()));
+
+    let share0 = div(mul(deposit0, total_share), pools0_amount).ok_or_else(|| {
+        StdError::generic_err(format!(
+            "Cannot calculate deposits[0] {} * total_share {} / pools[0].amount {}",
+            deposit0.unwrap(),
+            total_share.unwrap(),
+            pools0_amount.unwrap()
+        ))
+    })?;
+
+    let deposit1 = Some(U256::from(deposits[1].u128()));
+    let pools1_amount = Some(U256::from(pools[1].u128()));
+
+    let share1 = div(mul(deposit1, total_share), pools1_amount).ok_or_else(|| {
+        StdError::generic_err(format!(
+            "Cannot calculate deposits[1] {} * total_share {} / pools[1].amount {}",
+            deposit1.unwrap(),
+            total_share.unwrap(),
+            pools1_amount.unwrap()
+        ))
+    })?;
+
+    Ok(Uint128(std::cmp::min(share0, share1).low_u128()))
+}
+
 fn assert_slippage_tolerance(
     slippage_tolerance: &Option<Decimal>,
     deposits: &[Uint128; 2],
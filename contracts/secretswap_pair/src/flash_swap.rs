@@ -0,0 +1,81 @@
+use cosmwasm_std::{StdError, StdResult, Uint128};
+use primitive_types::U256;
+
+use secretswap::CurveType;
+
+use crate::stableswap::compute_d;
+use crate::u256_math::*;
+use crate::weighted::compute_weighted_initial_share;
+
+/// Value of the curve invariant, it never decreases with a swap:
+/// `x * y` for constant product, `D` for stable swap and the weighted geometric mean for
+/// weighted pools.
+fn compute_invariant(curve: &CurveType, pools: [U256; 2]) -> Option<U256> {
+    match curve {
+        CurveType::ConstantProduct {} => mul(Some(pools[0]), Some(pools[1])),
+        CurveType::StableSwap { amp } => compute_d(*amp, pools[0], pools[1]),
+        CurveType::Weighted { weights } => {
+            compute_weighted_initial_share(*weights, pools[0], pools[1])
+        }
+    }
+}
+
+/// Checks that a flash swap left the pool with an invariant at least as large as before, after
+/// the swap fee is charged on every amount paid back into the pool, like Uniswap v2 does:
+///
+/// balance_adjusted = balance_after - amount_in * commission_rate
+///
+/// where `amount_in = balance_after - (pool_before - amount_out)`.
+/// Returns the fees charged, aligned with the pools. They stay in the pool.
+pub fn assert_flash_swap_invariant(
+    curve: &CurveType,
+    pools_before: [Uint128; 2],
+    amounts_out: [Uint128; 2],
+    balances_after: [Uint128; 2],
+    commission_rate_nom: Uint128,
+    commission_rate_denom: Uint128,
+) -> StdResult<[Uint128; 2]> {
+    let mut fees = [Uint128::zero(); 2];
+    let mut adjusted = [U256::zero(); 2];
+    for i in 0..2 {
+        let lent =
+            U256::from(pools_before[i].u128()).saturating_sub(U256::from(amounts_out[i].u128()));
+        let balance = U256::from(balances_after[i].u128());
+        let amount_in = balance.saturating_sub(lent);
+
+        // fee = ceil(amount_in * commission_rate), rounded in favor of the pool
+        let fee = div(
+            sub(
+                add(
+                    mul(
+                        Some(amount_in),
+                        Some(U256::from(commission_rate_nom.u128())),
+                    ),
+                    Some(U256::from(commission_rate_denom.u128())),
+                ),
+                Some(U256::one()),
+            ),
+            Some(U256::from(commission_rate_denom.u128())),
+        )
+        .ok_or_else(|| StdError::generic_err("Cannot calculate the flash swap fee"))?;
+
+        fees[i] = Uint128(fee.low_u128());
+        adjusted[i] = balance.saturating_sub(fee);
+    }
+
+    let before = compute_invariant(
+        curve,
+        [
+            U256::from(pools_before[0].u128()),
+            U256::from(pools_before[1].u128()),
+        ],
+    );
+    let after = compute_invariant(curve, adjusted);
+    match (before, after) {
+        (Some(before), Some(after)) if after >= before => Ok(fees),
+        (Some(_), Some(_)) => Err(StdError::generic_err(
+            "Flash swap was not paid back with its fee",
+        )),
+        _ => Err(StdError::generic_err("Cannot calculate the pool invariant")),
+    }
+}
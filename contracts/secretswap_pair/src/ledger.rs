@@ -0,0 +1,174 @@
+use cosmwasm_std::{CanonicalAddr, StdError, StdResult, Storage, Uint128};
+use primitive_types::U256;
+use secret_toolkit::crypto::sha_256;
+
+use secretswap::CurveType;
+
+use crate::oracle::spot_price;
+use crate::state::{
+    read_fee_growth, read_ledger_entry, read_viewing_key, store_fee_growth, store_ledger_entry,
+    store_viewing_key, LedgerEntry,
+};
+
+/// Fee growth is a fixed point number with 18 decimals, like prices
+const FEE_GROWTH_SCALE: u128 = 1_000_000_000_000_000_000;
+
+/// Adds the liquidity provider part of swap fees, aligned with `asset_infos`, to the fee
+/// growth per share. `total_share` is the LP supply the fees are shared by.
+pub fn accrue_fees<S: Storage>(
+    storage: &mut S,
+    fees: [Uint128; 2],
+    total_share: Uint128,
+) -> StdResult<()> {
+    if total_share.is_zero() || fees.iter().all(|fee| fee.is_zero()) {
+        return Ok(());
+    }
+
+    let mut fee_growth = read_fee_growth(storage)?;
+    for i in 0..2 {
+        let growth = U256::from(fees[i].u128()) * U256::from(FEE_GROWTH_SCALE)
+            / U256::from(total_share.u128());
+        // like the price accumulators, only differences are meaningful
+        fee_growth[i] = Uint128(fee_growth[i].u128().wrapping_add(growth.low_u128()));
+    }
+    store_fee_growth(storage, &fee_growth)
+}
+
+/// Adds the fees earned by the shares of the entry since it was last settled
+fn settle_fees(entry: &mut LedgerEntry, fee_growth: [Uint128; 2]) {
+    for i in 0..2 {
+        let growth = fee_growth[i]
+            .u128()
+            .wrapping_sub(entry.fee_growth_last[i].u128());
+        let earned =
+            U256::from(entry.share.u128()) * U256::from(growth) / U256::from(FEE_GROWTH_SCALE);
+        entry.fees_earned[i] = entry.fees_earned[i] + Uint128(earned.low_u128());
+    }
+    entry.fee_growth_last = fee_growth;
+}
+
+pub fn record_deposit<S: Storage>(
+    storage: &mut S,
+    account: &CanonicalAddr,
+    deposits: [Uint128; 2],
+    share: Uint128,
+) -> StdResult<()> {
+    let mut entry = read_ledger_entry(storage, account)?.unwrap_or_default();
+    settle_fees(&mut entry, read_fee_growth(storage)?);
+
+    for i in 0..2 {
+        entry.deposited[i] = entry.deposited[i] + deposits[i];
+        entry.cost_basis[i] = entry.cost_basis[i] + deposits[i];
+    }
+    entry.share = entry.share + share;
+    store_ledger_entry(storage, account, &entry)
+}
+
+/// Shares the account got outside of the pair, e.g. by a transfer of LP tokens, aren't in
+/// the ledger. Withdrawing them only counts as withdrawn assets.
+pub fn record_withdrawal<S: Storage>(
+    storage: &mut S,
+    account: &CanonicalAddr,
+    withdrawn: [Uint128; 2],
+    share: Uint128,
+) -> StdResult<()> {
+    let mut entry = read_ledger_entry(storage, account)?.unwrap_or_default();
+    settle_fees(&mut entry, read_fee_growth(storage)?);
+
+    let burned_share = share.min(entry.share);
+    for i in 0..2 {
+        entry.withdrawn[i] = entry.withdrawn[i] + withdrawn[i];
+        if !entry.share.is_zero() {
+            entry.cost_basis[i] = (entry.cost_basis[i]
+                - entry.cost_basis[i].multiply_ratio(burned_share, entry.share))?;
+        }
+    }
+    entry.share = (entry.share - burned_share)?;
+    store_ledger_entry(storage, account, &entry)
+}
+
+/// Position of an account, amounts aligned with `asset_infos`.
+/// Values are denominated in asset 1 at the current spot price.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Position {
+    pub entry: LedgerEntry,
+    pub current_value: [Uint128; 2],
+    /// Value of the cost basis had it been held instead
+    pub hodl_value: Uint128,
+    pub position_value: Uint128,
+    /// How much less the position is worth without its fees than the cost basis held,
+    /// zero if it is worth more
+    pub impermanent_loss: Uint128,
+}
+
+pub fn compute_position<S: Storage>(
+    storage: &S,
+    account: &CanonicalAddr,
+    curve: &CurveType,
+    pools: [Uint128; 2],
+    total_share: Uint128,
+) -> StdResult<Position> {
+    let mut entry = read_ledger_entry(storage, account)?.unwrap_or_default();
+    settle_fees(&mut entry, read_fee_growth(storage)?);
+
+    let current_value = if total_share.is_zero() {
+        [Uint128::zero(); 2]
+    } else {
+        [
+            pools[0].multiply_ratio(entry.share, total_share),
+            pools[1].multiply_ratio(entry.share, total_share),
+        ]
+    };
+
+    // price0 is asset 0 denominated in asset 1, scaled like the fee growth
+    let price0 = spot_price(curve, pools)
+        .map(|(price0, _)| price0)
+        .unwrap_or_else(U256::zero);
+    let value = |amounts: [Uint128; 2]| -> U256 {
+        U256::from(amounts[0].u128()) * price0 / U256::from(FEE_GROWTH_SCALE)
+            + U256::from(amounts[1].u128())
+    };
+
+    let hodl_value = value(entry.cost_basis);
+    let position_value = value(current_value);
+    let fees_value = value(entry.fees_earned);
+    let impermanent_loss = hodl_value.saturating_sub(position_value.saturating_sub(fees_value));
+
+    Ok(Position {
+        entry,
+        current_value,
+        hodl_value: Uint128(hodl_value.low_u128()),
+        position_value: Uint128(position_value.low_u128()),
+        impermanent_loss: Uint128(impermanent_loss.low_u128()),
+    })
+}
+
+pub fn set_viewing_key<S: Storage>(
+    storage: &mut S,
+    account: &CanonicalAddr,
+    key: &str,
+) -> StdResult<()> {
+    store_viewing_key(storage, account, &sha_256(key.as_bytes()))
+}
+
+/// Compares the hashes in constant time, so the key can't be guessed byte by byte
+pub fn assert_viewing_key<S: Storage>(
+    storage: &S,
+    account: &CanonicalAddr,
+    key: &str,
+) -> StdResult<()> {
+    let key_hash = sha_256(key.as_bytes());
+    let stored_hash = read_viewing_key(storage, account)?.unwrap_or_else(|| vec![0u8; 32]);
+
+    let mut diff = (stored_hash.len() ^ key_hash.len()) as u8;
+    for (a, b) in stored_hash.iter().zip(key_hash.iter()) {
+        diff |= a ^ b;
+    }
+    if diff != 0 {
+        return Err(StdError::generic_err(
+            "Wrong viewing key for this address or viewing key not set",
+        ));
+    }
+
+    Ok(())
+}
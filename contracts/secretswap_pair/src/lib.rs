@@ -1,9 +1,18 @@
 pub mod contract;
+pub mod flash_swap;
+pub mod ledger;
+pub mod limit_order;
 pub mod math;
 pub mod msg;
+pub mod oracle;
+pub mod permit;
 pub mod querier;
+pub mod stableswap;
 pub mod state;
+pub mod stats;
 pub mod u256_math;
+pub mod weighted;
+pub mod zap;
 
 #[cfg(test)]
 mod testing;
@@ -0,0 +1,352 @@
+use cosmwasm_std::{CanonicalAddr, Decimal, StdError, StdResult, Storage, Uint128};
+use primitive_types::U256;
+
+use secretswap::{CurveType, Fee, PairSettings};
+
+use crate::contract::compute_swap;
+use crate::ledger::accrue_fees;
+use crate::oracle::spot_price;
+use crate::state::{
+    read_limit_order, read_order_book, read_order_count, read_order_escrow, read_owner_orders,
+    read_protocol_fees, read_reserves, remove_limit_order, store_limit_order, store_order_book,
+    store_order_count, store_order_escrow, store_owner_orders, store_protocol_fees, store_reserves,
+    BookEntry, LimitOrder,
+};
+use crate::stats::record_trade;
+
+/// Open orders per sold asset, every swap walks the cheapest of them
+pub const MAX_OPEN_ORDERS: usize = 100;
+/// Open orders per account
+pub const MAX_ORDERS_PER_OWNER: usize = 10;
+/// Orders filled after a single swap, to bound its gas
+pub const MAX_FILLS_PER_SWAP: usize = 8;
+/// Decimals of `Decimal`
+const DECIMAL_FRACTIONAL: u128 = 1_000_000_000_000_000_000;
+
+/// Escrows `amount` of the asset at `offer_index` and adds the order to the book.
+/// Orders at the same price are filled in the order they were placed. An account with
+/// `MAX_ORDERS_PER_OWNER` open orders has its oldest one evicted, and when the book is full
+/// the dearest order is evicted by a cheaper one. Evicted orders are returned, their unsold
+/// amount is to be paid back.
+pub fn place_limit_order<S: Storage>(
+    storage: &mut S,
+    owner: &CanonicalAddr,
+    offer_index: usize,
+    amount: Uint128,
+    price: Decimal,
+) -> StdResult<(u64, Vec<LimitOrder>)> {
+    if amount.is_zero() {
+        return Err(StdError::generic_err("Order amount must be positive"));
+    }
+    if price == Decimal::zero() {
+        return Err(StdError::generic_err("Order price must be positive"));
+    }
+
+    let mut evicted = vec![];
+    let owner_orders = read_owner_orders(storage, owner)?;
+    if owner_orders.len() >= MAX_ORDERS_PER_OWNER {
+        let oldest = owner_orders[0];
+        let order = read_limit_order(storage, oldest)?
+            .ok_or_else(|| StdError::generic_err("Limit order not found"))?;
+        remove_order(storage, oldest, &order)?;
+        evicted.push(order);
+    }
+
+    let mut book = read_order_book(storage, offer_index as u8)?;
+    if book.len() >= MAX_OPEN_ORDERS {
+        let worst = book[book.len() - 1].clone();
+        if price >= worst.price {
+            return Err(StdError::generic_err(
+                "The order book is full, the price must be below its dearest order",
+            ));
+        }
+        let order = read_limit_order(storage, worst.order_id)?
+            .ok_or_else(|| StdError::generic_err("Limit order not found"))?;
+        remove_order(storage, worst.order_id, &order)?;
+        book.pop();
+        evicted.push(order);
+    }
+
+    let order_id = read_order_count(storage)?;
+    store_order_count(storage, order_id + 1)?;
+    store_limit_order(
+        storage,
+        order_id,
+        &LimitOrder {
+            owner: owner.clone(),
+            offer_index: offer_index as u8,
+            price,
+            offer_amount: amount,
+            remaining: amount,
+            proceeds: Uint128::zero(),
+        },
+    )?;
+
+    let position = book
+        .iter()
+        .position(|entry| entry.price > price)
+        .unwrap_or_else(|| book.len());
+    book.insert(position, BookEntry { price, order_id });
+    store_order_book(storage, offer_index as u8, &book)?;
+
+    let mut owner_orders = read_owner_orders(storage, owner)?;
+    owner_orders.push(order_id);
+    store_owner_orders(storage, owner, &owner_orders)?;
+
+    let mut escrow = read_order_escrow(storage)?;
+    escrow[offer_index] = escrow[offer_index] + amount;
+    store_order_escrow(storage, &escrow)?;
+
+    Ok((order_id, evicted))
+}
+
+/// Removes an order of `owner`, the unsold amount is to be paid out.
+/// Orders of other accounts are reported as not found, so their ids aren't revealed.
+pub fn cancel_limit_order<S: Storage>(
+    storage: &mut S,
+    owner: &CanonicalAddr,
+    order_id: u64,
+) -> StdResult<LimitOrder> {
+    let order = match read_limit_order(storage, order_id)? {
+        Some(order) if &order.owner == owner => order,
+        _ => return Err(StdError::generic_err("Limit order not found")),
+    };
+    remove_order(storage, order_id, &order)?;
+
+    Ok(order)
+}
+
+/// Takes an order out of the book and releases its unsold amount from the escrow
+fn remove_order<S: Storage>(storage: &mut S, order_id: u64, order: &LimitOrder) -> StdResult<()> {
+    let mut book = read_order_book(storage, order.offer_index)?;
+    book.retain(|entry| entry.order_id != order_id);
+    store_order_book(storage, order.offer_index, &book)?;
+
+    let mut owner_orders = read_owner_orders(storage, &order.owner)?;
+    owner_orders.retain(|id| *id != order_id);
+    store_owner_orders(storage, &order.owner, &owner_orders)?;
+
+    remove_limit_order(storage, order_id);
+
+    let offer_index = order.offer_index as usize;
+    let mut escrow = read_order_escrow(storage)?;
+    escrow[offer_index] = (escrow[offer_index] - order.remaining)?;
+    store_order_escrow(storage, &escrow)?;
+
+    Ok(())
+}
+
+/// Largest part of `remaining` that returns at least `price` per sold unit when swapped
+/// against the pools, zero if none does or the marginal price after the commission is below
+/// `price`. The average price of a swap falls with its size, so it is found by bisection.
+pub fn compute_fill_amount(
+    curve: &CurveType,
+    offer_index: usize,
+    offer_pool: Uint128,
+    ask_pool: Uint128,
+    remaining: Uint128,
+    price: Decimal,
+    swap_fee: &Fee,
+) -> Uint128 {
+    // compared without rounding, return_amount / amount >= price
+    let price_fractional = U256::from((Uint128(DECIMAL_FRACTIONAL) * price).u128());
+
+    // rounding could fill a few units of an order the pools don't pay the price of yet
+    let mut pools = [Uint128::zero(); 2];
+    pools[offer_index] = offer_pool;
+    pools[1 - offer_index] = ask_pool;
+    let marginal_price = match spot_price(curve, pools) {
+        Some(prices) if offer_index == 0 => prices.0,
+        Some(prices) => prices.1,
+        None => return Uint128::zero(),
+    };
+    let commission_rate_denom = U256::from(swap_fee.commission_rate_denom.u128());
+    let commission_rate_nom = U256::from(swap_fee.commission_rate_nom.u128());
+    if commission_rate_denom.is_zero()
+        || marginal_price * (commission_rate_denom.saturating_sub(commission_rate_nom))
+            < price_fractional * commission_rate_denom
+    {
+        return Uint128::zero();
+    }
+    let fills = |amount: u128| -> bool {
+        match compute_swap(
+            curve,
+            offer_index,
+            offer_pool,
+            ask_pool,
+            Uint128(amount),
+            swap_fee.commission_rate_nom,
+            swap_fee.commission_rate_denom,
+        ) {
+            Ok((return_amount, _, _)) => {
+                !return_amount.is_zero()
+                    && U256::from(return_amount.u128()) * U256::from(DECIMAL_FRACTIONAL)
+                        >= U256::from(amount) * price_fractional
+            }
+            // e.g. the amount exceeds what the curve accepts
+            Err(_) => false,
+        }
+    };
+
+    if fills(remaining.u128()) {
+        return remaining;
+    }
+
+    let mut lo = 0u128;
+    let mut hi = remaining.u128();
+    while lo < hi {
+        let mid = lo + (hi - lo + 1) / 2;
+        if fills(mid) {
+            lo = mid;
+        } else {
+            hi = mid - 1;
+        }
+    }
+
+    Uint128(lo)
+}
+
+/// Order filled against the pools, `return_amount` is to be paid out to `owner`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fill {
+    pub order_id: u64,
+    pub owner: CanonicalAddr,
+    pub amount: Uint128,
+    pub return_amount: Uint128,
+    pub commission_amount: Uint128,
+    pub spread_amount: Uint128,
+    /// Reserves after the fill
+    pub reserves: [Uint128; 2],
+}
+
+/// Fills the cheapest orders selling the asset at `offer_index` that the pools pay their
+/// price for, like swaps: the commission goes to the pool and the protocol. Stops at the
+/// first order that can't be filled completely, since the orders after it are dearer.
+/// Orders that filled completely are removed. Orders whose rest is too small to return
+/// anything are closed and returned, their rest is to be paid back.
+pub fn fill_limit_orders<S: Storage>(
+    storage: &mut S,
+    curve: &CurveType,
+    pair_settings: &PairSettings,
+    offer_index: usize,
+    total_share: Uint128,
+    block_time: u64,
+) -> StdResult<(Vec<Fill>, Vec<LimitOrder>)> {
+    let ask_index = 1 - offer_index;
+    let mut book = read_order_book(storage, offer_index as u8)?;
+    let mut reserves = read_reserves(storage)?;
+    let mut escrow = read_order_escrow(storage)?;
+
+    let mut fills = vec![];
+    let mut closed = vec![];
+    let mut book_changed = false;
+    while fills.len() < MAX_FILLS_PER_SWAP && !book.is_empty() {
+        let order_id = book[0].order_id;
+        let mut order = read_limit_order(storage, order_id)?
+            .ok_or_else(|| StdError::generic_err("Limit order not found"))?;
+
+        let amount = compute_fill_amount(
+            curve,
+            offer_index,
+            reserves[offer_index],
+            reserves[ask_index],
+            order.remaining,
+            order.price,
+            &pair_settings.swap_fee,
+        );
+        if amount.is_zero() {
+            // a rest too small to return anything would block the book, it's paid back instead
+            let dust = compute_swap(
+                curve,
+                offer_index,
+                reserves[offer_index],
+                reserves[ask_index],
+                order.remaining,
+                pair_settings.swap_fee.commission_rate_nom,
+                pair_settings.swap_fee.commission_rate_denom,
+            )
+            .map(|(return_amount, _, _)| return_amount.is_zero())
+            .unwrap_or(false);
+            if !dust {
+                break;
+            }
+            book.remove(0);
+            book_changed = true;
+            escrow[offer_index] = (escrow[offer_index] - order.remaining)?;
+            close_order(storage, order_id, &order)?;
+            closed.push(order);
+            continue;
+        }
+
+        let (return_amount, spread_amount, commission_amount) = compute_swap(
+            curve,
+            offer_index,
+            reserves[offer_index],
+            reserves[ask_index],
+            amount,
+            pair_settings.swap_fee.commission_rate_nom,
+            pair_settings.swap_fee.commission_rate_denom,
+        )?;
+        let protocol_fee_amount = match &pair_settings.protocol_fee {
+            Some(protocol_fee) => protocol_fee.compute(commission_amount),
+            None => Uint128::zero(),
+        };
+        if !protocol_fee_amount.is_zero() {
+            let mut protocol_fees = read_protocol_fees(storage)?;
+            protocol_fees[ask_index] = protocol_fees[ask_index] + protocol_fee_amount;
+            store_protocol_fees(storage, &protocol_fees)?;
+        }
+        let mut lp_fees = [Uint128::zero(); 2];
+        lp_fees[ask_index] = (commission_amount - protocol_fee_amount)?;
+        accrue_fees(storage, lp_fees, total_share)?;
+        record_trade(storage, block_time, offer_index, amount, commission_amount)?;
+
+        // the sold amount moves from the escrow into the pool, the return is paid out
+        reserves[offer_index] = reserves[offer_index] + amount;
+        reserves[ask_index] = (reserves[ask_index] - (return_amount + protocol_fee_amount))?;
+        escrow[offer_index] = (escrow[offer_index] - amount)?;
+
+        order.remaining = (order.remaining - amount)?;
+        order.proceeds = order.proceeds + return_amount;
+
+        fills.push(Fill {
+            order_id,
+            owner: order.owner.clone(),
+            amount,
+            return_amount,
+            commission_amount,
+            spread_amount,
+            reserves,
+        });
+
+        if !order.remaining.is_zero() {
+            store_limit_order(storage, order_id, &order)?;
+            break;
+        }
+        book.remove(0);
+        book_changed = true;
+        close_order(storage, order_id, &order)?;
+    }
+
+    if book_changed {
+        store_order_book(storage, offer_index as u8, &book)?;
+    }
+    if !fills.is_empty() {
+        store_reserves(storage, &reserves)?;
+    }
+    if !fills.is_empty() || !closed.is_empty() {
+        store_order_escrow(storage, &escrow)?;
+    }
+
+    Ok((fills, closed))
+}
+
+/// Drops an order already taken out of the book and the escrow
+fn close_order<S: Storage>(storage: &mut S, order_id: u64, order: &LimitOrder) -> StdResult<()> {
+    let mut owner_orders = read_owner_orders(storage, &order.owner)?;
+    owner_orders.retain(|id| *id != order_id);
+    store_owner_orders(storage, &order.owner, &owner_orders)?;
+    remove_limit_order(storage, order_id);
+
+    Ok(())
+}
@@ -2,12 +2,12 @@ use std::collections::HashMap;
 
 use cosmwasm_std::testing::{MockApi, MockQuerier, MockStorage, MOCK_CONTRACT_ADDR};
 use cosmwasm_std::{
-    from_slice, to_binary, Api, CanonicalAddr, Coin, Decimal, Extern, HumanAddr, Querier,
-    QuerierResult, QueryRequest, SystemError, Uint128, WasmQuery,
+    from_binary, from_slice, to_binary, Coin, Empty, Extern, HumanAddr, Querier, QuerierResult,
+    QueryRequest, SystemError, Uint128, WasmQuery,
 };
-use cosmwasm_storage::to_length_prefixed;
+use serde::{Deserialize, Serialize};
 
-//use terra_cosmwasm::{TaxCapResponse, TaxRateResponse, TerraQuery, TerraQueryWrapper, TerraRoute};
+use secretswap::{Fee, PairInfo, PairSettings};
 
 /// mock_dependencies is a drop-in replacement for cosmwasm_std::testing::mock_dependencies
 /// this uses our CustomQuerier.
@@ -16,11 +16,8 @@ pub fn mock_dependencies(
     contract_balance: &[Coin],
 ) -> Extern<MockStorage, MockApi, WasmMockQuerier> {
     let contract_addr = HumanAddr::from(MOCK_CONTRACT_ADDR);
-    let custom_querier: WasmMockQuerier = WasmMockQuerier::new(
-        MockQuerier::new(&[(&contract_addr, contract_balance)]),
-        canonical_length,
-        MockApi::new(canonical_length),
-    );
+    let custom_querier: WasmMockQuerier =
+        WasmMockQuerier::new(MockQuerier::new(&[(&contract_addr, contract_balance)]));
 
     Extern {
         storage: MockStorage::default(),
@@ -30,10 +27,10 @@ pub fn mock_dependencies(
 }
 
 pub struct WasmMockQuerier {
-    base: MockQuerier<TerraQueryWrapper>,
+    base: MockQuerier<Empty>,
     token_querier: TokenQuerier,
-    tax_querier: TaxQuerier,
-    canonical_length: usize,
+    pair_settings: PairSettings,
+    pairs: HashMap<HumanAddr, PairInfo>,
 }
 
 #[derive(Clone, Default)]
@@ -65,34 +62,34 @@ pub(crate) fn balances_to_map(
     balances_map
 }
 
-#[derive(Clone, Default)]
-pub struct TaxQuerier {
-    rate: Decimal,
-    // this lets us iterate over all pairs that match the first string
-    caps: HashMap<String, Uint128>,
+/// Smart queries the pair sends to tokens, the factory and other pairs
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum MockQueryMsg {
+    Balance { address: HumanAddr },
+    TokenInfo {},
+    PairSettings {},
+    Pair {},
 }
 
-impl TaxQuerier {
-    pub fn new(rate: Decimal, caps: &[(&String, &Uint128)]) -> Self {
-        TaxQuerier {
-            rate,
-            caps: caps_to_map(caps),
-        }
-    }
-}
-
-pub(crate) fn caps_to_map(caps: &[(&String, &Uint128)]) -> HashMap<String, Uint128> {
-    let mut owner_map: HashMap<String, Uint128> = HashMap::new();
-    for (denom, cap) in caps.iter() {
-        owner_map.insert(denom.to_string(), **cap);
-    }
-    owner_map
+/// Answers of snip20 tokens
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum TokenQueryAnswer {
+    Balance {
+        amount: Uint128,
+    },
+    TokenInfo {
+        name: String,
+        symbol: String,
+        decimals: u8,
+        total_supply: Option<Uint128>,
+    },
 }
 
 impl Querier for WasmMockQuerier {
     fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
-        // MockQuerier doesn't support Custom, so we ignore it completely here
-        let request: QueryRequest<TerraQueryWrapper> = match from_slice(bin_request) {
+        let request: QueryRequest<Empty> = match from_slice(bin_request) {
             Ok(v) => v,
             Err(e) => {
                 return Err(SystemError::InvalidRequest {
@@ -106,103 +103,66 @@ impl Querier for WasmMockQuerier {
 }
 
 impl WasmMockQuerier {
-    pub fn handle_query(&self, request: &QueryRequest<TerraQueryWrapper>) -> QuerierResult {
+    pub fn handle_query(&self, request: &QueryRequest<Empty>) -> QuerierResult {
         match &request {
-            QueryRequest::Custom(TerraQueryWrapper { route, query_data }) => {
-                if route == &TerraRoute::Treasury {
-                    match query_data {
-                        TerraQuery::TaxRate {} => {
-                            let res = TaxRateResponse {
-                                rate: self.tax_querier.rate,
-                            };
-                            Ok(to_binary(&res))
-                        }
-                        TerraQuery::TaxCap { denom } => {
-                            let cap = self
-                                .tax_querier
-                                .caps
-                                .get(denom)
-                                .copied()
-                                .unwrap_or_default();
-                            let res = TaxCapResponse { cap };
-                            Ok(to_binary(&res))
-                        }
-                        _ => panic!("DO NOT ENTER HERE"),
-                    }
-                } else {
-                    panic!("DO NOT ENTER HERE")
-                }
-            }
-            QueryRequest::Wasm(WasmQuery::Raw {
-                contract_addr,
-                key,
-                callback_code_hash,
+            QueryRequest::Wasm(WasmQuery::Smart {
+                contract_addr, msg, ..
             }) => {
-                let key: &[u8] = key.as_slice();
-
-                let balances: &HashMap<HumanAddr, Uint128> =
-                    match self.token_querier.balances.get(contract_addr) {
-                        Some(balances) => balances,
-                        None => {
-                            return Err(SystemError::InvalidRequest {
-                                error: format!(
-                                    "No balance info exists for the contract {}",
-                                    contract_addr
-                                ),
-                                request: key.into(),
-                            });
-                        }
-                    };
-
-                let prefix_token_info = to_length_prefixed(b"token_info").to_vec();
-                let prefix_balance = to_length_prefixed(b"balance").to_vec();
-
-                if key.to_vec() == prefix_token_info {
-                    let mut total_supply = Uint128::zero();
-
-                    for balance in balances {
-                        total_supply += *balance.1;
+                let query: MockQueryMsg = match from_binary(msg) {
+                    Ok(v) => v,
+                    Err(e) => {
+                        return Err(SystemError::InvalidRequest {
+                            error: format!("Parsing smart query: {:?}", e),
+                            request: msg.clone(),
+                        });
                     }
+                };
+
+                match query {
+                    MockQueryMsg::PairSettings {} => Ok(to_binary(&self.pair_settings)),
+                    MockQueryMsg::Pair {} => match self.pairs.get(contract_addr) {
+                        Some(pair_info) => Ok(to_binary(pair_info)),
+                        None => Err(SystemError::InvalidRequest {
+                            error: format!("PairInfo is not found for {}", contract_addr),
+                            request: msg.clone(),
+                        }),
+                    },
+                    MockQueryMsg::Balance { .. } | MockQueryMsg::TokenInfo {} => {
+                        let balances: &HashMap<HumanAddr, Uint128> =
+                            match self.token_querier.balances.get(contract_addr) {
+                                Some(balances) => balances,
+                                None => {
+                                    return Err(SystemError::InvalidRequest {
+                                        error: format!(
+                                            "No balance info exists for the contract {}",
+                                            contract_addr
+                                        ),
+                                        request: msg.clone(),
+                                    });
+                                }
+                            };
 
-                    Ok(to_binary(
-                        &to_binary(&secret_toolkit::snip20::TokenInfoResponse {
-                            token_info: secret_toolkit::snip20::TokenInfo {
-                                name: "mAPPL".to_string(),
-                                symbol: "mAPPL".to_string(),
-                                decimals: 6,
-                                total_supply: Some(total_supply),
-                            },
-                        })
-                        .unwrap(),
-                    ))
-                } else if key[..prefix_balance.len()].to_vec() == prefix_balance {
-                    let key_address: &[u8] = &key[prefix_balance.len()..];
-                    let address_raw: CanonicalAddr = CanonicalAddr::from(key_address);
-
-                    let api: MockApi = MockApi::new(self.canonical_length);
-                    let address: HumanAddr = match api.human_address(&address_raw) {
-                        Ok(v) => v,
-                        Err(e) => {
-                            return Err(SystemError::InvalidRequest {
-                                error: format!("Parsing query request: {:?}", e),
-                                request: key.into(),
-                            });
+                        match query {
+                            MockQueryMsg::Balance { address } => {
+                                Ok(to_binary(&TokenQueryAnswer::Balance {
+                                    amount: balances.get(&address).copied().unwrap_or_default(),
+                                }))
+                            }
+                            _ => {
+                                let mut total_supply = Uint128::zero();
+                                for balance in balances {
+                                    total_supply += *balance.1;
+                                }
+
+                                Ok(to_binary(&TokenQueryAnswer::TokenInfo {
+                                    name: "mAPPL".to_string(),
+                                    symbol: "mAPPL".to_string(),
+                                    decimals: 6,
+                                    total_supply: Some(total_supply),
+                                }))
+                            }
                         }
-                    };
-
-                    let balance = match balances.get(&address) {
-                        Some(v) => v,
-                        None => {
-                            return Err(SystemError::InvalidRequest {
-                                error: "Balance not found".to_string(),
-                                request: key.into(),
-                            });
-                        }
-                    };
-
-                    Ok(to_binary(&to_binary(&balance).unwrap()))
-                } else {
-                    panic!("DO NOT ENTER HERE")
+                    }
                 }
             }
             _ => self.base.handle_query(request),
@@ -211,16 +171,23 @@ impl WasmMockQuerier {
 }
 
 impl WasmMockQuerier {
-    pub fn new<A: Api>(
-        base: MockQuerier<TerraQueryWrapper>,
-        canonical_length: usize,
-        _api: A,
-    ) -> Self {
+    pub fn new(base: MockQuerier<Empty>) -> Self {
         WasmMockQuerier {
             base,
             token_querier: TokenQuerier::default(),
-            tax_querier: TaxQuerier::default(),
-            canonical_length,
+            pair_settings: PairSettings {
+                swap_fee: Fee {
+                    commission_rate_nom: Uint128(3),
+                    commission_rate_denom: Uint128(1000),
+                },
+                swap_data_endpoint: None,
+                swap_data_endpoints: vec![],
+                protocol_fee: None,
+                status: Default::default(),
+                dynamic_fee: None,
+                max_price_move: None,
+            },
+            pairs: HashMap::new(),
         }
     }
 
@@ -229,14 +196,22 @@ impl WasmMockQuerier {
         self.token_querier = TokenQuerier::new(balances);
     }
 
-    // configure the token owner mock querier
-    pub fn with_tax(&mut self, rate: Decimal, caps: &[(&String, &Uint128)]) {
-        self.tax_querier = TaxQuerier::new(rate, caps);
+    // configure the settings the factory answers with, a 0.3% swap fee by default
+    pub fn with_pair_settings(&mut self, pair_settings: PairSettings) {
+        self.pair_settings = pair_settings;
+    }
+
+    // configure the pairs answering the `pair` query, e.g. the one a pair migrates from
+    pub fn with_pairs(&mut self, pairs: &[(&HumanAddr, &PairInfo)]) {
+        for (addr, pair_info) in pairs.iter() {
+            self.pairs
+                .insert(HumanAddr::from(*addr), (*pair_info).clone());
+        }
     }
 
     pub fn with_balance(&mut self, balances: &[(&HumanAddr, &[Coin])]) {
         for (addr, balance) in balances {
-            self.base.update_balance(addr, balance.to_vec());
+            self.base.update_balance((*addr).clone(), balance.to_vec());
         }
     }
 }
@@ -2,7 +2,8 @@ use cosmwasm_std::{Binary, Decimal, HumanAddr, Uint128};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use secretswap::Asset;
+use crate::permit::Permit;
+use secretswap::{Asset, AssetInfo, Deadline, DynamicFee, Fee, PairStatusLevel};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
@@ -18,6 +19,8 @@ pub enum HandleMsg {
     ProvideLiquidity {
         assets: [Asset; 2],
         slippage_tolerance: Option<Decimal>,
+        /// Fails if executed after the deadline
+        deadline: Option<Deadline>,
     },
     /// Swap an offer asset to the other
     Swap {
@@ -26,6 +29,85 @@ pub enum HandleMsg {
         belief_price: Option<Decimal>,
         max_spread: Option<Decimal>,
         to: Option<HumanAddr>,
+        /// Exact output: buy this amount of the other asset, `offer_asset` is the most to
+        /// spend and the unused part is refunded
+        ask_amount: Option<Uint128>,
+        /// Fails if executed after the deadline
+        deadline: Option<Deadline>,
+    },
+    /// ZapIn provides liquidity with a single native asset, part of it is swapped into the
+    /// other asset first
+    ZapIn {
+        offer_asset: Asset,
+        /// Fails if less LP shares would be minted
+        min_shares: Option<Uint128>,
+        /// Fails if executed after the deadline
+        deadline: Option<Deadline>,
+    },
+    /// Sends the accrued protocol fees to the collector set in the factory
+    CollectProtocolFees {},
+    /// Sets the reserves to the actual balances, e.g. to add donations to the pool
+    Sync {},
+    /// Sends the balances above the reserves to `to`
+    Skim { to: HumanAddr },
+    /// Lends `asset`, up to the whole reserve, to `receiver` and calls it back with
+    /// `FlashSwapReceiverMsg`. The pool must be paid back in the same transaction, in either
+    /// asset, so that the curve invariant holds after the swap fee.
+    FlashSwap {
+        asset: Asset,
+        receiver: HumanAddr,
+        receiver_code_hash: String,
+        msg: Option<Binary>,
+    },
+    /// Checks the invariant after a flash swap, only the pair itself can execute it
+    FinalizeFlashSwap {},
+    /// Sends the reserves to the pair replacing this one and stops this pair, only the
+    /// factory can execute it
+    MigrateReserves {
+        new_pair: HumanAddr,
+        new_pair_code_hash: String,
+    },
+    /// Takes over the reserves of the replaced pair and mints its LP supply to this pair, to
+    /// exchange for its LP tokens. Only the replaced pair can execute it.
+    FinalizeMigration {
+        reserves: [Uint128; 2],
+        total_share: Uint128,
+    },
+    /// Viewing key the sender's ledger is queried with. Only its hash is stored.
+    SetViewingKey {
+        key: String,
+        padding: Option<String>,
+    },
+    /// Rejects the sender's query permits named `permit_name` from now on
+    RevokePermit {
+        permit_name: String,
+        padding: Option<String>,
+    },
+    /// Sells the native `offer_asset` for at least `price` of the other asset per unit, filled
+    /// against the pools by the swaps that move the price there
+    PlaceLimitOrder { offer_asset: Asset, price: Decimal },
+    /// Cancels what is left of an order of the sender and pays it back
+    CancelLimitOrder { order_id: u64 },
+    /// Acknowledges the swaps a best-effort swap data endpoint read, up to `seq`, authenticated
+    /// by the key the factory registered it with
+    AckSwapData {
+        endpoint: HumanAddr,
+        key: String,
+        seq: u64,
+    },
+}
+
+/// Callback a flash swap receiver gets after the borrowed asset was sent to it.
+/// Tokens must be paid back with a plain `transfer`, `send` is rejected while the pair is
+/// locked by the flash swap.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FlashSwapReceiverMsg {
+    FlashSwapCallback {
+        /// Account that requested the flash swap
+        sender: HumanAddr,
+        asset: Asset,
+        msg: Option<Binary>,
     },
 }
 
@@ -38,8 +120,35 @@ pub enum Cw20HookMsg {
         belief_price: Option<Decimal>,
         max_spread: Option<Decimal>,
         to: Option<HumanAddr>,
+        /// Exact output: buy this amount of the other asset, the sent amount is the most to
+        /// spend and the unused part is refunded
+        ask_amount: Option<Uint128>,
+        /// Fails if executed after the deadline
+        deadline: Option<Deadline>,
+    },
+    /// Provide liquidity with a single token, part of it is swapped into the other asset first
+    ZapIn {
+        min_shares: Option<Uint128>,
+        /// Fails if executed after the deadline
+        deadline: Option<Deadline>,
+    },
+    WithdrawLiquidity {
+        /// Fails if executed after the deadline
+        deadline: Option<Deadline>,
+    },
+    /// Withdraw liquidity as `ask_asset` only, the other asset is swapped into it
+    ZapOut {
+        ask_asset: AssetInfo,
+        /// Fails if less `ask_asset` would be returned
+        min_return: Option<Uint128>,
+        /// Fails if executed after the deadline
+        deadline: Option<Deadline>,
     },
-    WithdrawLiquidity {},
+    /// Sent with LP tokens of the pair this one replaces: they are burned and as many LP
+    /// tokens of this pair are sent back
+    MigrateLiquidity {},
+    /// Sells the sent tokens for at least `price` of the other asset per token
+    PlaceLimitOrder { price: Decimal },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -47,8 +156,76 @@ pub enum Cw20HookMsg {
 pub enum QueryMsg {
     Pair {},
     Pool {},
-    Simulation { offer_asset: Asset },
-    ReverseSimulation { ask_asset: Asset },
+    Simulation {
+        offer_asset: Asset,
+    },
+    ReverseSimulation {
+        ask_asset: Asset,
+    },
+    /// Uniswap v2 style price accumulators as of the last update
+    CumulativePrices {},
+    /// Time weighted average prices between two block times, in seconds
+    Twap {
+        start_time: u64,
+        end_time: u64,
+    },
+    /// Protocol fees accrued but not collected yet
+    ProtocolFees {},
+    /// Emergency stop level set in the factory
+    Status {},
+    /// Swap fee charged now, and the volatility it follows with a dynamic fee
+    SwapFee {},
+    /// Largest price move allowed within a block and the prices it applies to
+    CircuitBreaker {},
+    /// Pair this one replaces and its LP token, if any
+    Migration {},
+    /// Volume, trade count and commission per interval, latest first.
+    /// `start_after` is a bucket start time, only older buckets are returned.
+    Stats {
+        interval: StatsInterval,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    /// Deposits, withdrawals and position of an account, authenticated by its viewing key
+    Ledger {
+        address: HumanAddr,
+        key: String,
+    },
+    /// Open limit orders of an account, authenticated by its viewing key
+    LimitOrders {
+        address: HumanAddr,
+        key: String,
+    },
+    /// Queries of the account that signed `permit`, instead of a viewing key. The permit must
+    /// list the pair in `allowed_tokens` and grant the `owner` permission.
+    WithPermit {
+        permit: Permit,
+        query: QueryWithPermit,
+    },
+    /// Swaps kept for a best-effort swap data endpoint, authenticated by the key the factory
+    /// registered it with. Returns a `SwapDataResponse`.
+    SwapData {
+        endpoint: HumanAddr,
+        key: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryWithPermit {
+    /// Same as `QueryMsg::Ledger`
+    Ledger {},
+    /// Same as `QueryMsg::LimitOrders`
+    LimitOrders {},
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StatsInterval {
+    Hourly,
+    Daily,
 }
 
 // We define a custom struct for each query response
@@ -73,3 +250,121 @@ pub struct ReverseSimulationResponse {
     pub spread_amount: Uint128,
     pub commission_amount: Uint128,
 }
+
+/// CumulativePricesResponse returns the price accumulators.
+/// Prices are scaled by 10^18 and accumulated per second. The accumulators wrap around on
+/// overflow, so only the difference between two readings is meaningful.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CumulativePricesResponse {
+    pub price0_cumulative_last: Uint128,
+    pub price1_cumulative_last: Uint128,
+    pub block_time_last: u64,
+}
+
+/// TwapResponse returns time weighted average prices.
+/// `price0_average` is asset 0 denominated in asset 1, `price1_average` the other way around.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TwapResponse {
+    pub price0_average: Decimal,
+    pub price1_average: Decimal,
+}
+
+/// ProtocolFeesResponse returns the protocol fees waiting to be collected
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ProtocolFeesResponse {
+    pub assets: [Asset; 2],
+}
+
+/// StatusResponse returns the stricter of the pair's and the global status in the factory
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct StatusResponse {
+    pub status: PairStatusLevel,
+}
+
+/// SwapFeeResponse returns the swap fee of the next swap if the pools don't move before it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SwapFeeResponse {
+    pub swap_fee: Fee,
+    pub dynamic_fee: Option<DynamicFee>,
+    /// Average relative price change between blocks, as of the last block the pair was used in
+    pub volatility: Decimal,
+}
+
+/// CircuitBreakerResponse returns `price0` at the start of `block_time`, the last block the
+/// pair was used in, and now. Swaps in a later block start from the current price.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CircuitBreakerResponse {
+    pub max_price_move: Option<Decimal>,
+    pub block_time: u64,
+    pub block_price: Option<Decimal>,
+    pub price: Option<Decimal>,
+    /// Band `price0` must stay in during `block_time`, transactions that would leave it fail
+    pub min_price: Option<Decimal>,
+    pub max_price: Option<Decimal>,
+}
+
+/// MigrationResponse returns the pair this one replaces and its LP token, which can be sent
+/// with the `migrate_liquidity` hook
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MigrationResponse {
+    pub old_pair: Option<HumanAddr>,
+    pub old_liquidity_token: Option<HumanAddr>,
+    /// Pair that took over the reserves, this pair is stopped
+    pub new_pair: Option<HumanAddr>,
+}
+
+/// StatsResponse returns the intervals that had trades, latest first
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct StatsResponse {
+    pub buckets: Vec<StatsBucketResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct StatsBucketResponse {
+    pub start_time: u64,
+    /// Offered amounts of each asset
+    pub volume: [Asset; 2],
+    pub trade_count: u64,
+    /// Commission kept by the pool for liquidity providers and the protocol
+    pub commission: [Asset; 2],
+}
+
+/// LedgerResponse returns what an account deposited and withdrew through the pair and the
+/// position of the shares it holds according to the ledger.
+/// `hodl_value`, `position_value` and `impermanent_loss` are denominated in asset 1 at the
+/// current spot price.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LedgerResponse {
+    pub deposited: [Asset; 2],
+    pub withdrawn: [Asset; 2],
+    pub share: Uint128,
+    /// Part of the deposits backing `share`
+    pub cost_basis: [Asset; 2],
+    /// Assets `share` withdraws right now
+    pub current_value: [Asset; 2],
+    /// Swap fees earned by `share` since it was deposited, included in `current_value`
+    pub fees_earned: [Asset; 2],
+    /// Value of `cost_basis`, had the assets been held instead
+    pub hodl_value: Uint128,
+    pub position_value: Uint128,
+    /// `hodl_value` minus `position_value` without the fees earned, zero if it is negative
+    pub impermanent_loss: Uint128,
+}
+
+/// LimitOrdersResponse returns the orders of an account, oldest first
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LimitOrdersResponse {
+    pub orders: Vec<LimitOrderResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LimitOrderResponse {
+    pub order_id: u64,
+    /// Amount placed
+    pub offer_asset: Asset,
+    pub price: Decimal,
+    /// Part of the offer not sold yet
+    pub remaining: Uint128,
+    /// What the sold part returned, paid out as the order fills
+    pub proceeds: Asset,
+}
@@ -0,0 +1,390 @@
+use std::str::FromStr;
+
+use cosmwasm_std::{Decimal, StdError, StdResult, Storage, Uint128};
+use primitive_types::U256;
+
+use secretswap::{CurveType, DynamicFee, Fee};
+
+use crate::math::{decimal_multiplication, decimal_subtraction};
+use crate::stableswap::compute_d;
+use crate::state::{
+    read_observation, read_price_cumulative, read_reserves, read_volatility, store_observation,
+    store_price_cumulative, store_volatility, Observation, PriceCumulative,
+};
+use crate::u256_math::*;
+
+/// Prices are fixed point numbers with 18 decimals, like `Decimal`
+const PRICE_SCALE: u128 = 1_000_000_000_000_000_000;
+/// Size of the observation ring buffer
+pub const OBSERVATION_CAPACITY: u32 = 1024;
+/// Seconds a price change weighs on the volatility, about 100 blocks
+pub const VOLATILITY_WINDOW: u64 = 600;
+
+fn price_scale() -> U256 {
+    U256::from(PRICE_SCALE)
+}
+
+/// Marginal prices of the pool `(price0, price1)`, scaled by `PRICE_SCALE`.
+/// `price0` is asset 0 denominated in asset 1 and `price1` is its inverse.
+/// Returns `None` while one side of the pool is empty.
+pub fn spot_price(curve: &CurveType, pools: [Uint128; 2]) -> Option<(U256, U256)> {
+    let x = U256::from(pools[0].u128());
+    let y = U256::from(pools[1].u128());
+    if x.is_zero() || y.is_zero() {
+        return None;
+    }
+
+    // price0 = nom / denom
+    let (nom, denom) = match curve {
+        CurveType::ConstantProduct {} => (y, x),
+        // (y / w1) / (x / w0)
+        CurveType::Weighted { weights } => (
+            y.checked_mul(U256::from(weights[0]))?,
+            x.checked_mul(U256::from(weights[1]))?,
+        ),
+        // -dy/dx of the invariant, with x and y normalized by D:
+        // (16 * A * x^2 * y^2 + y) / (16 * A * x^2 * y^2 + x)
+        CurveType::StableSwap { amp } => {
+            let d = compute_d(*amp, x, y)?;
+            let x = div(mul(Some(x), Some(price_scale())), Some(d));
+            let y = div(mul(Some(y), Some(price_scale())), Some(d));
+            let xy = div(mul(x, y), Some(price_scale()));
+            let x2y2 = div(mul(xy, xy), Some(price_scale()));
+            let term = mul(x2y2, Some(U256::from(16u64).checked_mul(U256::from(*amp))?));
+            (add(term, y)?, add(term, x)?)
+        }
+    };
+
+    let price0 = div(mul(Some(nom), Some(price_scale())), Some(denom))?;
+    let price1 = div(mul(Some(denom), Some(price_scale())), Some(nom))?;
+    Some((price0, price1))
+}
+
+/// Accumulates `price * elapsed seconds` since the last update, as in Uniswap v2.
+/// Must be called before the pools change, with the pools the previous transaction left.
+/// Records at most one observation per block time.
+pub fn update_price_cumulative<S: Storage>(
+    storage: &mut S,
+    curve: &CurveType,
+    pools: [Uint128; 2],
+    block_time: u64,
+) -> StdResult<()> {
+    let mut cumulative = read_price_cumulative(storage)?;
+    if block_time <= cumulative.block_time_last {
+        return Ok(());
+    }
+
+    let cumulative_block_time_last = cumulative.block_time_last;
+    let elapsed = U256::from(block_time - cumulative_block_time_last);
+    if let Some((price0, price1)) = spot_price(curve, pools) {
+        // accumulators are meant to overflow, only their differences are meaningful
+        cumulative.price0_cumulative_last =
+            accumulate(cumulative.price0_cumulative_last, price0, elapsed);
+        cumulative.price1_cumulative_last =
+            accumulate(cumulative.price1_cumulative_last, price1, elapsed);
+    }
+    cumulative.block_time_last = block_time;
+    update_volatility(
+        storage,
+        curve,
+        pools,
+        block_time - cumulative_block_time_last,
+    )?;
+
+    record_observation(storage, &mut cumulative)
+}
+
+/// Moves the volatility towards the relative change of `price0` since the start of the last
+/// block the pair was used in, weighing it by the `elapsed` seconds out of `VOLATILITY_WINDOW`.
+/// Changes are capped at 100%.
+fn update_volatility<S: Storage>(
+    storage: &mut S,
+    curve: &CurveType,
+    pools: [Uint128; 2],
+    elapsed: u64,
+) -> StdResult<()> {
+    let mut volatility = read_volatility(storage)?;
+
+    let prices = (
+        spot_price(curve, volatility.block_pools),
+        spot_price(curve, pools),
+    );
+    if let (Some((last_price, _)), Some((price, _))) = prices {
+        let change = relative_change(last_price, price).min(price_scale());
+
+        // average_change = (average_change * (window - weight) + change * weight) / window
+        let window = U256::from(VOLATILITY_WINDOW);
+        let weight = U256::from(elapsed.min(VOLATILITY_WINDOW));
+        let average_change = (U256::from(volatility.average_change.u128()) * (window - weight)
+            + change * weight)
+            / window;
+        volatility.average_change = Uint128(average_change.low_u128());
+    }
+    volatility.block_pools = pools;
+
+    store_volatility(storage, &volatility)
+}
+
+/// `|price - start_price| / start_price`, scaled by `PRICE_SCALE`
+fn relative_change(start_price: U256, price: U256) -> U256 {
+    let price_diff = if price > start_price {
+        price - start_price
+    } else {
+        start_price - price
+    };
+    div(
+        mul(Some(price_diff), Some(price_scale())),
+        Some(start_price),
+    )
+    .unwrap_or_else(U256::max_value)
+}
+
+/// Circuit breaker, fails when the reserves moved `price0` more than `max_price_move` away
+/// from its price at the start of the block. Must be called once the reserves are stored, in
+/// a transaction that updated the price accumulators. Pools that were empty at the start of
+/// the block have no price to compare with. The error reports the trip: the block height
+/// and the attempted move.
+pub fn assert_price_band<S: Storage>(
+    storage: &S,
+    curve: &CurveType,
+    max_price_move: Option<Decimal>,
+    block_height: u64,
+) -> StdResult<()> {
+    let max_price_move = match max_price_move {
+        Some(max_price_move) => max_price_move,
+        None => return Ok(()),
+    };
+
+    let prices = (
+        spot_price(curve, read_volatility(storage)?.block_pools),
+        spot_price(curve, read_reserves(storage)?),
+    );
+    let price_move = match prices {
+        (Some((start_price, _)), Some((price, _))) => relative_change(start_price, price),
+        (Some(_), None) => price_scale(),
+        _ => return Ok(()),
+    };
+
+    let max_move = U256::from((Uint128(PRICE_SCALE) * max_price_move).u128());
+    if price_move > max_move {
+        return Err(StdError::generic_err(format!(
+            "Circuit breaker tripped at height {}: the price would move {} from the start of the block, the limit is {}",
+            block_height,
+            decimal_from_scaled(price_move.min(U256::from(u128::MAX)).low_u128())?,
+            max_price_move
+        )));
+    }
+
+    Ok(())
+}
+
+/// `price0` at the start of the last block the pair was used in and now, `None` while one side
+/// of the pools is empty
+pub fn read_block_prices<S: Storage>(
+    storage: &S,
+    curve: &CurveType,
+) -> StdResult<(Option<Decimal>, Option<Decimal>)> {
+    let to_decimal = |pools: [Uint128; 2]| -> StdResult<Option<Decimal>> {
+        spot_price(curve, pools)
+            .map(|(price0, _)| decimal_from_scaled(price0.min(U256::from(u128::MAX)).low_u128()))
+            .transpose()
+    };
+
+    Ok((
+        to_decimal(read_volatility(storage)?.block_pools)?,
+        to_decimal(read_reserves(storage)?)?,
+    ))
+}
+
+/// Lowest and highest `price0` the circuit breaker lets transactions end at in the block that
+/// started at `block_price`
+pub fn compute_price_band(
+    block_price: Decimal,
+    max_price_move: Decimal,
+) -> StdResult<(Decimal, Decimal)> {
+    let band = decimal_multiplication(block_price, max_price_move);
+    let lower = if max_price_move >= Decimal::one() {
+        Decimal::zero()
+    } else {
+        decimal_subtraction(block_price, band)?
+    };
+    Ok((lower, block_price + band))
+}
+
+/// Volatility of the pair as of the last block it was used in
+pub fn read_average_change<S: Storage>(storage: &S) -> StdResult<Decimal> {
+    decimal_from_scaled(read_volatility(storage)?.average_change.u128())
+}
+
+/// Fee between `min_fee` and `max_fee`, linear in the volatility up to `max_volatility`
+pub fn compute_dynamic_fee<S: Storage>(storage: &S, dynamic_fee: &DynamicFee) -> StdResult<Fee> {
+    let rate = |fee: &Fee| -> U256 {
+        div(
+            mul(
+                Some(U256::from(fee.commission_rate_nom.u128())),
+                Some(price_scale()),
+            ),
+            Some(U256::from(fee.commission_rate_denom.u128())),
+        )
+        .unwrap_or_default()
+    };
+    let min_rate = rate(&dynamic_fee.min_fee);
+    let max_rate = rate(&dynamic_fee.max_fee).max(min_rate);
+
+    let max_volatility = U256::from((Uint128(PRICE_SCALE) * dynamic_fee.max_volatility).u128());
+    let volatility = U256::from(read_volatility(storage)?.average_change.u128());
+    let fee_rate = if volatility >= max_volatility {
+        max_rate
+    } else {
+        min_rate + (max_rate - min_rate) * volatility / max_volatility
+    };
+
+    Ok(Fee {
+        commission_rate_nom: Uint128(fee_rate.low_u128()),
+        commission_rate_denom: Uint128(PRICE_SCALE),
+    })
+}
+
+/// Starts the accumulators at the pair creation time
+pub fn init_price_cumulative<S: Storage>(storage: &mut S, block_time: u64) -> StdResult<()> {
+    let mut cumulative = PriceCumulative {
+        price0_cumulative_last: Uint128::zero(),
+        price1_cumulative_last: Uint128::zero(),
+        block_time_last: block_time,
+        observation_index: OBSERVATION_CAPACITY - 1,
+        observation_count: 0,
+    };
+
+    record_observation(storage, &mut cumulative)
+}
+
+fn record_observation<S: Storage>(
+    storage: &mut S,
+    cumulative: &mut PriceCumulative,
+) -> StdResult<()> {
+    cumulative.observation_index = (cumulative.observation_index + 1) % OBSERVATION_CAPACITY;
+    if cumulative.observation_count < OBSERVATION_CAPACITY {
+        cumulative.observation_count += 1;
+    }
+
+    store_observation(
+        storage,
+        cumulative.observation_index,
+        &Observation {
+            block_time: cumulative.block_time_last,
+            price0_cumulative: cumulative.price0_cumulative_last,
+            price1_cumulative: cumulative.price1_cumulative_last,
+        },
+    )?;
+    store_price_cumulative(storage, cumulative)
+}
+
+/// `cumulative + price * elapsed`, wrapping around on overflow
+fn accumulate(cumulative: Uint128, price: U256, elapsed: U256) -> Uint128 {
+    let growth = price.overflowing_mul(elapsed).0;
+    Uint128(cumulative.u128().wrapping_add(growth.low_u128()))
+}
+
+/// Accumulator values `(price0, price1)` at `time`.
+/// Between two observations the accumulators grow linearly, so they are interpolated exactly.
+/// After the latest observation they are extrapolated with the current spot price.
+fn cumulative_at<S: Storage>(
+    storage: &S,
+    curve: &CurveType,
+    pools: [Uint128; 2],
+    time: u64,
+) -> StdResult<(u128, u128)> {
+    let cumulative = read_price_cumulative(storage)?;
+
+    if time >= cumulative.block_time_last {
+        let elapsed = U256::from(time - cumulative.block_time_last);
+        let (price0, price1) = spot_price(curve, pools).unwrap_or((U256::zero(), U256::zero()));
+        return Ok((
+            accumulate(cumulative.price0_cumulative_last, price0, elapsed).u128(),
+            accumulate(cumulative.price1_cumulative_last, price1, elapsed).u128(),
+        ));
+    }
+
+    // ring buffer position of the n-th oldest observation
+    let oldest_index = (cumulative.observation_index + OBSERVATION_CAPACITY + 1
+        - cumulative.observation_count)
+        % OBSERVATION_CAPACITY;
+    let nth = |n: u32| -> StdResult<Observation> {
+        read_observation(storage, (oldest_index + n) % OBSERVATION_CAPACITY)
+    };
+
+    let oldest = nth(0)?;
+    if time < oldest.block_time {
+        return Err(StdError::generic_err(format!(
+            "No price observation before {}, the oldest one is at {}",
+            time, oldest.block_time
+        )));
+    }
+
+    // binary search for the last observation at or before `time`
+    let mut lo = 0u32;
+    let mut hi = cumulative.observation_count - 1;
+    while lo < hi {
+        let mid = (lo + hi + 1) / 2;
+        if nth(mid)?.block_time <= time {
+            lo = mid;
+        } else {
+            hi = mid - 1;
+        }
+    }
+
+    let before = nth(lo)?;
+    if before.block_time == time {
+        return Ok((
+            before.price0_cumulative.u128(),
+            before.price1_cumulative.u128(),
+        ));
+    }
+    let after = nth(lo + 1)?;
+
+    let interpolate = |start: Uint128, end: Uint128| -> u128 {
+        let growth = U256::from(end.u128().wrapping_sub(start.u128()))
+            * U256::from(time - before.block_time)
+            / U256::from(after.block_time - before.block_time);
+        start.u128().wrapping_add(growth.low_u128())
+    };
+
+    Ok((
+        interpolate(before.price0_cumulative, after.price0_cumulative),
+        interpolate(before.price1_cumulative, after.price1_cumulative),
+    ))
+}
+
+/// Time weighted average prices `(price0, price1)` between `start_time` and `end_time`
+pub fn compute_twap<S: Storage>(
+    storage: &S,
+    curve: &CurveType,
+    pools: [Uint128; 2],
+    start_time: u64,
+    end_time: u64,
+) -> StdResult<(Decimal, Decimal)> {
+    if end_time <= start_time {
+        return Err(StdError::generic_err(
+            "end_time must be greater than start_time",
+        ));
+    }
+
+    let start = cumulative_at(storage, curve, pools, start_time)?;
+    let end = cumulative_at(storage, curve, pools, end_time)?;
+    let elapsed = (end_time - start_time) as u128;
+
+    Ok((
+        decimal_from_scaled(end.0.wrapping_sub(start.0) / elapsed)?,
+        decimal_from_scaled(end.1.wrapping_sub(start.1) / elapsed)?,
+    ))
+}
+
+/// `Decimal` from a number scaled by `PRICE_SCALE`.
+/// `Decimal::from_ratio` would overflow for large prices, so the digits are parsed instead.
+fn decimal_from_scaled(value: u128) -> StdResult<Decimal> {
+    Decimal::from_str(&format!(
+        "{}.{:018}",
+        value / PRICE_SCALE,
+        value % PRICE_SCALE
+    ))
+}
@@ -0,0 +1,180 @@
+use bech32::ToBase32;
+use cosmwasm_std::{
+    to_vec, Api, Binary, Extern, HumanAddr, Querier, StdError, StdResult, Storage, Uint128,
+};
+use ripemd160::{Digest, Ripemd160};
+use schemars::JsonSchema;
+use secret_toolkit::crypto::sha_256;
+use serde::{Deserialize, Serialize};
+
+use crate::state::read_permit_revoked;
+
+/// Human readable part of the addresses permits are signed by
+pub const BECH32_PREFIX: &str = "secret";
+
+/// SNIP-24 query permit, signed offline by the account it authenticates
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Permit {
+    pub params: PermitParams,
+    pub signature: PermitSignature,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PermitParams {
+    /// Contracts the permit can be used with, the pair must be one of them
+    pub allowed_tokens: Vec<HumanAddr>,
+    /// Name the permit is revoked by
+    pub permit_name: String,
+    pub chain_id: String,
+    pub permissions: Vec<Permission>,
+}
+
+/// SNIP-24 permissions, the pair's queries need `owner`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    Allowance,
+    Balance,
+    History,
+    Owner,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PermitSignature {
+    pub pub_key: PubKey,
+    pub signature: Binary,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PubKey {
+    /// Always `tendermint/PubKeySecp256k1`
+    #[serde(rename = "type")]
+    pub r#type: String,
+    /// Compressed secp256k1 public key
+    pub value: Binary,
+}
+
+/// Amino sign doc the wallet signs, the fields are in the sorted order it is serialized in
+#[derive(Serialize)]
+struct SignDoc<'a> {
+    account_number: Uint128,
+    chain_id: &'a str,
+    fee: SignDocFee,
+    memo: &'a str,
+    msgs: [SignDocMsg<'a>; 1],
+    sequence: Uint128,
+}
+
+#[derive(Serialize)]
+struct SignDocFee {
+    amount: [SignDocCoin; 1],
+    gas: Uint128,
+}
+
+#[derive(Serialize)]
+struct SignDocCoin {
+    amount: Uint128,
+    denom: &'static str,
+}
+
+#[derive(Serialize)]
+struct SignDocMsg<'a> {
+    #[serde(rename = "type")]
+    r#type: &'static str,
+    value: SignDocParams<'a>,
+}
+
+#[derive(Serialize)]
+struct SignDocParams<'a> {
+    allowed_tokens: &'a [HumanAddr],
+    permissions: &'a [Permission],
+    permit_name: &'a str,
+}
+
+/// Bytes the wallet signs for `params`, the permit is a zero fee tx that is never broadcast
+pub fn permit_sign_bytes(params: &PermitParams) -> StdResult<Vec<u8>> {
+    to_vec(&SignDoc {
+        account_number: Uint128::zero(),
+        chain_id: &params.chain_id,
+        fee: SignDocFee {
+            amount: [SignDocCoin {
+                amount: Uint128::zero(),
+                denom: "uscrt",
+            }],
+            gas: Uint128(1),
+        },
+        memo: "",
+        msgs: [SignDocMsg {
+            r#type: "query_permit",
+            value: SignDocParams {
+                allowed_tokens: &params.allowed_tokens,
+                permissions: &params.permissions,
+                permit_name: &params.permit_name,
+            },
+        }],
+        sequence: Uint128::zero(),
+    })
+}
+
+/// Address of the account a secp256k1 public key belongs to
+pub fn pubkey_to_address(pubkey: &[u8]) -> StdResult<HumanAddr> {
+    let hash = Ripemd160::digest(&sha_256(pubkey));
+    bech32::encode(BECH32_PREFIX, hash.to_base32())
+        .map(HumanAddr)
+        .map_err(|err| StdError::generic_err(format!("Cannot encode the address: {}", err)))
+}
+
+/// Checks the permit was signed for `contract_addr` with `permission` and not revoked.
+/// Returns the account that signed it.
+pub fn validate_permit<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    permit: &Permit,
+    contract_addr: &HumanAddr,
+    permission: Permission,
+) -> StdResult<HumanAddr> {
+    if !permit.params.allowed_tokens.contains(contract_addr) {
+        return Err(StdError::generic_err(format!(
+            "Permit doesn't apply to {}",
+            contract_addr
+        )));
+    }
+    if !permit.params.permissions.contains(&permission) {
+        return Err(StdError::generic_err(format!(
+            "Permit doesn't grant the {:?} permission",
+            permission
+        )));
+    }
+
+    let pubkey = permit.signature.pub_key.value.as_slice();
+    let account = pubkey_to_address(pubkey)?;
+    if read_permit_revoked(
+        &deps.storage,
+        &deps.api.canonical_address(&account)?,
+        &permit.params.permit_name,
+    )? {
+        return Err(StdError::generic_err(format!(
+            "Permit {} was revoked",
+            permit.params.permit_name
+        )));
+    }
+
+    let hash = sha_256(&permit_sign_bytes(&permit.params)?);
+    let mut message = [0u8; 32];
+    message.copy_from_slice(&hash);
+    let verified = match (
+        libsecp256k1::PublicKey::parse_slice(pubkey, None),
+        libsecp256k1::Signature::parse_standard_slice(permit.signature.signature.as_slice()),
+    ) {
+        (Ok(pubkey), Ok(signature)) => {
+            libsecp256k1::verify(&libsecp256k1::Message::parse(&message), &signature, &pubkey)
+        }
+        _ => false,
+    };
+    if !verified {
+        return Err(StdError::generic_err(
+            "Failed to verify the permit signature",
+        ));
+    }
+
+    Ok(account)
+}
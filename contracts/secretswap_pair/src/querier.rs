@@ -4,26 +4,52 @@ use cosmwasm_std::{
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use secretswap::PairSettings;
+use secretswap::{AssetInfo, PairInfo, PairInfoRaw, PairSettings};
+
+use crate::msg::QueryMsg;
+use crate::oracle::compute_dynamic_fee;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryFactory {
-    PairSettings {},
+    PairSettings { asset_infos: Option<[AssetInfo; 2]> },
 }
 
+/// Settings the factory applies to this pair, including its swap fee override if any.
+/// With a dynamic fee, `swap_fee` is the fee for the volatility as of the last update.
 pub fn query_pair_settings<S: Storage, A: Api, Q: Querier>(
     deps: &Extern<S, A, Q>,
-    contract_addr: &HumanAddr,
-    code_hash: &String,
+    pair_info: &PairInfoRaw,
 ) -> StdResult<PairSettings> {
-    // load price form the oracle
-    let pair_settings: PairSettings =
+    let asset_infos = [
+        pair_info.asset_infos[0].to_normal(&deps)?,
+        pair_info.asset_infos[1].to_normal(&deps)?,
+    ];
+    let mut pair_settings: PairSettings =
         deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
-            callback_code_hash: code_hash.clone(),
-            contract_addr: contract_addr.clone(),
-            msg: to_binary(&QueryFactory::PairSettings {})?,
+            callback_code_hash: pair_info.factory.code_hash.clone(),
+            contract_addr: pair_info.factory.address.clone(),
+            msg: to_binary(&QueryFactory::PairSettings {
+                asset_infos: Some(asset_infos),
+            })?,
         }))?;
 
+    if let Some(dynamic_fee) = &pair_settings.dynamic_fee {
+        pair_settings.swap_fee = compute_dynamic_fee(&deps.storage, dynamic_fee)?;
+    }
+
     Ok(pair_settings)
 }
+
+/// Info of another pair contract, e.g. the one this pair migrates to
+pub fn query_other_pair_info<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    contract_addr: &HumanAddr,
+    code_hash: &str,
+) -> StdResult<PairInfo> {
+    deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+        callback_code_hash: code_hash.to_string(),
+        contract_addr: contract_addr.clone(),
+        msg: to_binary(&QueryMsg::Pair {})?,
+    }))
+}
@@ -0,0 +1,219 @@
+use cosmwasm_std::{StdError, StdResult, Uint128};
+use primitive_types::U256;
+
+use crate::u256_math::*;
+
+/// Number of coins in a pair
+const N_COINS: u64 = 2;
+/// Newton's method iterations before giving up on convergence
+const MAX_ITERATIONS: usize = 255;
+
+/// StableSwap invariant for two coins, from the Curve whitepaper:
+///
+/// A * n^n * (x + y) + D = A * D * n^n + D^(n+1) / (n^n * x * y)
+///
+/// Solved for D with Newton's method, as in Curve's `get_D`.
+pub fn compute_d(amp: u64, x: U256, y: U256) -> Option<U256> {
+    let n_coins = U256::from(N_COINS);
+    let sum = x.checked_add(y)?;
+    if sum.is_zero() {
+        return Some(U256::zero());
+    }
+
+    // Ann = A * n^n
+    let ann = U256::from(amp).checked_mul(U256::from(N_COINS * N_COINS))?;
+
+    let mut d = sum;
+    for _ in 0..MAX_ITERATIONS {
+        // d_p = D^(n+1) / (n^n * x * y)
+        let d_p = div(mul(Some(d), Some(d)), mul(Some(x), Some(n_coins)));
+        let d_p = div(mul(d_p, Some(d)), mul(Some(y), Some(n_coins)))?;
+
+        let d_prev = d;
+        // D = (Ann * S + D_P * n) * D / ((Ann - 1) * D + (n + 1) * D_P)
+        let numerator = mul(
+            add(mul(Some(ann), Some(sum)), mul(Some(d_p), Some(n_coins))),
+            Some(d),
+        );
+        let denominator = add(
+            mul(sub(Some(ann), Some(U256::one())), Some(d)),
+            mul(add(Some(n_coins), Some(U256::one())), Some(d_p)),
+        );
+        d = div(numerator, denominator)?;
+
+        if abs_diff(d, d_prev) <= U256::one() {
+            return Some(d);
+        }
+    }
+
+    None
+}
+
+/// Balance of one coin that keeps the invariant `d`, given the balance `x` of the other coin.
+/// Port of Curve's `get_y` for two coins.
+pub fn compute_y(amp: u64, x: U256, d: U256) -> Option<U256> {
+    let n_coins = U256::from(N_COINS);
+    let ann = U256::from(amp).checked_mul(U256::from(N_COINS * N_COINS))?;
+
+    // c = D^(n+1) / (n^n * x * Ann)
+    let c = div(mul(Some(d), Some(d)), mul(Some(x), Some(n_coins)));
+    let c = div(mul(c, Some(d)), mul(Some(ann), Some(n_coins)))?;
+    // b = x + D / Ann
+    let b = add(Some(x), div(Some(d), Some(ann)))?;
+
+    let mut y = d;
+    for _ in 0..MAX_ITERATIONS {
+        let y_prev = y;
+        // y = (y^2 + c) / (2 * y + b - D)
+        y = div(
+            add(mul(Some(y), Some(y)), Some(c)),
+            sub(add(mul(Some(y), Some(n_coins)), Some(b)), Some(d)),
+        )?;
+
+        if abs_diff(y, y_prev) <= U256::one() {
+            return Some(y);
+        }
+    }
+
+    None
+}
+
+fn abs_diff(a: U256, b: U256) -> U256 {
+    if a > b {
+        a - b
+    } else {
+        b - a
+    }
+}
+
+fn invariant(amp: u64, offer_pool: U256, ask_pool: U256) -> StdResult<U256> {
+    compute_d(amp, offer_pool, ask_pool).ok_or_else(|| {
+        StdError::generic_err(format!(
+            "Cannot calculate StableSwap invariant for offer_pool {} and ask_pool {}",
+            offer_pool, ask_pool
+        ))
+    })
+}
+
+/// StableSwap counterpart of `compute_swap`. The spread is measured against the 1:1 peg.
+pub fn compute_stable_swap(
+    amp: u64,
+    offer_pool: Uint128,
+    ask_pool: Uint128,
+    offer_amount: Uint128,
+    commission_rate_nom: Uint128,
+    commission_rate_denom: Uint128,
+) -> StdResult<(Uint128, Uint128, Uint128)> {
+    let offer_pool = U256::from(offer_pool.u128());
+    let ask_pool = U256::from(ask_pool.u128());
+    let offer_amount = U256::from(offer_amount.u128());
+
+    let d = invariant(amp, offer_pool, ask_pool)?;
+
+    // new_ask_pool = y(offer_pool + offer_amount, D)
+    let new_ask_pool = add(Some(offer_pool), Some(offer_amount))
+        .and_then(|new_offer_pool| compute_y(amp, new_offer_pool, d))
+        .ok_or_else(|| {
+            StdError::generic_err(format!(
+                "Cannot calculate new ask pool for offer_pool {} + offer_amount {}",
+                offer_pool, offer_amount
+            ))
+        })?;
+
+    // return_amount = ask_pool - new_ask_pool - 1, the extra unit covers rounding in y
+    let return_amount = ask_pool
+        .saturating_sub(new_ask_pool)
+        .saturating_sub(U256::one());
+
+    // spread = offer_amount - return_amount, measured against the peg
+    let spread_amount = offer_amount.saturating_sub(return_amount);
+
+    let commission_rate_nom = U256::from(commission_rate_nom.u128());
+    let commission_rate_denom = U256::from(commission_rate_denom.u128());
+    let commission_amount = div(
+        mul(Some(return_amount), Some(commission_rate_nom)),
+        Some(commission_rate_denom),
+    )
+    .ok_or_else(|| {
+        StdError::generic_err(format!(
+            "Cannot calculate return_amount {} * commission_rate_nom {} / commission_rate_denom {}",
+            return_amount, commission_rate_nom, commission_rate_denom
+        ))
+    })?;
+
+    // commission will be absorbed to pool
+    let return_amount = return_amount.saturating_sub(commission_amount);
+
+    Ok((
+        Uint128(return_amount.low_u128()),
+        Uint128(spread_amount.low_u128()),
+        Uint128(commission_amount.low_u128()),
+    ))
+}
+
+/// StableSwap counterpart of `compute_offer_amount`
+pub fn compute_stable_offer_amount(
+    amp: u64,
+    offer_pool: Uint128,
+    ask_pool: Uint128,
+    ask_amount: Uint128,
+    commission_rate_nom: u128,
+    commission_rate_denom: u128,
+) -> StdResult<(Uint128, Uint128, Uint128)> {
+    let offer_pool = U256::from(offer_pool.u128());
+    let ask_pool = U256::from(ask_pool.u128());
+    let ask_amount = U256::from(ask_amount.u128());
+    let commission_rate_nom = U256::from(commission_rate_nom);
+    let commission_rate_denom = U256::from(commission_rate_denom);
+
+    // before_commission_deduction = ceil(ask_amount / (1 - commission_rate))
+    let one_minus_commission = sub(Some(commission_rate_denom), Some(commission_rate_nom));
+    let before_commission_deduction = div(
+        sub(
+            add(
+                mul(Some(ask_amount), Some(commission_rate_denom)),
+                one_minus_commission,
+            ),
+            Some(U256::one()),
+        ),
+        one_minus_commission,
+    )
+    .ok_or_else(|| {
+        StdError::generic_err(format!(
+            "Cannot calculate ask_amount {} / (1 - {} / {})",
+            ask_amount, commission_rate_nom, commission_rate_denom
+        ))
+    })?;
+
+    if before_commission_deduction >= ask_pool {
+        return Err(StdError::generic_err(format!(
+            "Ask amount {} exceeds ask pool {}",
+            before_commission_deduction, ask_pool
+        )));
+    }
+
+    let d = invariant(amp, offer_pool, ask_pool)?;
+
+    // new_offer_pool = y(ask_pool - before_commission_deduction, D)
+    let new_offer_pool =
+        compute_y(amp, ask_pool - before_commission_deduction, d).ok_or_else(|| {
+            StdError::generic_err(format!(
+                "Cannot calculate new offer pool for ask_pool {} - ask_amount {}",
+                ask_pool, before_commission_deduction
+            ))
+        })?;
+
+    // offer_amount = new_offer_pool - offer_pool + 1, rounded in favor of the pool
+    let offer_amount = new_offer_pool
+        .saturating_sub(offer_pool)
+        .saturating_add(U256::one());
+
+    let spread_amount = offer_amount.saturating_sub(before_commission_deduction);
+    let commission_amount = before_commission_deduction.saturating_sub(ask_amount);
+
+    Ok((
+        Uint128(offer_amount.low_u128()),
+        Uint128(spread_amount.low_u128()),
+        Uint128(commission_amount.low_u128()),
+    ))
+}
@@ -1,9 +1,33 @@
-use cosmwasm_std::{StdResult, Storage};
-use cosmwasm_storage::{ReadonlySingleton, Singleton};
+use cosmwasm_std::{CanonicalAddr, Decimal, HumanAddr, StdResult, Storage, Uint128};
+use cosmwasm_storage::{Bucket, ReadonlyBucket, ReadonlySingleton, Singleton};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 
 use secretswap::PairInfoRaw;
 
+use crate::msg::StatsInterval;
+
 static KEY_PAIR_INFO: &[u8] = b"pair_info";
+static KEY_PRICE_CUMULATIVE: &[u8] = b"price_cumulative";
+static PREFIX_OBSERVATIONS: &[u8] = b"observations";
+static KEY_VOLATILITY: &[u8] = b"volatility";
+static KEY_PROTOCOL_FEES: &[u8] = b"protocol_fees";
+static KEY_FLASH_SWAP: &[u8] = b"flash_swap";
+static KEY_RESERVES: &[u8] = b"reserves";
+static KEY_MIGRATE_FROM: &[u8] = b"migrate_from";
+static KEY_MIGRATED_TO: &[u8] = b"migrated_to";
+static PREFIX_STATS_HOURLY: &[u8] = b"stats_hourly";
+static PREFIX_STATS_DAILY: &[u8] = b"stats_daily";
+static KEY_STATS_LAST_TIME: &[u8] = b"stats_last_time";
+static KEY_FEE_GROWTH: &[u8] = b"fee_growth";
+static PREFIX_LEDGER: &[u8] = b"ledger";
+static PREFIX_VIEWING_KEYS: &[u8] = b"viewing_keys";
+static PREFIX_REVOKED_PERMITS: &[u8] = b"revoked_permits";
+static KEY_ORDER_COUNT: &[u8] = b"order_count";
+static KEY_ORDER_ESCROW: &[u8] = b"order_escrow";
+static PREFIX_LIMIT_ORDERS: &[u8] = b"limit_orders";
+static PREFIX_ORDER_BOOK: &[u8] = b"order_book";
+static PREFIX_OWNER_ORDERS: &[u8] = b"owner_orders";
 
 pub fn store_pair_info<S: Storage>(storage: &mut S, data: &PairInfoRaw) -> StdResult<()> {
     Singleton::new(storage, KEY_PAIR_INFO).save(data)
@@ -12,3 +36,352 @@ pub fn store_pair_info<S: Storage>(storage: &mut S, data: &PairInfoRaw) -> StdRe
 pub fn read_pair_info<S: Storage>(storage: &S) -> StdResult<PairInfoRaw> {
     ReadonlySingleton::new(storage, KEY_PAIR_INFO).load()
 }
+
+/// Uniswap v2 style price accumulators
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PriceCumulative {
+    pub price0_cumulative_last: Uint128,
+    pub price1_cumulative_last: Uint128,
+    /// Block time of the last update, in seconds
+    pub block_time_last: u64,
+    /// Ring buffer position of the latest observation
+    pub observation_index: u32,
+    pub observation_count: u32,
+}
+
+/// Accumulator values recorded at a block time
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Observation {
+    pub block_time: u64,
+    pub price0_cumulative: Uint128,
+    pub price1_cumulative: Uint128,
+}
+
+pub fn store_price_cumulative<S: Storage>(
+    storage: &mut S,
+    data: &PriceCumulative,
+) -> StdResult<()> {
+    Singleton::new(storage, KEY_PRICE_CUMULATIVE).save(data)
+}
+
+pub fn read_price_cumulative<S: Storage>(storage: &S) -> StdResult<PriceCumulative> {
+    ReadonlySingleton::new(storage, KEY_PRICE_CUMULATIVE).load()
+}
+
+pub fn store_observation<S: Storage>(
+    storage: &mut S,
+    index: u32,
+    data: &Observation,
+) -> StdResult<()> {
+    Bucket::new(PREFIX_OBSERVATIONS, storage).save(&index.to_be_bytes(), data)
+}
+
+pub fn read_observation<S: Storage>(storage: &S, index: u32) -> StdResult<Observation> {
+    ReadonlyBucket::new(PREFIX_OBSERVATIONS, storage).load(&index.to_be_bytes())
+}
+
+/// Price movement the dynamic fee follows
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct Volatility {
+    /// Pools at the start of the last block the pair was used in
+    pub block_pools: [Uint128; 2],
+    /// Time weighted moving average of the relative change of `price0` between those
+    /// blocks, scaled by 10^18
+    pub average_change: Uint128,
+}
+
+pub fn store_volatility<S: Storage>(storage: &mut S, data: &Volatility) -> StdResult<()> {
+    Singleton::new(storage, KEY_VOLATILITY).save(data)
+}
+
+pub fn read_volatility<S: Storage>(storage: &S) -> StdResult<Volatility> {
+    Ok(ReadonlySingleton::new(storage, KEY_VOLATILITY)
+        .may_load()?
+        .unwrap_or_default())
+}
+
+/// Protocol fees accrued in the pair, aligned with `asset_infos`
+pub fn store_protocol_fees<S: Storage>(storage: &mut S, data: &[Uint128; 2]) -> StdResult<()> {
+    Singleton::new(storage, KEY_PROTOCOL_FEES).save(data)
+}
+
+pub fn read_protocol_fees<S: Storage>(storage: &S) -> StdResult<[Uint128; 2]> {
+    Ok(ReadonlySingleton::new(storage, KEY_PROTOCOL_FEES)
+        .may_load()?
+        .unwrap_or_default())
+}
+
+/// Pools accounted by the pair, aligned with `asset_infos`.
+/// Balances above them, besides the protocol fees, were donated and can be skimmed.
+pub fn store_reserves<S: Storage>(storage: &mut S, data: &[Uint128; 2]) -> StdResult<()> {
+    Singleton::new(storage, KEY_RESERVES).save(data)
+}
+
+pub fn read_reserves<S: Storage>(storage: &S) -> StdResult<[Uint128; 2]> {
+    Ok(ReadonlySingleton::new(storage, KEY_RESERVES)
+        .may_load()?
+        .unwrap_or_default())
+}
+
+/// Flash swap in progress, it locks the pair until it is finalized
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FlashSwap {
+    /// Reserves before the assets were sent out, aligned with `asset_infos`
+    pub pools_before: [Uint128; 2],
+    pub amounts_out: [Uint128; 2],
+}
+
+pub fn store_flash_swap<S: Storage>(storage: &mut S, data: &FlashSwap) -> StdResult<()> {
+    Singleton::new(storage, KEY_FLASH_SWAP).save(data)
+}
+
+pub fn read_flash_swap<S: Storage>(storage: &S) -> StdResult<Option<FlashSwap>> {
+    ReadonlySingleton::new(storage, KEY_FLASH_SWAP).may_load()
+}
+
+pub fn remove_flash_swap<S: Storage>(storage: &mut S) {
+    Singleton::<S, FlashSwap>::new(storage, KEY_FLASH_SWAP).remove()
+}
+
+/// Pair this one replaces, set at initialization. It hands its reserves over once and its LP
+/// tokens are exchanged 1:1 for LP tokens of this pair.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MigrateFrom {
+    pub pair: HumanAddr,
+    pub pair_code_hash: String,
+    pub liquidity_token: HumanAddr,
+    pub token_code_hash: String,
+}
+
+pub fn store_migrate_from<S: Storage>(storage: &mut S, data: &MigrateFrom) -> StdResult<()> {
+    Singleton::new(storage, KEY_MIGRATE_FROM).save(data)
+}
+
+pub fn read_migrate_from<S: Storage>(storage: &S) -> StdResult<Option<MigrateFrom>> {
+    ReadonlySingleton::new(storage, KEY_MIGRATE_FROM).may_load()
+}
+
+/// Pair that took over the reserves of this one. Set once the reserves are handed over, the
+/// pair is stopped from then on.
+pub fn store_migrated_to<S: Storage>(storage: &mut S, new_pair: &HumanAddr) -> StdResult<()> {
+    Singleton::new(storage, KEY_MIGRATED_TO).save(new_pair)
+}
+
+pub fn read_migrated_to<S: Storage>(storage: &S) -> StdResult<Option<HumanAddr>> {
+    ReadonlySingleton::new(storage, KEY_MIGRATED_TO).may_load()
+}
+
+/// Trading activity within one interval, amounts aligned with `asset_infos`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct StatsBucket {
+    /// Block time the interval starts at, in seconds
+    pub start_time: u64,
+    /// Offered amounts
+    pub volume: [Uint128; 2],
+    pub trade_count: u64,
+    /// Commission kept by the pool, in the asked asset
+    pub commission: [Uint128; 2],
+}
+
+fn stats_prefix(interval: StatsInterval) -> &'static [u8] {
+    match interval {
+        StatsInterval::Hourly => PREFIX_STATS_HOURLY,
+        StatsInterval::Daily => PREFIX_STATS_DAILY,
+    }
+}
+
+pub fn store_stats_bucket<S: Storage>(
+    storage: &mut S,
+    interval: StatsInterval,
+    slot: u64,
+    data: &StatsBucket,
+) -> StdResult<()> {
+    Bucket::new(stats_prefix(interval), storage).save(&slot.to_be_bytes(), data)
+}
+
+pub fn read_stats_bucket<S: Storage>(
+    storage: &S,
+    interval: StatsInterval,
+    slot: u64,
+) -> StdResult<Option<StatsBucket>> {
+    ReadonlyBucket::new(stats_prefix(interval), storage).may_load(&slot.to_be_bytes())
+}
+
+/// Block time of the latest recorded trade
+pub fn store_stats_last_time<S: Storage>(storage: &mut S, block_time: u64) -> StdResult<()> {
+    Singleton::new(storage, KEY_STATS_LAST_TIME).save(&block_time)
+}
+
+pub fn read_stats_last_time<S: Storage>(storage: &S) -> StdResult<Option<u64>> {
+    ReadonlySingleton::new(storage, KEY_STATS_LAST_TIME).may_load()
+}
+
+/// Liquidity provider fees accumulated per share, aligned with `asset_infos`.
+/// Wraps around on overflow like the price accumulators.
+pub fn store_fee_growth<S: Storage>(storage: &mut S, data: &[Uint128; 2]) -> StdResult<()> {
+    Singleton::new(storage, KEY_FEE_GROWTH).save(data)
+}
+
+pub fn read_fee_growth<S: Storage>(storage: &S) -> StdResult<[Uint128; 2]> {
+    Ok(ReadonlySingleton::new(storage, KEY_FEE_GROWTH)
+        .may_load()?
+        .unwrap_or_default())
+}
+
+/// Deposits and withdrawals of an account through the pair, amounts aligned with `asset_infos`
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct LedgerEntry {
+    pub deposited: [Uint128; 2],
+    pub withdrawn: [Uint128; 2],
+    /// Shares minted to the account minus the shares it withdrew
+    pub share: Uint128,
+    /// Part of the deposits backing `share`, reduced pro rata by withdrawals
+    pub cost_basis: [Uint128; 2],
+    pub fees_earned: [Uint128; 2],
+    /// Fee growth `fees_earned` was last settled at
+    pub fee_growth_last: [Uint128; 2],
+}
+
+pub fn store_ledger_entry<S: Storage>(
+    storage: &mut S,
+    account: &CanonicalAddr,
+    data: &LedgerEntry,
+) -> StdResult<()> {
+    Bucket::new(PREFIX_LEDGER, storage).save(account.as_slice(), data)
+}
+
+pub fn read_ledger_entry<S: Storage>(
+    storage: &S,
+    account: &CanonicalAddr,
+) -> StdResult<Option<LedgerEntry>> {
+    ReadonlyBucket::new(PREFIX_LEDGER, storage).may_load(account.as_slice())
+}
+
+/// Hashed viewing key of an account
+pub fn store_viewing_key<S: Storage>(
+    storage: &mut S,
+    account: &CanonicalAddr,
+    key_hash: &[u8],
+) -> StdResult<()> {
+    Bucket::new(PREFIX_VIEWING_KEYS, storage).save(account.as_slice(), &key_hash.to_vec())
+}
+
+pub fn read_viewing_key<S: Storage>(
+    storage: &S,
+    account: &CanonicalAddr,
+) -> StdResult<Option<Vec<u8>>> {
+    ReadonlyBucket::new(PREFIX_VIEWING_KEYS, storage).may_load(account.as_slice())
+}
+
+/// Query permits an account revoked, by name
+pub fn store_permit_revoked<S: Storage>(
+    storage: &mut S,
+    account: &CanonicalAddr,
+    permit_name: &str,
+) -> StdResult<()> {
+    Bucket::new(PREFIX_REVOKED_PERMITS, storage).save(
+        &[account.as_slice(), permit_name.as_bytes()].concat(),
+        &true,
+    )
+}
+
+pub fn read_permit_revoked<S: Storage>(
+    storage: &S,
+    account: &CanonicalAddr,
+    permit_name: &str,
+) -> StdResult<bool> {
+    Ok(ReadonlyBucket::new(PREFIX_REVOKED_PERMITS, storage)
+        .may_load(&[account.as_slice(), permit_name.as_bytes()].concat())?
+        .unwrap_or_default())
+}
+
+/// Order selling an asset of the pair for at least `price` of the other one
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LimitOrder {
+    pub owner: CanonicalAddr,
+    /// Position of the sold asset in `asset_infos`
+    pub offer_index: u8,
+    /// Least amount of the other asset per sold asset
+    pub price: Decimal,
+    pub offer_amount: Uint128,
+    /// Part of `offer_amount` not sold yet
+    pub remaining: Uint128,
+    /// Amount of the other asset the sold part returned, paid out as it fills
+    pub proceeds: Uint128,
+}
+
+/// Open order of the book, the book is sorted by price and then by order id
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BookEntry {
+    pub price: Decimal,
+    pub order_id: u64,
+}
+
+pub fn store_limit_order<S: Storage>(
+    storage: &mut S,
+    order_id: u64,
+    data: &LimitOrder,
+) -> StdResult<()> {
+    Bucket::new(PREFIX_LIMIT_ORDERS, storage).save(&order_id.to_be_bytes(), data)
+}
+
+pub fn read_limit_order<S: Storage>(storage: &S, order_id: u64) -> StdResult<Option<LimitOrder>> {
+    ReadonlyBucket::new(PREFIX_LIMIT_ORDERS, storage).may_load(&order_id.to_be_bytes())
+}
+
+pub fn remove_limit_order<S: Storage>(storage: &mut S, order_id: u64) {
+    Bucket::<S, LimitOrder>::new(PREFIX_LIMIT_ORDERS, storage).remove(&order_id.to_be_bytes())
+}
+
+/// Number of orders ever placed, the id of the next order
+pub fn store_order_count<S: Storage>(storage: &mut S, count: u64) -> StdResult<()> {
+    Singleton::new(storage, KEY_ORDER_COUNT).save(&count)
+}
+
+pub fn read_order_count<S: Storage>(storage: &S) -> StdResult<u64> {
+    Ok(ReadonlySingleton::new(storage, KEY_ORDER_COUNT)
+        .may_load()?
+        .unwrap_or_default())
+}
+
+/// Open orders selling the asset at `offer_index`
+pub fn store_order_book<S: Storage>(
+    storage: &mut S,
+    offer_index: u8,
+    data: &[BookEntry],
+) -> StdResult<()> {
+    Bucket::new(PREFIX_ORDER_BOOK, storage).save(&[offer_index], &data.to_vec())
+}
+
+pub fn read_order_book<S: Storage>(storage: &S, offer_index: u8) -> StdResult<Vec<BookEntry>> {
+    Ok(ReadonlyBucket::new(PREFIX_ORDER_BOOK, storage)
+        .may_load(&[offer_index])?
+        .unwrap_or_default())
+}
+
+/// Ids of the open orders of an account
+pub fn store_owner_orders<S: Storage>(
+    storage: &mut S,
+    owner: &CanonicalAddr,
+    data: &[u64],
+) -> StdResult<()> {
+    Bucket::new(PREFIX_OWNER_ORDERS, storage).save(owner.as_slice(), &data.to_vec())
+}
+
+pub fn read_owner_orders<S: Storage>(storage: &S, owner: &CanonicalAddr) -> StdResult<Vec<u64>> {
+    Ok(ReadonlyBucket::new(PREFIX_OWNER_ORDERS, storage)
+        .may_load(owner.as_slice())?
+        .unwrap_or_default())
+}
+
+/// Unsold amounts of the limit orders, aligned with `asset_infos`. They are not part of the
+/// pools.
+pub fn store_order_escrow<S: Storage>(storage: &mut S, data: &[Uint128; 2]) -> StdResult<()> {
+    Singleton::new(storage, KEY_ORDER_ESCROW).save(data)
+}
+
+pub fn read_order_escrow<S: Storage>(storage: &S) -> StdResult<[Uint128; 2]> {
+    Ok(ReadonlySingleton::new(storage, KEY_ORDER_ESCROW)
+        .may_load()?
+        .unwrap_or_default())
+}
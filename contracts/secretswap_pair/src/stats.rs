@@ -0,0 +1,110 @@
+use cosmwasm_std::{StdResult, Storage, Uint128};
+
+use crate::msg::StatsInterval;
+use crate::state::{
+    read_stats_bucket, read_stats_last_time, store_stats_bucket, store_stats_last_time, StatsBucket,
+};
+
+/// Hourly buckets kept, a week
+pub const HOURLY_CAPACITY: u64 = 168;
+/// Daily buckets kept, a year
+pub const DAILY_CAPACITY: u64 = 365;
+
+impl StatsInterval {
+    /// Length of the interval in seconds
+    pub fn seconds(self) -> u64 {
+        match self {
+            StatsInterval::Hourly => 3_600,
+            StatsInterval::Daily => 86_400,
+        }
+    }
+
+    /// Number of buckets kept in the ring buffer
+    pub fn capacity(self) -> u64 {
+        match self {
+            StatsInterval::Hourly => HOURLY_CAPACITY,
+            StatsInterval::Daily => DAILY_CAPACITY,
+        }
+    }
+
+    fn start_time(self, block_time: u64) -> u64 {
+        block_time - block_time % self.seconds()
+    }
+
+    fn slot(self, start_time: u64) -> u64 {
+        start_time / self.seconds() % self.capacity()
+    }
+}
+
+/// Adds a swap of `offer_amount` of asset `offer_index` to the buckets of `block_time`.
+/// The commission is in the asked asset. A slot still holding a bucket from a previous
+/// round of the ring buffer is reset first.
+pub fn record_trade<S: Storage>(
+    storage: &mut S,
+    block_time: u64,
+    offer_index: usize,
+    offer_amount: Uint128,
+    commission_amount: Uint128,
+) -> StdResult<()> {
+    for interval in [StatsInterval::Hourly, StatsInterval::Daily]
+        .iter()
+        .copied()
+    {
+        let start_time = interval.start_time(block_time);
+        let slot = interval.slot(start_time);
+        let mut bucket = match read_stats_bucket(storage, interval, slot)? {
+            Some(bucket) if bucket.start_time == start_time => bucket,
+            _ => StatsBucket {
+                start_time,
+                volume: [Uint128::zero(); 2],
+                trade_count: 0,
+                commission: [Uint128::zero(); 2],
+            },
+        };
+
+        bucket.volume[offer_index] = bucket.volume[offer_index] + offer_amount;
+        bucket.trade_count += 1;
+        bucket.commission[1 - offer_index] = bucket.commission[1 - offer_index] + commission_amount;
+        store_stats_bucket(storage, interval, slot, &bucket)?;
+    }
+
+    store_stats_last_time(storage, block_time)
+}
+
+/// Buckets with trades, latest first, starting before `start_after` when it is given.
+/// Queries have no block time, so the ring buffer is walked back from the latest trade.
+pub fn read_stats<S: Storage>(
+    storage: &S,
+    interval: StatsInterval,
+    start_after: Option<u64>,
+    limit: usize,
+) -> StdResult<Vec<StatsBucket>> {
+    let last_time = match read_stats_last_time(storage)? {
+        Some(last_time) => last_time,
+        None => return Ok(vec![]),
+    };
+    let latest_start = interval.start_time(last_time);
+    let oldest_start = latest_start.saturating_sub((interval.capacity() - 1) * interval.seconds());
+
+    let mut start_time = match start_after {
+        Some(0) => return Ok(vec![]),
+        Some(start_after) => latest_start.min(interval.start_time(start_after - 1)),
+        None => latest_start,
+    };
+
+    let mut buckets = vec![];
+    while buckets.len() < limit && start_time >= oldest_start {
+        if let Some(bucket) = read_stats_bucket(storage, interval, interval.slot(start_time))? {
+            if bucket.start_time == start_time {
+                buckets.push(bucket);
+            }
+        }
+
+        if start_time < interval.seconds() {
+            break;
+        }
+        start_time -= interval.seconds();
+    }
+
+    Ok(buckets)
+}
@@ -1,21 +1,58 @@
-use cosmwasm_std::testing::{mock_env, MOCK_CONTRACT_ADDR};
+use std::str::FromStr;
+
+use cosmwasm_std::testing::{mock_env, MockApi, MockStorage, MOCK_CONTRACT_ADDR};
 use cosmwasm_std::{
-    log, to_binary, BankMsg, BlockInfo, Coin, CosmosMsg, Decimal, Env, HandleResponse, HumanAddr,
-    StdError, Uint128, WasmMsg,
+    from_binary, log, to_binary, Api, BankMsg, Binary, BlockInfo, CanonicalAddr, Coin, CosmosMsg,
+    Decimal, Env, Extern, HandleResponse, HandleResult, HumanAddr, StdError, Uint128, WasmMsg,
 };
 
-use cw20::{Cw20HandleMsg, Cw20ReceiveMsg, MinterResponse};
-use secretswap::{Asset, AssetInfo, InitHook, PairInfo, PairInitMsg, TokenInitMsg};
+use secret_toolkit::crypto::sha_256;
+use secret_toolkit::snip20;
+use secretswap::{
+    ack_swap_data, deliver_swap_data, query_swap_data, Asset, AssetInfo, CurveType, Deadline,
+    DynamicFee, Factory, Fee, InitHook, MigrateFrom, PairInfo, PairInitMsg, PairSettings,
+    PairStatusLevel, ProtocolFee, SwapData, SwapDataEndpoint, SwapResponse, TokenInitMsg,
+    WithdrawResponse, ZapInResponse, ZapOutResponse,
+};
 
 use crate::contract::{
-    assert_max_spread, handle, init, query_pair_info, query_pool, query_reverse_simulation,
-    query_simulation,
+    assert_max_spread, compute_deposit_share, compute_initial_share, compute_offer_amount,
+    compute_swap, compute_used_deposits, handle, init, query_circuit_breaker, query_migration,
+    query_pair_info, query_pool, query_protocol_fees, query_reverse_simulation, query_simulation,
+    MINIMUM_LIQUIDITY,
+};
+use crate::flash_swap::assert_flash_swap_invariant;
+use crate::ledger::{
+    accrue_fees, assert_viewing_key, compute_position, record_deposit, record_withdrawal,
+    set_viewing_key,
+};
+use crate::limit_order::{
+    cancel_limit_order, compute_fill_amount, fill_limit_orders, place_limit_order, MAX_OPEN_ORDERS,
+    MAX_ORDERS_PER_OWNER,
 };
-use crate::math::{decimal_multiplication, reverse_decimal};
-use crate::mock_querier::mock_dependencies;
+use crate::mock_querier::{mock_dependencies, WasmMockQuerier};
 use crate::msg::{
     Cw20HookMsg, HandleMsg, PoolResponse, ReverseSimulationResponse, SimulationResponse,
+    StatsInterval,
+};
+use crate::oracle::{
+    assert_price_band, compute_dynamic_fee, compute_twap, init_price_cumulative,
+    read_average_change, update_price_cumulative,
+};
+use crate::permit::{
+    permit_sign_bytes, pubkey_to_address, validate_permit, Permission, Permit, PermitParams,
+    PermitSignature, PubKey,
+};
+use crate::stableswap::{compute_d, compute_stable_offer_amount, compute_stable_swap};
+use crate::state::{
+    read_flash_swap, read_order_escrow, read_owner_orders, read_reserves, store_protocol_fees,
+    store_reserves,
+};
+use crate::stats::{read_stats, record_trade, HOURLY_CAPACITY};
+use crate::weighted::{
+    compute_weighted_initial_share, compute_weighted_offer_amount, compute_weighted_swap,
 };
+use crate::zap::compute_zap_in_swap_amount;
 
 #[test]
 fn proper_initialization() {
@@ -28,43 +65,66 @@ fn proper_initialization() {
             },
             AssetInfo::Token {
                 contract_addr: HumanAddr::from("asset0000"),
+                token_code_hash: "asset0000_hash".to_string(),
+                viewing_key: "".to_string(),
             },
         ],
         token_code_id: 10u64,
+        token_code_hash: "liquidity0000_hash".to_string(),
         init_hook: Some(InitHook {
             contract_addr: HumanAddr::from("factory0000"),
+            code_hash: "factory0000_hash".to_string(),
             msg: to_binary(&Uint128(1000000u128)).unwrap(),
         }),
+        prng_seed: Binary::from("seed".as_bytes()),
+        curve: None,
+        migrate_from: None,
     };
 
     // we can just call .unwrap() to assert this was a success
     let env = mock_env("addr0000", &[]);
-    let res = init(&mut deps, env, msg).unwrap();
+    let res = init(&mut deps, env.clone(), msg).unwrap();
     assert_eq!(
         res.messages,
         vec![
+            snip20::set_viewing_key_msg(
+                "SecretSwap".to_string(),
+                None,
+                256,
+                "asset0000_hash".to_string(),
+                HumanAddr::from("asset0000"),
+            )
+            .unwrap(),
+            snip20::register_receive_msg(
+                env.contract_code_hash.clone(),
+                None,
+                256,
+                "asset0000_hash".to_string(),
+                HumanAddr::from("asset0000"),
+            )
+            .unwrap(),
             CosmosMsg::Wasm(WasmMsg::Instantiate {
                 code_id: 10u64,
-                msg: to_binary(&TokenInitMsg {
-                    name: "secretswap liquidity token".to_string(),
-                    symbol: "uLP".to_string(),
-                    decimals: 6,
-                    initial_balances: vec![],
-                    mint: Some(MinterResponse {
-                        minter: HumanAddr::from(MOCK_CONTRACT_ADDR),
-                        cap: None,
-                    }),
-                    init_hook: Some(InitHook {
+                callback_code_hash: "liquidity0000_hash".to_string(),
+                msg: to_binary(&TokenInitMsg::new(
+                    "SecretSwap Liquidity Provider (LP) token for uusd-asset0000".to_string(),
+                    HumanAddr::from(MOCK_CONTRACT_ADDR),
+                    "SWAP-LP".to_string(),
+                    6,
+                    Binary::from("seed".as_bytes()),
+                    InitHook {
                         msg: to_binary(&HandleMsg::PostInitialize {}).unwrap(),
                         contract_addr: HumanAddr::from(MOCK_CONTRACT_ADDR),
-                    }),
-                })
+                        code_hash: env.contract_code_hash.clone(),
+                    },
+                ))
                 .unwrap(),
                 send: vec![],
-                label: None,
+                label: format!("uusd-asset0000-SecretSwap-LP-Token-{}", MOCK_CONTRACT_ADDR),
             }),
             CosmosMsg::Wasm(WasmMsg::Execute {
                 contract_addr: HumanAddr::from("factory0000"),
+                callback_code_hash: "factory0000_hash".to_string(),
                 msg: to_binary(&Uint128(1000000u128)).unwrap(),
                 send: vec![],
             })
@@ -84,17 +144,7 @@ fn proper_initialization() {
     // // it worked, let's query the state
     let pair_info: PairInfo = query_pair_info(&deps).unwrap();
     assert_eq!("liquidity0000", pair_info.liquidity_token.as_str());
-    assert_eq!(
-        pair_info.asset_infos,
-        [
-            AssetInfo::NativeToken {
-                denom: "uusd".to_string(),
-            },
-            AssetInfo::Token {
-                contract_addr: HumanAddr::from("asset0000")
-            }
-        ]
-    );
+    assert_eq!(pair_info.asset_infos, [native_info(), token_info()]);
 }
 
 #[test]
@@ -103,7 +153,7 @@ fn provide_liquidity() {
         20,
         &[Coin {
             denom: "uusd".to_string(),
-            amount: Uint128(200u128),
+            amount: Uint128(2000u128),
         }],
     );
 
@@ -112,178 +162,132 @@ fn provide_liquidity() {
         &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128(0))],
     )]);
 
-    let msg = PairInitMsg {
-        asset_infos: [
-            AssetInfo::NativeToken {
-                denom: "uusd".to_string(),
-            },
-            AssetInfo::Token {
-                contract_addr: HumanAddr::from("asset0000"),
-            },
-        ],
-        token_code_id: 10u64,
-        init_hook: None,
-    };
-
-    let env = mock_env("addr0000", &[]);
-    // we can just call .unwrap() to assert this was a success
-    let _res = init(&mut deps, env, msg).unwrap();
-
-    // post initalize
-    let msg = HandleMsg::PostInitialize {};
-    let env = mock_env("liquidity0000", &[]);
-    let _res = handle(&mut deps, env, msg).unwrap();
+    init_pair(&mut deps);
 
     // successfully provide liquidity for the exist pool
     let msg = HandleMsg::ProvideLiquidity {
         assets: [
             Asset {
-                info: AssetInfo::Token {
-                    contract_addr: HumanAddr::from("asset0000".to_string()),
-                },
-                amount: Uint128::from(100u128),
+                info: token_info(),
+                amount: Uint128::from(2000u128),
             },
             Asset {
-                info: AssetInfo::NativeToken {
-                    denom: "uusd".to_string(),
-                },
-                amount: Uint128::from(100u128),
+                info: native_info(),
+                amount: Uint128::from(2000u128),
             },
         ],
         slippage_tolerance: None,
+        deadline: None,
     };
 
     let env = mock_env(
         "addr0000",
         &[Coin {
             denom: "uusd".to_string(),
-            amount: Uint128::from(100u128),
+            amount: Uint128::from(2000u128),
         }],
     );
     let res = handle(&mut deps, env, msg).unwrap();
     let transfer_from_msg = res.messages.get(0).expect("no message");
-    let mint_msg = res.messages.get(1).expect("no message");
+    let lock_msg = res.messages.get(1).expect("no message");
+    let mint_msg = res.messages.get(2).expect("no message");
     assert_eq!(
         transfer_from_msg,
-        &CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: HumanAddr::from("asset0000"),
-            msg: to_binary(&Cw20HandleMsg::TransferFrom {
-                owner: HumanAddr::from("addr0000"),
-                recipient: HumanAddr::from(MOCK_CONTRACT_ADDR),
-                amount: Uint128::from(100u128),
-            })
-            .unwrap(),
-            send: vec![],
-        })
+        &snip20::transfer_from_msg(
+            HumanAddr::from("addr0000"),
+            HumanAddr::from(MOCK_CONTRACT_ADDR),
+            Uint128::from(2000u128),
+            None,
+            256,
+            "asset0000_hash".to_string(),
+            HumanAddr::from("asset0000"),
+        )
+        .unwrap()
     );
+    // the minimum liquidity is locked in the pair
     assert_eq!(
-        mint_msg,
-        &CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: HumanAddr::from("liquidity0000"),
-            msg: to_binary(&Cw20HandleMsg::Mint {
-                recipient: HumanAddr::from("addr0000"),
-                amount: Uint128::from(100u128),
-            })
-            .unwrap(),
-            send: vec![],
-        })
+        lock_msg,
+        &lp_mint_msg(MOCK_CONTRACT_ADDR, MINIMUM_LIQUIDITY)
+    );
+    assert_eq!(mint_msg, &lp_mint_msg("addr0000", 1000));
+    assert_eq!(
+        read_reserves(&deps.storage).unwrap(),
+        [Uint128(2000), Uint128(2000)]
     );
 
     // provide more liquidity 1:2, which is not propotional to 1:1,
-    // then it must accept 1:1 and treat left amount as donation
-    deps.querier.with_balance(&[(
-        &HumanAddr::from(MOCK_CONTRACT_ADDR),
-        &[Coin {
-            denom: "uusd".to_string(),
-            amount: Uint128(200 + 200 /* user deposit must be pre-applied */),
-        }],
+    // then it must accept 1:1 and refund the excess native token
+    deps.querier.with_token_balances(&[(
+        &HumanAddr::from("liquidity0000"),
+        &[
+            (&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128(1000)),
+            (&HumanAddr::from("addr0000"), &Uint128(1000)),
+        ],
     )]);
 
-    deps.querier.with_token_balances(&[
-        (
-            &HumanAddr::from("liquidity0000"),
-            &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128(100))],
-        ),
-        (
-            &HumanAddr::from("asset0000"),
-            &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128(200))],
-        ),
-    ]);
-
     let msg = HandleMsg::ProvideLiquidity {
         assets: [
             Asset {
-                info: AssetInfo::Token {
-                    contract_addr: HumanAddr::from("asset0000".to_string()),
-                },
-                amount: Uint128::from(100u128),
+                info: token_info(),
+                amount: Uint128::from(1000u128),
             },
             Asset {
-                info: AssetInfo::NativeToken {
-                    denom: "uusd".to_string(),
-                },
-                amount: Uint128::from(200u128),
+                info: native_info(),
+                amount: Uint128::from(2000u128),
             },
         ],
         slippage_tolerance: None,
+        deadline: None,
     };
 
     let env = mock_env_with_block_time(
         "addr0000",
         &[Coin {
             denom: "uusd".to_string(),
-            amount: Uint128::from(200u128),
+            amount: Uint128::from(2000u128),
         }],
         1000,
     );
 
-    // only accept 100, then 50 share will be generated with 100 * (100 / 200)
+    // only accept 1000, then 1000 share will be generated with 1000 * (2000 / 2000)
     let res: HandleResponse = handle(&mut deps, env, msg).unwrap();
-    let transfer_from_msg = res.messages.get(0).expect("no message");
-    let mint_msg = res.messages.get(1).expect("no message");
+    let refund_msg = res.messages.get(0).expect("no message");
+    let transfer_from_msg = res.messages.get(1).expect("no message");
+    let mint_msg = res.messages.get(2).expect("no message");
+    assert_eq!(refund_msg, &native_send_msg("addr0000", 1000));
     assert_eq!(
         transfer_from_msg,
-        &CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: HumanAddr::from("asset0000"),
-            msg: to_binary(&Cw20HandleMsg::TransferFrom {
-                owner: HumanAddr::from("addr0000"),
-                recipient: HumanAddr::from(MOCK_CONTRACT_ADDR),
-                amount: Uint128::from(100u128),
-            })
-            .unwrap(),
-            send: vec![],
-        })
+        &snip20::transfer_from_msg(
+            HumanAddr::from("addr0000"),
+            HumanAddr::from(MOCK_CONTRACT_ADDR),
+            Uint128::from(1000u128),
+            None,
+            256,
+            "asset0000_hash".to_string(),
+            HumanAddr::from("asset0000"),
+        )
+        .unwrap()
     );
+    assert_eq!(mint_msg, &lp_mint_msg("addr0000", 1000));
     assert_eq!(
-        mint_msg,
-        &CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: HumanAddr::from("liquidity0000"),
-            msg: to_binary(&Cw20HandleMsg::Mint {
-                recipient: HumanAddr::from("addr0000"),
-                amount: Uint128::from(50u128),
-            })
-            .unwrap(),
-            send: vec![],
-        })
+        read_reserves(&deps.storage).unwrap(),
+        [Uint128(3000), Uint128(3000)]
     );
 
     // check wrong argument
     let msg = HandleMsg::ProvideLiquidity {
         assets: [
             Asset {
-                info: AssetInfo::Token {
-                    contract_addr: HumanAddr::from("asset0000".to_string()),
-                },
+                info: token_info(),
                 amount: Uint128::from(100u128),
             },
             Asset {
-                info: AssetInfo::NativeToken {
-                    denom: "uusd".to_string(),
-                },
+                info: native_info(),
                 amount: Uint128::from(50u128),
             },
         ],
         slippage_tolerance: None,
+        deadline: None,
     };
 
     let env = mock_env(
@@ -297,48 +301,25 @@ fn provide_liquidity() {
     match res {
         StdError::GenericErr { msg, .. } => assert_eq!(
             msg,
-            "Native token balance missmatch between the argument and the transferred".to_string()
+            "Native token balance mismatch between the argument and the transferred".to_string()
         ),
         _ => panic!("Must return generic error"),
     }
 
-    // initialize token balance to 1:1
-    deps.querier.with_balance(&[(
-        &HumanAddr::from(MOCK_CONTRACT_ADDR),
-        &[Coin {
-            denom: "uusd".to_string(),
-            amount: Uint128(100 + 100 /* user deposit must be pre-applied */),
-        }],
-    )]);
-
-    deps.querier.with_token_balances(&[
-        (
-            &HumanAddr::from("liquidity0000"),
-            &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128(100))],
-        ),
-        (
-            &HumanAddr::from("asset0000"),
-            &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128(100))],
-        ),
-    ]);
-
-    // failed because the price is under slippage_tolerance
+    // the pool is 1:1, failed because the price is under slippage_tolerance
     let msg = HandleMsg::ProvideLiquidity {
         assets: [
             Asset {
-                info: AssetInfo::Token {
-                    contract_addr: HumanAddr::from("asset0000".to_string()),
-                },
+                info: token_info(),
                 amount: Uint128::from(98u128),
             },
             Asset {
-                info: AssetInfo::NativeToken {
-                    denom: "uusd".to_string(),
-                },
+                info: native_info(),
                 amount: Uint128::from(100u128),
             },
         ],
         slippage_tolerance: Some(Decimal::percent(1)),
+        deadline: None,
     };
 
     let env = mock_env_with_block_time(
@@ -357,32 +338,20 @@ fn provide_liquidity() {
         _ => panic!("DO NOT ENTER HERE"),
     }
 
-    // initialize token balance to 1:1
-    deps.querier.with_balance(&[(
-        &HumanAddr::from(MOCK_CONTRACT_ADDR),
-        &[Coin {
-            denom: "uusd".to_string(),
-            amount: Uint128(100 + 98 /* user deposit must be pre-applied */),
-        }],
-    )]);
-
     // failed because the price is under slippage_tolerance
     let msg = HandleMsg::ProvideLiquidity {
         assets: [
             Asset {
-                info: AssetInfo::Token {
-                    contract_addr: HumanAddr::from("asset0000".to_string()),
-                },
+                info: token_info(),
                 amount: Uint128::from(100u128),
             },
             Asset {
-                info: AssetInfo::NativeToken {
-                    denom: "uusd".to_string(),
-                },
+                info: native_info(),
                 amount: Uint128::from(98u128),
             },
         ],
         slippage_tolerance: Some(Decimal::percent(1)),
+        deadline: None,
     };
 
     let env = mock_env_with_block_time(
@@ -401,32 +370,20 @@ fn provide_liquidity() {
         _ => panic!("DO NOT ENTER HERE"),
     }
 
-    // initialize token balance to 1:1
-    deps.querier.with_balance(&[(
-        &HumanAddr::from(MOCK_CONTRACT_ADDR),
-        &[Coin {
-            denom: "uusd".to_string(),
-            amount: Uint128(100 + 100 /* user deposit must be pre-applied */),
-        }],
-    )]);
-
     // successfully provides
     let msg = HandleMsg::ProvideLiquidity {
         assets: [
             Asset {
-                info: AssetInfo::Token {
-                    contract_addr: HumanAddr::from("asset0000".to_string()),
-                },
+                info: token_info(),
                 amount: Uint128::from(99u128),
             },
             Asset {
-                info: AssetInfo::NativeToken {
-                    denom: "uusd".to_string(),
-                },
+                info: native_info(),
                 amount: Uint128::from(100u128),
             },
         ],
         slippage_tolerance: Some(Decimal::percent(1)),
+        deadline: None,
     };
 
     let env = mock_env_with_block_time(
@@ -439,32 +396,20 @@ fn provide_liquidity() {
     );
     let _res = handle(&mut deps, env, msg).unwrap();
 
-    // initialize token balance to 1:1
-    deps.querier.with_balance(&[(
-        &HumanAddr::from(MOCK_CONTRACT_ADDR),
-        &[Coin {
-            denom: "uusd".to_string(),
-            amount: Uint128(100 + 99 /* user deposit must be pre-applied */),
-        }],
-    )]);
-
     // successfully provides
     let msg = HandleMsg::ProvideLiquidity {
         assets: [
             Asset {
-                info: AssetInfo::Token {
-                    contract_addr: HumanAddr::from("asset0000".to_string()),
-                },
+                info: token_info(),
                 amount: Uint128::from(100u128),
             },
             Asset {
-                info: AssetInfo::NativeToken {
-                    denom: "uusd".to_string(),
-                },
+                info: native_info(),
                 amount: Uint128::from(99u128),
             },
         ],
         slippage_tolerance: Some(Decimal::percent(1)),
+        deadline: None,
     };
 
     let env = mock_env_with_block_time(
@@ -488,10 +433,6 @@ fn withdraw_liquidity() {
         }],
     );
 
-    deps.querier.with_tax(
-        Decimal::zero(),
-        &[(&"uusd".to_string(), &Uint128::from(1000000u128))],
-    );
     deps.querier.with_token_balances(&[
         (
             &HumanAddr::from("liquidity0000"),
@@ -503,34 +444,15 @@ fn withdraw_liquidity() {
         ),
     ]);
 
-    let msg = PairInitMsg {
-        asset_infos: [
-            AssetInfo::NativeToken {
-                denom: "uusd".to_string(),
-            },
-            AssetInfo::Token {
-                contract_addr: HumanAddr::from("asset0000"),
-            },
-        ],
-        token_code_id: 10u64,
-        init_hook: None,
-    };
-
-    let env = mock_env("addr0000", &[]);
-    // we can just call .unwrap() to assert this was a success
-    let _res = init(&mut deps, env, msg).unwrap();
-
-    // post initalize
-    let msg = HandleMsg::PostInitialize {};
-    let env = mock_env("liquidity0000", &[]);
-    let _res = handle(&mut deps, env, msg).unwrap();
+    init_pair(&mut deps);
+    store_reserves(&mut deps.storage, &[Uint128(100u128), Uint128(100u128)]).unwrap();
 
     // withdraw liquidity
-    let msg = HandleMsg::Receive(Cw20ReceiveMsg {
-        sender: HumanAddr::from("addr0000"),
-        msg: Some(to_binary(&Cw20HookMsg::WithdrawLiquidity {}).unwrap()),
+    let msg = HandleMsg::Receive {
+        from: HumanAddr::from("addr0000"),
+        msg: Some(to_binary(&Cw20HookMsg::WithdrawLiquidity { deadline: None }).unwrap()),
         amount: Uint128(100u128),
-    });
+    };
 
     let env = mock_env("liquidity0000", &[]);
     let res = handle(&mut deps, env, msg).unwrap();
@@ -539,40 +461,9 @@ fn withdraw_liquidity() {
     let msg_refund_0 = res.messages.get(0).expect("no message");
     let msg_refund_1 = res.messages.get(1).expect("no message");
     let msg_burn_liquidity = res.messages.get(2).expect("no message");
-    assert_eq!(
-        msg_refund_0,
-        &CosmosMsg::Bank(BankMsg::Send {
-            from_address: HumanAddr::from(MOCK_CONTRACT_ADDR),
-            to_address: HumanAddr::from("addr0000"),
-            amount: vec![Coin {
-                denom: "uusd".to_string(),
-                amount: Uint128::from(100u128),
-            }],
-        })
-    );
-    assert_eq!(
-        msg_refund_1,
-        &CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: HumanAddr::from("asset0000"),
-            msg: to_binary(&Cw20HandleMsg::Transfer {
-                recipient: HumanAddr::from("addr0000"),
-                amount: Uint128::from(100u128),
-            })
-            .unwrap(),
-            send: vec![],
-        })
-    );
-    assert_eq!(
-        msg_burn_liquidity,
-        &CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: HumanAddr::from("liquidity0000"),
-            msg: to_binary(&Cw20HandleMsg::Burn {
-                amount: Uint128::from(100u128),
-            })
-            .unwrap(),
-            send: vec![],
-        })
-    );
+    assert_eq!(msg_refund_0, &native_send_msg("addr0000", 100));
+    assert_eq!(msg_refund_1, &token_send_msg("addr0000", 100));
+    assert_eq!(msg_burn_liquidity, &lp_burn_msg(100));
 
     assert_eq!(
         log_withdrawn_share,
@@ -582,6 +473,15 @@ fn withdraw_liquidity() {
         log_refund_assets,
         &log("refund_assets", "100uusd, 100asset0000")
     );
+    assert_eq!(
+        read_reserves(&deps.storage).unwrap(),
+        [Uint128::zero(), Uint128::zero()]
+    );
+
+    let withdraw_res: WithdrawResponse = from_binary(&res.data.unwrap()).unwrap();
+    assert_eq!(withdraw_res.withdrawn_share, Uint128(100u128));
+    assert_eq!(withdraw_res.refund_assets[0].amount, Uint128(100u128));
+    assert_eq!(withdraw_res.refund_assets[1].amount, Uint128(100u128));
 }
 
 #[test]
@@ -589,8 +489,6 @@ fn try_native_to_token() {
     let total_share = Uint128(30000000000u128);
     let asset_pool_amount = Uint128(20000000000u128);
     let collateral_pool_amount = Uint128(30000000000u128);
-    let price = Decimal::from_ratio(collateral_pool_amount, asset_pool_amount);
-    let exchange_rate = reverse_decimal(price);
     let offer_amount = Uint128(1500000000u128);
 
     let mut deps = mock_dependencies(
@@ -602,11 +500,6 @@ fn try_native_to_token() {
         }],
     );
 
-    deps.querier.with_tax(
-        Decimal::zero(),
-        &[(&"uusd".to_string(), &Uint128::from(1000000u128))],
-    );
-
     deps.querier.with_token_balances(&[
         (
             &HumanAddr::from("liquidity0000"),
@@ -618,39 +511,25 @@ fn try_native_to_token() {
         ),
     ]);
 
-    let msg = PairInitMsg {
-        asset_infos: [
-            AssetInfo::NativeToken {
-                denom: "uusd".to_string(),
-            },
-            AssetInfo::Token {
-                contract_addr: HumanAddr::from("asset0000"),
-            },
-        ],
-        token_code_id: 10u64,
-        init_hook: None,
-    };
-
-    let env = mock_env("addr0000", &[]);
-    // we can just call .unwrap() to assert this was a success
-    let _res = init(&mut deps, env, msg).unwrap();
-
-    // post initalize
-    let msg = HandleMsg::PostInitialize {};
-    let env = mock_env("liquidity0000", &[]);
-    let _res = handle(&mut deps, env, msg).unwrap();
+    init_pair(&mut deps);
+    store_reserves(
+        &mut deps.storage,
+        &[collateral_pool_amount, asset_pool_amount],
+    )
+    .unwrap();
 
     // normal swap
     let msg = HandleMsg::Swap {
         offer_asset: Asset {
-            info: AssetInfo::NativeToken {
-                denom: "uusd".to_string(),
-            },
+            info: native_info(),
             amount: offer_amount,
         },
+        expected_return: None,
         belief_price: None,
         max_spread: None,
         to: None,
+        ask_amount: None,
+        deadline: None,
     };
     let env = mock_env_with_block_time(
         "addr0000",
@@ -667,27 +546,33 @@ fn try_native_to_token() {
     // current price is 1.5, so expected return without spread is 1000
     // 952.380953 = 20000 - 20000 * 30000 / (30000 + 1500)
     let expected_ret_amount = Uint128(952_380_953u128);
-    let expected_spread_amount = (offer_amount * exchange_rate - expected_ret_amount).unwrap();
+    let expected_spread_amount = (offer_amount
+        .multiply_ratio(asset_pool_amount, collateral_pool_amount)
+        - expected_ret_amount)
+        .unwrap();
     let expected_commission_amount = expected_ret_amount.multiply_ratio(3u128, 1000u128); // 0.3%
     let expected_return_amount = (expected_ret_amount - expected_commission_amount).unwrap();
-    let expected_tax_amount = Uint128::zero(); // no tax for token
 
-    // check simulation res
-    deps.querier.with_balance(&[(
-        &HumanAddr::from(MOCK_CONTRACT_ADDR),
-        &[Coin {
-            denom: "uusd".to_string(),
-            amount: collateral_pool_amount,
-            /* user deposit must be pre-applied */
-        }],
-    )]);
+    // the swap moved the reserves
+    assert_eq!(
+        read_reserves(&deps.storage).unwrap(),
+        [
+            collateral_pool_amount + offer_amount,
+            (asset_pool_amount - expected_return_amount).unwrap()
+        ]
+    );
+
+    // check simulation res against the reserves before the swap
+    store_reserves(
+        &mut deps.storage,
+        &[collateral_pool_amount, asset_pool_amount],
+    )
+    .unwrap();
 
     let simulation_res: SimulationResponse = query_simulation(
         &deps,
         Asset {
-            info: AssetInfo::NativeToken {
-                denom: "uusd".to_string(),
-            },
+            info: native_info(),
             amount: offer_amount,
         },
     )
@@ -700,9 +585,7 @@ fn try_native_to_token() {
     let reverse_simulation_res: ReverseSimulationResponse = query_reverse_simulation(
         &deps,
         Asset {
-            info: AssetInfo::Token {
-                contract_addr: HumanAddr::from("asset0000"),
-            },
+            info: token_info(),
             amount: expected_return_amount,
         },
     )
@@ -735,24 +618,26 @@ fn try_native_to_token() {
             log("ask_asset", "asset0000"),
             log("offer_amount", offer_amount.to_string()),
             log("return_amount", expected_return_amount.to_string()),
-            log("tax_amount", expected_tax_amount.to_string()),
             log("spread_amount", expected_spread_amount.to_string()),
             log("commission_amount", expected_commission_amount.to_string()),
+            log("protocol_fee_amount", "0"),
+            log("refund_amount", "0"),
+            log("filled_orders", "0"),
         ]
     );
 
     assert_eq!(
-        &CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: HumanAddr::from("asset0000"),
-            msg: to_binary(&Cw20HandleMsg::Transfer {
-                recipient: HumanAddr::from("addr0000"),
-                amount: Uint128::from(expected_return_amount),
-            })
-            .unwrap(),
-            send: vec![],
-        }),
+        &token_send_msg("addr0000", expected_return_amount.u128()),
         msg_transfer,
     );
+
+    // calling contracts read the outcome from the data
+    let swap_res: SwapResponse = from_binary(&res.data.unwrap()).unwrap();
+    assert_eq!(swap_res.offer_asset.amount, offer_amount);
+    assert_eq!(swap_res.return_asset.amount, expected_return_amount);
+    assert_eq!(swap_res.spread_amount, expected_spread_amount);
+    assert_eq!(swap_res.commission_amount, expected_commission_amount);
+    assert_eq!(swap_res.refund_amount, Uint128::zero());
 }
 
 #[test]
@@ -760,8 +645,6 @@ fn try_token_to_native() {
     let total_share = Uint128(20000000000u128);
     let asset_pool_amount = Uint128(30000000000u128);
     let collateral_pool_amount = Uint128(20000000000u128);
-    let price = Decimal::from_ratio(collateral_pool_amount, asset_pool_amount);
-    let exchange_rate = decimal_multiplication(price, Decimal::one());
     let offer_amount = Uint128(1500000000u128);
 
     let mut deps = mock_dependencies(
@@ -771,10 +654,6 @@ fn try_token_to_native() {
             amount: collateral_pool_amount,
         }],
     );
-    deps.querier.with_tax(
-        Decimal::percent(1),
-        &[(&"uusd".to_string(), &Uint128::from(1000000u128))],
-    );
     deps.querier.with_token_balances(&[
         (
             &HumanAddr::from("liquidity0000"),
@@ -789,39 +668,25 @@ fn try_token_to_native() {
         ),
     ]);
 
-    let msg = PairInitMsg {
-        asset_infos: [
-            AssetInfo::NativeToken {
-                denom: "uusd".to_string(),
-            },
-            AssetInfo::Token {
-                contract_addr: HumanAddr::from("asset0000"),
-            },
-        ],
-        token_code_id: 10u64,
-        init_hook: None,
-    };
-
-    let env = mock_env("addr0000", &[]);
-    // we can just call .unwrap() to assert this was a success
-    let _res = init(&mut deps, env, msg).unwrap();
-
-    // post initalize
-    let msg = HandleMsg::PostInitialize {};
-    let env = mock_env("liquidity0000", &[]);
-    let _res = handle(&mut deps, env, msg).unwrap();
+    init_pair(&mut deps);
+    store_reserves(
+        &mut deps.storage,
+        &[collateral_pool_amount, asset_pool_amount],
+    )
+    .unwrap();
 
     // unauthorized access; can not execute swap directy for token swap
     let msg = HandleMsg::Swap {
         offer_asset: Asset {
-            info: AssetInfo::Token {
-                contract_addr: HumanAddr::from("asset0000"),
-            },
+            info: token_info(),
             amount: offer_amount,
         },
+        expected_return: None,
         belief_price: None,
         max_spread: None,
         to: None,
+        ask_amount: None,
+        deadline: None,
     };
     let env = mock_env_with_block_time("addr0000", &[], 1000);
     let res = handle(&mut deps, env, msg).unwrap_err();
@@ -831,18 +696,21 @@ fn try_token_to_native() {
     }
 
     // normal sell
-    let msg = HandleMsg::Receive(Cw20ReceiveMsg {
-        sender: HumanAddr::from("addr0000"),
+    let msg = HandleMsg::Receive {
+        from: HumanAddr::from("addr0000"),
         amount: offer_amount,
         msg: Some(
             to_binary(&Cw20HookMsg::Swap {
+                expected_return: None,
                 belief_price: None,
                 max_spread: None,
                 to: None,
+                ask_amount: None,
+                deadline: None,
             })
             .unwrap(),
         ),
-    });
+    };
     let env = mock_env_with_block_time("asset0000", &[], 1000);
 
     let res = handle(&mut deps, env, msg).unwrap();
@@ -851,35 +719,25 @@ fn try_token_to_native() {
     // current price is 1.5, so expected return without spread is 1000
     // 952.380953 = 20000 - 20000 * 30000 / (30000 + 1500)
     let expected_ret_amount = Uint128(952_380_953u128);
-    let expected_spread_amount = (offer_amount * exchange_rate - expected_ret_amount).unwrap();
+    let expected_spread_amount = (offer_amount
+        .multiply_ratio(collateral_pool_amount, asset_pool_amount)
+        - expected_ret_amount)
+        .unwrap();
     let expected_commission_amount = expected_ret_amount.multiply_ratio(3u128, 1000u128); // 0.3%
     let expected_return_amount = (expected_ret_amount - expected_commission_amount).unwrap();
-    let expected_tax_amount = std::cmp::min(
-        Uint128(1000000u128),
-        (expected_return_amount
-            - expected_return_amount.multiply_ratio(Uint128(100u128), Uint128(101u128)))
-        .unwrap(),
-    );
-    // check simulation res
-    // return asset token balance as normal
-    deps.querier.with_token_balances(&[
-        (
-            &HumanAddr::from("liquidity0000"),
-            &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &total_share)],
-        ),
-        (
-            &HumanAddr::from("asset0000"),
-            &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &(asset_pool_amount))],
-        ),
-    ]);
+
+    // check simulation res against the reserves before the swap
+    store_reserves(
+        &mut deps.storage,
+        &[collateral_pool_amount, asset_pool_amount],
+    )
+    .unwrap();
 
     let simulation_res: SimulationResponse = query_simulation(
         &deps,
         Asset {
             amount: offer_amount,
-            info: AssetInfo::Token {
-                contract_addr: HumanAddr::from("asset0000"),
-            },
+            info: token_info(),
         },
     )
     .unwrap();
@@ -892,9 +750,7 @@ fn try_token_to_native() {
         &deps,
         Asset {
             amount: expected_return_amount,
-            info: AssetInfo::NativeToken {
-                denom: "uusd".to_string(),
-            },
+            info: native_info(),
         },
     )
     .unwrap();
@@ -926,37 +782,40 @@ fn try_token_to_native() {
             log("ask_asset", "uusd"),
             log("offer_amount", offer_amount.to_string()),
             log("return_amount", expected_return_amount.to_string()),
-            log("tax_amount", expected_tax_amount.to_string()),
             log("spread_amount", expected_spread_amount.to_string()),
             log("commission_amount", expected_commission_amount.to_string()),
+            log("protocol_fee_amount", "0"),
+            log("refund_amount", "0"),
+            log("filled_orders", "0"),
         ]
     );
 
+    // there is no tax on Secret Network
     assert_eq!(
-        &CosmosMsg::Bank(BankMsg::Send {
-            from_address: HumanAddr::from(MOCK_CONTRACT_ADDR),
-            to_address: HumanAddr::from("addr0000"),
-            amount: vec![Coin {
-                denom: "uusd".to_string(),
-                amount: (expected_return_amount - expected_tax_amount).unwrap(),
-            }],
-        }),
+        &native_send_msg("addr0000", expected_return_amount.u128()),
         msg_transfer,
     );
 
+    let swap_res: SwapResponse = from_binary(&res.data.unwrap()).unwrap();
+    assert_eq!(swap_res.offer_asset.info, token_info());
+    assert_eq!(swap_res.return_asset.amount, expected_return_amount);
+
     // failed due to non asset token contract try to execute sell
-    let msg = HandleMsg::Receive(Cw20ReceiveMsg {
-        sender: HumanAddr::from("addr0000"),
+    let msg = HandleMsg::Receive {
+        from: HumanAddr::from("addr0000"),
         amount: offer_amount,
         msg: Some(
             to_binary(&Cw20HookMsg::Swap {
+                expected_return: None,
                 belief_price: None,
                 max_spread: None,
                 to: None,
+                ask_amount: None,
+                deadline: None,
             })
             .unwrap(),
         ),
-    });
+    };
     let env = mock_env_with_block_time("liquidtity0000", &[], 1000);
     let res = handle(&mut deps, env, msg).unwrap_err();
     match res {
@@ -966,139 +825,3000 @@ fn try_token_to_native() {
 }
 
 #[test]
-fn test_max_spread() {
-    assert_max_spread(
-        Some(Decimal::from_ratio(1200u128, 1u128)),
-        Some(Decimal::percent(1)),
-        Uint128::from(1200000000u128),
-        Uint128::from(989999u128),
-        Uint128::zero(),
-    )
-    .unwrap_err();
-
-    assert_max_spread(
-        Some(Decimal::from_ratio(1200u128, 1u128)),
-        Some(Decimal::percent(1)),
-        Uint128::from(1200000000u128),
-        Uint128::from(990000u128),
-        Uint128::zero(),
-    )
-    .unwrap();
+fn test_sync_and_skim() {
+    let mut deps = mock_dependencies(
+        20,
+        &[Coin {
+            denom: "uusd".to_string(),
+            amount: Uint128(1000u128),
+        }],
+    );
+    deps.querier.with_token_balances(&[
+        (
+            &HumanAddr::from("liquidity0000"),
+            &[(&HumanAddr::from("addr0000"), &Uint128(1000u128))],
+        ),
+        (
+            &HumanAddr::from("asset0000"),
+            &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128(1000u128))],
+        ),
+    ]);
 
-    assert_max_spread(
-        None,
-        Some(Decimal::percent(1)),
-        Uint128::zero(),
-        Uint128::from(989999u128),
-        Uint128::from(10001u128),
-    )
-    .unwrap_err();
+    init_pair(&mut deps);
+    store_reserves(&mut deps.storage, &[Uint128(1000u128), Uint128(1000u128)]).unwrap();
 
-    assert_max_spread(
-        None,
-        Some(Decimal::percent(1)),
-        Uint128::zero(),
-        Uint128::from(990000u128),
-        Uint128::from(10000u128),
-    )
-    .unwrap();
-}
+    // nothing to skim while the balances match the reserves
+    let msg = HandleMsg::Skim {
+        to: HumanAddr::from("addr0001"),
+    };
+    let res = handle(&mut deps, mock_env("addr0001", &[]), msg).unwrap();
+    assert_eq!(res.messages, vec![]);
 
-#[test]
-fn test_deduct() {
-    let mut deps = mock_dependencies(20, &[]);
+    // donations are sent to the pair directly, 10uusd of the balance are protocol fees
+    deps.querier.with_balance(&[(
+        &HumanAddr::from(MOCK_CONTRACT_ADDR),
+        &[Coin {
+            denom: "uusd".to_string(),
+            amount: Uint128(1110u128),
+        }],
+    )]);
+    deps.querier.with_token_balances(&[
+        (
+            &HumanAddr::from("liquidity0000"),
+            &[(&HumanAddr::from("addr0000"), &Uint128(1000u128))],
+        ),
+        (
+            &HumanAddr::from("asset0000"),
+            &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128(1050u128))],
+        ),
+    ]);
+    store_protocol_fees(&mut deps.storage, &[Uint128(10u128), Uint128::zero()]).unwrap();
 
-    let tax_rate = Decimal::percent(2);
-    let tax_cap = Uint128::from(1_000_000u128);
-    deps.querier.with_tax(
-        Decimal::percent(2),
-        &[(&"uusd".to_string(), &Uint128::from(1000000u128))],
+    // skimming sends the donations, the reserves and the protocol fees stay
+    let msg = HandleMsg::Skim {
+        to: HumanAddr::from("addr0001"),
+    };
+    let res = handle(&mut deps, mock_env("addr0002", &[]), msg).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            native_send_msg("addr0001", 100),
+            token_send_msg("addr0001", 50),
+        ]
+    );
+    assert_eq!(res.log[2], log("skimmed", "100uusd, 50asset0000"));
+    assert_eq!(
+        read_reserves(&deps.storage).unwrap(),
+        [Uint128(1000u128), Uint128(1000u128)]
     );
 
-    let amount = Uint128(1000_000_000u128);
-    let expected_after_amount = std::cmp::max(
-        (amount - amount * tax_rate).unwrap(),
-        (amount - tax_cap).unwrap(),
+    // syncing adds the donations to the pools instead
+    let res = handle(&mut deps, mock_env("addr0002", &[]), HandleMsg::Sync {}).unwrap();
+    assert_eq!(res.messages, vec![]);
+    assert_eq!(res.log[1], log("reserves", "1100uusd, 1050asset0000"));
+    assert_eq!(
+        read_reserves(&deps.storage).unwrap(),
+        [Uint128(1100u128), Uint128(1050u128)]
     );
+    let res: PoolResponse = query_pool(&deps).unwrap();
+    assert_eq!(res.assets[0].amount, Uint128(1100u128));
+    assert_eq!(res.assets[1].amount, Uint128(1050u128));
+    assert_eq!(res.total_share, Uint128(1000u128));
 
-    let after_amount = (Asset {
-        info: AssetInfo::NativeToken {
+    // and there is nothing left to skim
+    let msg = HandleMsg::Skim {
+        to: HumanAddr::from("addr0001"),
+    };
+    let res = handle(&mut deps, mock_env("addr0002", &[]), msg).unwrap();
+    assert_eq!(res.messages, vec![]);
+}
+
+#[test]
+fn test_zap_responses() {
+    let mut deps = mock_dependencies(
+        20,
+        &[Coin {
             denom: "uusd".to_string(),
+            amount: Uint128(1_010_000u128),
+        }],
+    );
+    deps.querier.with_token_balances(&[
+        (
+            &HumanAddr::from("liquidity0000"),
+            &[(&HumanAddr::from("addr0000"), &Uint128(1_000_000u128))],
+        ),
+        (
+            &HumanAddr::from("asset0000"),
+            &[(
+                &HumanAddr::from(MOCK_CONTRACT_ADDR),
+                &Uint128(1_000_000u128),
+            )],
+        ),
+    ]);
+
+    init_pair(&mut deps);
+    store_reserves(
+        &mut deps.storage,
+        &[Uint128(1_000_000u128), Uint128(1_000_000u128)],
+    )
+    .unwrap();
+
+    let msg = HandleMsg::ZapIn {
+        offer_asset: Asset {
+            info: native_info(),
+            amount: Uint128(10_000u128),
         },
-        amount,
-    })
-    .deduct_tax(&deps)
+        min_shares: None,
+        deadline: None,
+    };
+    let env = mock_env(
+        "addr0000",
+        &[Coin {
+            denom: "uusd".to_string(),
+            amount: Uint128(10_000u128),
+        }],
+    );
+    let res = handle(&mut deps, env, msg).unwrap();
+    let zap_res: ZapInResponse = from_binary(&res.data.unwrap()).unwrap();
+    let (return_amount, _, commission_amount) = compute_swap(
+        &CurveType::ConstantProduct {},
+        0,
+        Uint128(1_000_000u128),
+        Uint128(1_000_000u128),
+        zap_res.swap_amount,
+        Uint128(3u128),
+        Uint128(1000u128),
+    )
     .unwrap();
+    assert_eq!(zap_res.return_amount, return_amount);
+    assert_eq!(zap_res.commission_amount, commission_amount);
+    assert_eq!(zap_res.protocol_fee_amount, Uint128::zero());
+    assert_eq!(
+        res.messages,
+        vec![lp_mint_msg("addr0000", zap_res.share.u128())]
+    );
 
-    assert_eq!(expected_after_amount, after_amount.amount);
+    // withdraw 1% of the shares as uusd only from the 1:1 pool
+    store_reserves(
+        &mut deps.storage,
+        &[Uint128(1_000_000u128), Uint128(1_000_000u128)],
+    )
+    .unwrap();
+    let msg = HandleMsg::Receive {
+        from: HumanAddr::from("addr0000"),
+        amount: Uint128(10_000u128),
+        msg: Some(
+            to_binary(&Cw20HookMsg::ZapOut {
+                ask_asset: native_info(),
+                min_return: None,
+                deadline: None,
+            })
+            .unwrap(),
+        ),
+    };
+    let res = handle(&mut deps, mock_env("liquidity0000", &[]), msg).unwrap();
+    let zap_res: ZapOutResponse = from_binary(&res.data.unwrap()).unwrap();
+    // 9871 = 990000 - 990000 * 990000 / 1000000 - 29 commission
+    assert_eq!(
+        zap_res,
+        ZapOutResponse {
+            withdrawn_share: Uint128(10_000u128),
+            return_asset: Asset {
+                info: native_info(),
+                amount: Uint128(19_871u128),
+            },
+            swap_return_amount: Uint128(9_871u128),
+            spread_amount: Uint128(100u128),
+            commission_amount: Uint128(29u128),
+            protocol_fee_amount: Uint128::zero(),
+        }
+    );
+    assert_eq!(
+        res.messages,
+        vec![native_send_msg("addr0000", 19_871), lp_burn_msg(10_000)]
+    );
 }
 
 #[test]
-fn test_query_pool() {
-    let total_share_amount = Uint128::from(111u128);
-    let asset_0_amount = Uint128::from(222u128);
-    let asset_1_amount = Uint128::from(333u128);
+fn test_protocol_fees() {
     let mut deps = mock_dependencies(
         20,
         &[Coin {
             denom: "uusd".to_string(),
-            amount: asset_0_amount,
+            amount: Uint128(1_010_000u128),
         }],
     );
-
     deps.querier.with_token_balances(&[
+        (
+            &HumanAddr::from("liquidity0000"),
+            &[(&HumanAddr::from("addr0000"), &Uint128(1_000_000u128))],
+        ),
         (
             &HumanAddr::from("asset0000"),
-            &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &asset_1_amount)],
+            &[(
+                &HumanAddr::from(MOCK_CONTRACT_ADDR),
+                &Uint128(1_000_000u128),
+            )],
         ),
+    ]);
+    deps.querier.with_pair_settings(PairSettings {
+        swap_fee: Fee {
+            commission_rate_nom: Uint128(3u128),
+            commission_rate_denom: Uint128(1000u128),
+        },
+        swap_data_endpoint: None,
+        swap_data_endpoints: vec![],
+        protocol_fee: Some(ProtocolFee {
+            share_nom: Uint128(1u128),
+            share_denom: Uint128(2u128),
+            collector: HumanAddr::from("collector0000"),
+        }),
+        status: PairStatusLevel::NormalRun,
+        dynamic_fee: None,
+        max_price_move: None,
+    });
+
+    init_pair(&mut deps);
+    store_reserves(
+        &mut deps.storage,
+        &[Uint128(1_000_000u128), Uint128(1_000_000u128)],
+    )
+    .unwrap();
+
+    // 9901 = 1000000 - 1000000 * 1000000 / 1010000, the commission is 29
+    let msg = HandleMsg::Swap {
+        offer_asset: Asset {
+            info: native_info(),
+            amount: Uint128(10_000u128),
+        },
+        expected_return: None,
+        belief_price: None,
+        max_spread: None,
+        to: None,
+        ask_amount: None,
+        deadline: None,
+    };
+    let env = mock_env(
+        "addr0000",
+        &[Coin {
+            denom: "uusd".to_string(),
+            amount: Uint128(10_000u128),
+        }],
+    );
+    let res = handle(&mut deps, env, msg).unwrap();
+    let swap_res: SwapResponse = from_binary(&res.data.unwrap()).unwrap();
+    assert_eq!(swap_res.return_asset.amount, Uint128(9_872u128));
+    assert_eq!(swap_res.commission_amount, Uint128(29u128));
+    assert_eq!(swap_res.protocol_fee_amount, Uint128(14u128));
+
+    // the protocol fee accrues in the ask asset and leaves the pool
+    let res = query_protocol_fees(&deps).unwrap();
+    assert_eq!(res.assets[0].amount, Uint128::zero());
+    assert_eq!(res.assets[1].amount, Uint128(14u128));
+    assert_eq!(
+        read_reserves(&deps.storage).unwrap(),
+        [Uint128(1_010_000u128), Uint128(990_114u128)]
+    );
+
+    // the pair still holds the protocol fee, the balances exclude it
+    deps.querier.with_token_balances(&[
         (
             &HumanAddr::from("liquidity0000"),
-            &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &total_share_amount)],
+            &[(&HumanAddr::from("addr0000"), &Uint128(1_000_000u128))],
+        ),
+        (
+            &HumanAddr::from("asset0000"),
+            &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128(990_128u128))],
         ),
     ]);
-
-    let msg = PairInitMsg {
-        asset_infos: [
-            AssetInfo::NativeToken {
-                denom: "uusd".to_string(),
-            },
-            AssetInfo::Token {
-                contract_addr: HumanAddr::from("asset0000"),
-            },
-        ],
-        token_code_id: 10u64,
-        init_hook: None,
+    let res = handle(&mut deps, mock_env("addr0001", &[]), HandleMsg::Sync {}).unwrap();
+    assert_eq!(res.log[1], log("reserves", "1010000uusd, 990114asset0000"));
+    let msg = HandleMsg::Skim {
+        to: HumanAddr::from("addr0001"),
     };
+    let res = handle(&mut deps, mock_env("addr0001", &[]), msg).unwrap();
+    assert_eq!(res.messages, vec![]);
+
+    // anyone can collect, only the collector is paid
+    let res = handle(
+        &mut deps,
+        mock_env("addr0001", &[]),
+        HandleMsg::CollectProtocolFees {},
+    )
+    .unwrap();
+    assert_eq!(res.messages, vec![token_send_msg("collector0000", 14)]);
+    let res = query_protocol_fees(&deps).unwrap();
+    assert_eq!(res.assets[1].amount, Uint128::zero());
+
+    let res = handle(
+        &mut deps,
+        mock_env("addr0001", &[]),
+        HandleMsg::CollectProtocolFees {},
+    )
+    .unwrap();
+    assert_eq!(res.messages, vec![]);
 
-    let env = mock_env("addr0000", &[]);
-    // we can just call .unwrap() to assert this was a success
-    let _res = init(&mut deps, env, msg).unwrap();
+    // a zero share turns the protocol fee off, all of the commission stays in the pool
+    deps.querier.with_pair_settings(PairSettings {
+        swap_fee: Fee {
+            commission_rate_nom: Uint128(3u128),
+            commission_rate_denom: Uint128(1000u128),
+        },
+        swap_data_endpoint: None,
+        swap_data_endpoints: vec![],
+        protocol_fee: Some(ProtocolFee {
+            share_nom: Uint128::zero(),
+            share_denom: Uint128(2u128),
+            collector: HumanAddr::from("collector0000"),
+        }),
+        status: PairStatusLevel::NormalRun,
+        dynamic_fee: None,
+        max_price_move: None,
+    });
+    let msg = HandleMsg::Swap {
+        offer_asset: Asset {
+            info: native_info(),
+            amount: Uint128(10_000u128),
+        },
+        expected_return: None,
+        belief_price: None,
+        max_spread: None,
+        to: None,
+        ask_amount: None,
+        deadline: None,
+    };
+    let env = mock_env(
+        "addr0000",
+        &[Coin {
+            denom: "uusd".to_string(),
+            amount: Uint128(10_000u128),
+        }],
+    );
+    let res = handle(&mut deps, env, msg).unwrap();
+    let swap_res: SwapResponse = from_binary(&res.data.unwrap()).unwrap();
+    assert!(!swap_res.commission_amount.is_zero());
+    assert_eq!(swap_res.protocol_fee_amount, Uint128::zero());
+    let res = query_protocol_fees(&deps).unwrap();
+    assert_eq!(res.assets[1].amount, Uint128::zero());
+}
 
-    // post initalize
-    let msg = HandleMsg::PostInitialize {};
-    let env = mock_env("liquidity0000", &[]);
-    let _res = handle(&mut deps, env, msg).unwrap();
+#[test]
+fn test_flash_swap() {
+    let mut deps = mock_dependencies(
+        20,
+        &[Coin {
+            denom: "uusd".to_string(),
+            amount: Uint128(1_000_000u128),
+        }],
+    );
+    with_pair_token_balances(&mut deps.querier, 1_000_000, 1_000_000);
 
-    let res: PoolResponse = query_pool(&deps).unwrap();
+    init_pair(&mut deps);
+    store_reserves(
+        &mut deps.storage,
+        &[Uint128(1_000_000u128), Uint128(1_000_000u128)],
+    )
+    .unwrap();
 
-    assert_eq!(
+    let msg = HandleMsg::FlashSwap {
+        asset: Asset {
+            info: token_info(),
+            amount: Uint128(10_000u128),
+        },
+        receiver: HumanAddr::from("receiver0000"),
+        receiver_code_hash: "receiver0000_hash".to_string(),
+        msg: None,
+    };
+    let res = handle(&mut deps, mock_env("addr0000", &[]), msg).unwrap();
+    assert_eq!(res.messages.len(), 3);
+    assert_eq!(res.messages[0], token_send_msg("receiver0000", 10_000));
+    assert_eq!(
+        res.messages[2],
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: HumanAddr::from(MOCK_CONTRACT_ADDR),
+            callback_code_hash: "".to_string(),
+            msg: to_binary(&HandleMsg::FinalizeFlashSwap {}).unwrap(),
+            send: vec![],
+        })
+    );
+    with_pair_token_balances(&mut deps.querier, 1_000_000, 990_000);
+
+    // other messages are rejected while the lock is held
+    let msg = HandleMsg::Swap {
+        offer_asset: Asset {
+            info: native_info(),
+            amount: Uint128(100u128),
+        },
+        expected_return: None,
+        belief_price: None,
+        max_spread: None,
+        to: None,
+        ask_amount: None,
+        deadline: None,
+    };
+    let env = mock_env(
+        "addr0000",
+        &[Coin {
+            denom: "uusd".to_string(),
+            amount: Uint128(100u128),
+        }],
+    );
+    match handle(&mut deps, env, msg).unwrap_err() {
+        StdError::GenericErr { msg, .. } => assert_eq!(msg, "Pair is locked by a flash swap"),
+        _ => panic!("Must return generic error"),
+    }
+    match handle(&mut deps, mock_env("addr0000", &[]), HandleMsg::Sync {}).unwrap_err() {
+        StdError::GenericErr { msg, .. } => assert_eq!(msg, "Pair is locked by a flash swap"),
+        _ => panic!("Must return generic error"),
+    }
+
+    // only the pair itself can finalize
+    let msg = HandleMsg::FinalizeFlashSwap {};
+    match handle(&mut deps, mock_env("receiver0000", &[]), msg).unwrap_err() {
+        StdError::Unauthorized { .. } => (),
+        _ => panic!("Must return unauthorized error"),
+    }
+
+    // 10030 repaid, the fee is ceil(10030 * 0.3%) = 31 so the invariant dropped
+    with_pair_token_balances(&mut deps.querier, 1_000_000, 1_000_030);
+    let msg = HandleMsg::FinalizeFlashSwap {};
+    match handle(&mut deps, mock_env(MOCK_CONTRACT_ADDR, &[]), msg).unwrap_err() {
+        StdError::GenericErr { msg, .. } => {
+            assert_eq!(msg, "Flash swap was not paid back with its fee")
+        }
+        _ => panic!("Must return generic error"),
+    }
+    assert!(read_flash_swap(&deps.storage).unwrap().is_some());
+
+    // 10031 repaid covers the fee exactly
+    with_pair_token_balances(&mut deps.querier, 1_000_000, 1_000_031);
+    let msg = HandleMsg::FinalizeFlashSwap {};
+    let res = handle(&mut deps, mock_env(MOCK_CONTRACT_ADDR, &[]), msg).unwrap();
+    assert_eq!(res.log[1], log("fees", "0uusd, 31asset0000"));
+    assert!(read_flash_swap(&deps.storage).unwrap().is_none());
+    assert_eq!(
+        read_reserves(&deps.storage).unwrap(),
+        [Uint128(1_000_000u128), Uint128(1_000_031u128)]
+    );
+
+    // the lock is cleared
+    handle(&mut deps, mock_env("addr0000", &[]), HandleMsg::Sync {}).unwrap();
+}
+
+#[test]
+fn test_zap_in() {
+    let mut deps = mock_dependencies(
+        20,
+        &[Coin {
+            denom: "uusd".to_string(),
+            amount: Uint128(1_012_345u128),
+        }],
+    );
+    with_pair_token_balances(&mut deps.querier, 1_000_000, 3_000_000);
+    init_pair(&mut deps);
+
+    // the first provider has to deposit both assets
+    let msg = HandleMsg::ZapIn {
+        offer_asset: Asset {
+            info: native_info(),
+            amount: Uint128(12_345u128),
+        },
+        min_shares: None,
+        deadline: None,
+    };
+    let env = mock_env(
+        "addr0000",
+        &[Coin {
+            denom: "uusd".to_string(),
+            amount: Uint128(12_345u128),
+        }],
+    );
+    match handle(&mut deps, env, msg).unwrap_err() {
+        StdError::GenericErr { msg, .. } => assert_eq!(
+            msg,
+            "Cannot zap into an empty pool, provide both assets instead"
+        ),
+        _ => panic!("Must return generic error"),
+    }
+
+    store_reserves(
+        &mut deps.storage,
+        &[Uint128(1_000_000u128), Uint128(3_000_000u128)],
+    )
+    .unwrap();
+
+    // 6162uusd are swapped and 6143 shares minted
+    let zap_in = |min_shares: u128| HandleMsg::ZapIn {
+        offer_asset: Asset {
+            info: native_info(),
+            amount: Uint128(12_345u128),
+        },
+        min_shares: Some(Uint128(min_shares)),
+        deadline: None,
+    };
+    let env = mock_env(
+        "addr0000",
+        &[Coin {
+            denom: "uusd".to_string(),
+            amount: Uint128(12_345u128),
+        }],
+    );
+    match handle(&mut deps, env.clone(), zap_in(6_144)).unwrap_err() {
+        StdError::GenericErr { msg, .. } => {
+            assert_eq!(msg, "Share 6143 is lower than min_shares 6144")
+        }
+        _ => panic!("Must return generic error"),
+    }
+
+    // the offer asset the deposit doesn't use is refunded
+    let res = handle(&mut deps, env, zap_in(6_143)).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            lp_mint_msg("addr0000", 6_143),
+            native_send_msg("addr0000", 1)
+        ]
+    );
+    let zap_res: ZapInResponse = from_binary(&res.data.unwrap()).unwrap();
+    assert_eq!(zap_res.swap_amount, Uint128(6_162u128));
+    assert_eq!(zap_res.refund_amount, Uint128(1u128));
+    assert_eq!(
+        read_reserves(&deps.storage).unwrap(),
+        [Uint128(1_012_344u128), Uint128(3_000_000u128)]
+    );
+
+    // through the token hook
+    store_reserves(
+        &mut deps.storage,
+        &[Uint128(1_000_000u128), Uint128(3_000_000u128)],
+    )
+    .unwrap();
+    with_pair_token_balances(&mut deps.querier, 1_000_000, 3_020_001);
+    let msg = HandleMsg::Receive {
+        from: HumanAddr::from("addr0000"),
+        amount: Uint128(20_001u128),
+        msg: Some(
+            to_binary(&Cw20HookMsg::ZapIn {
+                min_shares: Some(Uint128(3_323u128)),
+                deadline: None,
+            })
+            .unwrap(),
+        ),
+    };
+    let res = handle(&mut deps, mock_env("asset0000", &[]), msg).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            lp_mint_msg("addr0000", 3_323),
+            token_send_msg("addr0000", 2)
+        ]
+    );
+    assert_eq!(
+        read_reserves(&deps.storage).unwrap(),
+        [Uint128(1_000_000u128), Uint128(3_019_999u128)]
+    );
+}
+
+#[test]
+fn test_zap_in_swap_routing() {
+    let mut deps = mock_dependencies(
+        20,
+        &[Coin {
+            denom: "uusd".to_string(),
+            amount: Uint128(1_100_000u128),
+        }],
+    );
+    with_pair_token_balances(&mut deps.querier, 1_000_000, 3_001_000);
+    deps.querier.with_pair_settings(PairSettings {
+        swap_fee: Fee {
+            commission_rate_nom: Uint128(3u128),
+            commission_rate_denom: Uint128(1000u128),
+        },
+        swap_data_endpoint: None,
+        swap_data_endpoints: vec![SwapDataEndpoint {
+            address: HumanAddr::from("indexer0000"),
+            code_hash: "indexer0000_hash".to_string(),
+            best_effort_key_hash: None,
+        }],
+        protocol_fee: None,
+        status: PairStatusLevel::NormalRun,
+        dynamic_fee: None,
+        max_price_move: None,
+    });
+    init_pair(&mut deps);
+    store_reserves(
+        &mut deps.storage,
+        &[Uint128(1_000_000u128), Uint128(3_000_000u128)],
+    )
+    .unwrap();
+
+    // sells 1000asset0000 for at least 0.35uusd each, the pools pay 0.33
+    let owner = deps
+        .api
+        .canonical_address(&HumanAddr::from("addr0001"))
+        .unwrap();
+    place_limit_order(
+        &mut deps.storage,
+        &owner,
+        1,
+        Uint128(1_000u128),
+        Decimal::from_str("0.35").unwrap(),
+    )
+    .unwrap();
+
+    // the swap of the zap raises the price to 0.37
+    let msg = HandleMsg::ZapIn {
+        offer_asset: Asset {
+            info: native_info(),
+            amount: Uint128(100_000u128),
+        },
+        min_shares: None,
+        deadline: None,
+    };
+    let env = mock_env(
+        "addr0000",
+        &[Coin {
+            denom: "uusd".to_string(),
+            amount: Uint128(100_000u128),
+        }],
+    );
+    let res = handle(&mut deps, env, msg).unwrap();
+    assert_eq!(res.log.last(), Some(&log("filled_orders", "1")));
+    let zap_res: ZapInResponse = from_binary(&res.data.unwrap()).unwrap();
+
+    // the endpoint is called with the swap part of the zap
+    let (return_amount, spread_amount, commission_amount) = compute_swap(
+        &CurveType::ConstantProduct {},
+        0,
+        Uint128(1_000_000u128),
+        Uint128(3_000_000u128),
+        zap_res.swap_amount,
+        Uint128(3u128),
+        Uint128(1000u128),
+    )
+    .unwrap();
+    assert_eq!(zap_res.return_amount, return_amount);
+    let reserves = read_reserves(&deps.storage).unwrap();
+
+    // the order is paid out right away, at least its price
+    match &res.messages[res.messages.len() - 3] {
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address, amount, ..
+        }) => {
+            assert_eq!(to_address, &HumanAddr::from("addr0001"));
+            assert!(amount[0].amount >= Uint128(350u128));
+        }
+        _ => panic!("Must pay out the filled order"),
+    }
+    let endpoint = SwapDataEndpoint {
+        address: HumanAddr::from("indexer0000"),
+        code_hash: "indexer0000_hash".to_string(),
+        best_effort_key_hash: None,
+    };
+    assert_eq!(
+        res.messages.get(res.messages.len() - 2),
+        Some(
+            &endpoint
+                .clone()
+                .into_msg(SwapData {
+                    pair: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                    account: HumanAddr::from("addr0000"),
+                    asset_in: Asset {
+                        info: native_info(),
+                        amount: zap_res.swap_amount,
+                    },
+                    asset_out: Asset {
+                        info: token_info(),
+                        amount: return_amount + commission_amount,
+                    },
+                    commission: Asset {
+                        info: token_info(),
+                        amount: commission_amount,
+                    },
+                    spread_amount,
+                    reserves: [
+                        Asset {
+                            info: native_info(),
+                            amount: reserves[0],
+                        },
+                        Asset {
+                            info: token_info(),
+                            amount: reserves[1],
+                        },
+                    ],
+                })
+                .unwrap()
+        )
+    );
+
+    // and then with the fill of the order
+    match res.messages.last() {
+        Some(CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, .. })) => {
+            assert_eq!(contract_addr, &endpoint.address)
+        }
+        _ => panic!("Must report the filled order"),
+    }
+}
+
+#[test]
+fn test_zap_out() {
+    let mut deps = mock_dependencies(
+        20,
+        &[Coin {
+            denom: "uusd".to_string(),
+            amount: Uint128(1_000_000u128),
+        }],
+    );
+    with_pair_token_balances(&mut deps.querier, 1_000_000, 1_000_000);
+    init_pair(&mut deps);
+    store_reserves(
+        &mut deps.storage,
+        &[Uint128(1_000_000u128), Uint128(1_000_000u128)],
+    )
+    .unwrap();
+
+    // 10000uusd and 10000asset0000 are withdrawn, the uusd is swapped into 9871asset0000
+    let zap_out = |min_return: u128| HandleMsg::Receive {
+        from: HumanAddr::from("addr0000"),
+        amount: Uint128(10_000u128),
+        msg: Some(
+            to_binary(&Cw20HookMsg::ZapOut {
+                ask_asset: token_info(),
+                min_return: Some(Uint128(min_return)),
+                deadline: None,
+            })
+            .unwrap(),
+        ),
+    };
+    match handle(&mut deps, mock_env("liquidity0000", &[]), zap_out(19_872)).unwrap_err() {
+        StdError::GenericErr { msg, .. } => {
+            assert_eq!(msg, "Return amount 19871 is lower than min_return 19872")
+        }
+        _ => panic!("Must return generic error"),
+    }
+
+    // only the liquidity token can zap out
+    match handle(&mut deps, mock_env("asset0000", &[]), zap_out(19_871)).unwrap_err() {
+        StdError::Unauthorized { .. } => (),
+        _ => panic!("Must return unauthorized error"),
+    }
+
+    let res = handle(&mut deps, mock_env("liquidity0000", &[]), zap_out(19_871)).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![token_send_msg("addr0000", 19_871), lp_burn_msg(10_000)]
+    );
+    assert_eq!(
+        read_reserves(&deps.storage).unwrap(),
+        [Uint128(1_000_000u128), Uint128(980_129u128)]
+    );
+}
+
+#[test]
+fn test_zap_out_swap_routing() {
+    let mut deps = mock_dependencies(
+        20,
+        &[Coin {
+            denom: "uusd".to_string(),
+            amount: Uint128(1_000_100u128),
+        }],
+    );
+    with_pair_token_balances(&mut deps.querier, 1_000_000, 1_000_000);
+    let endpoint = SwapDataEndpoint {
+        address: HumanAddr::from("indexer0000"),
+        code_hash: "indexer0000_hash".to_string(),
+        best_effort_key_hash: None,
+    };
+    deps.querier.with_pair_settings(PairSettings {
+        swap_fee: Fee {
+            commission_rate_nom: Uint128(3u128),
+            commission_rate_denom: Uint128(1000u128),
+        },
+        swap_data_endpoint: None,
+        swap_data_endpoints: vec![endpoint.clone()],
+        protocol_fee: None,
+        status: PairStatusLevel::NormalRun,
+        dynamic_fee: None,
+        max_price_move: None,
+    });
+    init_pair(&mut deps);
+    store_reserves(
+        &mut deps.storage,
+        &[Uint128(1_000_000u128), Uint128(1_000_000u128)],
+    )
+    .unwrap();
+
+    // sells 100uusd for at least 1.01asset0000 each, the pools pay 1
+    let owner = deps
+        .api
+        .canonical_address(&HumanAddr::from("addr0001"))
+        .unwrap();
+    place_limit_order(
+        &mut deps.storage,
+        &owner,
+        0,
+        Uint128(100u128),
+        Decimal::from_str("1.01").unwrap(),
+    )
+    .unwrap();
+
+    // the swap of the zap raises the price of uusd to 1.02
+    let msg = HandleMsg::Receive {
+        from: HumanAddr::from("addr0000"),
+        amount: Uint128(10_000u128),
+        msg: Some(
+            to_binary(&Cw20HookMsg::ZapOut {
+                ask_asset: native_info(),
+                min_return: None,
+                deadline: None,
+            })
+            .unwrap(),
+        ),
+    };
+    let res = handle(&mut deps, mock_env("liquidity0000", &[]), msg).unwrap();
+    assert_eq!(res.log.last(), Some(&log("filled_orders", "1")));
+    assert_eq!(res.messages.len(), 5);
+    assert_eq!(res.messages[0], native_send_msg("addr0000", 19_871));
+    assert_eq!(res.messages[1], lp_burn_msg(10_000));
+
+    // the order fills against the pools after the zap, 980129uusd and 1000000asset0000, and
+    // is paid out with a plain transfer
+    let (fill_return, fill_spread, fill_commission) = compute_swap(
+        &CurveType::ConstantProduct {},
+        0,
+        Uint128(980_129u128),
+        Uint128(1_000_000u128),
+        Uint128(100u128),
+        Uint128(3u128),
+        Uint128(1000u128),
+    )
+    .unwrap();
+    assert!(fill_return >= Uint128(101u128));
+    assert_eq!(
+        res.messages[2],
+        token_transfer_msg("addr0001", fill_return.u128())
+    );
+
+    // the endpoint is called with the swap part of the zap
+    let reserves = read_reserves(&deps.storage).unwrap();
+    assert_eq!(
+        res.messages[3],
+        endpoint
+            .clone()
+            .into_msg(SwapData {
+                pair: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                account: HumanAddr::from("addr0000"),
+                asset_in: Asset {
+                    info: token_info(),
+                    amount: Uint128(10_000u128),
+                },
+                asset_out: Asset {
+                    info: native_info(),
+                    amount: Uint128(9_900u128),
+                },
+                commission: Asset {
+                    info: native_info(),
+                    amount: Uint128(29u128),
+                },
+                spread_amount: Uint128(100u128),
+                reserves: [
+                    Asset {
+                        info: native_info(),
+                        amount: reserves[0],
+                    },
+                    Asset {
+                        info: token_info(),
+                        amount: reserves[1],
+                    },
+                ],
+            })
+            .unwrap()
+    );
+
+    // and then with the fill, as a swap of the order owner
+    assert_eq!(
+        reserves,
+        [
+            Uint128(980_229u128),
+            Uint128(1_000_000u128 - fill_return.u128())
+        ]
+    );
+    assert_eq!(
+        res.messages[4],
+        endpoint
+            .into_msg(SwapData {
+                pair: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                account: HumanAddr::from("addr0001"),
+                asset_in: Asset {
+                    info: native_info(),
+                    amount: Uint128(100u128),
+                },
+                asset_out: Asset {
+                    info: token_info(),
+                    amount: fill_return + fill_commission,
+                },
+                commission: Asset {
+                    info: token_info(),
+                    amount: fill_commission,
+                },
+                spread_amount: fill_spread,
+                reserves: [
+                    Asset {
+                        info: native_info(),
+                        amount: reserves[0],
+                    },
+                    Asset {
+                        info: token_info(),
+                        amount: reserves[1],
+                    },
+                ],
+            })
+            .unwrap()
+    );
+}
+
+#[test]
+fn test_exact_output_swap() {
+    let mut deps = mock_dependencies(
+        20,
+        &[Coin {
+            denom: "uusd".to_string(),
+            amount: Uint128(1_012_000u128),
+        }],
+    );
+    with_pair_token_balances(&mut deps.querier, 1_000_000, 1_000_000);
+    init_pair(&mut deps);
+    store_reserves(
+        &mut deps.storage,
+        &[Uint128(1_000_000u128), Uint128(1_000_000u128)],
+    )
+    .unwrap();
+
+    let (offer_amount, _, _) = compute_offer_amount(
+        &CurveType::ConstantProduct {},
+        0,
+        Uint128(1_000_000u128),
+        Uint128(1_000_000u128),
+        Uint128(9_872u128),
+        3,
+        1000,
+    )
+    .unwrap();
+    // the offer amount is rounded up, it returns at least the ask amount
+    let (return_amount, _, _) = compute_swap(
+        &CurveType::ConstantProduct {},
+        0,
+        Uint128(1_000_000u128),
+        Uint128(1_000_000u128),
+        offer_amount,
+        Uint128(3u128),
+        Uint128(1000u128),
+    )
+    .unwrap();
+    assert!(return_amount >= Uint128(9_872u128));
+
+    let swap = |max_offer: u128| HandleMsg::Swap {
+        offer_asset: Asset {
+            info: native_info(),
+            amount: Uint128(max_offer),
+        },
+        expected_return: None,
+        belief_price: None,
+        max_spread: None,
+        to: None,
+        ask_amount: Some(Uint128(9_872u128)),
+        deadline: None,
+    };
+    let env = |max_offer: u128| {
+        mock_env(
+            "addr0000",
+            &[Coin {
+                denom: "uusd".to_string(),
+                amount: Uint128(max_offer),
+            }],
+        )
+    };
+
+    // the maximum offer doesn't buy the ask amount
+    match handle(&mut deps, env(9_000), swap(9_000)).unwrap_err() {
+        StdError::GenericErr { msg, .. } => assert_eq!(
+            msg,
+            format!(
+                "Offer amount {} exceeds the maximum offer amount 9000",
+                offer_amount
+            )
+        ),
+        _ => panic!("Must return generic error"),
+    }
+
+    // exactly the ask amount is returned, the unused offer is refunded
+    let res = handle(&mut deps, env(12_000), swap(12_000)).unwrap();
+    let refund_amount = 12_000 - offer_amount.u128();
+    assert_eq!(
+        res.messages,
+        vec![
+            token_send_msg("addr0000", 9_872),
+            native_send_msg("addr0000", refund_amount),
+        ]
+    );
+    let swap_res: SwapResponse = from_binary(&res.data.unwrap()).unwrap();
+    assert_eq!(swap_res.offer_asset.amount, offer_amount);
+    assert_eq!(swap_res.return_asset.amount, Uint128(9_872u128));
+    assert_eq!(swap_res.refund_amount, Uint128(refund_amount));
+    // what the offer returns above the ask amount stays in the pool
+    assert_eq!(
+        read_reserves(&deps.storage).unwrap(),
+        [
+            Uint128(1_000_000u128) + offer_amount,
+            Uint128(1_000_000u128 - 9_872u128)
+        ]
+    );
+
+    // through the token hook, the unused tokens are sent back
+    store_reserves(
+        &mut deps.storage,
+        &[Uint128(1_000_000u128), Uint128(1_000_000u128)],
+    )
+    .unwrap();
+    with_pair_token_balances(&mut deps.querier, 1_000_000, 1_012_000);
+    let msg = HandleMsg::Receive {
+        from: HumanAddr::from("addr0000"),
+        amount: Uint128(12_000u128),
+        msg: Some(
+            to_binary(&Cw20HookMsg::Swap {
+                expected_return: None,
+                belief_price: None,
+                max_spread: None,
+                to: Some(HumanAddr::from("addr0001")),
+                ask_amount: Some(Uint128(9_872u128)),
+                deadline: None,
+            })
+            .unwrap(),
+        ),
+    };
+    let res = handle(&mut deps, mock_env("asset0000", &[]), msg).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            native_send_msg("addr0001", 9_872),
+            token_send_msg("addr0000", refund_amount),
+        ]
+    );
+    let swap_res: SwapResponse = from_binary(&res.data.unwrap()).unwrap();
+    assert_eq!(swap_res.return_asset.amount, Uint128(9_872u128));
+    assert_eq!(swap_res.refund_amount, Uint128(refund_amount));
+}
+
+#[test]
+fn test_deadline() {
+    let mut deps = mock_dependencies(
+        20,
+        &[Coin {
+            denom: "uusd".to_string(),
+            amount: Uint128(1_000_000u128),
+        }],
+    );
+    with_pair_token_balances(&mut deps.querier, 1_000_000, 1_000_000);
+    init_pair(&mut deps);
+    store_reserves(
+        &mut deps.storage,
+        &[Uint128(1_000_000u128), Uint128(1_000_000u128)],
+    )
+    .unwrap();
+
+    let env = mock_env(
+        "addr0000",
+        &[Coin {
+            denom: "uusd".to_string(),
+            amount: Uint128(1_000u128),
+        }],
+    );
+    let expired = Some(Deadline {
+        time: Some(env.block.time - 1),
+        height: None,
+    });
+    let assert_expired = |res: HandleResult| match res.unwrap_err() {
+        StdError::GenericErr { msg, .. } => assert_eq!(
+            msg,
+            format!(
+                "Deadline expired at block time {}, current block time is {}",
+                env.block.time - 1,
+                env.block.time
+            )
+        ),
+        _ => panic!("Must return generic error"),
+    };
+
+    let msg = HandleMsg::Swap {
+        offer_asset: Asset {
+            info: native_info(),
+            amount: Uint128(1_000u128),
+        },
+        expected_return: None,
+        belief_price: None,
+        max_spread: None,
+        to: None,
+        ask_amount: None,
+        deadline: expired.clone(),
+    };
+    assert_expired(handle(&mut deps, env.clone(), msg));
+
+    let msg = HandleMsg::Receive {
+        from: HumanAddr::from("addr0000"),
+        amount: Uint128(1_000u128),
+        msg: Some(
+            to_binary(&Cw20HookMsg::Swap {
+                expected_return: None,
+                belief_price: None,
+                max_spread: None,
+                to: None,
+                ask_amount: None,
+                deadline: expired.clone(),
+            })
+            .unwrap(),
+        ),
+    };
+    assert_expired(handle(&mut deps, mock_env("asset0000", &[]), msg));
+
+    let msg = HandleMsg::ProvideLiquidity {
+        assets: [
+            Asset {
+                info: token_info(),
+                amount: Uint128(1_000u128),
+            },
+            Asset {
+                info: native_info(),
+                amount: Uint128(1_000u128),
+            },
+        ],
+        slippage_tolerance: None,
+        deadline: expired.clone(),
+    };
+    assert_expired(handle(&mut deps, env.clone(), msg));
+
+    let msg = HandleMsg::Receive {
+        from: HumanAddr::from("addr0000"),
+        amount: Uint128(1_000u128),
+        msg: Some(
+            to_binary(&Cw20HookMsg::WithdrawLiquidity {
+                deadline: expired.clone(),
+            })
+            .unwrap(),
+        ),
+    };
+    assert_expired(handle(&mut deps, mock_env("liquidity0000", &[]), msg));
+
+    // nothing moved
+    assert_eq!(
+        read_reserves(&deps.storage).unwrap(),
+        [Uint128(1_000_000u128), Uint128(1_000_000u128)]
+    );
+
+    // the deadline is inclusive
+    let msg = HandleMsg::Receive {
+        from: HumanAddr::from("addr0000"),
+        amount: Uint128(1_000u128),
+        msg: Some(
+            to_binary(&Cw20HookMsg::WithdrawLiquidity {
+                deadline: Some(Deadline {
+                    time: Some(env.block.time),
+                    height: Some(env.block.height),
+                }),
+            })
+            .unwrap(),
+        ),
+    };
+    handle(&mut deps, mock_env("liquidity0000", &[]), msg).unwrap();
+}
+
+#[test]
+fn test_pair_status() {
+    let swap = || HandleMsg::Swap {
+        offer_asset: Asset {
+            info: native_info(),
+            amount: Uint128(1_000u128),
+        },
+        expected_return: None,
+        belief_price: None,
+        max_spread: None,
+        to: None,
+        ask_amount: None,
+        deadline: None,
+    };
+    let provide = || HandleMsg::ProvideLiquidity {
+        assets: [
+            Asset {
+                info: token_info(),
+                amount: Uint128(1_000u128),
+            },
+            Asset {
+                info: native_info(),
+                amount: Uint128(1_000u128),
+            },
+        ],
+        slippage_tolerance: None,
+        deadline: None,
+    };
+    let withdraw = || HandleMsg::Receive {
+        from: HumanAddr::from("addr0000"),
+        amount: Uint128(1_000u128),
+        msg: Some(to_binary(&Cw20HookMsg::WithdrawLiquidity { deadline: None }).unwrap()),
+    };
+    let env = mock_env(
+        "addr0000",
+        &[Coin {
+            denom: "uusd".to_string(),
+            amount: Uint128(1_000u128),
+        }],
+    );
+
+    // (status, swaps allowed, deposits allowed), withdrawals are always allowed
+    for (status, swaps, deposits) in [
+        (PairStatusLevel::NormalRun, true, true),
+        (PairStatusLevel::StopSwaps, false, true),
+        (PairStatusLevel::StopAllButWithdrawals, false, false),
+    ]
+    .iter()
+    {
+        let mut deps = mock_dependencies(
+            20,
+            &[Coin {
+                denom: "uusd".to_string(),
+                amount: Uint128(1_001_000u128),
+            }],
+        );
+        with_pair_token_balances(&mut deps.querier, 1_000_000, 1_000_000);
+        deps.querier.with_pair_settings(PairSettings {
+            swap_fee: Fee {
+                commission_rate_nom: Uint128(3u128),
+                commission_rate_denom: Uint128(1000u128),
+            },
+            swap_data_endpoint: None,
+            swap_data_endpoints: vec![],
+            protocol_fee: None,
+            status: *status,
+            dynamic_fee: None,
+            max_price_move: None,
+        });
+        init_pair(&mut deps);
+        let reserves = [Uint128(1_000_000u128), Uint128(1_000_000u128)];
+
+        store_reserves(&mut deps.storage, &reserves).unwrap();
+        match handle(&mut deps, env.clone(), swap()) {
+            Ok(_) => assert!(swaps),
+            Err(StdError::GenericErr { msg, .. }) => {
+                assert!(!swaps);
+                assert_eq!(msg, "Swaps are stopped on this pair");
+            }
+            Err(_) => panic!("Must return generic error"),
+        }
+
+        store_reserves(&mut deps.storage, &reserves).unwrap();
+        match handle(&mut deps, env.clone(), provide()) {
+            Ok(_) => assert!(deposits),
+            Err(StdError::GenericErr { msg, .. }) => {
+                assert!(!deposits);
+                assert_eq!(msg, "Deposits are stopped on this pair");
+            }
+            Err(_) => panic!("Must return generic error"),
+        }
+
+        store_reserves(&mut deps.storage, &reserves).unwrap();
+        handle(&mut deps, mock_env("liquidity0000", &[]), withdraw()).unwrap();
+    }
+}
+
+#[test]
+fn test_migrate_liquidity() {
+    let mut deps = mock_dependencies(20, &[]);
+    with_pair_token_balances(&mut deps.querier, 0, 0);
+    let old_pair_info = PairInfo {
+        asset_infos: [native_info(), token_info()],
+        contract_addr: HumanAddr::from("pair0000"),
+        liquidity_token: HumanAddr::from("liquidity0001"),
+        token_code_hash: "liquidity0001_hash".to_string(),
+        asset0_volume: Uint128::zero(),
+        asset1_volume: Uint128::zero(),
+        factory: Factory {
+            address: HumanAddr::from("factory0000"),
+            code_hash: "factory0000_hash".to_string(),
+        },
+        curve: CurveType::default(),
+    };
+    deps.querier.with_pairs(&[
+        (&HumanAddr::from("pair0000"), &old_pair_info),
+        (
+            &HumanAddr::from("pair0001"),
+            &PairInfo {
+                asset_infos: [
+                    AssetInfo::NativeToken {
+                        denom: "uscrt".to_string(),
+                    },
+                    token_info(),
+                ],
+                ..old_pair_info.clone()
+            },
+        ),
+    ]);
+
+    // the old pair must have the same assets
+    let msg = PairInitMsg {
+        migrate_from: Some(MigrateFrom {
+            address: HumanAddr::from("pair0001"),
+            code_hash: "pair0001_hash".to_string(),
+        }),
+        ..pair_init_msg(None)
+    };
+    match init(&mut deps, mock_env("addr0000", &[]), msg).unwrap_err() {
+        StdError::GenericErr { msg, .. } => assert_eq!(
+            msg,
+            "The pair to migrate from must have the same asset infos"
+        ),
+        _ => panic!("Must return generic error"),
+    }
+
+    // the pair registers to receive the old LP token
+    let msg = PairInitMsg {
+        migrate_from: Some(MigrateFrom {
+            address: HumanAddr::from("pair0000"),
+            code_hash: "pair0000_hash".to_string(),
+        }),
+        ..pair_init_msg(None)
+    };
+    let res = init(&mut deps, mock_env("addr0000", &[]), msg).unwrap();
+    assert_eq!(
+        res.messages.last().unwrap(),
+        &snip20::register_receive_msg(
+            "".to_string(),
+            None,
+            256,
+            "liquidity0001_hash".to_string(),
+            HumanAddr::from("liquidity0001"),
+        )
+        .unwrap()
+    );
+    handle(
+        &mut deps,
+        mock_env("liquidity0000", &[]),
+        HandleMsg::PostInitialize {},
+    )
+    .unwrap();
+    let res = query_migration(&deps).unwrap();
+    assert_eq!(res.old_pair, Some(HumanAddr::from("pair0000")));
+    assert_eq!(
+        res.old_liquidity_token,
+        Some(HumanAddr::from("liquidity0001"))
+    );
+
+    assert_eq!(res.new_pair, None);
+
+    // only the old pair hands its reserves over
+    let finalize_msg = |total_share: u128| HandleMsg::FinalizeMigration {
+        reserves: [Uint128(2_000u128), Uint128(8_000u128)],
+        total_share: Uint128(total_share),
+    };
+    for sender in ["addr0000", MOCK_CONTRACT_ADDR].iter() {
+        match handle(&mut deps, mock_env(*sender, &[]), finalize_msg(4_000)) {
+            Err(StdError::Unauthorized { .. }) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+    }
+
+    // the reserves are taken over once and the old LP supply minted to the pair
+    with_pair_token_balances(&mut deps.querier, 0, 8_000);
+    let res = handle(&mut deps, mock_env("pair0000", &[]), finalize_msg(4_000)).unwrap();
+    assert_eq!(res.messages, vec![lp_mint_msg(MOCK_CONTRACT_ADDR, 4_000)]);
+    assert_eq!(
+        read_reserves(&deps.storage).unwrap(),
+        [Uint128(2_000u128), Uint128(8_000u128)]
+    );
+    with_pair_token_balances(&mut deps.querier, 4_000, 8_000);
+    match handle(&mut deps, mock_env("pair0000", &[]), finalize_msg(4_000)) {
+        Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "Pair already has liquidity"),
+        _ => panic!("Must return generic error"),
+    }
+
+    // only the old LP token can send the hook
+    let migrate_msg = |amount: u128, from: &str| HandleMsg::Receive {
+        from: HumanAddr::from(from),
+        amount: Uint128(amount),
+        msg: Some(to_binary(&Cw20HookMsg::MigrateLiquidity {}).unwrap()),
+    };
+    for sender in ["liquidity0000", "asset0000"].iter() {
+        match handle(
+            &mut deps,
+            mock_env(*sender, &[]),
+            migrate_msg(1_000, "addr0001"),
+        ) {
+            Err(StdError::Unauthorized { .. }) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+    }
+
+    // old LP tokens are burned and as many new ones sent back, the reserves don't move
+    let res = handle(
+        &mut deps,
+        mock_env("liquidity0001", &[]),
+        migrate_msg(1_000, "addr0001"),
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            snip20::burn_msg(
+                Uint128(1_000u128),
+                None,
+                256,
+                "liquidity0001_hash".to_string(),
+                HumanAddr::from("liquidity0001"),
+            )
+            .unwrap(),
+            snip20::transfer_msg(
+                HumanAddr::from("addr0001"),
+                Uint128(1_000u128),
+                None,
+                256,
+                "liquidity0000_hash".to_string(),
+                HumanAddr::from("liquidity0000"),
+            )
+            .unwrap(),
+        ]
+    );
+    assert_eq!(
+        read_reserves(&deps.storage).unwrap(),
+        [Uint128(2_000u128), Uint128(8_000u128)]
+    );
+
+    // the share counts as a deposit of what it is worth
+    let account = deps
+        .api
+        .canonical_address(&HumanAddr::from("addr0001"))
+        .unwrap();
+    let position = compute_position(
+        &deps.storage,
+        &account,
+        &CurveType::default(),
+        [Uint128(2_000u128), Uint128(8_000u128)],
+        Uint128(4_000u128),
+    )
+    .unwrap();
+    assert_eq!(position.entry.share, Uint128(1_000u128));
+    assert_eq!(
+        position.entry.cost_basis,
+        [Uint128(500u128), Uint128(2_000u128)]
+    );
+}
+
+#[test]
+fn test_migrate_reserves() {
+    let mut deps = mock_dependencies(
+        20,
+        &[Coin {
+            denom: "uusd".to_string(),
+            amount: Uint128(2_000u128),
+        }],
+    );
+    with_pair_token_balances(&mut deps.querier, 4_000, 8_000);
+    init_pair(&mut deps);
+    store_reserves(&mut deps.storage, &[Uint128(2_000u128), Uint128(8_000u128)]).unwrap();
+
+    // only the factory migrates the reserves
+    let msg = HandleMsg::MigrateReserves {
+        new_pair: HumanAddr::from("pair0001"),
+        new_pair_code_hash: "pair0001_hash".to_string(),
+    };
+    match handle(&mut deps, mock_env("addr0000", &[]), msg.clone()) {
+        Err(StdError::Unauthorized { .. }) => {}
+        _ => panic!("Must return unauthorized error"),
+    }
+
+    // the reserves are sent without a callback, then the new pair takes them over with the
+    // LP supply they back
+    let res = handle(&mut deps, mock_env("factory0000", &[]), msg.clone()).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            native_send_msg("pair0001", 2_000),
+            snip20::transfer_msg(
+                HumanAddr::from("pair0001"),
+                Uint128(8_000u128),
+                None,
+                256,
+                "asset0000_hash".to_string(),
+                HumanAddr::from("asset0000"),
+            )
+            .unwrap(),
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: HumanAddr::from("pair0001"),
+                callback_code_hash: "pair0001_hash".to_string(),
+                msg: to_binary(&HandleMsg::FinalizeMigration {
+                    reserves: [Uint128(2_000u128), Uint128(8_000u128)],
+                    total_share: Uint128(4_000u128),
+                })
+                .unwrap(),
+                send: vec![],
+            }),
+        ]
+    );
+    assert_eq!(
+        read_reserves(&deps.storage).unwrap(),
+        [Uint128::zero(), Uint128::zero()]
+    );
+    let res = query_migration(&deps).unwrap();
+    assert_eq!(res.new_pair, Some(HumanAddr::from("pair0001")));
+
+    // the pair is stopped, including a second migration and withdrawals
+    let swap_msg = HandleMsg::Swap {
+        offer_asset: Asset {
+            info: native_info(),
+            amount: Uint128(100u128),
+        },
+        expected_return: None,
+        belief_price: None,
+        max_spread: None,
+        to: None,
+        ask_amount: None,
+        deadline: None,
+    };
+    let withdraw_msg = HandleMsg::Receive {
+        from: HumanAddr::from("addr0000"),
+        amount: Uint128(1_000u128),
+        msg: Some(to_binary(&Cw20HookMsg::WithdrawLiquidity { deadline: None }).unwrap()),
+    };
+    let stopped = [
+        (mock_env("factory0000", &[]), msg),
+        (
+            mock_env(
+                "addr0000",
+                &[Coin {
+                    denom: "uusd".to_string(),
+                    amount: Uint128(100u128),
+                }],
+            ),
+            swap_msg,
+        ),
+        (mock_env("liquidity0000", &[]), withdraw_msg),
+    ];
+    for (env, msg) in stopped.iter() {
+        match handle(&mut deps, env.clone(), msg.clone()) {
+            Err(StdError::GenericErr { msg, .. }) => {
+                assert_eq!(msg, "Pair was migrated to pair0001")
+            }
+            _ => panic!("Must return generic error"),
+        }
+    }
+
+    // collecting accrued protocol fees still runs, it only lacks a collector here
+    match handle(
+        &mut deps,
+        mock_env("addr0000", &[]),
+        HandleMsg::CollectProtocolFees {},
+    ) {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "Protocol fee collector is not set")
+        }
+        _ => panic!("Must return generic error"),
+    }
+}
+
+#[test]
+fn test_max_spread() {
+    assert_max_spread(
+        Some(Decimal::from_ratio(1200u128, 1u128)),
+        Some(Decimal::percent(1)),
+        None,
+        Uint128::from(1200000000u128),
+        Uint128::from(989999u128),
+        Uint128::zero(),
+        Uint128::zero(),
+    )
+    .unwrap_err();
+
+    assert_max_spread(
+        Some(Decimal::from_ratio(1200u128, 1u128)),
+        Some(Decimal::percent(1)),
+        None,
+        Uint128::from(1200000000u128),
+        Uint128::from(990000u128),
+        Uint128::zero(),
+        Uint128::zero(),
+    )
+    .unwrap();
+
+    assert_max_spread(
+        None,
+        Some(Decimal::percent(1)),
+        None,
+        Uint128::zero(),
+        Uint128::from(989999u128),
+        Uint128::zero(),
+        Uint128::from(10001u128),
+    )
+    .unwrap_err();
+
+    assert_max_spread(
+        None,
+        Some(Decimal::percent(1)),
+        None,
+        Uint128::zero(),
+        Uint128::from(990000u128),
+        Uint128::zero(),
+        Uint128::from(10000u128),
+    )
+    .unwrap();
+
+    // expected_return takes precedence
+    assert_max_spread(
+        None,
+        None,
+        Some(Uint128::from(990001u128)),
+        Uint128::zero(),
+        Uint128::from(990000u128),
+        Uint128::zero(),
+        Uint128::zero(),
+    )
+    .unwrap_err();
+}
+
+#[test]
+fn test_deduct() {
+    let deps = mock_dependencies(20, &[]);
+
+    // there is no tax on Secret Network
+    let amount = Uint128(1000_000_000u128);
+    let after_amount = (Asset {
+        info: native_info(),
+        amount,
+    })
+    .deduct_tax(&deps)
+    .unwrap();
+
+    assert_eq!(amount, after_amount.amount);
+}
+
+#[test]
+fn test_query_pool() {
+    let total_share_amount = Uint128::from(111u128);
+    let asset_0_amount = Uint128::from(222u128);
+    let asset_1_amount = Uint128::from(333u128);
+    let mut deps = mock_dependencies(
+        20,
+        &[Coin {
+            denom: "uusd".to_string(),
+            amount: asset_0_amount,
+        }],
+    );
+
+    deps.querier.with_token_balances(&[
+        (
+            &HumanAddr::from("asset0000"),
+            &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &asset_1_amount)],
+        ),
+        (
+            &HumanAddr::from("liquidity0000"),
+            &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &total_share_amount)],
+        ),
+    ]);
+
+    init_pair(&mut deps);
+    store_reserves(&mut deps.storage, &[asset_0_amount, asset_1_amount]).unwrap();
+
+    let res: PoolResponse = query_pool(&deps).unwrap();
+
+    assert_eq!(
         res.assets,
         [
             Asset {
-                info: AssetInfo::NativeToken {
-                    denom: "uusd".to_string(),
+                info: native_info(),
+                amount: asset_0_amount,
+            },
+            Asset {
+                info: token_info(),
+                amount: asset_1_amount,
+            }
+        ]
+    );
+    assert_eq!(res.total_share, total_share_amount);
+}
+
+#[test]
+fn test_stable_swap() {
+    let pool = Uint128(1_000_000_000_000);
+
+    // balanced pool: D is the sum of both sides
+    assert_eq!(
+        compute_d(100, pool.u128().into(), pool.u128().into()).unwrap(),
+        (2 * pool.u128()).into()
+    );
+
+    // balanced pool trades close to 1:1, only the commission is taken
+    let (return_amount, spread_amount, commission_amount) = compute_stable_swap(
+        100,
+        pool,
+        pool,
+        Uint128(1_000_000),
+        Uint128(3),
+        Uint128(1000),
+    )
+    .unwrap();
+    assert_eq!(return_amount, Uint128(997_000));
+    assert_eq!(spread_amount, Uint128(1));
+    assert_eq!(commission_amount, Uint128(2_999));
+
+    // reverse simulation asks at least as much as the swap returned
+    let (offer_amount, _, _) =
+        compute_stable_offer_amount(100, pool, pool, return_amount, 3, 1000).unwrap();
+    assert!(offer_amount >= Uint128(1_000_000));
+
+    // imbalanced pool prices in a spread
+    let (return_amount, spread_amount, _) = compute_stable_swap(
+        100,
+        pool,
+        Uint128(100_000_000_000),
+        Uint128(1_000_000),
+        Uint128(0),
+        Uint128(1000),
+    )
+    .unwrap();
+    assert_eq!(return_amount, Uint128(931_827));
+    assert_eq!(spread_amount, Uint128(68_173));
+}
+
+#[test]
+fn test_weighted_swap() {
+    let pool_0 = Uint128(1_000_000_000_000);
+    let pool_1 = Uint128(250_000_000_000);
+
+    // 50/50 weights behave like the constant product
+    let (return_amount, _, commission_amount) = compute_weighted_swap(
+        50,
+        50,
+        pool_0,
+        pool_0,
+        Uint128(1_000_000),
+        Uint128(3),
+        Uint128(1000),
+    )
+    .unwrap();
+    assert_eq!(return_amount, Uint128(996_999));
+    assert_eq!(commission_amount, Uint128(2_999));
+
+    // 80/20 pool with a spot price of 1 asset_1 = 1 asset_0
+    let (return_amount, spread_amount, commission_amount) = compute_weighted_swap(
+        80,
+        20,
+        pool_0,
+        pool_1,
+        Uint128(1_000_000_000),
+        Uint128(3),
+        Uint128(1000),
+    )
+    .unwrap();
+    assert_eq!(return_amount, Uint128(994_512_477));
+    assert_eq!(spread_amount, Uint128(2_495_009));
+    assert_eq!(commission_amount, Uint128(2_992_514));
+
+    // reverse simulation asks at least as much as the swap offered
+    let (offer_amount, _, _) =
+        compute_weighted_offer_amount(80, 20, pool_0, pool_1, return_amount, 3, 1000).unwrap();
+    assert!(offer_amount >= Uint128(1_000_000_000));
+
+    // swaps are limited to a fraction of the pools
+    compute_weighted_swap(
+        80,
+        20,
+        pool_0,
+        pool_1,
+        Uint128(500_000_000_001),
+        Uint128(3),
+        Uint128(1000),
+    )
+    .unwrap_err();
+    compute_weighted_offer_amount(80, 20, pool_0, pool_1, Uint128(100_000_000_000), 3, 1000)
+        .unwrap_err();
+
+    // initial share is the weighted geometric mean of the deposits
+    assert_eq!(
+        compute_weighted_initial_share([50, 50], 4_000_000u64.into(), 1_000_000u64.into()).unwrap(),
+        2_000_000u64.into()
+    );
+    assert_eq!(
+        compute_weighted_initial_share([80, 20], 16_000_000u64.into(), 1_000_000u64.into())
+            .unwrap(),
+        9_189_586u64.into()
+    );
+}
+
+#[test]
+fn test_twap() {
+    let mut storage = MockStorage::new();
+    let curve = CurveType::ConstantProduct {};
+
+    init_price_cumulative(&mut storage, 100).unwrap();
+    // empty pools don't accumulate
+    update_price_cumulative(&mut storage, &curve, [Uint128(0), Uint128(0)], 105).unwrap();
+    // price0 was 2 between 105 and 115
+    update_price_cumulative(&mut storage, &curve, [Uint128(1000), Uint128(2000)], 115).unwrap();
+    // only the first update of a block counts
+    update_price_cumulative(&mut storage, &curve, [Uint128(1000), Uint128(4000)], 115).unwrap();
+    update_price_cumulative(&mut storage, &curve, [Uint128(1000), Uint128(2000)], 115).unwrap();
+    // price0 was 4 between 115 and 125
+    update_price_cumulative(&mut storage, &curve, [Uint128(1000), Uint128(4000)], 125).unwrap();
+
+    // current pools price asset 0 at 1
+    let pools = [Uint128(1000), Uint128(1000)];
+
+    let (price0, price1) = compute_twap(&storage, &curve, pools, 105, 125).unwrap();
+    assert_eq!(price0, Decimal::from_str("3").unwrap());
+    assert_eq!(price1, Decimal::from_str("0.375").unwrap());
+
+    // interpolated between observations
+    let (price0, _) = compute_twap(&storage, &curve, pools, 110, 120).unwrap();
+    assert_eq!(price0, Decimal::from_str("3").unwrap());
+
+    // extrapolated with the current pools after the last observation
+    let (price0, _) = compute_twap(&storage, &curve, pools, 120, 130).unwrap();
+    assert_eq!(price0, Decimal::from_str("2.5").unwrap());
+
+    // before the first observation
+    compute_twap(&storage, &curve, pools, 99, 130).unwrap_err();
+    // empty range
+    compute_twap(&storage, &curve, pools, 130, 130).unwrap_err();
+}
+
+#[test]
+fn test_dynamic_fee() {
+    let mut storage = MockStorage::new();
+    let curve = CurveType::ConstantProduct {};
+    let fee = |nom: u128, denom: u128| Fee {
+        commission_rate_nom: Uint128(nom),
+        commission_rate_denom: Uint128(denom),
+    };
+    let dynamic_fee = DynamicFee {
+        min_fee: fee(1, 1000),
+        max_fee: fee(10, 1000),
+        max_volatility: Decimal::percent(2),
+    };
+    dynamic_fee.validate().unwrap();
+    DynamicFee {
+        min_fee: fee(10, 1000),
+        max_fee: fee(1, 1000),
+        ..dynamic_fee.clone()
+    }
+    .validate()
+    .unwrap_err();
+    DynamicFee {
+        max_fee: fee(1000, 1000),
+        ..dynamic_fee.clone()
+    }
+    .validate()
+    .unwrap_err();
+    DynamicFee {
+        max_volatility: Decimal::zero(),
+        ..dynamic_fee.clone()
+    }
+    .validate()
+    .unwrap_err();
+
+    init_price_cumulative(&mut storage, 100).unwrap();
+    // the first block only records the pools
+    update_price_cumulative(&mut storage, &curve, [Uint128(1000), Uint128(1000)], 106).unwrap();
+    assert_eq!(read_average_change(&storage).unwrap(), Decimal::zero());
+    assert_eq!(
+        compute_dynamic_fee(&storage, &dynamic_fee).unwrap(),
+        fee(1_000_000_000_000_000, 1_000_000_000_000_000_000)
+    );
+
+    // price0 moved 10% in 60 seconds out of the 600 second window
+    update_price_cumulative(&mut storage, &curve, [Uint128(1000), Uint128(1100)], 166).unwrap();
+    // only the first update of a block counts
+    update_price_cumulative(&mut storage, &curve, [Uint128(1000), Uint128(2000)], 166).unwrap();
+    assert_eq!(read_average_change(&storage).unwrap(), Decimal::percent(1));
+    // half of max_volatility, halfway between the bounds
+    assert_eq!(
+        compute_dynamic_fee(&storage, &dynamic_fee).unwrap(),
+        fee(5_500_000_000_000_000, 1_000_000_000_000_000_000)
+    );
+    assert_eq!(
+        compute_dynamic_fee(
+            &storage,
+            &DynamicFee {
+                max_volatility: Decimal::percent(1),
+                ..dynamic_fee.clone()
+            }
+        )
+        .unwrap(),
+        fee(10_000_000_000_000_000, 1_000_000_000_000_000_000)
+    );
+
+    // a whole window without moves
+    update_price_cumulative(&mut storage, &curve, [Uint128(1000), Uint128(1100)], 766).unwrap();
+    assert_eq!(read_average_change(&storage).unwrap(), Decimal::zero());
+
+    // changes are capped at 100%
+    update_price_cumulative(&mut storage, &curve, [Uint128(1000), Uint128(3300)], 772).unwrap();
+    assert_eq!(read_average_change(&storage).unwrap(), Decimal::percent(1));
+}
+
+#[test]
+fn test_circuit_breaker() {
+    let mut storage = MockStorage::new();
+    let curve = CurveType::ConstantProduct {};
+    let max_price_move = Some(Decimal::percent(5));
+
+    init_price_cumulative(&mut storage, 100).unwrap();
+    // the pools were empty at the start of the block
+    update_price_cumulative(&mut storage, &curve, [Uint128(0), Uint128(0)], 106).unwrap();
+    store_reserves(&mut storage, &[Uint128(1000), Uint128(1000)]).unwrap();
+    assert_price_band(&storage, &curve, max_price_move, 7).unwrap();
+
+    // the next block starts at price0 1
+    update_price_cumulative(&mut storage, &curve, [Uint128(1000), Uint128(1000)], 112).unwrap();
+    store_reserves(&mut storage, &[Uint128(1000), Uint128(1040)]).unwrap();
+    assert_price_band(&storage, &curve, max_price_move, 7).unwrap();
+    store_reserves(&mut storage, &[Uint128(1000), Uint128(1060)]).unwrap();
+    match assert_price_band(&storage, &curve, max_price_move, 7) {
+        Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+            msg,
+            "Circuit breaker tripped at height 7: the price would move 0.06 from the start of the block, the limit is 0.05"
+        ),
+        _ => panic!("Must return generic error"),
+    }
+    // pairs without a limit
+    assert_price_band(&storage, &curve, None, 7).unwrap();
+
+    // moves within a block add up, the band stays around the start of the block
+    update_price_cumulative(&mut storage, &curve, [Uint128(1000), Uint128(1040)], 112).unwrap();
+    store_reserves(&mut storage, &[Uint128(1000), Uint128(1080)]).unwrap();
+    assert_price_band(&storage, &curve, max_price_move, 7).unwrap_err();
+    store_reserves(&mut storage, &[Uint128(1000), Uint128(940)]).unwrap();
+    assert_price_band(&storage, &curve, max_price_move, 7).unwrap_err();
+    store_reserves(&mut storage, &[Uint128(1000), Uint128(960)]).unwrap();
+    assert_price_band(&storage, &curve, max_price_move, 7).unwrap();
+
+    // the next block starts from the current price
+    update_price_cumulative(&mut storage, &curve, [Uint128(1000), Uint128(1040)], 118).unwrap();
+    store_reserves(&mut storage, &[Uint128(1000), Uint128(1080)]).unwrap();
+    assert_price_band(&storage, &curve, max_price_move, 7).unwrap();
+}
+
+#[test]
+fn test_circuit_breaker_swap() {
+    let mut deps = mock_dependencies(
+        20,
+        &[Coin {
+            denom: "uusd".to_string(),
+            amount: Uint128(1_100_000u128),
+        }],
+    );
+    with_pair_token_balances(&mut deps.querier, 1_000_000, 1_000_000);
+    deps.querier.with_pair_settings(PairSettings {
+        swap_fee: Fee {
+            commission_rate_nom: Uint128(3u128),
+            commission_rate_denom: Uint128(1000u128),
+        },
+        swap_data_endpoint: None,
+        swap_data_endpoints: vec![],
+        protocol_fee: None,
+        status: PairStatusLevel::NormalRun,
+        dynamic_fee: None,
+        max_price_move: Some(Decimal::percent(5)),
+    });
+    init_pair(&mut deps);
+    store_reserves(
+        &mut deps.storage,
+        &[Uint128(1_000_000u128), Uint128(1_000_000u128)],
+    )
+    .unwrap();
+
+    let swap = |amount: u128| {
+        let msg = HandleMsg::Swap {
+            offer_asset: Asset {
+                info: native_info(),
+                amount: Uint128(amount),
+            },
+            expected_return: None,
+            belief_price: None,
+            max_spread: None,
+            to: None,
+            ask_amount: None,
+            deadline: None,
+        };
+        let env = mock_env_with_block_time(
+            "addr0000",
+            &[Coin {
+                denom: "uusd".to_string(),
+                amount: Uint128(amount),
+            }],
+            1_571_797_425,
+        );
+        (msg, env)
+    };
+
+    // a swap moving the price 17% fails and reports the trip
+    let (msg, env) = swap(100_000);
+    match handle(&mut deps, env, msg) {
+        Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+            msg,
+            "Circuit breaker tripped at height 1: the price would move 0.173307272727272728 from the start of the block, the limit is 0.05"
+        ),
+        _ => panic!("Must return generic error"),
+    }
+
+    // the chain reverts the failed swap, the mock storage doesn't
+    store_reserves(
+        &mut deps.storage,
+        &[Uint128(1_000_000u128), Uint128(1_000_000u128)],
+    )
+    .unwrap();
+
+    // swaps within the band go through, the query shows the band of the block
+    let (msg, env) = swap(10_000);
+    handle(&mut deps, env, msg).unwrap();
+    let circuit_breaker = query_circuit_breaker(&deps).unwrap();
+    assert_eq!(circuit_breaker.block_time, 1_571_797_425);
+    assert_eq!(circuit_breaker.block_price, Some(Decimal::one()));
+    assert_eq!(circuit_breaker.min_price, Some(Decimal::percent(95)));
+    assert_eq!(circuit_breaker.max_price, Some(Decimal::percent(105)));
+}
+
+#[test]
+fn test_stats() {
+    let mut storage = MockStorage::new();
+    assert_eq!(
+        read_stats(&storage, StatsInterval::Hourly, None, 10).unwrap(),
+        vec![]
+    );
+
+    // two trades in the first hour, one in the third
+    record_trade(&mut storage, 3_600, 0, Uint128(100), Uint128(3)).unwrap();
+    record_trade(&mut storage, 7_199, 1, Uint128(50), Uint128(1)).unwrap();
+    record_trade(&mut storage, 10_800, 0, Uint128(10), Uint128(2)).unwrap();
+
+    let hourly = read_stats(&storage, StatsInterval::Hourly, None, 10).unwrap();
+    assert_eq!(hourly.len(), 2);
+    assert_eq!(hourly[0].start_time, 10_800);
+    assert_eq!(hourly[0].volume, [Uint128(10), Uint128(0)]);
+    assert_eq!(hourly[0].commission, [Uint128(0), Uint128(2)]);
+    assert_eq!(hourly[1].start_time, 3_600);
+    assert_eq!(hourly[1].trade_count, 2);
+    assert_eq!(hourly[1].volume, [Uint128(100), Uint128(50)]);
+    assert_eq!(hourly[1].commission, [Uint128(1), Uint128(3)]);
+
+    // paginated by start time
+    let page = read_stats(&storage, StatsInterval::Hourly, None, 1).unwrap();
+    assert_eq!(page, hourly[..1].to_vec());
+    let page = read_stats(&storage, StatsInterval::Hourly, Some(10_800), 1).unwrap();
+    assert_eq!(page, hourly[1..].to_vec());
+
+    let daily = read_stats(&storage, StatsInterval::Daily, None, 10).unwrap();
+    assert_eq!(daily.len(), 1);
+    assert_eq!(daily[0].start_time, 0);
+    assert_eq!(daily[0].trade_count, 3);
+
+    // a week later the first hour's slot is reused and the old bucket is dropped
+    let later = 3_600 * (HOURLY_CAPACITY + 1);
+    record_trade(&mut storage, later, 1, Uint128(7), Uint128(0)).unwrap();
+    let hourly = read_stats(&storage, StatsInterval::Hourly, None, 10).unwrap();
+    assert_eq!(hourly.len(), 2);
+    assert_eq!(hourly[0].start_time, later);
+    assert_eq!(hourly[0].volume, [Uint128(0), Uint128(7)]);
+    assert_eq!(hourly[0].trade_count, 1);
+    assert_eq!(hourly[1].start_time, 10_800);
+}
+
+#[test]
+fn test_ledger() {
+    let mut storage = MockStorage::new();
+    let account = CanonicalAddr(Binary(b"addr0000".to_vec()));
+    let curve = CurveType::ConstantProduct {};
+
+    // 100 of 1000 shares for 100 of each asset
+    record_deposit(
+        &mut storage,
+        &account,
+        [Uint128(100), Uint128(100)],
+        Uint128(100),
+    )
+    .unwrap();
+    // swaps left 30 of asset 1 in fees and moved the price of asset 0 to 0.265
+    accrue_fees(&mut storage, [Uint128(0), Uint128(30)], Uint128(1000)).unwrap();
+    let pools = [Uint128(2000), Uint128(530)];
+
+    let position = compute_position(&storage, &account, &curve, pools, Uint128(1000)).unwrap();
+    assert_eq!(position.entry.share, Uint128(100));
+    assert_eq!(position.entry.cost_basis, [Uint128(100), Uint128(100)]);
+    assert_eq!(position.current_value, [Uint128(200), Uint128(53)]);
+    assert_eq!(position.entry.fees_earned, [Uint128(0), Uint128(3)]);
+    // 100 * 0.265 + 100
+    assert_eq!(position.hodl_value, Uint128(126));
+    // 200 * 0.265 + 53
+    assert_eq!(position.position_value, Uint128(106));
+    // 126 - (106 - 3)
+    assert_eq!(position.impermanent_loss, Uint128(23));
+
+    // withdrawing half the shares halves the cost basis
+    record_withdrawal(
+        &mut storage,
+        &account,
+        [Uint128(100), Uint128(26)],
+        Uint128(50),
+    )
+    .unwrap();
+    let position = compute_position(&storage, &account, &curve, pools, Uint128(950)).unwrap();
+    assert_eq!(position.entry.share, Uint128(50));
+    assert_eq!(position.entry.cost_basis, [Uint128(50), Uint128(50)]);
+    assert_eq!(position.entry.withdrawn, [Uint128(100), Uint128(26)]);
+    assert_eq!(position.entry.deposited, [Uint128(100), Uint128(100)]);
+
+    // shares received outside of the pair only count as withdrawn assets
+    record_withdrawal(
+        &mut storage,
+        &account,
+        [Uint128(160), Uint128(42)],
+        Uint128(80),
+    )
+    .unwrap();
+    let position = compute_position(&storage, &account, &curve, pools, Uint128(870)).unwrap();
+    assert_eq!(position.entry.share, Uint128(0));
+    assert_eq!(position.entry.cost_basis, [Uint128(0), Uint128(0)]);
+    assert_eq!(position.entry.withdrawn, [Uint128(260), Uint128(68)]);
+    assert_eq!(position.entry.fees_earned, [Uint128(0), Uint128(3)]);
+
+    // the ledger is only readable with the viewing key
+    assert_viewing_key(&storage, &account, "key").unwrap_err();
+    set_viewing_key(&mut storage, &account, "key").unwrap();
+    assert_viewing_key(&storage, &account, "key").unwrap();
+    assert_viewing_key(&storage, &account, "other key").unwrap_err();
+}
+
+#[test]
+fn test_limit_orders() {
+    let mut storage = MockStorage::new();
+    let curve = CurveType::ConstantProduct {};
+    let owner = CanonicalAddr(Binary(b"addr0000".to_vec()));
+    let pair_settings = PairSettings {
+        swap_fee: Fee {
+            commission_rate_nom: Uint128(3),
+            commission_rate_denom: Uint128(1000),
+        },
+        swap_data_endpoint: None,
+        swap_data_endpoints: vec![],
+        protocol_fee: None,
+        status: Default::default(),
+        dynamic_fee: None,
+        max_price_move: None,
+    };
+    store_reserves(&mut storage, &[Uint128(1_000_000), Uint128(1_000_000)]).unwrap();
+
+    place_limit_order(&mut storage, &owner, 0, Uint128::zero(), Decimal::one()).unwrap_err();
+    place_limit_order(&mut storage, &owner, 0, Uint128(10), Decimal::zero()).unwrap_err();
+
+    // sell asset 0 at 1.1, 1.05 and 2
+    for price in ["1.1", "1.05", "2"].iter() {
+        let price = Decimal::from_str(price).unwrap();
+        place_limit_order(&mut storage, &owner, 0, Uint128(10_000), price).unwrap();
+    }
+    assert_eq!(
+        read_order_escrow(&storage).unwrap(),
+        [Uint128(30_000), Uint128(0)]
+    );
+
+    // the pools pay 1 per asset 0
+    let (fills, closed) =
+        fill_limit_orders(&mut storage, &curve, &pair_settings, 0, Uint128(1_000), 100).unwrap();
+    assert_eq!(fills, vec![]);
+    assert_eq!(closed, vec![]);
+
+    // a swap moved the price of asset 0 to 1.5625, the cheapest orders fill first
+    store_reserves(&mut storage, &[Uint128(800_000), Uint128(1_250_000)]).unwrap();
+    let (fills, _) =
+        fill_limit_orders(&mut storage, &curve, &pair_settings, 0, Uint128(1_000), 100).unwrap();
+    assert_eq!(
+        fills.iter().map(|fill| fill.order_id).collect::<Vec<_>>(),
+        vec![1, 0]
+    );
+    let proceeds = fills[0].return_amount + fills[1].return_amount;
+    assert!(fills[0].return_amount >= Uint128(10_500));
+    assert!(fills[1].return_amount >= Uint128(11_000));
+    assert!(fills.iter().all(|fill| fill.owner == owner));
+    assert_eq!(
+        read_reserves(&storage).unwrap(),
+        [Uint128(820_000), (Uint128(1_250_000) - proceeds).unwrap()]
+    );
+    // the proceeds are paid out with the fill, filled orders are removed
+    assert_eq!(
+        read_order_escrow(&storage).unwrap(),
+        [Uint128(10_000), Uint128(0)]
+    );
+    assert_eq!(read_owner_orders(&storage, &owner).unwrap(), vec![2]);
+    cancel_limit_order(&mut storage, &owner, 1).unwrap_err();
+
+    // only the owner can cancel, and gets the unsold amount back
+    let other = CanonicalAddr(Binary(b"addr0001".to_vec()));
+    cancel_limit_order(&mut storage, &other, 2).unwrap_err();
+    let order = cancel_limit_order(&mut storage, &owner, 2).unwrap();
+    assert_eq!(order.remaining, Uint128(10_000));
+    assert_eq!(
+        read_order_escrow(&storage).unwrap(),
+        [Uint128(0), Uint128(0)]
+    );
+    cancel_limit_order(&mut storage, &owner, 2).unwrap_err();
+
+    // an account has a limited number of open orders, a new one evicts its oldest
+    let price = Decimal::from_str("3").unwrap();
+    let mut order_ids = vec![];
+    for _ in 0..MAX_ORDERS_PER_OWNER {
+        let (order_id, evicted) =
+            place_limit_order(&mut storage, &owner, 0, Uint128(10), price).unwrap();
+        assert_eq!(evicted, vec![]);
+        order_ids.push(order_id);
+    }
+    let (order_id, evicted) =
+        place_limit_order(&mut storage, &owner, 1, Uint128(20), price).unwrap();
+    assert_eq!(evicted.len(), 1);
+    assert_eq!(evicted[0].owner, owner);
+    assert_eq!(evicted[0].offer_index, 0);
+    assert_eq!(evicted[0].remaining, Uint128(10));
+    order_ids.remove(0);
+    order_ids.push(order_id);
+    assert_eq!(read_owner_orders(&storage, &owner).unwrap(), order_ids);
+    assert_eq!(
+        read_order_escrow(&storage).unwrap(),
+        [Uint128(90), Uint128(20)]
+    );
+    cancel_limit_order(&mut storage, &owner, order_id).unwrap();
+    place_limit_order(&mut storage, &owner, 0, Uint128(10), price).unwrap();
+
+    // a full book only takes orders cheaper than its dearest one, which is evicted
+    for i in 1..MAX_OPEN_ORDERS / MAX_ORDERS_PER_OWNER {
+        let account = CanonicalAddr(Binary(format!("addr{:04}", i).into_bytes()));
+        for _ in 0..MAX_ORDERS_PER_OWNER {
+            place_limit_order(&mut storage, &account, 0, Uint128(10), price).unwrap();
+        }
+    }
+    let dearest = CanonicalAddr(Binary(b"addr0009".to_vec()));
+    let newcomer = CanonicalAddr(Binary(b"addr0100".to_vec()));
+    place_limit_order(&mut storage, &newcomer, 0, Uint128(10), price).unwrap_err();
+    let (order_id, evicted) = place_limit_order(
+        &mut storage,
+        &newcomer,
+        0,
+        Uint128(20),
+        Decimal::from_str("2.9").unwrap(),
+    )
+    .unwrap();
+    assert_eq!(evicted.len(), 1);
+    assert_eq!(evicted[0].owner, dearest);
+    assert_eq!(evicted[0].remaining, Uint128(10));
+    assert_eq!(read_owner_orders(&storage, &dearest).unwrap().len(), 9);
+    assert_eq!(
+        read_owner_orders(&storage, &newcomer).unwrap(),
+        vec![order_id]
+    );
+    assert_eq!(
+        read_order_escrow(&storage).unwrap(),
+        [Uint128(1_010), Uint128(0)]
+    );
+
+    // a large order only fills while the average price stays above its limit
+    let price = Decimal::from_str("1.05").unwrap();
+    let amount = compute_fill_amount(
+        &curve,
+        0,
+        Uint128(800_000),
+        Uint128(1_250_000),
+        Uint128(1_000_000),
+        price,
+        &pair_settings.swap_fee,
+    );
+    assert!(!amount.is_zero() && amount < Uint128(1_000_000));
+    let fee = &pair_settings.swap_fee;
+    let (return_amount, _, _) = compute_swap(
+        &curve,
+        0,
+        Uint128(800_000),
+        Uint128(1_250_000),
+        amount,
+        fee.commission_rate_nom,
+        fee.commission_rate_denom,
+    )
+    .unwrap();
+    assert!(return_amount >= amount * price);
+    let (return_amount, _, _) = compute_swap(
+        &curve,
+        0,
+        Uint128(800_000),
+        Uint128(1_250_000),
+        amount + Uint128(1),
+        fee.commission_rate_nom,
+        fee.commission_rate_denom,
+    )
+    .unwrap();
+    // 1.05 per unit, without rounding
+    assert!(return_amount.u128() * 100 < (amount.u128() + 1) * 105);
+}
+
+#[test]
+fn test_limit_order_dust() {
+    let mut deps = mock_dependencies(
+        20,
+        &[Coin {
+            denom: "uusd".to_string(),
+            amount: Uint128(1_000_001u128),
+        }],
+    );
+    with_pair_token_balances(&mut deps.querier, 1_000_000, 1_000_000);
+    init(
+        &mut deps,
+        mock_env("addr0000", &[]),
+        pair_init_msg(Some(CurveType::StableSwap { amp: 100 })),
+    )
+    .unwrap();
+    handle(
+        &mut deps,
+        mock_env("liquidity0000", &[]),
+        HandleMsg::PostInitialize {},
+    )
+    .unwrap();
+    store_reserves(
+        &mut deps.storage,
+        &[Uint128(1_000_000u128), Uint128(1_000_000u128)],
+    )
+    .unwrap();
+
+    // 1uusd returns nothing from the pools, the order would block the book
+    let msg = HandleMsg::PlaceLimitOrder {
+        offer_asset: Asset {
+            info: native_info(),
+            amount: Uint128(1u128),
+        },
+        price: Decimal::one(),
+    };
+    let env = mock_env(
+        "addr0000",
+        &[Coin {
+            denom: "uusd".to_string(),
+            amount: Uint128(1u128),
+        }],
+    );
+    let res = handle(&mut deps, env, msg).unwrap();
+
+    // it's closed right away and paid back
+    assert_eq!(res.messages, vec![native_send_msg("addr0000", 1)]);
+    assert_eq!(res.log[2], log("filled_orders", "0"));
+    let owner = deps
+        .api
+        .canonical_address(&HumanAddr::from("addr0000"))
+        .unwrap();
+    assert!(read_owner_orders(&deps.storage, &owner).unwrap().is_empty());
+    assert_eq!(
+        read_order_escrow(&deps.storage).unwrap(),
+        [Uint128(0), Uint128(0)]
+    );
+    assert_eq!(
+        read_reserves(&deps.storage).unwrap(),
+        [Uint128(1_000_000u128), Uint128(1_000_000u128)]
+    );
+}
+
+#[test]
+fn test_query_permit() {
+    let mut deps = mock_dependencies(45, &[]);
+    let contract_addr = HumanAddr::from(MOCK_CONTRACT_ADDR);
+    let secret_key = libsecp256k1::SecretKey::parse_slice(&[1u8; 32]).unwrap();
+    let pub_key = libsecp256k1::PublicKey::from_secret_key(&secret_key);
+    let account = pubkey_to_address(&pub_key.serialize_compressed()).unwrap();
+
+    let sign = |params: PermitParams| -> Permit {
+        let mut message = [0u8; 32];
+        message.copy_from_slice(&sha_256(&permit_sign_bytes(&params).unwrap()));
+        let (signature, _) =
+            libsecp256k1::sign(&libsecp256k1::Message::parse(&message), &secret_key);
+        Permit {
+            params,
+            signature: PermitSignature {
+                pub_key: PubKey {
+                    r#type: "tendermint/PubKeySecp256k1".to_string(),
+                    value: Binary(pub_key.serialize_compressed().to_vec()),
                 },
-                amount: asset_0_amount,
+                signature: Binary(signature.serialize().to_vec()),
             },
-            Asset {
-                info: AssetInfo::Token {
-                    contract_addr: HumanAddr::from("asset0000"),
+        }
+    };
+    let params = PermitParams {
+        allowed_tokens: vec![contract_addr.clone()],
+        permit_name: "pair".to_string(),
+        chain_id: "secret-4".to_string(),
+        permissions: vec![Permission::Owner],
+    };
+    let permit = sign(params.clone());
+    assert_eq!(
+        validate_permit(&deps, &permit, &contract_addr, Permission::Owner).unwrap(),
+        account
+    );
+
+    // the permit must name the pair and grant the owner permission
+    let other_pair = HumanAddr::from("pair0001");
+    validate_permit(&deps, &permit, &other_pair, Permission::Owner).unwrap_err();
+    let balance_permit = sign(PermitParams {
+        permissions: vec![Permission::Balance],
+        ..params.clone()
+    });
+    validate_permit(&deps, &balance_permit, &contract_addr, Permission::Owner).unwrap_err();
+
+    // the signature must cover the params
+    let mut tampered = permit.clone();
+    tampered.params.allowed_tokens.push(other_pair.clone());
+    match validate_permit(&deps, &tampered, &other_pair, Permission::Owner) {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "Failed to verify the permit signature")
+        }
+        _ => panic!("Must return generic error"),
+    }
+
+    // a revoked permit stops working, the other names of the account don't
+    let env = mock_env(account.clone(), &[]);
+    let msg = HandleMsg::RevokePermit {
+        permit_name: "pair".to_string(),
+        padding: None,
+    };
+    handle(&mut deps, env, msg).unwrap();
+    match validate_permit(&deps, &permit, &contract_addr, Permission::Owner) {
+        Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "Permit pair was revoked"),
+        _ => panic!("Must return generic error"),
+    }
+    let renamed = sign(PermitParams {
+        permit_name: "pair2".to_string(),
+        ..params
+    });
+    assert_eq!(
+        validate_permit(&deps, &renamed, &contract_addr, Permission::Owner).unwrap(),
+        account
+    );
+}
+
+#[test]
+fn test_flash_swap_invariant() {
+    let pools = [Uint128(1_000_000), Uint128(1_000_000)];
+    let borrowed = [Uint128(100_000), Uint128::zero()];
+    let check = |curve: &CurveType, balances: [u128; 2]| {
+        assert_flash_swap_invariant(
+            curve,
+            pools,
+            borrowed,
+            [Uint128(balances[0]), Uint128(balances[1])],
+            Uint128(3),
+            Uint128(1000),
+        )
+    };
+    let constant_product = CurveType::ConstantProduct {};
+
+    // paying back without the fee
+    check(&constant_product, [1_000_000, 1_000_000]).unwrap_err();
+    // 100_301 * 0.3% rounds up to 301
+    check(&constant_product, [1_000_300, 1_000_000]).unwrap_err();
+    assert_eq!(
+        check(&constant_product, [1_000_301, 1_000_000]).unwrap(),
+        [Uint128(301), Uint128::zero()]
+    );
+
+    // paying back with the other asset is a swap
+    check(&constant_product, [900_000, 1_111_445]).unwrap_err();
+    assert_eq!(
+        check(&constant_product, [900_000, 1_111_447]).unwrap(),
+        [Uint128::zero(), Uint128(335)]
+    );
+
+    for curve in [
+        CurveType::StableSwap { amp: 100 },
+        CurveType::Weighted { weights: [80, 20] },
+    ]
+    .iter()
+    {
+        check(curve, [1_000_000, 1_000_000]).unwrap_err();
+        check(curve, [1_000_301, 1_000_000]).unwrap();
+    }
+}
+
+#[test]
+fn test_zap_in_swap_amount() {
+    let offer_pool = Uint128(1_000_000);
+    let ask_pool = Uint128(2_000_000);
+    let amount = Uint128(100_000);
+    // constant product with a 0.3% commission, a third of it going to the protocol
+    let swap = |offer_amount: Uint128| {
+        let return_amount = ask_pool.multiply_ratio(offer_amount, offer_pool + offer_amount);
+        let commission_amount = return_amount.multiply_ratio(3u128, 1000u128);
+        let protocol_fee_amount = commission_amount.multiply_ratio(1u128, 3u128);
+        Ok((
+            (return_amount - commission_amount).unwrap(),
+            (return_amount - commission_amount).unwrap() + protocol_fee_amount,
+        ))
+    };
+    // whether the rest of the deposit covers the return amount at the pool ratio after the swap
+    let balanced = |swap_amount: u128| {
+        let (return_amount, ask_out): (Uint128, Uint128) = swap(Uint128(swap_amount)).unwrap();
+        (amount.u128() - swap_amount) * (ask_pool.u128() - ask_out.u128())
+            >= return_amount.u128() * (offer_pool.u128() + swap_amount)
+    };
+
+    let swap_amount = compute_zap_in_swap_amount(offer_pool, ask_pool, amount, swap).unwrap();
+    // a bit less than half of the deposit is swapped
+    assert_eq!(swap_amount, Uint128(48_884));
+    assert!(balanced(swap_amount.u128()));
+    assert!(!balanced(swap_amount.u128() + 1));
+
+    compute_zap_in_swap_amount(Uint128::zero(), ask_pool, amount, swap).unwrap_err();
+}
+
+#[test]
+fn test_minimum_liquidity() {
+    let curve = CurveType::ConstantProduct {};
+
+    // initial deposits must mint more than the locked shares
+    compute_initial_share(&curve, &[Uint128(1000), Uint128(1000)]).unwrap_err();
+    compute_initial_share(&curve, &[Uint128(10), Uint128(100_000)]).unwrap_err();
+    compute_initial_share(
+        &CurveType::StableSwap { amp: 100 },
+        &[Uint128(500), Uint128(500)],
+    )
+    .unwrap_err();
+    assert_eq!(
+        compute_initial_share(&curve, &[Uint128(1001), Uint128(1001)]).unwrap(),
+        Uint128(1001)
+    );
+}
+
+#[test]
+fn test_donation_attack() {
+    let curve = CurveType::ConstantProduct {};
+
+    // the attacker makes the smallest first deposit, all but one share are locked
+    let total_share = compute_initial_share(&curve, &[Uint128(1001), Uint128(1001)]).unwrap();
+    let attacker_share = (total_share - Uint128(MINIMUM_LIQUIDITY)).unwrap();
+    assert_eq!(attacker_share, Uint128(1));
+
+    // then donates to the pair to inflate the price of a share
+    let donation = 1_000_000_000u128;
+    let pools = [Uint128(1001 + donation), Uint128(1001 + donation)];
+
+    // a victim deposits as much as the donation
+    let deposit = Uint128(donation);
+    let victim_share = compute_deposit_share(&[deposit, deposit], &pools, total_share).unwrap();
+    assert_eq!(victim_share, Uint128(1000));
+
+    let total_share = total_share + victim_share;
+    let pool = pools[0] + deposit;
+
+    // the victim loses less than 0.1% to rounding
+    let victim_value = pool.multiply_ratio(victim_share, total_share);
+    assert_eq!(victim_value, Uint128(999_500_750));
+    assert!(victim_value.u128() > donation * 999 / 1000);
+
+    // while the attacker gave away almost all of the donation to the locked shares
+    let attacker_value = pool.multiply_ratio(attacker_share, total_share);
+    assert_eq!(attacker_value, Uint128(999_500));
+    assert!(attacker_value.u128() < donation / 1000);
+}
+
+#[test]
+fn test_donation_provide_liquidity() {
+    let donation = 1_000_000_000u128;
+    let excess = 500u128;
+
+    // donations stay out of the reserves until someone syncs them in
+    for &(sync, victim_share) in [(false, donation), (true, 1000u128)].iter() {
+        let mut deps = mock_dependencies(
+            20,
+            &[Coin {
+                denom: "uusd".to_string(),
+                amount: Uint128(1001u128),
+            }],
+        );
+        deps.querier.with_token_balances(&[(
+            &HumanAddr::from("liquidity0000"),
+            &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128::zero())],
+        )]);
+        init_pair(&mut deps);
+
+        // the attacker makes the smallest first deposit
+        let msg = HandleMsg::ProvideLiquidity {
+            assets: [
+                Asset {
+                    info: native_info(),
+                    amount: Uint128(1001u128),
                 },
-                amount: asset_1_amount,
+                Asset {
+                    info: token_info(),
+                    amount: Uint128(1001u128),
+                },
+            ],
+            slippage_tolerance: None,
+            deadline: None,
+        };
+        let env = mock_env(
+            "addr0000",
+            &[Coin {
+                denom: "uusd".to_string(),
+                amount: Uint128(1001u128),
+            }],
+        );
+        let res = handle(&mut deps, env, msg).unwrap();
+        assert_eq!(res.messages[2], lp_mint_msg("addr0000", 1));
+
+        // and donates to the pair balance
+        deps.querier.with_balance(&[(
+            &HumanAddr::from(MOCK_CONTRACT_ADDR),
+            &[Coin {
+                denom: "uusd".to_string(),
+                amount: Uint128(1001 + donation),
+            }],
+        )]);
+        deps.querier.with_token_balances(&[
+            (
+                &HumanAddr::from("liquidity0000"),
+                &[
+                    (
+                        &HumanAddr::from(MOCK_CONTRACT_ADDR),
+                        &Uint128(MINIMUM_LIQUIDITY),
+                    ),
+                    (&HumanAddr::from("addr0000"), &Uint128(1u128)),
+                ],
+            ),
+            (
+                &HumanAddr::from("asset0000"),
+                &[(
+                    &HumanAddr::from(MOCK_CONTRACT_ADDR),
+                    &Uint128(1001 + donation),
+                )],
+            ),
+        ]);
+        if sync {
+            handle(&mut deps, mock_env("addr0000", &[]), HandleMsg::Sync {}).unwrap();
+        }
+
+        // a victim deposits as much as the donation, with some native tokens too many
+        let msg = HandleMsg::ProvideLiquidity {
+            assets: [
+                Asset {
+                    info: native_info(),
+                    amount: Uint128(donation + excess),
+                },
+                Asset {
+                    info: token_info(),
+                    amount: Uint128(donation),
+                },
+            ],
+            slippage_tolerance: None,
+            deadline: None,
+        };
+        let env = mock_env(
+            "addr0001",
+            &[Coin {
+                denom: "uusd".to_string(),
+                amount: Uint128(donation + excess),
+            }],
+        );
+        let res = handle(&mut deps, env, msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                native_send_msg("addr0001", excess),
+                snip20::transfer_from_msg(
+                    HumanAddr::from("addr0001"),
+                    HumanAddr::from(MOCK_CONTRACT_ADDR),
+                    Uint128(donation),
+                    None,
+                    256,
+                    "asset0000_hash".to_string(),
+                    HumanAddr::from("asset0000"),
+                )
+                .unwrap(),
+                lp_mint_msg("addr0001", victim_share),
+            ]
+        );
+        let reserve = if sync {
+            1001 + 2 * donation
+        } else {
+            1001 + donation
+        };
+        assert_eq!(
+            read_reserves(&deps.storage).unwrap(),
+            [Uint128(reserve), Uint128(reserve)]
+        );
+    }
+}
+
+#[test]
+fn test_used_deposits() {
+    let pools = [Uint128(3_000), Uint128(7_000)];
+    let total_share = Uint128(1_000);
+
+    // asset 1 is over-supplied, ceil(10 * 7_000 / 3_000) of it is used
+    let deposits = [Uint128(10), Uint128(100)];
+    let used_deposits = compute_used_deposits(&deposits, &pools);
+    assert_eq!(used_deposits, [Uint128(10), Uint128(24)]);
+    // the share is the same as for the whole deposit
+    assert_eq!(
+        compute_deposit_share(&used_deposits, &pools, total_share).unwrap(),
+        compute_deposit_share(&deposits, &pools, total_share).unwrap()
+    );
+
+    // asset 0 is over-supplied
+    let deposits = [Uint128(1_000), Uint128(700)];
+    assert_eq!(
+        compute_used_deposits(&deposits, &pools),
+        [Uint128(300), Uint128(700)]
+    );
+
+    // balanced deposits are used in full
+    let deposits = [Uint128(300), Uint128(700)];
+    assert_eq!(compute_used_deposits(&deposits, &pools), deposits);
+
+    // the first deposit sets the ratio
+    assert_eq!(
+        compute_used_deposits(&deposits, &[Uint128::zero(), Uint128::zero()]),
+        deposits
+    );
+}
+
+#[test]
+fn test_reverse_simulation_matches_forward() {
+    // deterministic pseudo random numbers, a 64-bit LCG
+    let mut seed = 0x2545_f491_4f6c_dd1du64;
+    let mut next = |bound: u128| -> u128 {
+        seed = seed
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        (seed >> 16) as u128 % bound
+    };
+
+    for _ in 0..500 {
+        // pools from 10^3 to 10^27 with any ratio between them
+        let offer_pool = Uint128((next(1_000) + 1) * 10u128.pow(next(25) as u32 + 3));
+        let ask_pool = Uint128((next(1_000) + 1) * 10u128.pow(next(25) as u32 + 3));
+        let nom = Uint128(next(100));
+        let denom = Uint128(1_000);
+        // up to the whole ask pool minus its commission
+        let ask_amount = ask_pool.multiply_ratio(next(900) + 1, 1_000u128);
+
+        let (offer_amount, _, _) = compute_offer_amount(
+            &CurveType::ConstantProduct {},
+            0,
+            offer_pool,
+            ask_pool,
+            ask_amount,
+            nom.u128(),
+            denom.u128(),
+        )
+        .unwrap();
+
+        // the offer amount returns at least the ask amount
+        let (return_amount, _, _) = compute_swap(
+            &CurveType::ConstantProduct {},
+            0,
+            offer_pool,
+            ask_pool,
+            offer_amount,
+            nom,
+            denom,
+        )
+        .unwrap();
+        assert!(return_amount >= ask_amount);
+
+        // and it is the smallest one that does
+        if !offer_amount.is_zero() {
+            let (return_amount, _, _) = compute_swap(
+                &CurveType::ConstantProduct {},
+                0,
+                offer_pool,
+                ask_pool,
+                (offer_amount - Uint128(1)).unwrap(),
+                nom,
+                denom,
+            )
+            .unwrap();
+            assert!(return_amount < ask_amount);
+        }
+    }
+}
+
+#[test]
+fn test_reverse_simulation_other_curves() {
+    let mut seed = 0x9e37_79b9_7f4a_7c15u64;
+    let mut next = |bound: u128| -> u128 {
+        seed = seed
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        (seed >> 16) as u128 % bound
+    };
+
+    for i in 0..300 {
+        let curve = if i % 2 == 0 {
+            CurveType::StableSwap {
+                amp: next(1_000) as u64 + 1,
             }
-        ]
+        } else {
+            CurveType::Weighted {
+                weights: [next(98) as u64 + 1, next(98) as u64 + 1],
+            }
+        };
+        // pools from 10^6 to 10^24, within a factor of 10 of each other
+        let magnitude = 10u128.pow(next(19) as u32 + 6);
+        let offer_pool = Uint128((next(90) + 10) * magnitude);
+        let ask_pool = Uint128((next(90) + 10) * magnitude);
+        let nom = Uint128(next(100));
+        let denom = Uint128(1_000);
+        // up to 30% of the ask pool, weighted pools also take at most half of the offer pool,
+        // asking for `1 - (1 / 1.4)^(offer weight / ask weight)` needs 40% of it before fees
+        let max_ask_ratio = match &curve {
+            CurveType::Weighted { weights } => {
+                let exponent = weights[0] as f64 / weights[1] as f64;
+                (1.0 - (1.0 / 1.4f64).powf(exponent)).min(0.3)
+            }
+            _ => 0.3,
+        };
+        let max_ask_amount = ask_pool.multiply_ratio((max_ask_ratio * 1e6) as u128, 1_000_000u128);
+        let ask_amount = max_ask_amount.multiply_ratio(next(1_000) + 1, 1_000u128);
+
+        let (offer_amount, _, _) = compute_offer_amount(
+            &curve,
+            0,
+            offer_pool,
+            ask_pool,
+            ask_amount,
+            nom.u128(),
+            denom.u128(),
+        )
+        .unwrap();
+
+        // the offer amount returns at least the ask amount
+        let (return_amount, _, _) =
+            compute_swap(&curve, 0, offer_pool, ask_pool, offer_amount, nom, denom).unwrap();
+        assert!(return_amount >= ask_amount);
+    }
+
+    // on large pools the weighted forward math moves in steps wider than a proportional raise
+    let curve = CurveType::Weighted { weights: [25, 79] };
+    let (offer_pool, ask_pool) = (Uint128(500 * 10u128.pow(18)), Uint128(350 * 10u128.pow(18)));
+    let ask_amount = Uint128(27_503_661_500_000_000_000);
+    let (offer_amount, _, _) =
+        compute_offer_amount(&curve, 0, offer_pool, ask_pool, ask_amount, 84, 1_000).unwrap();
+    let (return_amount, _, _) = compute_swap(
+        &curve,
+        0,
+        offer_pool,
+        ask_pool,
+        offer_amount,
+        Uint128(84),
+        Uint128(1_000),
+    )
+    .unwrap();
+    assert!(return_amount >= ask_amount);
+}
+
+#[test]
+fn test_swap_data() {
+    let mut storage = MockStorage::new();
+    let native = |denom: &str, amount: u128| Asset {
+        info: AssetInfo::NativeToken {
+            denom: denom.to_string(),
+        },
+        amount: Uint128(amount),
+    };
+    let endpoints = vec![
+        SwapDataEndpoint {
+            address: HumanAddr::from("stats0000"),
+            code_hash: "stats_hash".to_string(),
+            best_effort_key_hash: None,
+        },
+        SwapDataEndpoint {
+            address: HumanAddr::from("indexer0000"),
+            code_hash: "indexer_hash".to_string(),
+            best_effort_key_hash: Some(Binary::from(&sha_256(b"key"))),
+        },
+        SwapDataEndpoint {
+            address: HumanAddr::from("indexer0001"),
+            code_hash: "indexer_hash".to_string(),
+            best_effort_key_hash: Some(Binary::from(&sha_256(b"key1"))),
+        },
+    ];
+
+    let mut env = mock_env("addr0000", &[]);
+    for i in 0..105u128 {
+        env.block.height = 1000 + i as u64;
+        let messages = deliver_swap_data(
+            &mut storage,
+            &env,
+            &endpoints,
+            SwapData {
+                pair: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                account: HumanAddr::from("addr0000"),
+                asset_in: native("uscrt", 1000 + i),
+                asset_out: native("uusd", 997),
+                commission: native("uusd", 3),
+                spread_amount: Uint128(10),
+                reserves: [native("uscrt", 1_000_000), native("uusd", 1_000_000)],
+            },
+        )
+        .unwrap();
+        // only the endpoint that isn't best-effort is called
+        assert_eq!(messages.len(), 1);
+    }
+
+    // the key of the endpoint is required
+    query_swap_data(
+        &storage,
+        &endpoints,
+        &HumanAddr::from("indexer0000"),
+        "other key",
+        None,
+        None,
+    )
+    .unwrap_err();
+    query_swap_data(
+        &storage,
+        &endpoints,
+        &HumanAddr::from("stats0000"),
+        "key",
+        None,
+        None,
+    )
+    .unwrap_err();
+
+    // nothing is overwritten, an endpoint reads from its cursor
+    let query = |storage: &MockStorage, endpoint: &str, key: &str, start_after: Option<u64>| {
+        query_swap_data(
+            storage,
+            &endpoints,
+            &HumanAddr::from(endpoint),
+            key,
+            start_after,
+            None,
+        )
+        .unwrap()
+    };
+    let res = query(&storage, "indexer0000", "key", None);
+    assert_eq!(res.count, 105);
+    assert_eq!(res.cursor, 0);
+    assert_eq!(res.entries.len(), 30);
+    assert_eq!(res.entries[0].seq, 0);
+    assert_eq!(res.entries[0].height, 1000);
+    assert_eq!(res.entries[0].data.asset_in.amount, Uint128(1000));
+
+    let res = query(&storage, "indexer0000", "key", Some(100));
+    assert_eq!(
+        res.entries.iter().map(|e| e.seq).collect::<Vec<_>>(),
+        vec![101, 102, 103, 104]
     );
-    assert_eq!(res.total_share, total_share_amount);
+
+    // acknowledging moves the cursor, only up to the swaps kept so far
+    let ack = |storage: &mut MockStorage, endpoint: &str, key: &str, seq: u64| {
+        ack_swap_data(storage, &endpoints, &HumanAddr::from(endpoint), key, seq)
+    };
+    ack(&mut storage, "indexer0000", "other key", 49).unwrap_err();
+    ack(&mut storage, "stats0000", "key", 49).unwrap_err();
+    match ack(&mut storage, "indexer0000", "key", 105).unwrap_err() {
+        StdError::GenericErr { msg, .. } => assert_eq!(msg, "Swap 105 wasn't kept yet"),
+        _ => panic!("Must return generic error"),
+    }
+    ack(&mut storage, "indexer0000", "key", 49).unwrap();
+    let res = query(&storage, "indexer0000", "key", None);
+    assert_eq!(res.cursor, 50);
+    assert_eq!(res.entries[0].seq, 50);
+
+    // the swaps are kept until every best-effort endpoint acknowledged them
+    let res = query(&storage, "indexer0001", "key1", None);
+    assert_eq!(res.cursor, 0);
+    assert_eq!(res.entries[0].seq, 0);
+    ack(&mut storage, "indexer0001", "key1", 29).unwrap();
+    let res = query(&storage, "indexer0000", "key", Some(0));
+    assert_eq!(res.entries[0].seq, 30);
+
+    // a cursor doesn't move back
+    ack(&mut storage, "indexer0000", "key", 10).unwrap();
+    assert_eq!(query(&storage, "indexer0000", "key", None).cursor, 50);
 }
 
 fn mock_env_with_block_time<U: Into<HumanAddr>>(sender: U, sent: &[Coin], time: u64) -> Env {
@@ -1113,3 +3833,117 @@ fn mock_env_with_block_time<U: Into<HumanAddr>>(sender: U, sent: &[Coin], time:
         ..env
     };
 }
+
+fn native_info() -> AssetInfo {
+    AssetInfo::NativeToken {
+        denom: "uusd".to_string(),
+    }
+}
+
+fn token_info() -> AssetInfo {
+    AssetInfo::Token {
+        contract_addr: HumanAddr::from("asset0000"),
+        token_code_hash: "asset0000_hash".to_string(),
+        viewing_key: "SecretSwap".to_string(),
+    }
+}
+
+fn pair_init_msg(curve: Option<CurveType>) -> PairInitMsg {
+    PairInitMsg {
+        asset_infos: [native_info(), token_info()],
+        token_code_id: 10u64,
+        token_code_hash: "liquidity0000_hash".to_string(),
+        init_hook: Some(InitHook {
+            contract_addr: HumanAddr::from("factory0000"),
+            code_hash: "factory0000_hash".to_string(),
+            msg: to_binary(&Uint128(1000000u128)).unwrap(),
+        }),
+        prng_seed: Binary::from("seed".as_bytes()),
+        curve,
+        migrate_from: None,
+    }
+}
+
+/// Initializes a uusd-asset0000 pair whose liquidity token is liquidity0000
+fn init_pair(deps: &mut Extern<MockStorage, MockApi, WasmMockQuerier>) {
+    init(deps, mock_env("addr0000", &[]), pair_init_msg(None)).unwrap();
+    handle(
+        deps,
+        mock_env("liquidity0000", &[]),
+        HandleMsg::PostInitialize {},
+    )
+    .unwrap();
+}
+
+fn lp_mint_msg(recipient: &str, amount: u128) -> CosmosMsg {
+    snip20::mint_msg(
+        HumanAddr::from(recipient),
+        Uint128(amount),
+        None,
+        256,
+        "liquidity0000_hash".to_string(),
+        HumanAddr::from("liquidity0000"),
+    )
+    .unwrap()
+}
+
+fn lp_burn_msg(amount: u128) -> CosmosMsg {
+    snip20::burn_msg(
+        Uint128(amount),
+        None,
+        256,
+        "liquidity0000_hash".to_string(),
+        HumanAddr::from("liquidity0000"),
+    )
+    .unwrap()
+}
+
+fn token_send_msg(recipient: &str, amount: u128) -> CosmosMsg {
+    Asset {
+        info: token_info(),
+        amount: Uint128(amount),
+    }
+    .into_msg(
+        &mock_dependencies(20, &[]),
+        HumanAddr::from(MOCK_CONTRACT_ADDR),
+        HumanAddr::from(recipient),
+    )
+    .unwrap()
+}
+
+fn token_transfer_msg(recipient: &str, amount: u128) -> CosmosMsg {
+    snip20::transfer_msg(
+        HumanAddr::from(recipient),
+        Uint128(amount),
+        None,
+        256,
+        "asset0000_hash".to_string(),
+        HumanAddr::from("asset0000"),
+    )
+    .unwrap()
+}
+
+fn native_send_msg(recipient: &str, amount: u128) -> CosmosMsg {
+    CosmosMsg::Bank(BankMsg::Send {
+        from_address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+        to_address: HumanAddr::from(recipient),
+        amount: vec![Coin {
+            denom: "uusd".to_string(),
+            amount: Uint128(amount),
+        }],
+    })
+}
+
+/// Sets the LP supply, held by addr0000, and the asset0000 balance of the pair
+fn with_pair_token_balances(querier: &mut WasmMockQuerier, total_share: u128, balance: u128) {
+    querier.with_token_balances(&[
+        (
+            &HumanAddr::from("liquidity0000"),
+            &[(&HumanAddr::from("addr0000"), &Uint128(total_share))],
+        ),
+        (
+            &HumanAddr::from("asset0000"),
+            &[(&HumanAddr::from(MOCK_CONTRACT_ADDR), &Uint128(balance))],
+        ),
+    ]);
+}
@@ -0,0 +1,249 @@
+use cosmwasm_std::{StdError, StdResult, Uint128};
+use primitive_types::U256;
+
+use crate::u256_math::*;
+
+/// Fixed point scale used by the weighted math, 1.0 == 10^18
+const ONE: u128 = 1_000_000_000_000_000_000;
+/// Binary digits of the fractional exponent that are evaluated
+const PRECISION_BITS: usize = 48;
+/// Upper bound of the relative error of `pow`, in units of `ONE`.
+/// Results are rounded by this amount in favor of the pool.
+const MAX_POW_RELATIVE_ERROR: u128 = 10_000;
+/// A swap can't offer more than 1/2 of the offer pool
+const MAX_IN_RATIO_DENOM: u128 = 2;
+/// A swap can't ask for more than 1/3 of the ask pool
+const MAX_OUT_RATIO_DENOM: u128 = 3;
+
+fn one() -> U256 {
+    U256::from(ONE)
+}
+
+/// Fixed point `base ^ (exp_nom / exp_denom)`.
+///
+/// The integer part of the exponent is applied by repeated multiplication, the fractional
+/// part by its binary expansion: the n-th bit multiplies the result by `base ^ (1 / 2^n)`,
+/// which is `base` square rooted n times.
+pub fn pow(base: U256, exp_nom: U256, exp_denom: U256) -> Option<U256> {
+    let one = Some(one());
+
+    let exp_int = div(Some(exp_nom), Some(exp_denom))?;
+    let mut result = one;
+    let mut i = U256::zero();
+    while i < exp_int {
+        result = div(mul(result, Some(base)), one);
+        i = i.checked_add(U256::one())?;
+    }
+
+    let mut frac = exp_nom.checked_rem(exp_denom)?;
+    let mut root = base;
+    for _ in 0..PRECISION_BITS {
+        if frac.is_zero() {
+            break;
+        }
+        // root = sqrt(root * ONE) keeps the root in fixed point
+        root = mul(Some(root), one).and_then(u256_sqrt)?;
+        frac = frac.checked_mul(U256::from(2))?;
+        if frac >= exp_denom {
+            frac = frac.checked_sub(exp_denom)?;
+            result = div(mul(result, Some(root)), one);
+        }
+    }
+
+    result
+}
+
+/// `pow` rounded up by its maximum error
+fn pow_up(base: U256, exp_nom: U256, exp_denom: U256) -> Option<U256> {
+    let result = pow(base, exp_nom, exp_denom)?;
+    let error = div(
+        mul(Some(result), Some(U256::from(MAX_POW_RELATIVE_ERROR))),
+        Some(one()),
+    );
+    add(add(Some(result), error), Some(U256::one()))
+}
+
+fn weighted_math_err(what: &str) -> StdError {
+    StdError::generic_err(format!("Cannot calculate weighted {}", what))
+}
+
+/// Weighted counterpart of `compute_swap`. Implements Balancer's `outGivenIn`:
+///
+/// return_amount = ask_pool * (1 - (offer_pool / (offer_pool + offer_amount)) ^ (w_offer / w_ask))
+///
+/// The spread is measured against the weighted spot price
+/// `(ask_pool / w_ask) / (offer_pool / w_offer)`.
+pub fn compute_weighted_swap(
+    offer_weight: u64,
+    ask_weight: u64,
+    offer_pool: Uint128,
+    ask_pool: Uint128,
+    offer_amount: Uint128,
+    commission_rate_nom: Uint128,
+    commission_rate_denom: Uint128,
+) -> StdResult<(Uint128, Uint128, Uint128)> {
+    let offer_weight = U256::from(offer_weight);
+    let ask_weight = U256::from(ask_weight);
+    let offer_pool = U256::from(offer_pool.u128());
+    let ask_pool = U256::from(ask_pool.u128());
+    let offer_amount = U256::from(offer_amount.u128());
+
+    if offer_amount > offer_pool / U256::from(MAX_IN_RATIO_DENOM) {
+        return Err(StdError::generic_err(format!(
+            "Offer amount {} exceeds 1/{} of the offer pool {}",
+            offer_amount, MAX_IN_RATIO_DENOM, offer_pool
+        )));
+    }
+
+    // base = offer_pool / (offer_pool + offer_amount), rounded up
+    let new_offer_pool =
+        add(Some(offer_pool), Some(offer_amount)).ok_or_else(|| weighted_math_err("base"))?;
+    let base = div(
+        sub(
+            add(mul(Some(offer_pool), Some(one())), Some(new_offer_pool)),
+            Some(U256::one()),
+        ),
+        Some(new_offer_pool),
+    )
+    .ok_or_else(|| weighted_math_err("base"))?;
+
+    let power = pow_up(base, offer_weight, ask_weight).ok_or_else(|| weighted_math_err("power"))?;
+
+    let return_amount = div(
+        mul(Some(ask_pool), Some(one().saturating_sub(power))),
+        Some(one()),
+    )
+    .ok_or_else(|| weighted_math_err("return amount"))?;
+
+    // spread = offer_amount * spot_price - return_amount
+    let spread_amount = div(
+        mul(mul(Some(offer_amount), Some(ask_pool)), Some(offer_weight)),
+        mul(Some(offer_pool), Some(ask_weight)),
+    )
+    .ok_or_else(|| weighted_math_err("spread"))?
+    .saturating_sub(return_amount);
+
+    let commission_amount = div(
+        mul(
+            Some(return_amount),
+            Some(U256::from(commission_rate_nom.u128())),
+        ),
+        Some(U256::from(commission_rate_denom.u128())),
+    )
+    .ok_or_else(|| weighted_math_err("commission"))?;
+
+    // commission will be absorbed to pool
+    let return_amount = return_amount.saturating_sub(commission_amount);
+
+    Ok((
+        Uint128(return_amount.low_u128()),
+        Uint128(spread_amount.low_u128()),
+        Uint128(commission_amount.low_u128()),
+    ))
+}
+
+/// Weighted counterpart of `compute_offer_amount`. Implements Balancer's `inGivenOut`:
+///
+/// offer_amount = offer_pool * ((ask_pool / (ask_pool - ask_amount)) ^ (w_ask / w_offer) - 1)
+pub fn compute_weighted_offer_amount(
+    offer_weight: u64,
+    ask_weight: u64,
+    offer_pool: Uint128,
+    ask_pool: Uint128,
+    ask_amount: Uint128,
+    commission_rate_nom: u128,
+    commission_rate_denom: u128,
+) -> StdResult<(Uint128, Uint128, Uint128)> {
+    let offer_weight = U256::from(offer_weight);
+    let ask_weight = U256::from(ask_weight);
+    let offer_pool = U256::from(offer_pool.u128());
+    let ask_pool = U256::from(ask_pool.u128());
+    let ask_amount = U256::from(ask_amount.u128());
+    let commission_rate_nom = U256::from(commission_rate_nom);
+    let commission_rate_denom = U256::from(commission_rate_denom);
+
+    // before_commission_deduction = ceil(ask_amount / (1 - commission_rate))
+    let one_minus_commission = sub(Some(commission_rate_denom), Some(commission_rate_nom));
+    let before_commission_deduction = div(
+        sub(
+            add(
+                mul(Some(ask_amount), Some(commission_rate_denom)),
+                one_minus_commission,
+            ),
+            Some(U256::one()),
+        ),
+        one_minus_commission,
+    )
+    .ok_or_else(|| weighted_math_err("ask amount before commission"))?;
+
+    if before_commission_deduction > ask_pool / U256::from(MAX_OUT_RATIO_DENOM) {
+        return Err(StdError::generic_err(format!(
+            "Ask amount {} exceeds 1/{} of the ask pool {}",
+            before_commission_deduction, MAX_OUT_RATIO_DENOM, ask_pool
+        )));
+    }
+
+    // base = ask_pool / (ask_pool - before_commission_deduction), rounded up
+    let new_ask_pool = ask_pool - before_commission_deduction;
+    let base = div(
+        sub(
+            add(mul(Some(ask_pool), Some(one())), Some(new_ask_pool)),
+            Some(U256::one()),
+        ),
+        Some(new_ask_pool),
+    )
+    .ok_or_else(|| weighted_math_err("base"))?;
+
+    let power = pow_up(base, ask_weight, offer_weight).ok_or_else(|| weighted_math_err("power"))?;
+
+    // offer_amount = ceil(offer_pool * (power - 1))
+    let offer_amount = div(
+        sub(
+            add(
+                mul(Some(offer_pool), Some(power.saturating_sub(one()))),
+                Some(one()),
+            ),
+            Some(U256::one()),
+        ),
+        Some(one()),
+    )
+    .ok_or_else(|| weighted_math_err("offer amount"))?;
+
+    // spread = offer_amount * spot_price - before_commission_deduction
+    let spread_amount = div(
+        mul(mul(Some(offer_amount), Some(ask_pool)), Some(offer_weight)),
+        mul(Some(offer_pool), Some(ask_weight)),
+    )
+    .ok_or_else(|| weighted_math_err("spread"))?
+    .saturating_sub(before_commission_deduction);
+    let commission_amount = before_commission_deduction.saturating_sub(ask_amount);
+
+    Ok((
+        Uint128(offer_amount.low_u128()),
+        Uint128(spread_amount.low_u128()),
+        Uint128(commission_amount.low_u128()),
+    ))
+}
+
+/// Initial LP share of a weighted pool, the weighted geometric mean
+/// `deposit_0 ^ (w0 / (w0 + w1)) * deposit_1 ^ (w1 / (w0 + w1))`.
+/// It is evaluated as `lo * (hi / lo) ^ (w_hi / (w0 + w1))` to keep the base above one.
+pub fn compute_weighted_initial_share(
+    weights: [u64; 2],
+    deposit_0: U256,
+    deposit_1: U256,
+) -> Option<U256> {
+    let total_weight = U256::from(weights[0]).checked_add(U256::from(weights[1]))?;
+    let (hi, lo, hi_weight) = if deposit_0 >= deposit_1 {
+        (deposit_0, deposit_1, weights[0])
+    } else {
+        (deposit_1, deposit_0, weights[1])
+    };
+    if lo.is_zero() {
+        return Some(U256::zero());
+    }
+
+    let ratio = div(mul(Some(hi), Some(one())), Some(lo))?;
+    let power = pow(ratio, U256::from(hi_weight), total_weight)?;
+    div(mul(Some(lo), Some(power)), Some(one()))
+}
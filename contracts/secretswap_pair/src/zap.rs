@@ -0,0 +1,58 @@
+use cosmwasm_std::{StdError, StdResult, Uint128};
+use primitive_types::U256;
+
+/// Portion of a single sided deposit of `amount` to swap so that the rest matches the pool
+/// ratio after the swap:
+///
+/// (amount - swap_amount) / (offer_pool + swap_amount) == return_amount / (ask_pool - ask_out)
+///
+/// `swap` returns `(return_amount, ask_out)` for an offer amount, where `ask_out` is what
+/// leaves the ask pool: the return amount plus the protocol fee. Works for any curve, since
+/// the left side decreases and the right side increases with the swap amount, so it is found
+/// by bisection. The result is rounded down, leaving dust of the offer asset to refund.
+pub fn compute_zap_in_swap_amount<F>(
+    offer_pool: Uint128,
+    ask_pool: Uint128,
+    amount: Uint128,
+    swap: F,
+) -> StdResult<Uint128>
+where
+    F: Fn(Uint128) -> StdResult<(Uint128, Uint128)>,
+{
+    if offer_pool.is_zero() || ask_pool.is_zero() {
+        return Err(StdError::generic_err(
+            "Cannot zap into an empty pool, provide both assets instead",
+        ));
+    }
+
+    // whether swapping `swap_amount` leaves at least enough offer asset for the return amount
+    let offer_side_left = |swap_amount: u128| -> bool {
+        let (return_amount, ask_out) = match swap(Uint128(swap_amount)) {
+            Ok(swapped) => swapped,
+            // e.g. the offer amount exceeds what the curve accepts
+            Err(_) => return false,
+        };
+        let ask_pool_after = match ask_pool.u128().checked_sub(ask_out.u128()) {
+            Some(ask_pool_after) => ask_pool_after,
+            None => return false,
+        };
+
+        U256::from(amount.u128() - swap_amount) * U256::from(ask_pool_after)
+            >= U256::from(return_amount.u128())
+                * (U256::from(offer_pool.u128()) + U256::from(swap_amount))
+    };
+
+    // the largest swap amount that still leaves enough offer asset
+    let mut lo = 0u128;
+    let mut hi = amount.u128();
+    while lo < hi {
+        let mid = lo + (hi - lo + 1) / 2;
+        if offer_side_left(mid) {
+            lo = mid;
+        } else {
+            hi = mid - 1;
+        }
+    }
+
+    Ok(Uint128(lo))
+}
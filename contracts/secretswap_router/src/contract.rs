@@ -143,8 +143,14 @@ fn handle_first_hop<S: Storage, A: Api, Q: Querier>(
         mut hops,
         to,
         expected_return,
+        deadline,
     } = from_binary(&msg)?;
 
+    // all hops run in the same transaction, so checking the deadline once is enough
+    if let Some(deadline) = &deadline {
+        deadline.assert_not_expired(&env.block)?;
+    }
+
     if hops.len() < 2 {
         return Err(StdError::generic_err("route must be at least 2 hops"));
     }
@@ -192,6 +198,7 @@ fn handle_first_hop<S: Storage, A: Api, Q: Querier>(
                 hops, // hops was mutated earlier when we did `hops.pop_front()`
                 expected_return,
                 to,
+                deadline,
             },
         },
     )?;
@@ -282,6 +289,7 @@ fn handle_hop<S: Storage, A: Api, Q: Querier>(
                     mut hops,
                     expected_return,
                     to,
+                    deadline,
                 },
         }) => {
             let next_hop: Hop = match hops.pop_front() {
@@ -362,6 +370,7 @@ fn handle_hop<S: Storage, A: Api, Q: Querier>(
                         hops, // hops was mutated earlier when we did `hops.pop_front()`
                         expected_return,
                         to,
+                        deadline,
                     },
                 },
             )?;
@@ -2,5 +2,8 @@ pub mod contract;
 pub mod msg;
 pub mod state;
 
+#[cfg(test)]
+mod testing;
+
 #[cfg(all(target_arch = "wasm32", not(feature = "library")))]
 cosmwasm_std::create_entry_points!(contract);
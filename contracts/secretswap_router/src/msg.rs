@@ -3,7 +3,7 @@ use std::collections::VecDeque;
 use crate::state::SecretContract;
 use cosmwasm_std::{Binary, HumanAddr, Uint128};
 use schemars::JsonSchema;
-use secretswap::Asset;
+use secretswap::{Asset, Deadline};
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -25,6 +25,8 @@ pub struct Route {
     pub hops: VecDeque<Hop>,
     pub expected_return: Option<Uint128>,
     pub to: HumanAddr,
+    /// The route fails if it starts after the deadline
+    pub deadline: Option<Deadline>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -0,0 +1,98 @@
+use std::collections::VecDeque;
+
+use cosmwasm_std::testing::{mock_dependencies, mock_env};
+use cosmwasm_std::{to_binary, HumanAddr, StdError, Uint128};
+use secretswap::Deadline;
+
+use crate::contract::{handle, init};
+use crate::msg::{HandleMsg, Hop, InitMsg, Route, Snip20Data, Token};
+
+fn route(deadline: Option<Deadline>) -> HandleMsg {
+    let hop = |token: &str, pair: &str| Hop {
+        from_token: Token::Snip20(Snip20Data {
+            address: HumanAddr::from(token),
+            code_hash: format!("{}_hash", token),
+        }),
+        pair_address: HumanAddr::from(pair),
+        pair_code_hash: format!("{}_hash", pair),
+    };
+    let mut hops = VecDeque::new();
+    hops.push_back(hop("token0000", "pair0000"));
+    hops.push_back(hop("token0001", "pair0001"));
+
+    HandleMsg::Receive {
+        from: HumanAddr::from("addr0000"),
+        amount: Uint128(1000u128),
+        msg: Some(
+            to_binary(&Route {
+                hops,
+                expected_return: None,
+                to: HumanAddr::from("addr0000"),
+                deadline,
+            })
+            .unwrap(),
+        ),
+    }
+}
+
+#[test]
+fn route_deadline() {
+    let mut deps = mock_dependencies(20, &[]);
+    let msg = InitMsg {
+        register_tokens: None,
+        cashback: None,
+        owner: None,
+    };
+    init(&mut deps, mock_env("owner0000", &[]), msg).unwrap();
+
+    let env = mock_env("token0000", &[]);
+    let deadline = |time: Option<u64>, height: Option<u64>| route(Some(Deadline { time, height }));
+
+    // an expired route fails before any hop is sent
+    match handle(
+        &mut deps,
+        env.clone(),
+        deadline(Some(env.block.time - 1), None),
+    )
+    .unwrap_err()
+    {
+        StdError::GenericErr { msg, .. } => assert_eq!(
+            msg,
+            format!(
+                "Deadline expired at block time {}, current block time is {}",
+                env.block.time - 1,
+                env.block.time
+            )
+        ),
+        _ => panic!("Must return generic error"),
+    }
+    match handle(
+        &mut deps,
+        env.clone(),
+        deadline(None, Some(env.block.height - 1)),
+    )
+    .unwrap_err()
+    {
+        StdError::GenericErr { msg, .. } => assert_eq!(
+            msg,
+            format!(
+                "Deadline expired at block height {}, current block height is {}",
+                env.block.height - 1,
+                env.block.height
+            )
+        ),
+        _ => panic!("Must return generic error"),
+    }
+
+    // the deadline is inclusive
+    let res = handle(
+        &mut deps,
+        env.clone(),
+        deadline(Some(env.block.time), Some(env.block.height)),
+    )
+    .unwrap();
+    assert_eq!(res.messages.len(), 2);
+
+    let res = handle(&mut deps, env, route(None)).unwrap();
+    assert_eq!(res.messages.len(), 2);
+}
@@ -8,6 +8,7 @@ use schemars::JsonSchema;
 use secret_toolkit::snip20::HandleMsg;
 use serde::{Deserialize, Serialize};
 
+use crate::curve::CurveType;
 use crate::querier::{query_balance, query_token_balance};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -349,6 +350,8 @@ pub struct PairInfo {
     pub asset0_volume: Uint128,
     pub asset1_volume: Uint128,
     pub factory: Factory,
+    #[serde(default)]
+    pub curve: CurveType,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -360,6 +363,8 @@ pub struct PairInfoRaw {
     pub asset0_volume: Uint128,
     pub asset1_volume: Uint128,
     pub factory: Factory,
+    #[serde(default)]
+    pub curve: CurveType,
 }
 
 impl PairInfoRaw {
@@ -378,6 +383,7 @@ impl PairInfoRaw {
             asset0_volume: self.asset0_volume.clone(),
             asset1_volume: self.asset1_volume.clone(),
             factory: self.factory.clone(),
+            curve: self.curve.clone(),
         })
     }
 
@@ -400,3 +406,45 @@ impl PairInfoRaw {
         ])
     }
 }
+
+/// Multi-asset pool info, kept by the pool and the factory
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MultiPoolInfo {
+    pub asset_infos: Vec<AssetInfo>,
+    pub contract_addr: HumanAddr,
+    pub liquidity_token: HumanAddr,
+    /// Code hash of the liquidity token
+    pub token_code_hash: String,
+    pub factory: Factory,
+    pub curve: CurveType,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MultiPoolInfoRaw {
+    pub asset_infos: Vec<AssetInfoRaw>,
+    pub contract_addr: CanonicalAddr,
+    pub liquidity_token: CanonicalAddr,
+    pub token_code_hash: String,
+    pub factory: Factory,
+    pub curve: CurveType,
+}
+
+impl MultiPoolInfoRaw {
+    pub fn to_normal<S: Storage, A: Api, Q: Querier>(
+        &self,
+        deps: &Extern<S, A, Q>,
+    ) -> StdResult<MultiPoolInfo> {
+        Ok(MultiPoolInfo {
+            asset_infos: self
+                .asset_infos
+                .iter()
+                .map(|asset_info| asset_info.to_normal(&deps))
+                .collect::<StdResult<Vec<AssetInfo>>>()?,
+            contract_addr: deps.api.human_address(&self.contract_addr)?,
+            liquidity_token: deps.api.human_address(&self.liquidity_token)?,
+            token_code_hash: self.token_code_hash.clone(),
+            factory: self.factory.clone(),
+            curve: self.curve.clone(),
+        })
+    }
+}
@@ -0,0 +1,75 @@
+use std::fmt;
+
+use cosmwasm_std::{StdError, StdResult};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Upper bound for the StableSwap amplification coefficient
+pub const MAX_AMP: u64 = 1_000_000;
+/// Lower bound for a normalized weight of a weighted pool, in percent
+pub const MIN_WEIGHT_PERCENT: u64 = 1;
+
+/// CurveType selects the invariant a pair uses to price swaps and liquidity
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CurveType {
+    /// Uniswap-style `x * y = k`
+    ConstantProduct {},
+    /// Curve-style StableSwap invariant for pegged assets.
+    /// `amp` is the amplification coefficient `A` of the whitepaper.
+    StableSwap { amp: u64 },
+    /// Balancer-style weighted constant product `x^wx * y^wy = k`.
+    /// `weights` are aligned with the pair's `asset_infos`, e.g. `[80, 20]`.
+    Weighted { weights: [u64; 2] },
+}
+
+impl Default for CurveType {
+    fn default() -> Self {
+        CurveType::ConstantProduct {}
+    }
+}
+
+impl fmt::Display for CurveType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CurveType::ConstantProduct {} => write!(f, "constant_product"),
+            CurveType::StableSwap { amp } => write!(f, "stable_swap(amp={})", amp),
+            CurveType::Weighted { weights } => {
+                write!(f, "weighted({}/{})", weights[0], weights[1])
+            }
+        }
+    }
+}
+
+impl CurveType {
+    pub fn validate(&self) -> StdResult<()> {
+        match self {
+            CurveType::ConstantProduct {} => Ok(()),
+            CurveType::StableSwap { amp } => {
+                if *amp == 0 || *amp > MAX_AMP {
+                    return Err(StdError::generic_err(format!(
+                        "StableSwap amp must be between 1 and {}",
+                        MAX_AMP
+                    )));
+                }
+                Ok(())
+            }
+            CurveType::Weighted { weights } => {
+                let total = weights[0]
+                    .checked_add(weights[1])
+                    .ok_or_else(|| StdError::generic_err("Weights are too large"))?;
+                for weight in weights.iter() {
+                    if *weight == 0
+                        || (*weight as u128) * 100 < (total as u128) * (MIN_WEIGHT_PERCENT as u128)
+                    {
+                        return Err(StdError::generic_err(format!(
+                            "Each weight must be at least {}% of the total weight",
+                            MIN_WEIGHT_PERCENT
+                        )));
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
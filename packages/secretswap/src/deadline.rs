@@ -0,0 +1,35 @@
+use cosmwasm_std::{BlockInfo, StdError, StdResult};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Deadline makes a message fail when it is executed after the given block time or height.
+/// Both are inclusive, a message executed exactly at the deadline still goes through.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Deadline {
+    /// Block time in seconds
+    pub time: Option<u64>,
+    pub height: Option<u64>,
+}
+
+impl Deadline {
+    pub fn assert_not_expired(&self, block: &BlockInfo) -> StdResult<()> {
+        if let Some(time) = self.time {
+            if block.time > time {
+                return Err(StdError::generic_err(format!(
+                    "Deadline expired at block time {}, current block time is {}",
+                    time, block.time
+                )));
+            }
+        }
+        if let Some(height) = self.height {
+            if block.height > height {
+                return Err(StdError::generic_err(format!(
+                    "Deadline expired at block height {}, current block height is {}",
+                    height, block.height
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
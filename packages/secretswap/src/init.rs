@@ -1,8 +1,9 @@
-use cosmwasm_std::{Binary, HumanAddr, StdError, StdResult, Uint128};
+use cosmwasm_std::{Binary, Decimal, HumanAddr, StdError, StdResult, Uint128};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::asset::AssetInfo;
+use crate::curve::CurveType;
 use crate::hook::InitHook;
 
 //use secret_toolkit::snip20::{MinterResponse};
@@ -40,6 +41,77 @@ pub struct PairInitMsg {
     /// Hook for post initalization
     pub init_hook: Option<InitHook>,
     pub prng_seed: Binary,
+    /// Pool curve, defaults to constant product
+    pub curve: Option<CurveType>,
+    /// Pair whose liquidity this one takes over, set by the factory when migrating a pair
+    #[serde(default)]
+    pub migrate_from: Option<MigrateFrom>,
+}
+
+/// Pair a migrated pair was instantiated to replace
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MigrateFrom {
+    pub address: HumanAddr,
+    pub code_hash: String,
+}
+
+/// Concentrated liquidity pair InitMsg
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ConcentratedPairInitMsg {
+    /// Asset infos
+    pub asset_infos: [AssetInfo; 2],
+    /// Positions start and end at multiples of it
+    pub tick_spacing: u32,
+    /// Price of asset 0 in asset 1 the pool starts at
+    pub initial_price: Decimal,
+    /// Hook for post initalization
+    pub init_hook: Option<InitHook>,
+}
+
+/// Fewest assets a multi-asset pool holds, pairs cover two
+pub const MIN_MULTI_POOL_ASSETS: usize = 3;
+/// Most assets a multi-asset pool holds
+pub const MAX_MULTI_POOL_ASSETS: usize = 8;
+
+/// Multi-asset pool InitMsg
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MultiPoolInitMsg {
+    /// Asset infos
+    pub asset_infos: Vec<AssetInfo>,
+    /// Token contract code id for initialization
+    pub token_code_id: u64,
+    pub token_code_hash: String,
+    /// Hook for post initalization
+    pub init_hook: Option<InitHook>,
+    pub prng_seed: Binary,
+    /// Pool curve, defaults to constant product
+    pub curve: Option<CurveType>,
+}
+
+/// Checks the number of assets and the curve of a multi-asset pool, and that no asset is
+/// given twice
+pub fn validate_multi_pool(asset_infos: &[AssetInfo], curve: &CurveType) -> StdResult<()> {
+    if asset_infos.len() < MIN_MULTI_POOL_ASSETS || asset_infos.len() > MAX_MULTI_POOL_ASSETS {
+        return Err(StdError::generic_err(format!(
+            "A multi-asset pool holds between {} and {} assets",
+            MIN_MULTI_POOL_ASSETS, MAX_MULTI_POOL_ASSETS
+        )));
+    }
+    for (i, asset_info) in asset_infos.iter().enumerate() {
+        if asset_infos[..i].iter().any(|other| other.equal(asset_info)) {
+            return Err(StdError::generic_err(format!(
+                "Asset {} is given more than once",
+                asset_info
+            )));
+        }
+    }
+
+    match curve {
+        CurveType::Weighted { .. } => Err(StdError::generic_err(
+            "Multi-asset pools support the constant product and StableSwap curves",
+        )),
+        _ => curve.validate(),
+    }
 }
 
 /// TokenContract InitMsg
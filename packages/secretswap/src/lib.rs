@@ -1,21 +1,39 @@
-pub use crate::asset::{Asset, AssetInfo, AssetInfoRaw, AssetRaw, Factory, PairInfo, PairInfoRaw};
+pub use crate::asset::{
+    Asset, AssetInfo, AssetInfoRaw, AssetRaw, Factory, MultiPoolInfo, MultiPoolInfoRaw, PairInfo,
+    PairInfoRaw,
+};
+pub use crate::curve::{CurveType, MAX_AMP, MIN_WEIGHT_PERCENT};
+pub use crate::deadline::Deadline;
 pub use crate::hook::InitHook;
-pub use crate::init::{Balance, PairInitMsg, TokenInitMsg};
+pub use crate::init::{
+    validate_multi_pool, Balance, ConcentratedPairInitMsg, MigrateFrom, MultiPoolInitMsg,
+    PairInitMsg, TokenInitMsg, MAX_MULTI_POOL_ASSETS, MIN_MULTI_POOL_ASSETS,
+};
 pub use crate::msg::{
     FactoryHandleMsg, FactoryQueryMsg, PairCw20HookMsg, PairHandleMsg, PairQueryMsg,
+    ProvideResponse, SwapResponse, WithdrawResponse, ZapInResponse, ZapOutResponse,
+};
+pub use crate::pair_settings::{
+    DynamicFee, Fee, PairSettings, PairStatusLevel, ProtocolFee, SwapDataEndpoint,
+    SwapDataEndpointMsg,
 };
-pub use crate::pair_settings::{Fee, PairSettings, SwapDataEndpoint, SwapDataEndpointMsg};
 pub use crate::querier::{
     query_all_balances, query_balance, query_pair_info, query_supply, query_token_balance,
     reverse_simulate, simulate,
 };
+pub use crate::swap_data::{
+    ack_swap_data, deliver_swap_data, query_swap_data, SwapData, SwapDataEntry, SwapDataResponse,
+};
 
 mod asset;
+mod curve;
+mod deadline;
 mod hook;
 mod init;
 mod msg;
 mod pair_settings;
 mod querier;
+mod swap_data;
 
 #[cfg(test)]
 mod mock_querier;
@@ -2,7 +2,7 @@ use cosmwasm_std::{Decimal, HumanAddr, Uint128};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::{Asset, AssetInfo, InitHook};
+use crate::{Asset, AssetInfo, CurveType, InitHook};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
@@ -13,6 +13,8 @@ pub enum FactoryHandleMsg {
         asset_infos: [AssetInfo; 2],
         /// Init hook for after works
         init_hook: Option<InitHook>,
+        /// Pool curve, defaults to constant product
+        curve: Option<CurveType>,
     },
 }
 
@@ -73,3 +75,67 @@ pub struct ReverseSimulationResponse {
     pub spread_amount: Uint128,
     pub commission_amount: Uint128,
 }
+
+/// SwapResponse is returned in the data of a swap
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SwapResponse {
+    /// Part of the offer asset that was swapped
+    pub offer_asset: Asset,
+    /// Asset paid to the receiver, after the commission
+    pub return_asset: Asset,
+    pub spread_amount: Uint128,
+    pub commission_amount: Uint128,
+    /// Part of the commission kept for the protocol
+    pub protocol_fee_amount: Uint128,
+    /// Offer amount refunded to the sender
+    pub refund_amount: Uint128,
+}
+
+/// ProvideResponse is returned in the data of a liquidity provision
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ProvideResponse {
+    /// Liquidity tokens minted to the provider
+    pub share: Uint128,
+    /// Assets taken from the provider, aligned with `asset_infos`
+    pub used_assets: [Asset; 2],
+}
+
+/// WithdrawResponse is returned in the data of a liquidity withdrawal
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct WithdrawResponse {
+    /// Liquidity tokens burned
+    pub withdrawn_share: Uint128,
+    /// Assets paid out, aligned with `asset_infos`
+    pub refund_assets: [Asset; 2],
+}
+
+/// ZapInResponse is returned in the data of a single asset liquidity provision
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ZapInResponse {
+    /// Liquidity tokens minted to the provider
+    pub share: Uint128,
+    /// Part of the offer asset swapped into the other asset before the deposit
+    pub swap_amount: Uint128,
+    /// Other asset returned by the swap, after the commission
+    pub return_amount: Uint128,
+    pub commission_amount: Uint128,
+    /// Part of the commission kept for the protocol
+    pub protocol_fee_amount: Uint128,
+    /// Offer amount the deposit didn't use, refunded to the sender
+    pub refund_amount: Uint128,
+}
+
+/// ZapOutResponse is returned in the data of a single asset liquidity withdrawal
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ZapOutResponse {
+    /// Liquidity tokens burned
+    pub withdrawn_share: Uint128,
+    /// Asset paid out, the withdrawn ask asset plus the swap return
+    pub return_asset: Asset,
+    /// Ask asset returned by swapping the withdrawn offer asset, after the commission
+    pub swap_return_amount: Uint128,
+    pub spread_amount: Uint128,
+    pub commission_amount: Uint128,
+    /// Part of the commission kept for the protocol
+    pub protocol_fee_amount: Uint128,
+}
@@ -1,5 +1,8 @@
+use crate::swap_data::SwapData;
 use crate::Asset;
-use cosmwasm_std::{to_binary, CosmosMsg, HumanAddr, StdResult, Uint128, WasmMsg};
+use cosmwasm_std::{
+    to_binary, Binary, CosmosMsg, Decimal, HumanAddr, StdError, StdResult, Uint128, WasmMsg,
+};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
@@ -7,32 +10,170 @@ use serde::{Deserialize, Serialize};
 pub struct SwapDataEndpoint {
     pub address: HumanAddr,
     pub code_hash: String,
+    /// Best-effort endpoints aren't called by swaps, so they can't make a swap fail. They
+    /// read the swap data from the pairs with the key whose hash is stored here.
+    #[serde(default)]
+    pub best_effort_key_hash: Option<Binary>,
 }
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Fee {
     pub commission_rate_nom: Uint128,
     pub commission_rate_denom: Uint128,
 }
+/// Swap fee that follows the price volatility of a pair, between two bounds
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DynamicFee {
+    /// Fee while the price doesn't move
+    pub min_fee: Fee,
+    /// Fee once the volatility reaches `max_volatility`
+    pub max_fee: Fee,
+    /// Average relative price change between blocks at which the fee is `max_fee`
+    pub max_volatility: Decimal,
+}
+/// Share of the swap commission that goes to the protocol instead of the LPs
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ProtocolFee {
+    pub share_nom: Uint128,
+    pub share_denom: Uint128,
+    /// Receives the accrued protocol fees of every pair
+    pub collector: HumanAddr,
+}
+/// Emergency stop levels of pairs, set by the factory owner.
+/// Levels are ordered from the least to the most restrictive.
+#[derive(
+    Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum PairStatusLevel {
+    NormalRun,
+    /// Swaps (including zaps and flash swaps) are stopped, liquidity can still be provided
+    /// and withdrawn
+    StopSwaps,
+    /// Only `withdraw_liquidity` is allowed
+    StopAllButWithdrawals,
+}
+impl Default for PairStatusLevel {
+    fn default() -> Self {
+        PairStatusLevel::NormalRun
+    }
+}
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct PairSettings {
     pub swap_fee: Fee,
+    /// First endpoint called by swaps, for pairs running older code
     pub swap_data_endpoint: Option<SwapDataEndpoint>,
+    #[serde(default)]
+    pub swap_data_endpoints: Vec<SwapDataEndpoint>,
+    #[serde(default)]
+    pub protocol_fee: Option<ProtocolFee>,
+    /// Status of the pair, the stricter of its own and the global one
+    #[serde(default)]
+    pub status: PairStatusLevel,
+    /// Replaces `swap_fee` in pairs that support it
+    #[serde(default)]
+    pub dynamic_fee: Option<DynamicFee>,
+    /// Largest relative move of the pair price within a block, swaps moving it further fail
+    #[serde(default)]
+    pub max_price_move: Option<Decimal>,
+}
+
+impl PairStatusLevel {
+    pub fn assert_swaps_allowed(&self) -> StdResult<()> {
+        match self {
+            PairStatusLevel::NormalRun => Ok(()),
+            _ => Err(StdError::generic_err("Swaps are stopped on this pair")),
+        }
+    }
+
+    pub fn assert_deposits_allowed(&self) -> StdResult<()> {
+        match self {
+            PairStatusLevel::StopAllButWithdrawals => {
+                Err(StdError::generic_err("Deposits are stopped on this pair"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Fee {
+    pub fn validate(&self) -> StdResult<()> {
+        if self.commission_rate_denom.is_zero()
+            || self.commission_rate_nom >= self.commission_rate_denom
+        {
+            return Err(StdError::generic_err("Swap fee must be lower than 100%"));
+        }
+        Ok(())
+    }
+}
+
+impl DynamicFee {
+    pub fn validate(&self) -> StdResult<()> {
+        self.min_fee.validate()?;
+        self.max_fee.validate()?;
+
+        // min_nom / min_denom <= max_nom / max_denom
+        let min = self
+            .min_fee
+            .commission_rate_nom
+            .u128()
+            .checked_mul(self.max_fee.commission_rate_denom.u128());
+        let max = self
+            .max_fee
+            .commission_rate_nom
+            .u128()
+            .checked_mul(self.min_fee.commission_rate_denom.u128());
+        match (min, max) {
+            (Some(min), Some(max)) if min <= max => {}
+            _ => {
+                return Err(StdError::generic_err(
+                    "Minimum fee must not exceed the maximum fee",
+                ))
+            }
+        }
+
+        // price changes are capped at 100%
+        if self.max_volatility == Decimal::zero() || self.max_volatility > Decimal::one() {
+            return Err(StdError::generic_err(
+                "Maximum volatility must be greater than 0 and at most 1",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl ProtocolFee {
+    pub fn validate(&self) -> StdResult<()> {
+        if self.share_denom.is_zero() || self.share_nom > self.share_denom {
+            return Err(StdError::generic_err(
+                "Protocol fee share must be a fraction between 0 and 1",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Protocol part of a commission amount
+    pub fn compute(&self, commission_amount: Uint128) -> Uint128 {
+        commission_amount.multiply_ratio(self.share_nom, self.share_denom)
+    }
 }
 
 impl SwapDataEndpoint {
-    pub fn into_msg(
-        self,
-        asset_in: Asset,
-        asset_out: Asset,
-        account: HumanAddr,
-    ) -> StdResult<CosmosMsg> {
+    pub fn is_best_effort(&self) -> bool {
+        self.best_effort_key_hash.is_some()
+    }
+
+    pub fn into_msg(self, data: SwapData) -> StdResult<CosmosMsg> {
         Ok(CosmosMsg::Wasm(WasmMsg::Execute {
             contract_addr: self.address,
             callback_code_hash: self.code_hash,
             msg: to_binary(&SwapDataEndpointMsg::ReceiveSwapData {
-                asset_in,
-                asset_out,
-                account,
+                asset_in: data.asset_in,
+                asset_out: data.asset_out,
+                account: data.account,
+                pair: data.pair,
+                commission: data.commission,
+                spread_amount: data.spread_amount,
+                reserves: data.reserves,
             })?,
             send: vec![],
         }))
@@ -42,9 +183,14 @@ impl SwapDataEndpoint {
 #[derive(Serialize, Clone, Debug, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum SwapDataEndpointMsg {
+    /// Fields are those of `SwapData`
     ReceiveSwapData {
         asset_in: Asset,
         asset_out: Asset,
         account: HumanAddr,
+        pair: HumanAddr,
+        commission: Asset,
+        spread_amount: Uint128,
+        reserves: [Asset; 2],
     },
 }
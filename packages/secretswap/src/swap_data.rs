@@ -0,0 +1,194 @@
+use cosmwasm_std::{CosmosMsg, Env, HumanAddr, StdError, StdResult, Storage, Uint128};
+use cosmwasm_storage::{Bucket, ReadonlyBucket, ReadonlySingleton, Singleton};
+use schemars::JsonSchema;
+use secret_toolkit::crypto::sha_256;
+use serde::{Deserialize, Serialize};
+
+use crate::pair_settings::SwapDataEndpoint;
+use crate::Asset;
+
+static KEY_SWAP_DATA_COUNT: &[u8] = b"swap_data_count";
+static KEY_SWAP_DATA_PRUNED: &[u8] = b"swap_data_pruned";
+static PREFIX_SWAP_DATA: &[u8] = b"swap_data";
+static PREFIX_SWAP_DATA_CURSOR: &[u8] = b"swap_data_cursor";
+
+// settings for pagination
+const MAX_LIMIT: u32 = 100;
+const DEFAULT_LIMIT: u32 = 30;
+
+/// A swap, as reported to the swap data endpoints
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SwapData {
+    pub pair: HumanAddr,
+    /// Receiver of the swap
+    pub account: HumanAddr,
+    pub asset_in: Asset,
+    /// Return of the swap before the commission is taken from it, if it is taken from the
+    /// return
+    pub asset_out: Asset,
+    pub commission: Asset,
+    /// Shortfall of the return against the pool price, in the ask asset
+    pub spread_amount: Uint128,
+    /// Reserves of the pair after the swap, aligned with `asset_infos`
+    pub reserves: [Asset; 2],
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SwapDataEntry {
+    /// Position of the swap among the swaps the pair kept, starting at 0
+    pub seq: u64,
+    pub height: u64,
+    pub time: u64,
+    pub data: SwapData,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SwapDataResponse {
+    pub entries: Vec<SwapDataEntry>,
+    /// `seq` the next swap will get
+    pub count: u64,
+    /// First `seq` the endpoint hasn't acknowledged. Entries every best-effort endpoint
+    /// acknowledged are removed.
+    pub cursor: u64,
+}
+
+/// Messages calling the endpoints swaps report to. The swap is kept for best-effort
+/// endpoints instead, if there are any, until all of them acknowledged it.
+pub fn deliver_swap_data<S: Storage>(
+    storage: &mut S,
+    env: &Env,
+    endpoints: &[SwapDataEndpoint],
+    data: SwapData,
+) -> StdResult<Vec<CosmosMsg>> {
+    if endpoints.iter().any(|endpoint| endpoint.is_best_effort()) {
+        let seq: u64 = ReadonlySingleton::new(storage, KEY_SWAP_DATA_COUNT)
+            .may_load()?
+            .unwrap_or_default();
+        Bucket::new(PREFIX_SWAP_DATA, storage).save(
+            &seq.to_be_bytes(),
+            &SwapDataEntry {
+                seq,
+                height: env.block.height,
+                time: env.block.time,
+                data: data.clone(),
+            },
+        )?;
+        Singleton::new(storage, KEY_SWAP_DATA_COUNT).save(&(seq + 1))?;
+    }
+
+    endpoints
+        .iter()
+        .filter(|endpoint| !endpoint.is_best_effort())
+        .map(|endpoint| endpoint.clone().into_msg(data.clone()))
+        .collect()
+}
+
+/// Swaps kept after `start_after`, or from the endpoint's cursor, for a best-effort endpoint
+/// authenticated with its key
+pub fn query_swap_data<S: Storage>(
+    storage: &S,
+    endpoints: &[SwapDataEndpoint],
+    endpoint: &HumanAddr,
+    key: &str,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<SwapDataResponse> {
+    authenticate(endpoints, endpoint, key)?;
+
+    let count = read_count(storage)?;
+    let cursor = read_cursor(storage, endpoint)?;
+    let start = start_after.map_or(cursor, |seq| seq.saturating_add(1));
+    let start = start.max(read_pruned(storage)?);
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as u64;
+
+    let bucket: ReadonlyBucket<S, SwapDataEntry> = ReadonlyBucket::new(PREFIX_SWAP_DATA, storage);
+    let entries = (start..count.min(start.saturating_add(limit)))
+        .map(|seq| bucket.load(&seq.to_be_bytes()))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(SwapDataResponse {
+        entries,
+        count,
+        cursor,
+    })
+}
+
+/// Moves the cursor of a best-effort endpoint past `seq`, the last swap it read, and removes
+/// the swaps every best-effort endpoint acknowledged
+pub fn ack_swap_data<S: Storage>(
+    storage: &mut S,
+    endpoints: &[SwapDataEndpoint],
+    endpoint: &HumanAddr,
+    key: &str,
+    seq: u64,
+) -> StdResult<()> {
+    authenticate(endpoints, endpoint, key)?;
+
+    let count = read_count(storage)?;
+    if seq >= count {
+        return Err(StdError::generic_err(format!(
+            "Swap {} wasn't kept yet",
+            seq
+        )));
+    }
+    let cursor = read_cursor(storage, endpoint)?.max(seq + 1);
+    Bucket::new(PREFIX_SWAP_DATA_CURSOR, storage).save(endpoint.as_str().as_bytes(), &cursor)?;
+
+    let pruned = read_pruned(storage)?;
+    let mut acknowledged = count;
+    for e in endpoints.iter().filter(|e| e.is_best_effort()) {
+        acknowledged = acknowledged.min(read_cursor(storage, &e.address)?);
+    }
+    if acknowledged > pruned {
+        let mut bucket: Bucket<S, SwapDataEntry> = Bucket::new(PREFIX_SWAP_DATA, storage);
+        for seq in pruned..acknowledged {
+            bucket.remove(&seq.to_be_bytes());
+        }
+        Singleton::new(storage, KEY_SWAP_DATA_PRUNED).save(&acknowledged)?;
+    }
+
+    Ok(())
+}
+
+/// Checks `key` against the hash the best-effort `endpoint` was registered with
+fn authenticate(endpoints: &[SwapDataEndpoint], endpoint: &HumanAddr, key: &str) -> StdResult<()> {
+    let key_hash = endpoints
+        .iter()
+        .find(|e| &e.address == endpoint)
+        .and_then(|e| e.best_effort_key_hash.clone())
+        .ok_or_else(StdError::unauthorized)?;
+    // compares the hashes in constant time, so the key can't be guessed byte by byte
+    let given_hash = sha_256(key.as_bytes());
+    let mut diff = (key_hash.0.len() ^ given_hash.len()) as u8;
+    for (a, b) in key_hash.0.iter().zip(given_hash.iter()) {
+        diff |= a ^ b;
+    }
+    if diff != 0 {
+        return Err(StdError::unauthorized());
+    }
+
+    Ok(())
+}
+
+fn read_count<S: Storage>(storage: &S) -> StdResult<u64> {
+    Ok(ReadonlySingleton::new(storage, KEY_SWAP_DATA_COUNT)
+        .may_load()?
+        .unwrap_or_default())
+}
+
+/// First `seq` still kept
+fn read_pruned<S: Storage>(storage: &S) -> StdResult<u64> {
+    Ok(ReadonlySingleton::new(storage, KEY_SWAP_DATA_PRUNED)
+        .may_load()?
+        .unwrap_or_default())
+}
+
+/// An endpoint that never acknowledged anything starts at the oldest swap kept
+fn read_cursor<S: Storage>(storage: &S, endpoint: &HumanAddr) -> StdResult<u64> {
+    let cursor: Option<u64> = ReadonlyBucket::new(PREFIX_SWAP_DATA_CURSOR, storage)
+        .may_load(endpoint.as_str().as_bytes())?;
+    match cursor {
+        Some(cursor) => Ok(cursor),
+        None => read_pruned(storage),
+    }
+}
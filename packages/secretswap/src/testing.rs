@@ -1,10 +1,14 @@
 use cosmwasm_std::testing::MOCK_CONTRACT_ADDR;
-use cosmwasm_std::{to_binary, BankMsg, Coin, CosmosMsg, Decimal, HumanAddr, Uint128, WasmMsg};
+use cosmwasm_std::{
+    to_binary, BankMsg, BlockInfo, Coin, CosmosMsg, Decimal, HumanAddr, Uint128, WasmMsg,
+};
 
 use secret_toolkit::snip20;
 
 use crate::asset::{Asset, AssetInfo, PairInfo};
+use crate::deadline::Deadline;
 use crate::mock_querier::mock_dependencies;
+use crate::pair_settings::ProtocolFee;
 use crate::querier::{
     query_all_balances, query_balance, query_pair_info, query_supply, query_token_balance,
 };
@@ -304,3 +308,73 @@ fn query_terraswap_pair_contract() {
     assert_eq!(pair_info.contract_addr, HumanAddr::from("pair0000"),);
     assert_eq!(pair_info.liquidity_token, HumanAddr::from("liquidity0000"),);
 }
+
+#[test]
+fn test_protocol_fee() {
+    let protocol_fee = ProtocolFee {
+        share_nom: Uint128(1),
+        share_denom: Uint128(6),
+        collector: HumanAddr::from("collector0000"),
+    };
+    protocol_fee.validate().unwrap();
+    // a sixth of a 0.3% commission is 0.05% of the swap
+    assert_eq!(protocol_fee.compute(Uint128(3000)), Uint128(500));
+    assert_eq!(protocol_fee.compute(Uint128(5)), Uint128(0));
+
+    // turned off
+    let protocol_fee = ProtocolFee {
+        share_nom: Uint128(0),
+        ..protocol_fee
+    };
+    protocol_fee.validate().unwrap();
+    assert_eq!(protocol_fee.compute(Uint128(3000)), Uint128(0));
+
+    // more than the whole commission
+    ProtocolFee {
+        share_nom: Uint128(7),
+        ..protocol_fee.clone()
+    }
+    .validate()
+    .unwrap_err();
+    ProtocolFee {
+        share_denom: Uint128(0),
+        ..protocol_fee
+    }
+    .validate()
+    .unwrap_err();
+}
+
+#[test]
+fn test_deadline() {
+    let block = BlockInfo {
+        height: 100,
+        time: 1_000,
+        chain_id: "secret-2".to_string(),
+    };
+
+    let deadline = Deadline {
+        time: Some(1_000),
+        height: None,
+    };
+    deadline.assert_not_expired(&block).unwrap();
+    Deadline {
+        time: Some(999),
+        ..deadline.clone()
+    }
+    .assert_not_expired(&block)
+    .unwrap_err();
+
+    // both must hold
+    Deadline {
+        time: Some(1_000),
+        height: Some(99),
+    }
+    .assert_not_expired(&block)
+    .unwrap_err();
+    Deadline {
+        time: None,
+        height: Some(100),
+    }
+    .assert_not_expired(&block)
+    .unwrap();
+}